@@ -6,5 +6,11 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+pub mod config;
+pub mod flight_recorder;
 pub mod list;
+pub mod metrics;
+pub mod port_forward;
+pub mod profile;
 pub mod show;
+pub mod trace;
@@ -0,0 +1,41 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use hyperactor::ActorRef;
+use hyperactor::reference::ProcId;
+use hyperactor_mesh::proc_mesh::global_root_client;
+use hyperactor_mesh::proc_mesh::mesh_agent::ConfigMessageClient;
+use hyperactor_mesh::proc_mesh::mesh_agent::ProcMeshAgent;
+
+#[derive(clap::Args, Debug)]
+pub struct ConfigCommand {
+    /// The proc whose configuration to show.
+    proc: ProcId,
+
+    /// Print the report as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+impl ConfigCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let client = global_root_client();
+
+        // Codify obtaining a proc's agent in `hyperactor_mesh` somewhere.
+        let agent: ActorRef<ProcMeshAgent> = ActorRef::attest(self.proc.actor_id("agent", 0));
+
+        let report = agent.report(&client).await?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use hyperactor::ActorRef;
+use hyperactor::reference::ProcId;
+use hyperactor_mesh::proc_mesh::global_root_client;
+use hyperactor_mesh::proc_mesh::mesh_agent::FlightRecorderMessageClient;
+use hyperactor_mesh::proc_mesh::mesh_agent::ProcMeshAgent;
+
+#[derive(clap::Args, Debug)]
+pub struct FlightRecorderCommand {
+    /// The proc whose flight recorder to dump.
+    proc: ProcId,
+}
+
+impl FlightRecorderCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let client = global_root_client();
+
+        // Codify obtaining a proc's agent in `hyperactor_mesh` somewhere.
+        let agent: ActorRef<ProcMeshAgent> = ActorRef::attest(self.proc.actor_id("agent", 0));
+
+        // The path is local to the proc's host.
+        let path = agent.dump(&client).await?.map_err(anyhow::Error::msg)?;
+        println!("{}", path);
+
+        Ok(())
+    }
+}
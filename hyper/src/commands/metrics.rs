@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::io::Write;
+
+use hyperactor::ActorRef;
+use hyperactor::reference::ProcId;
+use hyperactor_mesh::metrics_aggregator::METRICS_AGENT_NAME;
+use hyperactor_mesh::metrics_aggregator::MetricsAgent;
+use hyperactor_mesh::metrics_aggregator::MetricsQueryClient;
+use hyperactor_mesh::proc_mesh::global_root_client;
+use hyperactor_telemetry::metrics_snapshot::HistogramSnapshot;
+use hyperactor_telemetry::metrics_snapshot::MetricsSnapshot;
+use tabwriter::TabWriter;
+
+#[derive(clap::Args, Debug)]
+pub struct MetricsCommand {
+    /// The proc of the root metrics agent (rank 0 of the mesh).
+    proc: ProcId,
+
+    /// Break the metrics down along this mesh dimension, e.g. "hosts".
+    #[arg(long)]
+    by: Option<String>,
+
+    /// Print the metrics as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+impl MetricsCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let client = global_root_client();
+
+        let agent: ActorRef<MetricsAgent> =
+            ActorRef::attest(self.proc.actor_id(METRICS_AGENT_NAME, 0));
+        let metrics = agent
+            .get_job_metrics(&client)
+            .await?
+            .map_err(anyhow::Error::msg)?;
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&metrics)?);
+            return Ok(());
+        }
+
+        println!(
+            "{} of {} ranks reporting, from {} processes",
+            metrics.reporting_ranks, metrics.num_ranks, metrics.reporting_processes
+        );
+        let mut tw = TabWriter::new(std::io::stdout());
+        match self.by {
+            None => {
+                writeln!(tw, "METRIC\tVALUE")?;
+                write_rows(&mut tw, None, &metrics.total)?;
+            }
+            Some(dimension) => {
+                let coords = metrics.by_dimension.get(&dimension).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "no dimension {}; the mesh has: {}",
+                        dimension,
+                        metrics
+                            .by_dimension
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?;
+                writeln!(tw, "{}\tMETRIC\tVALUE", dimension.to_uppercase())?;
+                for (coord, snapshot) in coords {
+                    write_rows(&mut tw, Some(*coord), snapshot)?;
+                }
+            }
+        }
+        tw.flush()?;
+
+        Ok(())
+    }
+}
+
+fn write_rows(
+    tw: &mut impl Write,
+    coord: Option<usize>,
+    snapshot: &MetricsSnapshot,
+) -> anyhow::Result<()> {
+    let prefix = coord.map_or(String::new(), |coord| format!("{}\t", coord));
+    for (name, value) in &snapshot.counters {
+        writeln!(tw, "{}{}\t{}", prefix, name, value)?;
+    }
+    for (name, histogram) in &snapshot.histograms {
+        writeln!(tw, "{}{}\t{}", prefix, name, format_histogram(histogram))?;
+    }
+    Ok(())
+}
+
+fn format_histogram(histogram: &HistogramSnapshot) -> String {
+    let format = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.3}", v));
+    format!(
+        "count={} mean={} p50={} p99={} max={}",
+        histogram.count,
+        format(histogram.mean()),
+        format(histogram.quantile(0.5)),
+        format(histogram.quantile(0.99)),
+        format(histogram.max),
+    )
+}
@@ -0,0 +1,39 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use hyperactor::ActorRef;
+use hyperactor::reference::ProcId;
+use hyperactor_mesh::port_forward::PORT_FORWARD_ACTOR_NAME;
+use hyperactor_mesh::port_forward::PortForwardActor;
+use hyperactor_mesh::port_forward::PortForwardListener;
+use hyperactor_mesh::port_forward::PortForwardSpec;
+use hyperactor_mesh::proc_mesh::global_root_client;
+
+#[derive(clap::Args, Debug)]
+pub struct PortForwardCommand {
+    /// The proc to forward through.
+    proc: ProcId,
+
+    /// What to forward, as `<local>:<remote>`. The local side is a
+    /// port or `unix:PATH`; the remote side is a port, `HOST:PORT` or
+    /// `unix:PATH`, dialed from the proc's host.
+    spec: PortForwardSpec,
+}
+
+impl PortForwardCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let client = global_root_client();
+
+        let forwarder: ActorRef<PortForwardActor> =
+            ActorRef::attest(self.proc.actor_id(PORT_FORWARD_ACTOR_NAME, 0));
+
+        let listener = PortForwardListener::bind(&self.spec.local).await?;
+        eprintln!("forwarding {} via {}", self.spec, self.proc);
+        listener.serve(client, forwarder, self.spec.remote).await
+    }
+}
@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use hyperactor::profiler::FoldedStacks;
+use hyperactor_mesh::proc_mesh::global_root_client;
+use hyperactor_mesh::v1::HostMeshRef;
+use ndslice::view::Ranked;
+
+#[derive(clap::Args, Debug)]
+pub struct ProfileCommand {
+    /// The host mesh whose procs to profile, as `name:host,host,...@region`.
+    mesh: HostMeshRef,
+
+    /// How long to profile for, e.g. "10s".
+    #[arg(long, default_value = "10s", value_parser = humantime::parse_duration)]
+    duration: Duration,
+
+    /// The interval between samples, e.g. "10ms".
+    #[arg(long, default_value = "10ms", value_parser = humantime::parse_duration)]
+    interval: Duration,
+
+    /// Root the stacks of each host at its point in the mesh, instead
+    /// of merging all hosts together.
+    #[arg(long)]
+    by_host: bool,
+
+    /// Write the folded stacks to this file instead of stdout. They can
+    /// be rendered with any flamegraph tool, e.g. `flamegraph.pl`.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+impl ProfileCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let client = global_root_client();
+
+        let profiles = self
+            .mesh
+            .profile(&client, self.duration, self.interval)
+            .await?;
+
+        let extent = profiles.region().extent();
+        let mut stacks = FoldedStacks::default();
+        for rank in 0..extent.num_ranks() {
+            let Some(host_stacks) = profiles.get(rank) else {
+                continue;
+            };
+            if self.by_host {
+                let point = extent.point_of_rank(rank)?;
+                stacks.merge_under(&point.to_string(), host_stacks);
+            } else {
+                stacks.merge(host_stacks);
+            }
+        }
+
+        match self.output {
+            Some(path) => {
+                std::fs::write(&path, stacks.to_string())?;
+                eprintln!("wrote {} samples to {}", stacks.total(), path.display());
+            }
+            None => print!("{}", stacks),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use hyperactor_telemetry::trace_query::QueryResult;
+use hyperactor_telemetry::trace_query::Report;
+use hyperactor_telemetry::trace_query::TraceDb;
+use serde_json::Value;
+use tabwriter::TabWriter;
+
+#[derive(clap::Args, Debug)]
+pub struct TraceCommand {
+    #[command(subcommand)]
+    query: TraceQuery,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Table,
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+struct Databases {
+    /// Trace databases to query. Directories contribute every `*.db` file
+    /// directly inside them.
+    #[arg(required = true)]
+    databases: Vec<PathBuf>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum TraceQuery {
+    /// Per-actor message counts and handler latencies.
+    Actors {
+        #[command(flatten)]
+        databases: Databases,
+    },
+
+    /// The slowest message handlers.
+    Slowest {
+        /// Number of handlers to show.
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+
+        #[command(flatten)]
+        databases: Databases,
+    },
+
+    /// The messages sent by and delivered to an actor, in time order.
+    Path {
+        /// The actor id.
+        #[arg(long)]
+        actor: String,
+
+        #[command(flatten)]
+        databases: Databases,
+    },
+
+    /// Actors that never reached a terminal status.
+    Running {
+        #[command(flatten)]
+        databases: Databases,
+    },
+
+    /// Error events, grouped by proc.
+    Errors {
+        #[command(flatten)]
+        databases: Databases,
+    },
+
+    /// Run an ad-hoc SQL query. The `actor_lifecycle`, `messages` and
+    /// `log_events` tables have an additional `proc` column naming the
+    /// database each row came from.
+    Sql {
+        /// The query to run.
+        sql: String,
+
+        #[command(flatten)]
+        databases: Databases,
+    },
+}
+
+impl TraceCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let result = match self.query {
+            TraceQuery::Actors { databases } => {
+                TraceDb::open(&databases.databases)?.report(&Report::Actors)?
+            }
+            TraceQuery::Slowest { limit, databases } => {
+                TraceDb::open(&databases.databases)?.report(&Report::SlowestHandlers { limit })?
+            }
+            TraceQuery::Path { actor, databases } => {
+                TraceDb::open(&databases.databases)?.report(&Report::MessagePath { actor })?
+            }
+            TraceQuery::Running { databases } => {
+                TraceDb::open(&databases.databases)?.report(&Report::NeverStopped)?
+            }
+            TraceQuery::Errors { databases } => {
+                TraceDb::open(&databases.databases)?.report(&Report::ErrorsByProc)?
+            }
+            TraceQuery::Sql { sql, databases } => {
+                TraceDb::open(&databases.databases)?.query(&sql, &[])?
+            }
+        };
+
+        match self.format {
+            Format::Json => println!("{}", serde_json::to_string_pretty(&result.to_json())?),
+            Format::Table => print_table(&result)?,
+        }
+        Ok(())
+    }
+}
+
+fn print_table(result: &QueryResult) -> anyhow::Result<()> {
+    let mut tw = TabWriter::new(std::io::stdout());
+    writeln!(tw, "{}", result.columns.join("\t"))?;
+    for row in &result.rows {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                Value::String(s) => s.replace(['\t', '\n'], " "),
+                value => value.to_string(),
+            })
+            .collect();
+        writeln!(tw, "{}", cells.join("\t"))?;
+    }
+    tw.flush()?;
+    Ok(())
+}
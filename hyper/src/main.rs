@@ -11,8 +11,14 @@ mod commands;
 use clap::Parser;
 use clap::Subcommand;
 
+use crate::commands::config::ConfigCommand;
+use crate::commands::flight_recorder::FlightRecorderCommand;
 use crate::commands::list::ListCommand;
+use crate::commands::metrics::MetricsCommand;
+use crate::commands::port_forward::PortForwardCommand;
+use crate::commands::profile::ProfileCommand;
 use crate::commands::show::ShowCommand;
+use crate::commands::trace::TraceCommand;
 
 #[derive(Parser)]
 #[command()]
@@ -28,6 +34,24 @@ enum Command {
 
     #[clap(about = r#"List available resources"#)]
     List(ListCommand),
+
+    #[clap(about = r#"Show a proc's effective configuration and where each value came from"#)]
+    Config(ConfigCommand),
+
+    #[clap(about = r#"Forward a local port to an address reachable from a proc"#)]
+    PortForward(PortForwardCommand),
+
+    #[clap(about = r#"Query the sqlite trace databases written by procs"#)]
+    Trace(TraceCommand),
+
+    #[clap(about = r#"Dump a proc's flight recorder of recent trace events"#)]
+    FlightRecorder(FlightRecorderCommand),
+
+    #[clap(about = r#"Show job-wide metrics aggregated by a mesh's metrics service"#)]
+    Metrics(MetricsCommand),
+
+    #[clap(about = r#"Sample what the actors on a host mesh are doing, as folded stacks"#)]
+    Profile(ProfileCommand),
 }
 
 #[cfg(fbcode_build)]
@@ -49,5 +73,11 @@ async fn run() -> Result<(), anyhow::Error> {
     match args.command {
         Command::Show(command) => Ok(command.run().await?),
         Command::List(command) => Ok(command.run().await?),
+        Command::Config(command) => Ok(command.run().await?),
+        Command::PortForward(command) => Ok(command.run().await?),
+        Command::Trace(command) => Ok(command.run().await?),
+        Command::FlightRecorder(command) => Ok(command.run().await?),
+        Command::Metrics(command) => Ok(command.run().await?),
+        Command::Profile(command) => Ok(command.run().await?),
     }
 }
@@ -26,6 +26,7 @@ use hyperactor::channel::TcpMode;
 use hyperactor::channel::Tx;
 use hyperactor::channel::dial;
 use hyperactor::channel::serve;
+use hyperactor::config;
 use hyperactor::mailbox::Mailbox;
 use hyperactor::mailbox::PortSender;
 use hyperactor::mailbox::monitored_return_handle;
@@ -103,6 +104,50 @@ fn bench_message_sizes(c: &mut Criterion) {
     }
 }
 
+// Benchmark large messages whose parts are striped across parallel connections
+fn bench_striped_messages(c: &mut Criterion) {
+    #[derive(Clone, Debug, Named, Serialize, Deserialize)]
+    struct Message(Part);
+
+    let transports = vec![
+        ("tcp", ChannelTransport::Tcp(TcpMode::Hostname)),
+        ("unix", ChannelTransport::Unix),
+    ];
+
+    for (transport_name, transport) in &transports {
+        for size in [100_000_000, 1_000_000_000] {
+            let mut group = c.benchmark_group(format!("striped/{}/{}", transport_name, size));
+            group.throughput(Throughput::Bytes(size as u64));
+            group.sampling_mode(criterion::SamplingMode::Flat);
+            group.sample_size(10);
+            for stripes in [1usize, 2, 4, 8] {
+                let transport = transport.clone();
+                group.bench_function(BenchmarkId::from_parameter(stripes), move |b| {
+                    let mut b = b.to_async(Runtime::new().unwrap());
+                    let tt = &transport;
+                    b.iter_custom(|iters| async move {
+                        let config = hyperactor_config::global::lock();
+                        let _guard1 = config.override_key(config::CHANNEL_STRIPE_COUNT, stripes);
+                        let _guard2 = config.override_key(config::CHANNEL_STRIPE_THRESHOLD, 0);
+
+                        let (listen_addr, mut rx) =
+                            serve::<Message>(ChannelAddr::any(tt.clone())).unwrap();
+                        let tx = dial::<Message>(listen_addr).unwrap();
+                        let msg = Message(Part::from(vec![0u8; size]));
+                        let start = Instant::now();
+                        for _ in 0..iters {
+                            tx.post(msg.clone() /* cheap */);
+                            rx.recv().await.unwrap();
+                        }
+                        start.elapsed()
+                    });
+                });
+            }
+            group.finish();
+        }
+    }
+}
+
 // Benchmark message rates with a single client
 fn bench_message_rates(c: &mut Criterion) {
     let mut group = c.benchmark_group("message_rates");
@@ -357,6 +402,7 @@ criterion_group!(
     benches,
     bench_message_sizes,
     bench_message_rates,
+    bench_striped_messages,
     bench_mailbox_message_sizes,
     bench_mailbox_message_rates,
     bench_channel_ping_pong,
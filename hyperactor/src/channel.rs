@@ -39,6 +39,7 @@ use crate::simnet::SimNetError;
 
 pub(crate) mod local;
 pub(crate) mod net;
+pub(crate) mod shm;
 pub mod sim;
 
 /// The type of error that can occur on channel operations.
@@ -366,6 +367,13 @@ pub enum ChannelTransport {
 
     /// Transport over unix domain socket.
     Unix,
+
+    /// Transport over unix domain socket, with large message parts
+    /// passed through shared memory. Only valid between procs on the
+    /// same host. Delivery is best-effort: messages are not
+    /// acknowledged, so messages lost with a failed connection are
+    /// not returned to their senders.
+    Shm,
 }
 
 impl fmt::Display for ChannelTransport {
@@ -376,6 +384,7 @@ impl fmt::Display for ChannelTransport {
             Self::Local => write!(f, "local"),
             Self::Sim(transport) => write!(f, "sim({})", transport),
             Self::Unix => write!(f, "unix"),
+            Self::Shm => write!(f, "shm"),
         }
     }
 }
@@ -405,6 +414,7 @@ impl FromStr for ChannelTransport {
             }
             "local" => Ok(ChannelTransport::Local),
             "unix" => Ok(ChannelTransport::Unix),
+            "shm" => Ok(ChannelTransport::Shm),
             s if s.starts_with("metatls(") && s.ends_with(")") => {
                 let inner = &s["metatls(".len()..s.len() - 1];
                 let mode = inner.parse()?;
@@ -424,6 +434,8 @@ impl ChannelTransport {
             ChannelTransport::Tcp(TcpMode::Hostname),
             ChannelTransport::Local,
             ChannelTransport::Unix,
+            // Shm is left out: it is best-effort, and does not provide
+            // the delivery guarantees exercised by the channel tests.
             // TODO add MetaTls (T208303369)
             // TODO ChannelTransport::Sim(Box::new(ChannelTransport::Tcp)),
             // TODO ChannelTransport::Sim(Box::new(ChannelTransport::Local)),
@@ -443,6 +455,7 @@ impl ChannelTransport {
             ChannelTransport::Local => false,
             ChannelTransport::Sim(_) => false,
             ChannelTransport::Unix => false,
+            ChannelTransport::Shm => false,
         }
     }
 }
@@ -539,6 +552,8 @@ pub type Port = u16;
 /// - `tcp:192.168.0.1:1111` - 192.168.0.1 port 1111 over TCP
 /// - `local:123` - the (in-process) local port 123
 /// - `unix:/some/path` - the Unix socket at `/some/path`
+/// - `shm:@name` - the shared memory channel served on the abstract Unix
+///   socket `name`
 ///
 /// Both local and TCP ports 0 are reserved to indicate "any available
 /// port" when serving.
@@ -585,6 +600,11 @@ pub enum ChannelAddr {
     ///  well as "abstract" names per https://manpages.debian.org/unstable/manpages/unix.7.en.html#Abstract_sockets
     Unix(net::unix::SocketAddr),
 
+    /// A unix domain socket address for the shared memory channel.
+    /// Large message parts are passed out of band in shared memory
+    /// segments; see [`shm`].
+    Shm(net::unix::SocketAddr),
+
     /// A pair of addresses, one for the client and one for the server:
     ///   - The client should dial to the `dial_to` address.
     ///   - The server should bind to the `bind_to` address.
@@ -675,6 +695,7 @@ impl ChannelAddr {
             ChannelTransport::Sim(transport) => sim::any(*transport),
             // This works because the file will be deleted but we know we have a unique file by this point.
             ChannelTransport::Unix => Self::Unix(net::unix::SocketAddr::from_str("").unwrap()),
+            ChannelTransport::Shm => Self::Shm(net::unix::SocketAddr::from_str("").unwrap()),
         }
     }
 
@@ -702,6 +723,7 @@ impl ChannelAddr {
             Self::Local(_) => ChannelTransport::Local,
             Self::Sim(addr) => ChannelTransport::Sim(Box::new(addr.transport())),
             Self::Unix(_) => ChannelTransport::Unix,
+            Self::Shm(_) => ChannelTransport::Shm,
             // bind_to's transport is what is actually used in communication.
             // Therefore we use its transport to represent the Alias.
             Self::Alias { bind_to, .. } => bind_to.transport(),
@@ -717,6 +739,7 @@ impl fmt::Display for ChannelAddr {
             Self::Local(index) => write!(f, "local:{}", index),
             Self::Sim(sim_addr) => write!(f, "sim:{}", sim_addr),
             Self::Unix(addr) => write!(f, "unix:{}", addr),
+            Self::Shm(addr) => write!(f, "shm:{}", addr),
             Self::Alias { dial_to, bind_to } => {
                 write!(f, "alias:dial_to={};bind_to={}", dial_to, bind_to)
             }
@@ -740,6 +763,7 @@ impl FromStr for ChannelAddr {
             Some(("metatls", rest)) => net::meta::parse(rest).map_err(|e| e.into()),
             Some(("sim", rest)) => sim::parse(rest).map_err(|e| e.into()),
             Some(("unix", rest)) => Ok(Self::Unix(net::unix::SocketAddr::from_str(rest)?)),
+            Some(("shm", rest)) => Ok(Self::Shm(net::unix::SocketAddr::from_str(rest)?)),
             Some(("alias", _)) => Err(anyhow::anyhow!(
                 "detect possible alias address, but we currently do not support \
                 parsing alias' string representation since we only want to \
@@ -897,6 +921,7 @@ enum ChannelTxKind<M: RemoteMessage> {
     Tcp(net::NetTx<M>),
     MetaTls(net::NetTx<M>),
     Unix(net::NetTx<M>),
+    Shm(shm::ShmTx<M>),
     Sim(sim::SimTx<M>),
 }
 
@@ -909,6 +934,7 @@ impl<M: RemoteMessage> Tx<M> for ChannelTx<M> {
             ChannelTxKind::MetaTls(tx) => tx.do_post(message, return_channel),
             ChannelTxKind::Sim(tx) => tx.do_post(message, return_channel),
             ChannelTxKind::Unix(tx) => tx.do_post(message, return_channel),
+            ChannelTxKind::Shm(tx) => tx.do_post(message, return_channel),
         }
     }
 
@@ -919,6 +945,7 @@ impl<M: RemoteMessage> Tx<M> for ChannelTx<M> {
             ChannelTxKind::MetaTls(tx) => Tx::<M>::addr(tx),
             ChannelTxKind::Sim(tx) => tx.addr(),
             ChannelTxKind::Unix(tx) => Tx::<M>::addr(tx),
+            ChannelTxKind::Shm(tx) => tx.addr(),
         }
     }
 
@@ -929,6 +956,7 @@ impl<M: RemoteMessage> Tx<M> for ChannelTx<M> {
             ChannelTxKind::MetaTls(tx) => tx.status(),
             ChannelTxKind::Sim(tx) => tx.status(),
             ChannelTxKind::Unix(tx) => tx.status(),
+            ChannelTxKind::Shm(tx) => tx.status(),
         }
     }
 }
@@ -952,6 +980,7 @@ enum ChannelRxKind<M: RemoteMessage> {
     Tcp(net::NetRx<M>),
     MetaTls(net::NetRx<M>),
     Unix(net::NetRx<M>),
+    Shm(shm::ShmRx<M>),
     Sim(sim::SimRx<M>),
 }
 
@@ -965,6 +994,7 @@ impl<M: RemoteMessage> Rx<M> for ChannelRx<M> {
             ChannelRxKind::MetaTls(rx) => rx.recv().await,
             ChannelRxKind::Sim(rx) => rx.recv().await,
             ChannelRxKind::Unix(rx) => rx.recv().await,
+            ChannelRxKind::Shm(rx) => rx.recv().await,
         }
     }
 
@@ -975,6 +1005,7 @@ impl<M: RemoteMessage> Rx<M> for ChannelRx<M> {
             ChannelRxKind::MetaTls(rx) => rx.addr(),
             ChannelRxKind::Sim(rx) => rx.addr(),
             ChannelRxKind::Unix(rx) => rx.addr(),
+            ChannelRxKind::Shm(rx) => rx.addr(),
         }
     }
 }
@@ -992,6 +1023,7 @@ pub fn dial<M: RemoteMessage>(addr: ChannelAddr) -> Result<ChannelTx<M>, Channel
         ChannelAddr::MetaTls(meta_addr) => ChannelTxKind::MetaTls(net::meta::dial(meta_addr)?),
         ChannelAddr::Sim(sim_addr) => ChannelTxKind::Sim(sim::dial::<M>(sim_addr)?),
         ChannelAddr::Unix(path) => ChannelTxKind::Unix(net::unix::dial(path)),
+        ChannelAddr::Shm(path) => ChannelTxKind::Shm(shm::dial(path)),
         ChannelAddr::Alias { dial_to, .. } => dial(*dial_to)?.inner,
     };
     Ok(ChannelTx { inner })
@@ -1031,6 +1063,10 @@ fn serve_inner<M: RemoteMessage>(
             let (addr, rx) = net::unix::serve::<M>(path)?;
             Ok((addr, ChannelRxKind::Unix(rx)))
         }
+        ChannelAddr::Shm(path) => {
+            let (addr, rx) = shm::serve::<M>(path)?;
+            Ok((addr, ChannelRxKind::Shm(rx)))
+        }
         ChannelAddr::Local(0) => {
             let (port, rx) = local::serve::<M>();
             Ok((ChannelAddr::Local(port), ChannelRxKind::Local(rx)))
@@ -1115,6 +1151,13 @@ mod tests {
                         .expect("can't make socket from path"),
                 ),
             ),
+            (
+                "shm<DELIM>@yolo",
+                ChannelAddr::Shm(
+                    unix::SocketAddr::from_abstract_name("yolo")
+                        .expect("can't make socket from abstract name"),
+                ),
+            ),
         ];
 
         for (raw, parsed) in cases_ok.clone() {
@@ -67,8 +67,9 @@ use crate::clock::RealClock;
 
 mod client;
 mod framed;
-use client::dial;
+mod mux;
 mod server;
+mod stripe;
 pub use server::ServerHandle;
 use server::serve;
 
@@ -101,6 +102,17 @@ enum Frame<M> {
 
     /// Send a message with the provided sequence number.
     Message(u64, M),
+
+    /// Switch the connection to carry multiplexed sessions. See [`mux`].
+    Mux,
+
+    /// Carry the stripe with the given index of the striped transfer
+    /// with the given id. See [`stripe`].
+    Stripe(u64, usize),
+
+    /// Send a message whose parts are carried by a striped transfer.
+    /// See [`stripe`].
+    Striped(stripe::Header),
 }
 
 #[derive(Debug, Serialize, Deserialize, EnumAsInner)]
@@ -120,6 +132,17 @@ fn deserialize_response(data: Bytes) -> Result<NetRxResponse, bincode::Error> {
     bincode::deserialize(&data)
 }
 
+/// Dial the given link. If [`crate::config::CHANNEL_MULTIPLEX_LINKS`] is
+/// set, the session is carried over a connection shared with all other
+/// multiplexed sessions to the same destination.
+fn dial<M: RemoteMessage>(link: impl Link + 'static) -> NetTx<M> {
+    if hyperactor_config::global::get(crate::config::CHANNEL_MULTIPLEX_LINKS) {
+        client::dial(mux::MuxLink::new(link))
+    } else {
+        client::dial(link)
+    }
+}
+
 /// A Tx implemented on top of a Link. The Tx manages the link state,
 /// reconnections, etc.
 pub(crate) struct NetTx<M: RemoteMessage> {
@@ -249,24 +272,29 @@ pub(crate) mod unix {
         }
 
         async fn connect(&self) -> Result<Self::Stream, ClientError> {
-            match &self.0 {
-                SocketAddr::Bound(sock_addr) => {
-                    let std_stream: StdUnixStream = StdUnixStream::connect_addr(sock_addr)
-                        .map_err(|err| {
-                            ClientError::Connect(
-                                self.dest(),
-                                err,
-                                "cannot connect unix socket".to_string(),
-                            )
-                        })?;
-                    std_stream
-                        .set_nonblocking(true)
-                        .map_err(|err| ClientError::Io(self.dest(), err))?;
-                    UnixStream::from_std(std_stream)
-                        .map_err(|err| ClientError::Io(self.dest(), err))
-                }
-                SocketAddr::Unbound => Err(ClientError::Resolve(self.dest())),
+            connect(&self.0, self.dest())
+        }
+    }
+
+    /// Connect a nonblocking stream to the given socket address. `dest`
+    /// is the channel address reported in errors.
+    pub(crate) fn connect(addr: &SocketAddr, dest: ChannelAddr) -> Result<UnixStream, ClientError> {
+        match addr {
+            SocketAddr::Bound(sock_addr) => {
+                let std_stream: StdUnixStream =
+                    StdUnixStream::connect_addr(sock_addr).map_err(|err| {
+                        ClientError::Connect(
+                            dest.clone(),
+                            err,
+                            "cannot connect unix socket".to_string(),
+                        )
+                    })?;
+                std_stream
+                    .set_nonblocking(true)
+                    .map_err(|err| ClientError::Io(dest.clone(), err))?;
+                UnixStream::from_std(std_stream).map_err(|err| ClientError::Io(dest, err))
             }
+            SocketAddr::Unbound => Err(ClientError::Resolve(dest)),
         }
     }
 
@@ -280,15 +308,25 @@ pub(crate) mod unix {
     pub fn serve<M: RemoteMessage>(
         addr: SocketAddr,
     ) -> Result<(ChannelAddr, NetRx<M>), ServerError> {
-        let caddr = ChannelAddr::Unix(addr.clone());
-        let maybe_listener = match &addr {
+        let (local_addr, listener) = bind(&addr, ChannelAddr::Unix(addr.clone()))?;
+        super::serve(listener, local_addr.into(), false)
+    }
+
+    /// Bind a nonblocking listener on the given socket address, returning
+    /// the bound address along with the listener. An unbound address binds
+    /// to a fresh autobound name. `caddr` is the channel address reported
+    /// in errors.
+    pub(crate) fn bind(
+        addr: &SocketAddr,
+        caddr: ChannelAddr,
+    ) -> Result<(StdSocketAddr, UnixListener), ServerError> {
+        let maybe_listener = match addr {
             SocketAddr::Bound(sock_addr) => StdUnixListener::bind_addr(sock_addr),
             SocketAddr::Unbound => StdUnixDatagram::unbound()
                 .and_then(|u| u.local_addr())
                 .and_then(|uaddr| StdUnixListener::bind_addr(&uaddr)),
         };
-        let std_listener =
-            maybe_listener.map_err(|err| ServerError::Listen(ChannelAddr::Unix(addr), err))?;
+        let std_listener = maybe_listener.map_err(|err| ServerError::Listen(caddr.clone(), err))?;
 
         std_listener
             .set_nonblocking(true)
@@ -296,9 +334,9 @@ pub(crate) mod unix {
         let local_addr = std_listener
             .local_addr()
             .map_err(|err| ServerError::Resolve(caddr.clone(), err))?;
-        let listener: UnixListener = UnixListener::from_std(std_listener)
-            .map_err(|err| ServerError::Io(caddr.clone(), err))?;
-        super::serve(listener, local_addr.into(), false)
+        let listener: UnixListener =
+            UnixListener::from_std(std_listener).map_err(|err| ServerError::Io(caddr, err))?;
+        Ok((local_addr, listener))
     }
 
     /// Wrapper around std-lib's unix::SocketAddr that lets us implement equality functions
@@ -900,6 +938,8 @@ mod tests {
     use tokio_util::sync::CancellationToken;
 
     use super::*;
+    use crate as hyperactor; // for macros
+    use crate::Named;
     use crate::channel;
     use crate::channel::net::framed::FrameReader;
     use crate::channel::net::framed::FrameWrite;
@@ -999,6 +1039,143 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(target_os = "linux")] // uses abstract names
+    #[tracing_test::traced_test]
+    #[tokio::test]
+    async fn test_unix_multiplexed() -> Result<()> {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(config::CHANNEL_MULTIPLEX_LINKS, true);
+
+        let timestamp = RealClock
+            .system_time_now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let socket_addr =
+            unix::SocketAddr::from_abstract_name(&format!("test_unix_multiplexed_{}", timestamp))
+                .unwrap();
+        let (addr, mut rx) = net::unix::serve::<(u64, u64)>(socket_addr).unwrap();
+
+        // Sessions sharing a connection are each delivered in order.
+        let txs: Vec<ChannelTx<(u64, u64)>> = (0..3)
+            .map(|_| channel::dial(addr.clone()).unwrap())
+            .collect();
+        for seq in 0..100 {
+            for (index, tx) in txs.iter().enumerate() {
+                tx.post((index as u64, seq));
+            }
+        }
+        let mut next = vec![0; txs.len()];
+        for _ in 0..(100 * txs.len()) {
+            let (index, seq) = rx.recv().await.unwrap();
+            assert_eq!(next[index as usize], seq);
+            next[index as usize] += 1;
+        }
+
+        // Each session still learns that the server has closed.
+        rx.2.stop("testing");
+        assert!(rx.recv().await.is_err());
+        for tx in &txs {
+            tx.post((0, 0));
+            let mut watcher = tx.status().clone();
+            let _ = watcher.wait_for(|val| *val == TxStatus::Closed).await;
+            assert_eq!(*watcher.borrow(), TxStatus::Closed);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")] // uses abstract names
+    #[tokio::test]
+    async fn test_unix_multiplexed_flow_control() -> Result<()> {
+        #[derive(Named, Serialize, Deserialize, Debug, Clone, PartialEq)]
+        struct Payload(u64, u64, serde_multipart::Part);
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(config::CHANNEL_MULTIPLEX_LINKS, true);
+
+        let timestamp = RealClock
+            .system_time_now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let socket_addr = unix::SocketAddr::from_abstract_name(&format!(
+            "test_unix_multiplexed_flow_control_{}",
+            timestamp
+        ))
+        .unwrap();
+        let (addr, mut rx) = net::unix::serve::<Payload>(socket_addr).unwrap();
+
+        // Each session sends many times its stream window, and so has to
+        // wait for credit along the way.
+        let txs: Vec<ChannelTx<Payload>> = (0..2)
+            .map(|_| channel::dial(addr.clone()).unwrap())
+            .collect();
+        for seq in 0..32 {
+            for (index, tx) in txs.iter().enumerate() {
+                let part = serde_multipart::Part::from(vec![seq as u8; 256 * 1024]);
+                tx.post(Payload(index as u64, seq, part));
+            }
+        }
+        let mut next = vec![0; txs.len()];
+        for _ in 0..(32 * txs.len()) {
+            let Payload(index, seq, part) = rx.recv().await.unwrap();
+            assert_eq!(next[index as usize], seq);
+            assert_eq!(part.len(), 256 * 1024);
+            next[index as usize] += 1;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")] // uses abstract names
+    #[tracing_test::traced_test]
+    #[tokio::test]
+    async fn test_unix_striped() -> Result<()> {
+        #[derive(Named, Serialize, Deserialize, Debug, Clone, PartialEq)]
+        struct Payload(u64, serde_multipart::Part, serde_multipart::Part);
+
+        let config = hyperactor_config::global::lock();
+        let _guard1 = config.override_key(config::CHANNEL_STRIPE_COUNT, 4);
+        let _guard2 = config.override_key(config::CHANNEL_STRIPE_THRESHOLD, 1024);
+
+        let timestamp = RealClock
+            .system_time_now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let socket_addr =
+            unix::SocketAddr::from_abstract_name(&format!("test_unix_striped_{}", timestamp))
+                .unwrap();
+        let (addr, mut rx) = net::unix::serve::<Payload>(socket_addr).unwrap();
+        let tx = channel::dial::<Payload>(addr).unwrap();
+
+        // Striped and unstriped messages are delivered in order.
+        let payloads: Vec<_> = [10, 100_000, 3, 1_000_001, 1_000]
+            .into_iter()
+            .enumerate()
+            .map(|(seq, len)| {
+                let bytes: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+                Payload(
+                    seq as u64,
+                    serde_multipart::Part::from(bytes.clone()),
+                    serde_multipart::Part::from(bytes.into_iter().rev().collect::<Vec<_>>()),
+                )
+            })
+            .collect();
+        for payload in &payloads {
+            tx.post(payload.clone());
+        }
+        for payload in payloads {
+            let received = rx.recv().await.unwrap();
+            assert_eq!(received.0, payload.0);
+            assert_eq!(received.1.to_bytes(), payload.1.to_bytes());
+            assert_eq!(received.2.to_bytes(), payload.2.to_bytes());
+        }
+
+        Ok(())
+    }
+
     #[tracing_test::traced_test]
     #[async_timed_test(timeout_secs = 60)]
     // TODO: OSS: called `Result::unwrap()` on an `Err` value: Listen(Tcp([::1]:0), Os { code: 99, kind: AddrNotAvailable, message: "Cannot assign requested address" })
@@ -42,6 +42,7 @@ use crate::channel::net::NetRxResponse;
 use crate::channel::net::NetTx;
 use crate::channel::net::Stream;
 use crate::channel::net::deserialize_response;
+use crate::channel::net::stripe;
 use crate::clock::Clock;
 use crate::clock::RealClock;
 use crate::config;
@@ -860,17 +861,57 @@ where
         (
             State::Running(Deliveries {
                 mut outbox,
-                unacked,
+                mut unacked,
             }),
             Conn::Connected {
-                reader,
+                mut reader,
                 write_state: WriteState::Idle(writer),
                 ..
             },
         ) if !outbox.is_empty() => {
             let max = hyperactor_config::global::get(config::CODEC_MAX_FRAME_LENGTH);
             let len = outbox.front_size().expect("not empty");
-            let message = outbox.front_message().expect("not empty");
+            let mut message = outbox.front_message().expect("not empty");
+
+            // Large messages have their parts striped across parallel
+            // connections; only their header is sent over the session.
+            // Acks keep being processed while the stripes are sent.
+            if stripe::should_stripe(&message) {
+                let send = stripe::send::<_, M>(link, message);
+                tokio::pin!(send);
+                let result = loop {
+                    tokio::select! {
+                        result = &mut send => break result.map_err(|err| err.to_string()),
+                        ack_result = reader.next() => match ack_result.map(|buffer| buffer.map(deserialize_response)) {
+                            Ok(Some(Ok(NetRxResponse::Ack(ack)))) => {
+                                unacked.prune(ack, RealClock.now(), &link.dest(), session_id);
+                            }
+                            // Anything else is seen again by the next session.
+                            Ok(Some(Ok(response))) => break Err(format!("received {:?} while striping", response)),
+                            Ok(Some(Err(err))) => break Err(format!("failed deserializing response: {}", err)),
+                            Ok(None) => break Err("EOF while striping".to_string()),
+                            Err(err) => break Err(format!("failed reading ack: {}", err)),
+                        },
+                    }
+                };
+                message = match result {
+                    Ok(header) => header,
+                    Err(err) => {
+                        tracing::info!(
+                            dest = %link.dest(),
+                            session_id = session_id,
+                            error = %err,
+                            "failed to stripe message; reconnecting"
+                        );
+                        // The message stays in the outbox, and is striped
+                        // again once we have reconnected.
+                        return (
+                            State::Running(Deliveries { outbox, unacked }),
+                            Conn::reconnect_with_default(),
+                        );
+                    }
+                };
+            }
 
             match FrameWrite::new(writer, message.framed(), max) {
                 Ok(fw) => (
@@ -11,6 +11,7 @@
 use std::fmt;
 use std::io;
 use std::io::IoSlice;
+use std::mem::replace;
 use std::mem::take;
 use std::task::Poll;
 
@@ -26,10 +27,15 @@ use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
 use tokio::io::ReadBuf;
 
+/// The size of the buffer through which spilled frames are written to
+/// their files.
+const SPILL_BUFFER_SIZE: usize = 1024 * 1024;
+
 /// A FrameReader reads frames from an underlying [`AsyncRead`].
 pub struct FrameReader<R> {
     reader: R,
     max_frame_length: usize,
+    spill_threshold: Option<usize>,
     state: FrameReaderState,
 }
 
@@ -38,6 +44,13 @@ enum FrameReaderState {
     ReadLen { buf: [u8; 8], off: usize },
     /// Accumulating body of exactly `len` bytes.
     ReadBody { buf: Vec<u8>, len: usize }, // buf.len() <= len
+    /// Spilling a body to `file` through `buf`, with `remaining` bytes
+    /// yet to be read.
+    SpillBody {
+        file: tokio::fs::File,
+        buf: BytesMut,
+        remaining: usize,
+    },
 }
 
 impl<R: AsyncRead + Unpin> FrameReader<R> {
@@ -47,6 +60,7 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
         Self {
             reader,
             max_frame_length,
+            spill_threshold: None,
             state: FrameReaderState::ReadLen {
                 buf: [0; 8],
                 off: 0,
@@ -54,6 +68,16 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
         }
     }
 
+    /// Spill frames longer than `threshold` bytes to anonymous temporary
+    /// files as they are read, instead of buffering them in memory. Such
+    /// frames are returned memory-mapped from their files, so that they,
+    /// and the message parts sliced from them, are paged in only as they
+    /// are used.
+    pub fn spill_above(mut self, threshold: usize) -> Self {
+        self.spill_threshold = Some(threshold);
+        self
+    }
+
     /// Read the next frame from the underlying reader. If the frame
     /// exceeds the configured maximum length, `next` returns an
     /// `io::ErrorKind::InvalidData` error.
@@ -98,9 +122,20 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
                     if len > self.max_frame_length {
                         return Err(io::ErrorKind::InvalidData.into());
                     }
-                    self.state = FrameReaderState::ReadBody {
-                        buf: Vec::with_capacity(len),
-                        len,
+                    self.state = if self
+                        .spill_threshold
+                        .is_some_and(|threshold| len > threshold)
+                    {
+                        FrameReaderState::SpillBody {
+                            file: tokio::fs::File::from_std(tempfile::tempfile()?),
+                            buf: BytesMut::with_capacity(SPILL_BUFFER_SIZE),
+                            remaining: len,
+                        }
+                    } else {
+                        FrameReaderState::ReadBody {
+                            buf: Vec::with_capacity(len),
+                            len,
+                        }
                     };
                 }
 
@@ -140,6 +175,42 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
                     };
                     return Ok(Some(frame));
                 }
+
+                // Fill the buffer before writing it out.
+                FrameReaderState::SpillBody { buf, remaining, .. }
+                    if *remaining > 0 && buf.len() < SPILL_BUFFER_SIZE =>
+                {
+                    let limit = (*remaining).min(SPILL_BUFFER_SIZE - buf.len());
+                    let num_read = (&mut self.reader).take(limit as u64).read_buf(buf).await?;
+                    if num_read == 0 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    *remaining -= num_read;
+                }
+
+                FrameReaderState::SpillBody { file, buf, .. } if !buf.is_empty() => {
+                    file.write_buf(buf).await?;
+                }
+
+                FrameReaderState::SpillBody { file, .. } => {
+                    // Wait for outstanding writes before mapping the file.
+                    file.flush().await?;
+                    let FrameReaderState::SpillBody { file, .. } = replace(
+                        &mut self.state,
+                        FrameReaderState::ReadLen {
+                            buf: [0; 8],
+                            off: 0,
+                        },
+                    ) else {
+                        unreachable!()
+                    };
+                    let file = file
+                        .try_into_std()
+                        .map_err(|_| io::Error::other("spill file has outstanding writes"))?;
+                    // SAFETY: the file is anonymous, and is never modified once mapped.
+                    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+                    return Ok(Some(Bytes::from_owner(mmap)));
+                }
             }
         }
     }
@@ -902,6 +973,8 @@ mod tests {
     use tokio::io::AsyncWriteExt;
 
     use super::*;
+    use crate::clock::Clock;
+    use crate::clock::RealClock;
 
     fn random_buffer(max_len: usize) -> Bytes {
         let mut rng = thread_rng();
@@ -1131,6 +1204,47 @@ mod tests {
         w.shutdown().await.unwrap();
         assert!(reader.next().await.unwrap().is_none());
     }
+
+    #[tokio::test]
+    async fn test_reader_spills_large_frames() {
+        const MAX_LEN: usize = 16 * 1024 * 1024;
+
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let (r, _wu) = tokio::io::split(a);
+        let (_ru, w) = tokio::io::split(b);
+        let mut reader = FrameReader::new(r, MAX_LEN).spill_above(1024);
+
+        let bodies: Vec<Bytes> = [10, 1025, 0, 3 * SPILL_BUFFER_SIZE + 7, 1024]
+            .into_iter()
+            .map(|len| (0..len).map(|i| (i % 251) as u8).collect::<Vec<_>>().into())
+            .collect();
+        let writer = tokio::spawn({
+            let bodies = bodies.clone();
+            async move {
+                let mut w = w;
+                for body in bodies {
+                    w = FrameWrite::write_frame(w, body, MAX_LEN)
+                        .await
+                        .map_err(|(_, e)| e)
+                        .unwrap();
+                }
+            }
+        });
+
+        for body in bodies {
+            // Reads are repeatedly cancelled, which must not lose progress.
+            let frame = loop {
+                if let Ok(frame) = RealClock
+                    .timeout(std::time::Duration::from_micros(50), reader.next())
+                    .await
+                {
+                    break frame.unwrap().unwrap();
+                }
+            };
+            assert_eq!(frame, body);
+        }
+        writer.await.unwrap();
+    }
 }
 
 #[cfg(test)]
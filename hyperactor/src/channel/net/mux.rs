@@ -0,0 +1,590 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Multiplexing of many sessions over a single connection.
+//!
+//! A [`MuxLink`] is a [`Link`] whose streams are logical streams
+//! carried over a connection that is shared by all multiplexed links to
+//! the same destination. Each session runs its regular protocol (init,
+//! sequenced messages, acks, reconnects) over its own logical stream, so
+//! ordering, ack and `TxStatus` semantics are unchanged; only the
+//! underlying connection is shared.
+//!
+//! A multiplexed connection starts with a [`Frame::Mux`] frame in place
+//! of the usual [`Frame::Init`], after which every frame is a
+//! [`MuxFrame`] tagged with the id of the logical stream it belongs to.
+//! On the server, each logical stream is handed to the [`SessionManager`]
+//! as if it were a connection of its own.
+//!
+//! Each logical stream is flow controlled: an endpoint may have at most
+//! [`STREAM_WINDOW`] bytes of a stream's data in flight, and the peer
+//! returns credit (see [`MuxFrame::Credit`]) as the stream's session
+//! reads the data. Received data is queued without blocking the
+//! connection, so a slow session stalls only its own stream, and each
+//! stream's queue is bounded by its window.
+//!
+//! If the connection fails, every logical stream carried by it is closed,
+//! and each session reconnects independently, reestablishing the shared
+//! connection as needed.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use anyhow::Context;
+use bytes::BytesMut;
+use serde_multipart::Part;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::io::DuplexStream;
+use tokio::io::ReadHalf;
+use tokio::io::WriteHalf;
+use tokio::sync::Semaphore;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+use super::*;
+use crate::channel::net::framed::FrameReader;
+use crate::channel::net::framed::FrameWrite;
+use crate::channel::net::server::Handshake;
+use crate::channel::net::server::ServerConn;
+use crate::channel::net::server::SessionManager;
+use crate::config;
+use crate::metrics;
+
+/// The buffer size of each logical stream, and the largest chunk of
+/// stream data carried by a single [`MuxFrame::Data`].
+const STREAM_BUFFER_SIZE: usize = 256 * 1024;
+
+/// The number of bytes of a logical stream that may be in flight: sent
+/// by one endpoint, but not yet read by the session at the other.
+const STREAM_WINDOW: usize = 4 * STREAM_BUFFER_SIZE;
+
+/// The number of frames that may be queued for writing to a connection
+/// before logical streams are made to wait.
+const FRAME_QUEUE_SIZE: usize = 1024;
+
+/// Frames exchanged over a multiplexed connection.
+#[derive(Debug, Serialize, Deserialize)]
+enum MuxFrame {
+    /// Open the logical stream with the given id. Sent by the client.
+    Open(u64),
+    /// Data written to the logical stream with the given id.
+    Data(u64, Part),
+    /// The sender will write no more data to the logical stream with
+    /// the given id.
+    Close(u64),
+    /// The sender's session has read this many more bytes of the
+    /// logical stream with the given id, which the peer may now send.
+    Credit(u64, usize),
+}
+
+/// Data received for a logical stream, queued until its session reads it.
+struct Incoming {
+    parts: mpsc::UnboundedSender<Part>,
+    /// The number of bytes queued; the peer may not send more than
+    /// [`STREAM_WINDOW`] bytes before it is returned credit.
+    queued: Arc<AtomicUsize>,
+}
+
+/// One end of a multiplexed connection.
+struct Endpoint {
+    frames: mpsc::Sender<MuxFrame>,
+    /// Queues of data received for each open logical stream.
+    streams: Mutex<HashMap<u64, Incoming>>,
+    /// The credit available to each logical stream that is still being
+    /// written: the number of bytes that may be sent to the peer.
+    credits: Mutex<HashMap<u64, Arc<Semaphore>>>,
+    /// Tracks the tasks forwarding data written by sessions.
+    pumps: TaskTracker,
+    closed: CancellationToken,
+    peer: ChannelAddr,
+}
+
+impl Endpoint {
+    /// Run an endpoint over the given connection. Logical streams opened
+    /// by the peer are sent on `accepted`; if it is `None`, the peer may
+    /// not open streams.
+    fn start<S: AsyncRead + AsyncWrite + Send + 'static>(
+        reader: FrameReader<ReadHalf<S>>,
+        writer: WriteHalf<S>,
+        peer: ChannelAddr,
+        accepted: Option<mpsc::UnboundedSender<DuplexStream>>,
+    ) -> Arc<Self> {
+        let (frames, frames_rx) = mpsc::channel(FRAME_QUEUE_SIZE);
+        let endpoint = Arc::new(Self {
+            frames,
+            streams: Mutex::new(HashMap::new()),
+            credits: Mutex::new(HashMap::new()),
+            pumps: TaskTracker::new(),
+            closed: CancellationToken::new(),
+            peer,
+        });
+        metrics::CHANNEL_MUX_LINKS.add(
+            1,
+            hyperactor_telemetry::kv_pairs!("peer" => endpoint.peer.to_string()),
+        );
+        tokio::spawn(write_frames(
+            writer,
+            frames_rx,
+            endpoint.closed.clone(),
+            endpoint.peer.clone(),
+        ));
+        tokio::spawn(read_frames(endpoint.clone(), reader, accepted));
+        endpoint
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.is_cancelled()
+    }
+
+    /// Attach the logical stream with the given id, returning the end
+    /// used by its session. If the connection is already closed, the
+    /// returned stream is closed as well.
+    fn attach(self: &Arc<Self>, id: u64) -> DuplexStream {
+        let (stream, link_end) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+        let (link_reader, link_writer) = tokio::io::split(link_end);
+        let (parts, parts_rx) = mpsc::unbounded_channel();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let credit = Arc::new(Semaphore::new(STREAM_WINDOW));
+        {
+            let mut streams = self.streams.lock().unwrap();
+            if !self.closed.is_cancelled() {
+                streams.insert(
+                    id,
+                    Incoming {
+                        parts,
+                        queued: queued.clone(),
+                    },
+                );
+                self.credits.lock().unwrap().insert(id, credit.clone());
+                self.record_stream(1);
+            }
+        }
+        let endpoint = self.clone();
+        self.pumps.spawn(async move {
+            pump_out(id, link_reader, &credit, &endpoint.frames, &endpoint.closed).await;
+            endpoint.credits.lock().unwrap().remove(&id);
+        });
+        tokio::spawn(pump_in(
+            id,
+            link_writer,
+            parts_rx,
+            queued,
+            self.frames.clone(),
+        ));
+        stream
+    }
+
+    /// Detach the logical stream with the given id, after the peer has
+    /// closed it. Data already queued for the stream is still delivered.
+    fn detach(&self, id: u64) {
+        if self.streams.lock().unwrap().remove(&id).is_some() {
+            self.record_stream(-1);
+        }
+    }
+
+    /// Close the connection, along with all of its logical streams.
+    /// Frames that are already queued are still written.
+    fn close(&self) {
+        self.closed.cancel();
+        let detached = std::mem::take(&mut *self.streams.lock().unwrap());
+        if !detached.is_empty() {
+            self.record_stream(-(detached.len() as i64));
+        }
+    }
+
+    /// Close the connection once everything written by the sessions
+    /// attached to it has been queued. Sessions should be done writing.
+    async fn shutdown(&self) {
+        self.pumps.close();
+        self.pumps.wait().await;
+        self.close();
+    }
+
+    fn record_stream(&self, delta: i64) {
+        metrics::CHANNEL_MUX_LOGICAL_CHANNELS.add(
+            delta,
+            hyperactor_telemetry::kv_pairs!("peer" => self.peer.to_string()),
+        );
+    }
+}
+
+/// Write queued frames to the connection until it is closed.
+async fn write_frames<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    mut frames: mpsc::Receiver<MuxFrame>,
+    closed: CancellationToken,
+    peer: ChannelAddr,
+) {
+    let max = hyperactor_config::global::get(config::CODEC_MAX_FRAME_LENGTH);
+    loop {
+        let frame = tokio::select! {
+            // Drain queued frames before exiting.
+            biased;
+            frame = frames.recv() => match frame {
+                Some(frame) => frame,
+                None => break,
+            },
+            _ = closed.cancelled() => break,
+        };
+        let message = match serde_multipart::serialize_bincode(&frame) {
+            Ok(message) => message,
+            Err(err) => {
+                tracing::error!(peer = %peer, error = %err, "failed to serialize mux frame");
+                break;
+            }
+        };
+        writer = match FrameWrite::write_frame(writer, message.framed(), max).await {
+            Ok(writer) => writer,
+            Err((_, err)) => {
+                tracing::info!(peer = %peer, error = %err, "mux link write error");
+                break;
+            }
+        };
+    }
+    closed.cancel();
+}
+
+/// Read frames from the connection and dispatch them to their logical
+/// streams until the connection is closed.
+async fn read_frames<R: AsyncRead + Unpin>(
+    endpoint: Arc<Endpoint>,
+    mut reader: FrameReader<R>,
+    accepted: Option<mpsc::UnboundedSender<DuplexStream>>,
+) {
+    loop {
+        let bytes = tokio::select! {
+            _ = endpoint.closed.cancelled() => break,
+            result = reader.next() => match result {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::info!(peer = %endpoint.peer, error = %err, "mux link read error");
+                    break;
+                }
+            },
+        };
+        let frame = match serde_multipart::Message::from_framed(bytes)
+            .map_err(anyhow::Error::from)
+            .and_then(|message| {
+                serde_multipart::deserialize_bincode::<MuxFrame>(message).map_err(Into::into)
+            }) {
+            Ok(frame) => frame,
+            Err(err) => {
+                tracing::error!(peer = %endpoint.peer, error = %err, "failed to decode mux frame");
+                break;
+            }
+        };
+        match frame {
+            MuxFrame::Open(id) => {
+                let Some(accepted) = &accepted else {
+                    tracing::error!(peer = %endpoint.peer, "peer attempted to open stream {}", id);
+                    break;
+                };
+                if accepted.send(endpoint.attach(id)).is_err() {
+                    break;
+                }
+            }
+            MuxFrame::Data(id, part) => {
+                // Data for streams that are no longer attached is dropped;
+                // their sessions are gone.
+                let streams = endpoint.streams.lock().unwrap();
+                if let Some(incoming) = streams.get(&id) {
+                    let len = part.len();
+                    if incoming.queued.fetch_add(len, Ordering::Relaxed) + len > STREAM_WINDOW {
+                        tracing::error!(
+                            peer = %endpoint.peer,
+                            "peer exceeded the window of stream {}", id
+                        );
+                        break;
+                    }
+                    let _ = incoming.parts.send(part);
+                }
+            }
+            MuxFrame::Close(id) => endpoint.detach(id),
+            MuxFrame::Credit(id, len) => {
+                // Credit for streams that are done writing is dropped.
+                if let Some(credit) = endpoint.credits.lock().unwrap().get(&id) {
+                    if credit.available_permits() + len > STREAM_WINDOW {
+                        tracing::error!(
+                            peer = %endpoint.peer,
+                            "peer returned excess credit for stream {}", id
+                        );
+                        break;
+                    }
+                    credit.add_permits(len);
+                }
+            }
+        }
+    }
+    endpoint.close();
+    metrics::CHANNEL_MUX_LINKS.add(
+        -1,
+        hyperactor_telemetry::kv_pairs!("peer" => endpoint.peer.to_string()),
+    );
+}
+
+/// Forward data written by a session to the connection, as the
+/// stream's credit allows.
+async fn pump_out<R: AsyncRead + Unpin>(
+    id: u64,
+    mut reader: R,
+    credit: &Semaphore,
+    frames: &mpsc::Sender<MuxFrame>,
+    closed: &CancellationToken,
+) {
+    loop {
+        let mut buf = BytesMut::with_capacity(STREAM_BUFFER_SIZE);
+        let result = tokio::select! {
+            _ = closed.cancelled() => return,
+            result = reader.read_buf(&mut buf) => result,
+        };
+        match result {
+            Ok(0) | Err(_) => break,
+            Ok(len) => {
+                // Wait until the peer has room for the data.
+                let permits = tokio::select! {
+                    _ = closed.cancelled() => return,
+                    permits = credit.acquire_many(len as u32) => permits,
+                };
+                match permits {
+                    Ok(permits) => permits.forget(),
+                    Err(_) => return,
+                }
+                if frames
+                    .send(MuxFrame::Data(id, Part::from(buf.freeze())))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+    let _ = frames.send(MuxFrame::Close(id)).await;
+}
+
+/// Forward data received from the connection to a session, returning
+/// credit to the peer as the session reads it. The session sees the end
+/// of its stream once the stream is detached.
+async fn pump_in<W: AsyncWrite + Unpin>(
+    id: u64,
+    mut writer: W,
+    mut parts: mpsc::UnboundedReceiver<Part>,
+    queued: Arc<AtomicUsize>,
+    frames: mpsc::Sender<MuxFrame>,
+) {
+    // Credit is returned in batches, rather than with a frame per part.
+    let mut consumed = 0;
+    while let Some(part) = parts.recv().await {
+        let len = part.len();
+        for fragment in part.into_inner() {
+            if writer.write_all(&fragment).await.is_err() {
+                return;
+            }
+        }
+        queued.fetch_sub(len, Ordering::Relaxed);
+        consumed += len;
+        if consumed >= STREAM_WINDOW / 4 {
+            // The peer no longer needs credit once the connection is closed.
+            let _ = frames.send(MuxFrame::Credit(id, consumed)).await;
+            consumed = 0;
+        }
+    }
+    let _ = writer.shutdown().await;
+}
+
+/// The shared client side of multiplexed links to a destination.
+struct Mux<L: Link> {
+    link: L,
+    endpoint: tokio::sync::Mutex<Option<Arc<Endpoint>>>,
+    next_stream_id: AtomicU64,
+}
+
+impl<L: Link> Mux<L> {
+    /// Open a new logical stream, (re)connecting to the destination
+    /// if there is no live connection.
+    async fn open(&self) -> Result<DuplexStream, ClientError> {
+        let mut endpoint = self.endpoint.lock().await;
+        if endpoint
+            .as_ref()
+            .is_none_or(|endpoint| endpoint.is_closed())
+        {
+            *endpoint = Some(self.connect().await?);
+        }
+        let endpoint = endpoint.as_ref().expect("connected");
+        let id = self.next_stream_id.fetch_add(1, Ordering::Relaxed);
+        let stream = endpoint.attach(id);
+        // The open frame is queued before the stream is returned, and
+        // thus before any of its data.
+        endpoint
+            .frames
+            .send(MuxFrame::Open(id))
+            .await
+            .map_err(|_| {
+                ClientError::Io(
+                    self.link.dest(),
+                    io::Error::new(io::ErrorKind::BrokenPipe, "mux link closed"),
+                )
+            })?;
+        Ok(stream)
+    }
+
+    async fn connect(&self) -> Result<Arc<Endpoint>, ClientError> {
+        let stream = self.link.connect().await?;
+        let message = serde_multipart::serialize_bincode(&Frame::<()>::Mux)
+            .map_err(|err| ClientError::Serialize(self.link.dest(), *err))?;
+        let max = hyperactor_config::global::get(config::CODEC_MAX_FRAME_LENGTH);
+        let stream = FrameWrite::write_frame(stream, message.framed(), max)
+            .await
+            .map_err(|(_, err)| ClientError::Io(self.link.dest(), err))?;
+        let (reader, writer) = tokio::io::split(stream);
+        Ok(Endpoint::start(
+            FrameReader::new(reader, max),
+            writer,
+            self.link.dest(),
+            None,
+        ))
+    }
+}
+
+impl<L: Link> Drop for Mux<L> {
+    fn drop(&mut self) {
+        if let Some(endpoint) = self.endpoint.get_mut().take() {
+            endpoint.close();
+        }
+    }
+}
+
+/// Multiplexers by destination. Entries are dropped along with the last
+/// link to their destination.
+static MUXES: LazyLock<Mutex<HashMap<ChannelAddr, Weak<dyn Any + Send + Sync>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A link whose sessions are multiplexed over a connection shared with
+/// all other multiplexed links to the same destination.
+pub(crate) struct MuxLink<L: Link>(Arc<Mux<L>>);
+
+impl<L: Link + 'static> MuxLink<L> {
+    /// Multiplex the given link, sharing its connection with existing
+    /// multiplexed links to the same destination.
+    pub(crate) fn new(link: L) -> Self {
+        let dest = link.dest();
+        let mut muxes = MUXES.lock().unwrap();
+        if let Some(mux) = muxes
+            .get(&dest)
+            .and_then(Weak::upgrade)
+            .and_then(|mux| mux.downcast::<Mux<L>>().ok())
+        {
+            return Self(mux);
+        }
+        let mux = Arc::new(Mux {
+            link,
+            endpoint: tokio::sync::Mutex::new(None),
+            next_stream_id: AtomicU64::new(0),
+        });
+        muxes.retain(|_, mux| mux.strong_count() > 0);
+        let weak: Weak<dyn Any + Send + Sync> = Arc::downgrade(&mux) as Weak<Mux<L>>;
+        muxes.insert(dest, weak);
+        Self(mux)
+    }
+}
+
+impl<L: Link> fmt::Debug for MuxLink<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MuxLink").field(&self.0.link).finish()
+    }
+}
+
+#[async_trait]
+impl<L: Link + 'static> Link for MuxLink<L> {
+    type Stream = DuplexStream;
+
+    fn dest(&self) -> ChannelAddr {
+        self.0.link.dest()
+    }
+
+    async fn connect(&self) -> Result<Self::Stream, ClientError> {
+        self.0.open().await
+    }
+}
+
+/// Serve a connection that has switched to carrying multiplexed
+/// sessions, serving each of its logical streams with `manager`.
+pub(super) async fn serve<S, M>(
+    reader: FrameReader<ReadHalf<S>>,
+    writer: WriteHalf<S>,
+    source: ChannelAddr,
+    dest: ChannelAddr,
+    manager: SessionManager,
+    tx: mpsc::Sender<M>,
+    cancel_token: CancellationToken,
+) -> Result<(), anyhow::Error>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    M: RemoteMessage,
+{
+    let (accepted, mut accepted_rx) = mpsc::unbounded_channel();
+    let endpoint = Endpoint::start(reader, writer, source.clone(), Some(accepted));
+    let mut sessions: JoinSet<Result<(), anyhow::Error>> = JoinSet::new();
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break,
+            stream = accepted_rx.recv() => {
+                let Some(stream) = stream else {
+                    // The connection was closed.
+                    break;
+                };
+                let mut conn = ServerConn::new(stream, source.clone(), dest.clone());
+                let manager = manager.clone();
+                let tx = tx.clone();
+                let cancel_token = cancel_token.child_token();
+                sessions.spawn(async move {
+                    match conn.handshake::<M>().await.context("while serving handshake")? {
+                        Handshake::Session(session_id) => {
+                            manager.serve_session(conn, session_id, tx, cancel_token).await
+                        }
+                        Handshake::Stripe(transfer, index) => {
+                            let (reader, ..) = conn.into_parts();
+                            super::stripe::serve(reader, transfer, index, cancel_token).await
+                        }
+                        Handshake::Mux => anyhow::bail!("nested multiplexed connection"),
+                    }
+                });
+            }
+            Some(result) = sessions.join_next(), if !sessions.is_empty() => {
+                if let Ok(Err(err)) = result {
+                    tracing::info!(
+                        source = %source,
+                        dest = %dest,
+                        error = ?err,
+                        "error processing multiplexed session"
+                    );
+                }
+            }
+        }
+    }
+    // Let the sessions finish, and flush what they wrote (e.g., their
+    // final acks), before closing the connection.
+    while sessions.join_next().await.is_some() {}
+    endpoint.shutdown().await;
+    Ok(())
+}
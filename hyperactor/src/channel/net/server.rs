@@ -89,11 +89,16 @@ pub(super) struct ServerConn<S> {
 impl<S: AsyncRead + AsyncWrite> ServerConn<S> {
     pub(super) fn new(stream: S, source: ChannelAddr, dest: ChannelAddr) -> Self {
         let (reader, writer) = tokio::io::split(stream);
+        let mut reader = FrameReader::new(
+            reader,
+            hyperactor_config::global::get(config::CODEC_MAX_FRAME_LENGTH),
+        );
+        let spill_threshold = hyperactor_config::global::get(config::CODEC_SPILL_THRESHOLD);
+        if spill_threshold > 0 {
+            reader = reader.spill_above(spill_threshold);
+        }
         Self {
-            reader: FrameReader::new(
-                reader,
-                hyperactor_config::global::get(config::CODEC_MAX_FRAME_LENGTH),
-            ),
+            reader,
             write_state: WriteState::Idle(writer),
             source,
             dest,
@@ -101,6 +106,35 @@ impl<S: AsyncRead + AsyncWrite> ServerConn<S> {
     }
 }
 
+/// The result of a connection handshake.
+pub(super) enum Handshake {
+    /// The connection carries the session with the given id.
+    Session(u64),
+    /// The connection carries multiplexed sessions; see [`super::mux`].
+    Mux,
+    /// The connection carries the stripe with the given index of the
+    /// striped transfer with the given id; see [`super::stripe`].
+    Stripe(u64, usize),
+}
+
+impl<S> ServerConn<S> {
+    /// Take apart a connection that has completed its handshake,
+    /// returning its reader, writer, and source and destination addresses.
+    pub(super) fn into_parts(
+        self,
+    ) -> (
+        FrameReader<ReadHalf<S>>,
+        WriteHalf<S>,
+        ChannelAddr,
+        ChannelAddr,
+    ) {
+        let Ok(writer) = self.write_state.into_idle() else {
+            panic!("illegal state");
+        };
+        (self.reader, writer, self.source, self.dest)
+    }
+}
+
 #[derive(Debug)]
 enum RejectConn {
     /// Reject the connection due to the given error.
@@ -112,7 +146,7 @@ enum RejectConn {
 }
 
 impl<S: AsyncRead + AsyncWrite + Send + 'static + Unpin> ServerConn<S> {
-    async fn handshake<M: RemoteMessage>(&mut self) -> Result<u64, anyhow::Error> {
+    pub(super) async fn handshake<M: RemoteMessage>(&mut self) -> Result<Handshake, anyhow::Error> {
         let Some(frame) = self
             .reader
             .next()
@@ -122,11 +156,14 @@ impl<S: AsyncRead + AsyncWrite + Send + 'static + Unpin> ServerConn<S> {
             anyhow::bail!("end of stream before first frame from {}", self.source);
         };
         let message = serde_multipart::Message::from_framed(frame)?;
-        let Frame::Init(session_id) = serde_multipart::deserialize_bincode::<Frame<M>>(message)?
-        else {
-            anyhow::bail!("unexpected initial frame from {}", self.source);
-        };
-        Ok(session_id)
+        match serde_multipart::deserialize_bincode::<Frame<M>>(message)? {
+            Frame::Init(session_id) => Ok(Handshake::Session(session_id)),
+            Frame::Mux => Ok(Handshake::Mux),
+            Frame::Stripe(transfer, index) => Ok(Handshake::Stripe(transfer, index)),
+            Frame::Message(..) | Frame::Striped(_) => {
+                anyhow::bail!("unexpected initial frame from {}", self.source)
+            }
+        }
     }
 
     async fn process_step<M: RemoteMessage>(
@@ -244,7 +281,7 @@ impl<S: AsyncRead + AsyncWrite + Send + 'static + Unpin> ServerConn<S> {
                 };
 
                 // De-frame the multi-part message.
-                let bytes_len = bytes.len();
+                let mut bytes_len = bytes.len();
                 let message = match serde_multipart::Message::from_framed(bytes) {
                     Ok(message) => message,
                     Err(err) => {
@@ -273,8 +310,35 @@ impl<S: AsyncRead + AsyncWrite + Send + 'static + Unpin> ServerConn<S> {
 
                 // Finally decode the message. This assembles the M-typed message
                 // from its constituent parts.
-                match serde_multipart::deserialize_bincode(message) {
-                    Ok(Frame::Init(_)) => {
+                let frame = match serde_multipart::deserialize_bincode(message) {
+                    // The parts of striped messages are carried by their stripes,
+                    // which must be received before the message can be decoded.
+                    Ok(Frame::Striped(header)) => {
+                        let reassembled = tokio::select! {
+                            _ = cancel_token.cancelled() => return (next, Some((Ok(()), RejectConn::ServerClosing))),
+                            reassembled = header.reassemble() => reassembled,
+                        };
+                        match reassembled {
+                            Ok(message) => {
+                                bytes_len = message.frame_len();
+                                serde_multipart::deserialize_bincode(message)
+                            }
+                            Err(err) => {
+                                return (
+                                    next,
+                                    Some((
+                                        Err::<(), anyhow::Error>(err)
+                                            .context(format!("{log_id}: receiving striped message")),
+                                        RejectConn::No,
+                                    )),
+                                )
+                            }
+                        }
+                    }
+                    frame => frame,
+                };
+                match frame {
+                    Ok(Frame::Init(_) | Frame::Mux | Frame::Stripe(..) | Frame::Striped(_)) => {
                         return (
                             next,
                             Some((
@@ -634,11 +698,39 @@ impl SessionManager {
         S: AsyncRead + AsyncWrite + Send + 'static + Unpin,
         M: RemoteMessage,
     {
-        let session_id = conn
+        match conn
             .handshake::<M>()
             .await
-            .context("while serving handshake")?;
+            .context("while serving handshake")?
+        {
+            Handshake::Session(session_id) => {
+                self.serve_session(conn, session_id, tx, cancel_token).await
+            }
+            Handshake::Mux => {
+                let (reader, writer, source, dest) = conn.into_parts();
+                super::mux::serve(reader, writer, source, dest, self.clone(), tx, cancel_token)
+                    .await
+            }
+            Handshake::Stripe(transfer, index) => {
+                let (reader, ..) = conn.into_parts();
+                super::stripe::serve(reader, transfer, index, cancel_token).await
+            }
+        }
+    }
 
+    /// Serve the session with the given id over a connection that has
+    /// completed its handshake.
+    pub(super) async fn serve_session<S, M>(
+        &self,
+        mut conn: ServerConn<S>,
+        session_id: u64,
+        tx: mpsc::Sender<M>,
+        cancel_token: CancellationToken,
+    ) -> Result<(), anyhow::Error>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static + Unpin,
+        M: RemoteMessage,
+    {
         let session_var = match self.sessions.entry(session_id) {
             Entry::Occupied(entry) => entry.get().clone(),
             Entry::Vacant(entry) => {
@@ -0,0 +1,404 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Striping of large messages across parallel connections.
+//!
+//! A message sent over a single connection is limited to the bandwidth
+//! of a single flow. When the parts of a message add up to at least
+//! [`config::CHANNEL_STRIPE_THRESHOLD`] bytes, and
+//! [`config::CHANNEL_STRIPE_COUNT`] is greater than 1, the client splits
+//! the bytes of the parts into contiguous stripes of roughly equal size,
+//! and sends each stripe over its own connection to the destination,
+//! starting with a [`Frame::Stripe`] frame in place of the usual
+//! [`Frame::Init`]. The message itself is then sent over the session as
+//! a [`Frame::Striped`] frame, carrying its body and a [`Header`]
+//! describing the transfer.
+//!
+//! The server holds on to received stripes until the session reaches
+//! the header, at which point it reassembles the parts (without copying)
+//! and decodes the message as usual, so that striped messages keep their
+//! place in the session's sequence. If the stripes do not all arrive
+//! within [`config::MESSAGE_DELIVERY_TIMEOUT`], the session's connection
+//! is dropped, and the client retransmits the message in a new transfer.
+//! Stripes that are never claimed by a session, and the slots of
+//! transfers whose session gave up or was dropped, are evicted so that
+//! abandoned transfers do not stay resident.
+//!
+//! Stripes are sent over the session's link. If the link is multiplexed
+//! (see [`super::mux`]), the stripes share its single connection, and
+//! striping does not increase throughput.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use bytes::Bytes;
+use serde_multipart::Message;
+use serde_multipart::Part;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+
+use super::*;
+use crate::channel::net::framed::FrameReader;
+use crate::channel::net::framed::FrameWrite;
+use crate::config;
+use crate::metrics;
+
+/// Describes the striped transfer carrying the parts of a message.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(super) struct Header {
+    /// The id of the transfer.
+    transfer: u64,
+    /// The number of stripes in the transfer.
+    stripes: usize,
+    /// The length of each part of the message.
+    part_lens: Vec<usize>,
+    /// The body of the message.
+    body: Part,
+}
+
+impl Header {
+    /// Wait for the stripes of the transfer, and reassemble the message
+    /// from them.
+    pub(super) async fn reassemble(self) -> Result<Message, anyhow::Error> {
+        let timeout = hyperactor_config::global::get(config::MESSAGE_DELIVERY_TIMEOUT);
+        // Evicts the transfer however this future ends, including when
+        // the session is dropped while it is pending.
+        let _evict = EvictOnDrop {
+            transfer: self.transfer,
+            stripes: self.stripes,
+        };
+        let receivers = (0..self.stripes)
+            .map(|index| {
+                with_slot(self.transfer, index, |slot| slot.receiver.take()).with_context(|| {
+                    format!(
+                        "stripe {} of transfer {} is already awaited",
+                        index, self.transfer
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let stripes = RealClock
+            .timeout(timeout, futures::future::try_join_all(receivers))
+            .await
+            .with_context(|| format!("waiting for the stripes of transfer {}", self.transfer))?
+            .with_context(|| format!("a stripe of transfer {} was dropped", self.transfer))?;
+        let parts = join(stripes, &self.part_lens)
+            .with_context(|| format!("reassembling transfer {}", self.transfer))?;
+        Ok(Message::from_body_and_parts(self.body, parts))
+    }
+}
+
+/// Tells whether the parts of the given message should be striped.
+pub(super) fn should_stripe(message: &Message) -> bool {
+    let count = hyperactor_config::global::get(config::CHANNEL_STRIPE_COUNT);
+    let threshold = hyperactor_config::global::get(config::CHANNEL_STRIPE_THRESHOLD);
+    let max = hyperactor_config::global::get(config::CODEC_MAX_FRAME_LENGTH);
+    let len: usize = message.parts().iter().map(Part::len).sum();
+    // Oversized messages are left to be rejected by the session.
+    count > 1 && len >= threshold.max(1) && message.frame_len() <= max
+}
+
+/// Send the parts of the given message to the link's destination over a
+/// striped transfer, returning the message to send over the session in
+/// its place.
+pub(super) async fn send<L: Link, M: RemoteMessage>(
+    link: &L,
+    message: Message,
+) -> Result<Message, ClientError> {
+    let count = hyperactor_config::global::get(config::CHANNEL_STRIPE_COUNT);
+    let transfer = rand::random();
+    let (body, parts) = message.into_inner();
+    let part_lens: Vec<usize> = parts.iter().map(Part::len).collect();
+    let stripes = split(parts, count);
+    let num_stripes = stripes.len();
+
+    futures::future::try_join_all(
+        stripes
+            .into_iter()
+            .enumerate()
+            .map(|(index, stripe)| send_stripe(link, transfer, index, stripe)),
+    )
+    .await?;
+
+    let dest = link.dest();
+    metrics::CHANNEL_STRIPED_MESSAGES.add(
+        1,
+        hyperactor_telemetry::kv_pairs!("dest" => dest.to_string()),
+    );
+    metrics::CHANNEL_STRIPED_BYTES.add(
+        part_lens.iter().sum::<usize>() as u64,
+        hyperactor_telemetry::kv_pairs!("dest" => dest.to_string()),
+    );
+    serde_multipart::serialize_bincode(&Frame::<M>::Striped(Header {
+        transfer,
+        stripes: num_stripes,
+        part_lens,
+        body,
+    }))
+    .map_err(|err| ClientError::Serialize(dest, *err))
+}
+
+/// Send a single stripe over its own connection.
+async fn send_stripe<L: Link>(
+    link: &L,
+    transfer: u64,
+    index: usize,
+    stripe: Part,
+) -> Result<(), ClientError> {
+    let max = hyperactor_config::global::get(config::CODEC_MAX_FRAME_LENGTH);
+    let stream = link.connect().await?;
+    let init = serde_multipart::serialize_bincode(&Frame::<()>::Stripe(transfer, index))
+        .map_err(|err| ClientError::Serialize(link.dest(), *err))?;
+    let stream = FrameWrite::write_frame(stream, init.framed(), max)
+        .await
+        .map_err(|(_, err)| ClientError::Io(link.dest(), err))?;
+    let data = Message::from_body_and_parts(Part::default(), vec![stripe]);
+    let mut stream = FrameWrite::write_frame(stream, data.framed(), max)
+        .await
+        .map_err(|(_, err)| ClientError::Io(link.dest(), err))?;
+    stream
+        .shutdown()
+        .await
+        .map_err(|err| ClientError::Io(link.dest(), err))
+}
+
+/// Receive the stripe with the given index of the given transfer over a
+/// connection that has completed its handshake.
+pub(super) async fn serve<R: AsyncRead + Unpin>(
+    mut reader: FrameReader<R>,
+    transfer: u64,
+    index: usize,
+    cancel_token: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let bytes = tokio::select! {
+        _ = cancel_token.cancelled() => return Ok(()),
+        result = reader.next() => result?
+            .with_context(|| format!("end of stream before stripe {} of transfer {}", index, transfer))?,
+    };
+    let (_, parts) = Message::from_framed(bytes)?.into_inner();
+    let Ok([stripe]) = <[Part; 1]>::try_from(parts) else {
+        anyhow::bail!("malformed stripe {} of transfer {}", index, transfer);
+    };
+    let sender = with_slot(transfer, index, |slot| slot.sender.take())
+        .with_context(|| format!("duplicate stripe {} of transfer {}", index, transfer))?;
+    // The receiver is gone if the transfer was abandoned.
+    if sender.send(stripe).is_ok() {
+        // Evict the stripe if no session claims it in time.
+        let timeout = hyperactor_config::global::get(config::MESSAGE_DELIVERY_TIMEOUT);
+        tokio::spawn(async move {
+            RealClock.sleep(timeout).await;
+            evict(transfer, index);
+        });
+    }
+    Ok(())
+}
+
+/// Split the bytes of the given parts into at most `count` contiguous
+/// stripes of roughly equal size, without copying.
+fn split(parts: Vec<Part>, count: usize) -> Vec<Part> {
+    let len: usize = parts.iter().map(Part::len).sum();
+    let stripe_len = len.div_ceil(count.max(1));
+    let mut stripes = Vec::with_capacity(count);
+    let mut stripe = Vec::new();
+    let mut remaining = stripe_len;
+    for mut fragment in parts.into_iter().flat_map(Part::into_inner) {
+        while !fragment.is_empty() {
+            let chunk = fragment.split_to(remaining.min(fragment.len()));
+            remaining -= chunk.len();
+            stripe.push(chunk);
+            if remaining == 0 {
+                stripes.push(Part::from_fragments(std::mem::take(&mut stripe)));
+                remaining = stripe_len;
+            }
+        }
+    }
+    if !stripe.is_empty() {
+        stripes.push(Part::from_fragments(stripe));
+    }
+    stripes
+}
+
+/// Join the bytes of the given stripes, and split them into parts of the
+/// given lengths, without copying.
+fn join(stripes: Vec<Part>, part_lens: &[usize]) -> Result<Vec<Part>, anyhow::Error> {
+    let mut fragments = stripes
+        .into_iter()
+        .flat_map(Part::into_inner)
+        .filter(|fragment| !fragment.is_empty());
+    let mut fragment = Bytes::new();
+    let mut parts = Vec::with_capacity(part_lens.len());
+    for &len in part_lens {
+        let mut part = Vec::new();
+        let mut remaining = len;
+        while remaining > 0 {
+            if fragment.is_empty() {
+                fragment = fragments.next().context("stripes are too short")?;
+            }
+            let chunk = fragment.split_to(remaining.min(fragment.len()));
+            remaining -= chunk.len();
+            part.push(chunk);
+        }
+        parts.push(Part::from_fragments(part));
+    }
+    if !fragment.is_empty() || fragments.next().is_some() {
+        anyhow::bail!("stripes are too long");
+    }
+    Ok(parts)
+}
+
+/// Rendezvous between a stripe and the session awaiting it; whichever
+/// arrives first creates the slot.
+struct Slot {
+    sender: Option<oneshot::Sender<Part>>,
+    receiver: Option<oneshot::Receiver<Part>>,
+}
+
+/// Slots by transfer and stripe index.
+static SLOTS: LazyLock<Mutex<HashMap<(u64, usize), Slot>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Take a half of the slot for the given stripe, creating the slot if
+/// needed. The slot is removed once both of its halves are taken.
+fn with_slot<T>(
+    transfer: u64,
+    index: usize,
+    take: impl FnOnce(&mut Slot) -> Option<T>,
+) -> Option<T> {
+    let mut slots = SLOTS.lock().unwrap();
+    let slot = slots.entry((transfer, index)).or_insert_with(|| {
+        let (sender, receiver) = oneshot::channel();
+        Slot {
+            sender: Some(sender),
+            receiver: Some(receiver),
+        }
+    });
+    let taken = take(slot);
+    if slot.sender.is_none() && slot.receiver.is_none() {
+        slots.remove(&(transfer, index));
+    }
+    taken
+}
+
+/// Remove the slot for the given stripe, if any.
+fn evict(transfer: u64, index: usize) {
+    SLOTS.lock().unwrap().remove(&(transfer, index));
+}
+
+/// Removes the slots of a transfer when dropped.
+struct EvictOnDrop {
+    transfer: u64,
+    stripes: usize,
+}
+
+impl Drop for EvictOnDrop {
+    fn drop(&mut self) {
+        let mut slots = SLOTS.lock().unwrap();
+        for index in 0..self.stripes {
+            slots.remove(&(self.transfer, index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(fragments: &[&'static [u8]]) -> Part {
+        Part::from_fragments(fragments.iter().map(|f| Bytes::from_static(f)).collect())
+    }
+
+    #[test]
+    fn test_split_join() {
+        let parts = vec![
+            part(&[b"hello", b", "]),
+            part(&[]),
+            part(&[b"world"]),
+            part(&[b"!"]),
+        ];
+        let part_lens: Vec<usize> = parts.iter().map(Part::len).collect();
+        for count in 1..16 {
+            let stripes = split(parts.clone(), count);
+            assert!(stripes.len() <= count);
+            assert!(stripes.iter().all(|stripe| !stripe.is_empty()));
+            assert_eq!(stripes.iter().map(Part::len).sum::<usize>(), 13);
+
+            let joined = join(stripes, &part_lens).unwrap();
+            assert_eq!(
+                joined.iter().map(Part::to_bytes).collect::<Vec<_>>(),
+                parts.iter().map(Part::to_bytes).collect::<Vec<_>>(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_join_mismatched() {
+        let stripes = split(vec![part(&[b"hello"])], 2);
+        assert!(join(stripes.clone(), &[6]).is_err());
+        assert!(join(stripes.clone(), &[4]).is_err());
+        assert!(join(stripes, &[2, 3]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reassemble() {
+        let transfer = rand::random();
+        let parts = vec![part(&[b"striped "]), part(&[b"message"])];
+        let stripes = split(parts.clone(), 3);
+        let header = Header {
+            transfer,
+            stripes: stripes.len(),
+            part_lens: parts.iter().map(Part::len).collect(),
+            body: part(&[b"body"]),
+        };
+        // Stripes may arrive before or after the header.
+        let (early, late) = stripes.split_at(1);
+        for (index, stripe) in early.iter().enumerate() {
+            let sender = with_slot(transfer, index, |slot| slot.sender.take()).unwrap();
+            sender.send(stripe.clone()).unwrap();
+        }
+        let reassembled = tokio::spawn(header.reassemble());
+        for (index, stripe) in late.iter().enumerate() {
+            let sender =
+                with_slot(transfer, early.len() + index, |slot| slot.sender.take()).unwrap();
+            let _ = sender.send(stripe.clone());
+        }
+
+        let message = reassembled.await.unwrap().unwrap();
+        assert_eq!(message.body().to_bytes(), Bytes::from_static(b"body"));
+        assert_eq!(
+            message
+                .parts()
+                .iter()
+                .map(Part::to_bytes)
+                .collect::<Vec<_>>(),
+            parts.iter().map(Part::to_bytes).collect::<Vec<_>>(),
+        );
+        assert!((0..3).all(|index| !SLOTS.lock().unwrap().contains_key(&(transfer, index))));
+    }
+
+    #[tokio::test]
+    async fn test_reassemble_dropped() {
+        let transfer = rand::random();
+        let header = Header {
+            transfer,
+            stripes: 2,
+            part_lens: vec![2],
+            body: Part::default(),
+        };
+        let sender = with_slot(transfer, 0, |slot| slot.sender.take()).unwrap();
+        sender.send(part(&[b"a"])).unwrap();
+
+        // The session is dropped before the second stripe arrives.
+        let result = tokio::time::timeout(std::time::Duration::ZERO, header.reassemble()).await;
+        assert!(result.is_err());
+        assert!((0..2).all(|index| !SLOTS.lock().unwrap().contains_key(&(transfer, index))));
+    }
+}
@@ -0,0 +1,638 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Shared memory channel implementation for procs on the same host.
+//!
+//! The shm channel is a unix socket channel in which large message
+//! parts travel out of band. Messages are encoded as a
+//! [`serde_multipart::Message`]; parts of at least
+//! `config::CHANNEL_SHM_PART_THRESHOLD` bytes are copied once into a
+//! sealed memfd segment, whose file descriptor is passed to the
+//! receiver with `SCM_RIGHTS`. The receiver maps the segment read-only
+//! and hands it to the deserializer as [`Bytes`], so the part itself
+//! never passes through the socket.
+//!
+//! Each message is sent as a fixed-size header, which carries the
+//! segment descriptors as ancillary data, followed by the payload:
+//! ```text
+//! +--- len: u64 (BE) ---+--- nsegs: u64 (BE) ---+------------------- payload -------------------+
+//! | payload length      | number of segments    | framed `ShmFrame`: body + inline parts        |
+//! +---------------------+-----------------------+-----------------------------------------------+
+//!   SCM_RIGHTS: one memfd per segment, in the order they appear in the frame
+//! ```
+//!
+//! Segments are reclaimed by reference counting. The sender closes its
+//! descriptor once the header is sent, and the receiver closes its
+//! descriptor as soon as the segment is mapped. The mapping is owned by
+//! the [`Bytes`] handed out for the part, so the kernel frees the
+//! segment once the last clone of the part is dropped. Segments are
+//! sealed against writes and resizing before they are sent, and the
+//! receiver refuses segments that are not, so a mapped part cannot
+//! change underneath its readers.
+//!
+//! Unlike the net channels, the shm channel does not reconnect,
+//! retransmit or acknowledge messages: delivery is best-effort. If the
+//! connection fails, the message being sent and any queued after it
+//! are returned to their senders and the channel is closed, but
+//! messages already written to the socket are not: they are lost if
+//! the receiver goes away before reading them. For this reason procs
+//! are never bootstrapped onto shm channels; they must be dialed
+//! explicitly by callers that can tolerate message loss.
+
+use std::fs::File;
+use std::io;
+use std::io::IoSlice;
+use std::io::IoSliceMut;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
+
+use bytes::Buf;
+use bytes::Bytes;
+use bytes::BytesMut;
+use memmap2::Mmap;
+use nix::sys::socket::ControlMessage;
+use nix::sys::socket::ControlMessageOwned;
+use nix::sys::socket::MsgFlags;
+use nix::sys::socket::recvmsg;
+use nix::sys::socket::sendmsg;
+use serde_multipart::Part;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::Interest;
+use tokio::net::UnixListener;
+use tokio::net::UnixStream;
+use tokio::task::JoinHandle;
+use tokio::task::JoinSet;
+
+use super::*;
+use crate::config;
+
+/// The length of the fixed-size message header.
+const HEADER_LEN: usize = 16;
+
+/// The maximum number of segments passed with a single message. Parts
+/// beyond this are sent inline. This stays well below the kernel's
+/// `SCM_MAX_FD` limit of 253 descriptors per message.
+const MAX_SEGMENTS: usize = 64;
+
+/// A message part, as carried by a [`ShmFrame`].
+#[derive(Debug, Serialize, Deserialize)]
+enum ShmPart {
+    /// The part is carried inline in the payload.
+    Inline(Part),
+    /// The part is carried in the next shared memory segment passed
+    /// with the message, which has the given length.
+    Segment(u64),
+}
+
+/// The payload of a message: a multipart message whose large parts
+/// have been moved into shared memory segments.
+#[derive(Debug, Serialize, Deserialize)]
+struct ShmFrame {
+    body: Part,
+    parts: Vec<ShmPart>,
+}
+
+/// Encode a message, moving every part of at least `threshold` bytes
+/// into a sealed shared memory segment. Returns the framed payload
+/// along with the segments to pass with it.
+fn encode<M: RemoteMessage>(
+    message: &M,
+    threshold: usize,
+) -> Result<(serde_multipart::Frame, Vec<OwnedFd>), bincode::Error> {
+    let (body, parts) = serde_multipart::serialize_bincode(message)?.into_inner();
+    let mut segments = Vec::new();
+    let mut shm_parts = Vec::with_capacity(parts.len());
+    for part in parts {
+        if part.is_empty() || part.len() < threshold || segments.len() >= MAX_SEGMENTS {
+            shm_parts.push(ShmPart::Inline(part));
+            continue;
+        }
+        let len = part.len() as u64;
+        match create_segment(&part) {
+            Ok(segment) => {
+                segments.push(segment);
+                shm_parts.push(ShmPart::Segment(len));
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "failed to create shared memory segment, sending inline: {}",
+                    err
+                );
+                shm_parts.push(ShmPart::Inline(part));
+            }
+        }
+    }
+    let frame = serde_multipart::serialize_bincode(&ShmFrame {
+        body,
+        parts: shm_parts,
+    })?
+    .framed();
+    Ok((frame, segments))
+}
+
+/// Decode a message from its payload and the segments passed with it.
+fn decode<M: RemoteMessage>(payload: Bytes, segments: Vec<OwnedFd>) -> anyhow::Result<M> {
+    let frame: ShmFrame =
+        serde_multipart::deserialize_bincode(serde_multipart::Message::from_framed(payload)?)?;
+    let mut segments = segments.into_iter();
+    let mut parts = Vec::with_capacity(frame.parts.len());
+    for part in frame.parts {
+        match part {
+            ShmPart::Inline(part) => parts.push(part),
+            ShmPart::Segment(len) => {
+                let segment = segments
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("frame references a missing segment"))?;
+                parts.push(Part::from(map_segment(segment, len)?));
+            }
+        }
+    }
+    anyhow::ensure!(
+        segments.next().is_none(),
+        "message carries segments not referenced by its frame"
+    );
+    Ok(serde_multipart::deserialize_bincode(
+        serde_multipart::Message::from_body_and_parts(frame.body, parts),
+    )?)
+}
+
+/// Copy a part into a new memfd segment, sealed so that it can no
+/// longer be written or resized.
+#[cfg(target_os = "linux")]
+fn create_segment(part: &Part) -> io::Result<OwnedFd> {
+    use nix::fcntl::FcntlArg;
+    use nix::fcntl::SealFlag;
+    use nix::fcntl::fcntl;
+    use nix::sys::memfd::MFdFlags;
+    use nix::sys::memfd::memfd_create;
+
+    let fd = memfd_create(
+        c"hyperactor-shm",
+        MFdFlags::MFD_CLOEXEC | MFdFlags::MFD_ALLOW_SEALING,
+    )?;
+    let mut file = File::from(fd);
+    file.set_len(part.len() as u64)?;
+    for fragment in part.iter() {
+        file.write_all(fragment)?;
+    }
+    fcntl(
+        &file,
+        FcntlArg::F_ADD_SEALS(
+            SealFlag::F_SEAL_SHRINK
+                | SealFlag::F_SEAL_GROW
+                | SealFlag::F_SEAL_WRITE
+                | SealFlag::F_SEAL_SEAL,
+        ),
+    )?;
+    Ok(file.into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_segment(_part: &Part) -> io::Result<OwnedFd> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Map a received segment of the given length. The returned bytes own
+/// the mapping, which is released when the last reference is dropped.
+fn map_segment(segment: OwnedFd, len: u64) -> anyhow::Result<Bytes> {
+    let file = File::from(segment);
+    #[cfg(target_os = "linux")]
+    {
+        use nix::fcntl::FcntlArg;
+        use nix::fcntl::SealFlag;
+        use nix::fcntl::fcntl;
+
+        let seals = SealFlag::from_bits_truncate(fcntl(&file, FcntlArg::F_GET_SEALS)?);
+        anyhow::ensure!(
+            seals.contains(SealFlag::F_SEAL_WRITE | SealFlag::F_SEAL_SHRINK),
+            "segment is not sealed"
+        );
+    }
+    let actual = file.metadata()?.len();
+    anyhow::ensure!(
+        actual == len,
+        "segment has length {}, expected {}",
+        actual,
+        len
+    );
+    // SAFETY: the segment is sealed against writes and shrinking, so the
+    // mapped memory can neither change nor be truncated while it is mapped.
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(Bytes::from_owner(mmap))
+}
+
+/// Send a framed payload, passing the given segments along with its header.
+async fn send_frame(
+    stream: &mut UnixStream,
+    mut frame: serde_multipart::Frame,
+    segments: Vec<OwnedFd>,
+) -> io::Result<()> {
+    let mut header = [0u8; HEADER_LEN];
+    header[..8].copy_from_slice(&(frame.remaining() as u64).to_be_bytes());
+    header[8..].copy_from_slice(&(segments.len() as u64).to_be_bytes());
+
+    let fd = stream.as_raw_fd();
+    let fds: Vec<RawFd> = segments.iter().map(|segment| segment.as_raw_fd()).collect();
+    let mut cmsgs = Vec::new();
+    if !fds.is_empty() {
+        cmsgs.push(ControlMessage::ScmRights(&fds));
+    }
+    let sent = stream
+        .async_io(Interest::WRITABLE, || {
+            Ok(sendmsg::<()>(
+                fd,
+                &[IoSlice::new(&header)],
+                &cmsgs,
+                MsgFlags::MSG_NOSIGNAL,
+                None,
+            )?)
+        })
+        .await?;
+    // The kernel holds its own references to the segments once the
+    // header has been (even partially) sent.
+    drop(segments);
+
+    stream.write_all(&header[sent..]).await?;
+    stream.write_all_buf(&mut frame).await?;
+    Ok(())
+}
+
+/// Receive the next payload along with its segments. Returns `None`
+/// if the peer closed the connection on a message boundary.
+async fn recv_frame(
+    stream: &mut UnixStream,
+    max_len: usize,
+) -> io::Result<Option<(Bytes, Vec<OwnedFd>)>> {
+    let fd = stream.as_raw_fd();
+    let mut header = [0u8; HEADER_LEN];
+    let mut filled = 0;
+    let mut segments = Vec::new();
+    while filled < HEADER_LEN {
+        let received = stream
+            .async_io(Interest::READABLE, || {
+                let mut iov = [IoSliceMut::new(&mut header[filled..])];
+                let mut cmsg_buffer = nix::cmsg_space!([RawFd; MAX_SEGMENTS]);
+                let msg = recvmsg::<()>(
+                    fd,
+                    &mut iov,
+                    Some(&mut cmsg_buffer),
+                    MsgFlags::MSG_CMSG_CLOEXEC,
+                )?;
+                for cmsg in msg.cmsgs()? {
+                    if let ControlMessageOwned::ScmRights(fds) = cmsg {
+                        segments.extend(fds.into_iter().map(|fd| {
+                            // SAFETY: the descriptors were just installed
+                            // in this process by the kernel, and nothing
+                            // else owns them.
+                            unsafe { OwnedFd::from_raw_fd(fd) }
+                        }));
+                    }
+                }
+                if msg.flags.contains(MsgFlags::MSG_CTRUNC) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "segment descriptors were truncated",
+                    ));
+                }
+                Ok(msg.bytes)
+            })
+            .await?;
+        if received == 0 {
+            if filled == 0 && segments.is_empty() {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        filled += received;
+    }
+
+    let len = u64::from_be_bytes(header[..8].try_into().unwrap()) as usize;
+    let nsegs = u64::from_be_bytes(header[8..].try_into().unwrap()) as usize;
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame length {} exceeds max length {}", len, max_len),
+        ));
+    }
+    if nsegs != segments.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected {} segments, received {}", nsegs, segments.len()),
+        ));
+    }
+
+    let mut payload = BytesMut::zeroed(len);
+    stream.read_exact(&mut payload).await?;
+    Ok(Some((payload.freeze(), segments)))
+}
+
+/// A Tx for the shm channel. Messages are sent over a single
+/// connection, managed by a background task.
+pub(crate) struct ShmTx<M: RemoteMessage> {
+    sender: mpsc::UnboundedSender<(M, oneshot::Sender<SendError<M>>)>,
+    dest: ChannelAddr,
+    status: watch::Receiver<TxStatus>,
+}
+
+#[async_trait]
+impl<M: RemoteMessage> Tx<M> for ShmTx<M> {
+    fn addr(&self) -> ChannelAddr {
+        self.dest.clone()
+    }
+
+    fn status(&self) -> &watch::Receiver<TxStatus> {
+        &self.status
+    }
+
+    fn do_post(&self, message: M, return_channel: Option<oneshot::Sender<SendError<M>>>) {
+        tracing::trace!(
+            name = "post",
+            dest = %self.dest,
+            "sending message"
+        );
+
+        let return_channel = return_channel.unwrap_or_else(|| oneshot::channel().0);
+        if let Err(mpsc::error::SendError((message, return_channel))) =
+            self.sender.send((message, return_channel))
+        {
+            let _ = return_channel.send(SendError {
+                error: ChannelError::Closed,
+                message,
+                reason: None,
+            });
+        }
+    }
+}
+
+/// Dial the shm channel served at the given socket address.
+pub(crate) fn dial<M: RemoteMessage>(addr: net::unix::SocketAddr) -> ShmTx<M> {
+    let dest = ChannelAddr::Shm(addr.clone());
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let (status_sender, status) = watch::channel(TxStatus::Active);
+    tokio::spawn(run_tx(addr, dest.clone(), receiver, status_sender));
+    ShmTx {
+        sender,
+        dest,
+        status,
+    }
+}
+
+/// Connect to the destination and send queued messages until either the
+/// Tx is dropped or the connection fails. Once the connection fails,
+/// all outstanding and subsequent messages are returned to their senders.
+async fn run_tx<M: RemoteMessage>(
+    addr: net::unix::SocketAddr,
+    dest: ChannelAddr,
+    mut receiver: mpsc::UnboundedReceiver<(M, oneshot::Sender<SendError<M>>)>,
+    status: watch::Sender<TxStatus>,
+) {
+    match net::unix::connect(&addr, dest.clone()) {
+        Ok(mut stream) => {
+            while let Some((message, return_channel)) = receiver.recv().await {
+                let threshold = hyperactor_config::global::get(config::CHANNEL_SHM_PART_THRESHOLD);
+                let (frame, segments) = match encode(&message, threshold) {
+                    Ok(encoded) => encoded,
+                    Err(err) => {
+                        let _ = return_channel.send(SendError {
+                            error: err.into(),
+                            message,
+                            reason: None,
+                        });
+                        continue;
+                    }
+                };
+                if let Err(err) = send_frame(&mut stream, frame, segments).await {
+                    tracing::info!(dest = %dest, "shm channel connection failed: {}", err);
+                    let _ = return_channel.send(SendError {
+                        error: ChannelError::Closed,
+                        message,
+                        reason: None,
+                    });
+                    break;
+                }
+            }
+        }
+        Err(err) => tracing::info!(dest = %dest, "failed to connect shm channel: {}", err),
+    }
+
+    let _ = status.send(TxStatus::Closed);
+    receiver.close();
+    while let Some((message, return_channel)) = receiver.recv().await {
+        let _ = return_channel.send(SendError {
+            error: ChannelError::Closed,
+            message,
+            reason: None,
+        });
+    }
+}
+
+/// An Rx for the shm channel. Dropping the Rx stops the server and
+/// closes all of its connections.
+pub(crate) struct ShmRx<M: RemoteMessage> {
+    receiver: mpsc::Receiver<M>,
+    addr: ChannelAddr,
+    server: JoinHandle<()>,
+}
+
+#[async_trait]
+impl<M: RemoteMessage> Rx<M> for ShmRx<M> {
+    async fn recv(&mut self) -> Result<M, ChannelError> {
+        tracing::trace!(
+            name = "recv",
+            source = %self.addr,
+            "receiving message"
+        );
+        self.receiver.recv().await.ok_or(ChannelError::Closed)
+    }
+
+    fn addr(&self) -> ChannelAddr {
+        self.addr.clone()
+    }
+}
+
+impl<M: RemoteMessage> Drop for ShmRx<M> {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Serve an shm channel at the given socket address.
+pub(crate) fn serve<M: RemoteMessage>(
+    addr: net::unix::SocketAddr,
+) -> Result<(ChannelAddr, ShmRx<M>), ServerError> {
+    let (local_addr, listener) = net::unix::bind(&addr, ChannelAddr::Shm(addr.clone()))?;
+    let addr = ChannelAddr::Shm(net::unix::SocketAddr::new(local_addr));
+    let (sender, receiver) = mpsc::channel::<M>(1024);
+    let server = tokio::spawn(listen(listener, addr.clone(), sender));
+    Ok((
+        addr.clone(),
+        ShmRx {
+            receiver,
+            addr,
+            server,
+        },
+    ))
+}
+
+/// Accept connections, serving each on its own task. Connection tasks
+/// are aborted when this task is.
+async fn listen<M: RemoteMessage>(
+    listener: UnixListener,
+    addr: ChannelAddr,
+    sender: mpsc::Sender<M>,
+) {
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            result = listener.accept() => match result {
+                Ok((stream, _)) => {
+                    connections.spawn(serve_connection(stream, addr.clone(), sender.clone()));
+                }
+                Err(err) => {
+                    tracing::error!(addr = %addr, "shm channel failed to accept: {}", err);
+                    break;
+                }
+            },
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+}
+
+/// Receive messages from a single connection until it is closed.
+async fn serve_connection<M: RemoteMessage>(
+    mut stream: UnixStream,
+    addr: ChannelAddr,
+    sender: mpsc::Sender<M>,
+) {
+    let max_len = hyperactor_config::global::get(config::CODEC_MAX_FRAME_LENGTH);
+    loop {
+        let (payload, segments) = match recv_frame(&mut stream, max_len).await {
+            Ok(Some(received)) => received,
+            Ok(None) => break,
+            Err(err) => {
+                tracing::error!(addr = %addr, "shm channel connection failed: {}", err);
+                break;
+            }
+        };
+        // Framing is independent of decoding, so a message that fails to
+        // decode does not poison the rest of the connection.
+        match decode::<M>(payload, segments) {
+            Ok(message) => {
+                if sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+            Err(err) => tracing::error!(
+                addr = %addr,
+                "shm channel failed to decode message with M = {}: {:#}",
+                std::any::type_name::<M>(),
+                err
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate as hyperactor; // for macros
+    use crate::Named;
+
+    #[derive(Named, Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct TestMessage {
+        seq: u64,
+        parts: Vec<Part>,
+    }
+
+    fn test_message(seq: u64, sizes: &[usize]) -> TestMessage {
+        TestMessage {
+            seq,
+            parts: sizes
+                .iter()
+                .map(|size| Part::from(vec![seq as u8; *size]))
+                .collect(),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_encode_decode() {
+        let message = test_message(7, &[16, 4096, 0, 1024, 1023]);
+        let (mut frame, segments) = encode(&message, 1024).unwrap();
+        assert_eq!(segments.len(), 2);
+        // Only the small parts are carried inline.
+        assert!(frame.remaining() < 4096 + 1024);
+        let payload = frame.copy_to_bytes(frame.remaining());
+        assert_eq!(decode::<TestMessage>(payload, segments).unwrap(), message);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_decode_rejects_unsealed_segment() {
+        use nix::sys::memfd::MFdFlags;
+        use nix::sys::memfd::memfd_create;
+
+        let message = test_message(1, &[4096]);
+        let (mut frame, _segments) = encode(&message, 1024).unwrap();
+        let payload = frame.copy_to_bytes(frame.remaining());
+
+        let unsealed = File::from(memfd_create(c"test", MFdFlags::MFD_CLOEXEC).unwrap());
+        unsealed.set_len(4096).unwrap();
+        let err = decode::<TestMessage>(payload, vec![unsealed.into()]).unwrap_err();
+        assert!(err.to_string().contains("not sealed"), "{}", err);
+    }
+
+    #[test]
+    fn test_encode_below_threshold() {
+        let message = test_message(3, &[16, 4096]);
+        let (mut frame, segments) = encode(&message, usize::MAX).unwrap();
+        assert!(segments.is_empty());
+        let payload = frame.copy_to_bytes(frame.remaining());
+        assert_eq!(decode::<TestMessage>(payload, segments).unwrap(), message);
+    }
+
+    #[cfg(target_os = "linux")] // uses abstract names
+    #[tokio::test]
+    async fn test_roundtrip() {
+        let (addr, mut rx) =
+            crate::channel::serve::<TestMessage>(ChannelAddr::any(ChannelTransport::Shm)).unwrap();
+        assert_eq!(addr.transport(), ChannelTransport::Shm);
+        let tx = crate::channel::dial::<TestMessage>(addr).unwrap();
+
+        let messages: Vec<_> = (0..10u64)
+            .map(|seq| test_message(seq, &[8, 1024 * 1024, 128 * 1024]))
+            .collect();
+        for message in &messages {
+            tx.post(message.clone());
+        }
+        for message in messages {
+            assert_eq!(rx.recv().await.unwrap(), message);
+        }
+    }
+
+    #[cfg(target_os = "linux")] // uses abstract names
+    #[tokio::test]
+    async fn test_dial_unserved() {
+        let tx =
+            crate::channel::dial::<TestMessage>(ChannelAddr::any(ChannelTransport::Shm)).unwrap();
+        let (return_tx, return_rx) = oneshot::channel();
+        tx.try_post(test_message(0, &[]), return_tx);
+        assert!(matches!(
+            return_rx.await,
+            Ok(SendError {
+                error: ChannelError::Closed,
+                ..
+            })
+        ));
+        assert_eq!(*tx.status().borrow(), TxStatus::Closed);
+    }
+}
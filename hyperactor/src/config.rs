@@ -27,6 +27,16 @@ declare_attrs! {
     })
     pub attr CODEC_MAX_FRAME_LENGTH: usize = 10 * 1024 * 1024 * 1024; // 10 GiB
 
+    /// Frames received by net channels that are longer than this are
+    /// spilled to a temporary file as they are read, rather than buffered
+    /// in memory; the parts of their messages are then backed by the file.
+    /// If set to zero, frames are never spilled.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_CODEC_SPILL_THRESHOLD".to_string()),
+        py_name: None,
+    })
+    pub attr CODEC_SPILL_THRESHOLD: usize = 0;
+
     /// Message delivery timeout
     @meta(CONFIG = ConfigAttr {
         env_name: Some("HYPERACTOR_MESSAGE_DELIVERY_TIMEOUT".to_string()),
@@ -114,6 +124,41 @@ declare_attrs! {
     })
     pub attr CHANNEL_NET_RX_BUFFER_FULL_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 
+    /// Whether net channels dialed to the same destination share a
+    /// single multiplexed connection, rather than each opening their
+    /// own. Sessions keep their own ordering and acks either way.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_CHANNEL_MULTIPLEX_LINKS".to_string()),
+        py_name: None,
+    })
+    pub attr CHANNEL_MULTIPLEX_LINKS: bool = false;
+
+    /// Number of parallel connections over which the parts of a large
+    /// message are striped by net channels. Striping is disabled when
+    /// this is 1.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_CHANNEL_STRIPE_COUNT".to_string()),
+        py_name: None,
+    })
+    pub attr CHANNEL_STRIPE_COUNT: usize = 1;
+
+    /// Minimum total size of the parts of a message for it to be striped
+    /// across `CHANNEL_STRIPE_COUNT` connections.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_CHANNEL_STRIPE_THRESHOLD".to_string()),
+        py_name: None,
+    })
+    pub attr CHANNEL_STRIPE_THRESHOLD: usize = 64 * 1024 * 1024; // 64 MiB
+
+    /// Minimum size of a message part to be placed in a shared memory
+    /// segment by the shm channel. Smaller parts are sent inline over
+    /// the underlying unix socket.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_CHANNEL_SHM_PART_THRESHOLD".to_string()),
+        py_name: None,
+    })
+    pub attr CHANNEL_SHM_PART_THRESHOLD: usize = 64 * 1024; // 64 KiB
+
     /// Sampling rate for logging message latency
     /// Set to 0.01 for 1% sampling, 0.1 for 10% sampling, 0.90 for 90% sampling, etc.
     @meta(CONFIG = ConfigAttr {
@@ -134,6 +179,34 @@ declare_attrs! {
         py_name: Some("host_spawn_ready_timeout".to_string()),
     })
     pub attr HOST_SPAWN_READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// How long the profiler waits for a
+    /// [`crate::profiler::StackSampler`] to take a sample. This is
+    /// independent of the sampling interval, as samplers such as
+    /// Python's must first acquire a lock held by the code they sample.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_PROFILER_SAMPLER_TIMEOUT".to_string()),
+        py_name: None,
+    })
+    pub attr PROFILER_SAMPLER_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// How often each proc checks the reply waits of its actors for
+    /// deadlocks and stalls (see [`crate::wait_graph`]). If set to
+    /// zero, the default, disables the check. Long-running requests are
+    /// reported as stalls too, so the check is opt-in.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_STALL_DETECTION_INTERVAL".to_string()),
+        py_name: None,
+    })
+    pub attr STALL_DETECTION_INTERVAL: Duration = Duration::ZERO;
+
+    /// How long an actor may wait on a reply before the wait is
+    /// reported as stalled.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_STALL_WAIT_THRESHOLD".to_string()),
+        py_name: None,
+    })
+    pub attr STALL_WAIT_THRESHOLD: Duration = Duration::from_secs(60);
 }
 
 #[cfg(test)]
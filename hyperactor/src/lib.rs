@@ -83,6 +83,7 @@ pub mod metrics;
 mod ordering;
 pub mod panic_handler;
 pub mod proc;
+pub mod profiler;
 pub mod reference;
 mod signal_handler;
 pub mod simnet;
@@ -92,6 +93,7 @@ pub mod sync;
 /// Test utilities
 pub mod test_utils;
 pub mod time;
+pub mod wait_graph;
 
 pub use actor::Actor;
 pub use actor::ActorHandle;
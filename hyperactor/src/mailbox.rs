@@ -115,6 +115,8 @@ use crate::channel::ChannelAddr;
 use crate::channel::ChannelError;
 use crate::channel::SendError;
 use crate::channel::TxStatus;
+use crate::clock::Clock;
+use crate::clock::RealClock;
 use crate::context;
 use crate::data::Serialized;
 use crate::id;
@@ -122,6 +124,8 @@ use crate::metrics;
 use crate::reference::ActorId;
 use crate::reference::PortId;
 use crate::reference::Reference;
+use crate::wait_graph;
+use crate::wait_graph::PendingWait;
 
 mod undeliverable;
 /// For [`Undeliverable`], a message type for delivery failures.
@@ -1395,6 +1399,7 @@ impl Mailbox {
             OncePortReceiver {
                 receiver: Some(receiver),
                 port_id,
+                awaiting: None,
                 mailbox: self.clone(),
             },
         )
@@ -1902,16 +1907,39 @@ pub struct OncePortReceiver<M> {
     receiver: Option<oneshot::Receiver<M>>,
     port_id: PortId,
 
+    /// The actor expected to reply, and the message it is replying to,
+    /// if known. See [`crate::wait_graph`].
+    awaiting: Option<(ActorId, String)>,
+
     /// Mailbox is used to remove the port from service when the receiver
     /// is dropped.
     mailbox: Mailbox,
 }
 
 impl<M> OncePortReceiver<M> {
+    /// Record that the reply is expected from `awaited`, in response to
+    /// a `message_type` message, so that waiting on it is part of the
+    /// wait-for graph (see [`crate::wait_graph`]).
+    pub fn awaiting(mut self, awaited: &ActorId, message_type: &str) -> Self {
+        self.awaiting = Some((awaited.clone(), message_type.to_string()));
+        self
+    }
+
     /// Receive message from the one-shot port associated with this
     /// receiver.  Recv consumes the receiver: it is no longer valid
-    /// after this call.
+    /// after this call. The wait is recorded for stall detection (see
+    /// [`crate::wait_graph`]) until the message arrives.
     pub async fn recv(mut self) -> Result<M, MailboxError> {
+        let (awaited, message_type) = match self.awaiting.take() {
+            Some((awaited, message_type)) => (Some(awaited), message_type),
+            None => (None, std::any::type_name::<M>().to_string()),
+        };
+        let _wait = wait_graph::register(PendingWait {
+            waiter: self.actor_id().clone(),
+            awaited,
+            message_type,
+            since: RealClock.system_time_now(),
+        });
         std::mem::take(&mut self.receiver)
             .unwrap()
             .await
@@ -28,14 +28,51 @@ declare_attrs! {
 
     /// The rust type of the message.
     pub attr RUST_MESSAGE_TYPE: String;
+
+    /// Random identifier assigned to a message when it is first sent. It is
+    /// recorded as `sent_message_id` on the send trace event and as
+    /// `message_id` on the handler span, so traces from different procs can
+    /// be linked.
+    pub attr MESSAGE_ID: u64;
+
+    /// The [`MESSAGE_ID`] of the message on whose behalf this message was
+    /// relayed; see [`set_relayed_from`].
+    pub attr PARENT_MESSAGE_ID: u64;
 }
 
 /// Set the send timestamp for latency tracking if timestamp not already set.
+/// Also assigns the message a [`MESSAGE_ID`] if it does not have one yet,
+/// tracing the send.
 pub fn set_send_timestamp(headers: &mut Attrs) {
     if !headers.contains_key(SEND_TIMESTAMP) {
         let time = RealClock.system_time_now();
         headers.set(SEND_TIMESTAMP, time);
     }
+    if !headers.contains_key(MESSAGE_ID) {
+        let id = fastrand::u64(1..);
+        headers.set(MESSAGE_ID, id);
+        tracing::trace!(
+            name = "message_send",
+            sent_message_id = id,
+            parent_message_id = headers.get(PARENT_MESSAGE_ID).copied()
+        );
+    }
+}
+
+/// Prepare `headers` for a message relayed on behalf of the message with
+/// headers `parent`, such as a cast delivered by a comm actor. Relayed
+/// messages often carry a copy of their parent's headers; each is given
+/// its own [`MESSAGE_ID`] so that it is traced as a send of its own, and
+/// records its parent's as its [`PARENT_MESSAGE_ID`].
+pub fn set_relayed_from(headers: &mut Attrs, parent: &Attrs) {
+    headers.remove(MESSAGE_ID);
+    match parent.get(MESSAGE_ID) {
+        Some(id) => headers.set(PARENT_MESSAGE_ID, *id),
+        None => {
+            headers.remove(PARENT_MESSAGE_ID);
+        }
+    }
+    set_send_timestamp(headers);
 }
 
 /// Set the send timestamp for latency tracking if timestamp not already set.
@@ -69,3 +106,28 @@ pub fn log_message_latency_if_sampling(headers: &Attrs, actor_id: String) {
     let latency = now.duration_since(*send_timestamp).unwrap_or_default();
     MESSAGE_LATENCY_MICROS.record(latency.as_micros() as f64, metric_pairs);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_relayed_from() {
+        let mut parent = Attrs::new();
+        set_send_timestamp(&mut parent);
+        let parent_id = *parent.get(MESSAGE_ID).unwrap();
+
+        // Each copy relayed from the same parent is its own message.
+        let mut first = parent.clone();
+        set_relayed_from(&mut first, &parent);
+        let mut second = parent.clone();
+        set_relayed_from(&mut second, &parent);
+        for relayed in [&first, &second] {
+            assert_ne!(relayed.get(MESSAGE_ID), Some(&parent_id));
+            assert_eq!(relayed.get(PARENT_MESSAGE_ID), Some(&parent_id));
+        }
+        assert_ne!(first.get(MESSAGE_ID), second.get(MESSAGE_ID));
+        // The send timestamp is kept, so latency covers the whole relay.
+        assert_eq!(first.get(SEND_TIMESTAMP), parent.get(SEND_TIMESTAMP));
+    }
+}
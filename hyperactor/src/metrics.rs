@@ -85,6 +85,14 @@ declare_static_counter!(CHANNEL_THROUGHPUT_BYTES, "channel.throughput.bytes");
 declare_static_counter!(CHANNEL_THROUGHPUT_MESSAGES, "channel.throughput.messages");
 // Tracks message latency for each channel pair in microseconds
 declare_static_histogram!(CHANNEL_LATENCY_MICROS, "channel.latency.us");
+// Tracks the number of open multiplexed links
+declare_static_up_down_counter!(CHANNEL_MUX_LINKS, "channel.mux.links");
+// Tracks the number of logical channels carried by each multiplexed link
+declare_static_up_down_counter!(CHANNEL_MUX_LOGICAL_CHANNELS, "channel.mux.logical_channels");
+// Tracks the number of messages striped across parallel connections
+declare_static_counter!(CHANNEL_STRIPED_MESSAGES, "channel.striped.messages");
+// Tracks the number of bytes striped across parallel connections
+declare_static_counter!(CHANNEL_STRIPED_BYTES, "channel.striped.bytes");
 
 // PROC MESH
 // Tracks the number of active processes in the process mesh
@@ -85,6 +85,14 @@ pub fn set_panic_hook() {
             .map_or_else(|| "unavailable".to_owned(), |l| l.to_string());
         tracing::error!("stacktrace"=%backtrace, "panic at {loc_str}: {panic_msg}");
 
+        // Preserve the process's recent history, in case this panic
+        // brings it down.
+        match hyperactor_telemetry::recorder::dump_flight_recorder() {
+            Ok(Some(path)) => tracing::info!("dumped flight recorder to {}", path.display()),
+            Ok(None) => (),
+            Err(err) => tracing::warn!("failed to dump flight recorder: {}", err),
+        }
+
         let _result = BACKTRACE.try_with(|entry| match entry.try_borrow_mut() {
             Ok(mut entry_ref) => {
                 *entry_ref = Some(PanicInfo {
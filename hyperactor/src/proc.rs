@@ -107,6 +107,7 @@ use crate::reference::PortId;
 use crate::reference::ProcId;
 use crate::reference::id;
 use crate::supervision::ActorSupervisionEvent;
+use crate::wait_graph::PendingWait;
 
 /// This is used to mint new local ranks for [`Proc::local`].
 static NEXT_LOCAL_RANK: AtomicUsize = AtomicUsize::new(0);
@@ -232,6 +233,24 @@ pub struct ActorTreeSnapshot {
     pub spans: Vec<Vec<String>>,
 }
 
+/// What an actor is doing at a point in time; see [`Proc::actor_activity`].
+#[derive(Debug, Clone)]
+pub struct ActorActivity {
+    /// The actor's id.
+    pub actor_id: ActorId,
+
+    /// The type name of the actor.
+    pub type_name: String,
+
+    /// The actor's current status. While the actor is handling a message,
+    /// this includes the type (and arm) of the message.
+    pub status: ActorStatus,
+
+    /// The spans currently entered by the actor, each stack listed
+    /// outermost first.
+    pub spans: Vec<Vec<String>>,
+}
+
 impl Hash for ActorTreeSnapshot {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.pid.hash(state);
@@ -476,6 +495,53 @@ impl Proc {
         self.state().ledger.snapshot()
     }
 
+    /// Sample what every live actor in the proc is currently doing. Unlike
+    /// [`Proc::ledger_snapshot`], this includes child actors directly and
+    /// does not copy recent events, so it is cheap enough to call
+    /// repeatedly, as the [`crate::profiler`] does.
+    pub fn actor_activity(&self) -> Vec<ActorActivity> {
+        self.inner
+            .instances
+            .iter()
+            .filter_map(|entry| entry.value().upgrade())
+            .map(|cell| ActorActivity {
+                actor_id: cell.actor_id().clone(),
+                type_name: cell.inner.actor_type.type_name().to_string(),
+                status: cell.status().borrow().clone(),
+                spans: cell
+                    .inner
+                    .recording
+                    .stacks()
+                    .into_iter()
+                    .map(|stack| {
+                        stack
+                            .into_iter()
+                            .rev()
+                            .map(|meta| meta.name().to_string())
+                            .collect()
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// The number of messages waiting in the work queues of the proc's
+    /// live actors. This is a cheap measure of how loaded the proc is.
+    pub fn queued_messages(&self) -> u64 {
+        self.inner
+            .instances
+            .iter()
+            .filter_map(|entry| entry.value().upgrade())
+            .map(|cell| cell.inner.num_queued_messages.load(Ordering::SeqCst))
+            .sum()
+    }
+
+    /// The reply waits currently outstanding in the proc. See
+    /// [`crate::wait_graph`].
+    pub fn pending_waits(&self) -> Vec<PendingWait> {
+        crate::wait_graph::pending_waits(self.proc_id())
+    }
+
     /// Attach a mailbox to the proc with the provided root name.
     pub fn attach(&self, name: &str) -> Result<Mailbox, anyhow::Error> {
         let actor_id: ActorId = self.allocate_root_id(name)?;
@@ -1046,7 +1112,12 @@ impl<A: Actor> Instance<A> {
             actor_id.to_string(),
             hyperactor_config::global::get(config::ENABLE_CLIENT_SEQ_ASSIGNMENT),
         );
-        let ports: Arc<Ports<A>> = Arc::new(Ports::new(mailbox.clone(), work_tx));
+        let num_queued_messages = Arc::new(AtomicU64::new(0));
+        let ports: Arc<Ports<A>> = Arc::new(Ports::new(
+            mailbox.clone(),
+            work_tx,
+            num_queued_messages.clone(),
+        ));
         proc.state().proc_muxer.bind_mailbox(mailbox.clone());
         let (status_tx, status_rx) = watch::channel(ActorStatus::Created);
 
@@ -1075,6 +1146,7 @@ impl<A: Actor> Instance<A> {
             status_rx,
             parent,
             ports.clone(),
+            num_queued_messages,
         );
         let instance_id = Uuid::now_v7();
         let inner = Arc::new(InstanceState {
@@ -1131,6 +1203,12 @@ impl<A: Actor> Instance<A> {
                 caller = %Location::caller(),
                 change_reason,
             );
+            tracing::debug!(
+                target: "actor_lifecycle",
+                actor_id = %self.self_id(),
+                actor = self.self_id().name(),
+                actor_status = new_status,
+            );
         }
     }
 
@@ -1436,6 +1514,11 @@ impl<A: Actor> Instance<A> {
                 work = work_rx.recv() => {
                     ACTOR_MESSAGES_RECEIVED.add(1, metric_pairs);
                     ACTOR_MESSAGE_QUEUE_SIZE.add(-1, metric_pairs);
+                    self.inner
+                        .cell
+                        .inner
+                        .num_queued_messages
+                        .fetch_sub(1, Ordering::SeqCst);
                     let _ = ACTOR_MESSAGE_HANDLER_DURATION.start(metric_pairs);
                     let work = work.expect("inconsistent work queue state");
                     if let Err(err) = work.handle(actor, self).await {
@@ -1476,6 +1559,11 @@ impl<A: Actor> Instance<A> {
         if need_drain {
             let mut n = 0;
             while let Ok(work) = work_rx.try_recv() {
+                self.inner
+                    .cell
+                    .inner
+                    .num_queued_messages
+                    .fetch_sub(1, Ordering::SeqCst);
                 if let Err(err) = work.handle(actor, self).await {
                     return Err(ActorError::new(
                         self.self_id(),
@@ -1521,7 +1609,7 @@ impl<A: Actor> Instance<A> {
         }
     }
 
-    #[hyperactor::instrument(fields(actor_id = self.self_id().to_string(), actor_name = self.self_id().name()))]
+    #[hyperactor::instrument(fields(actor_id = self.self_id().to_string(), actor_name = self.self_id().name(), message_id = headers.get(crate::mailbox::headers::MESSAGE_ID).copied()))]
     async unsafe fn handle_message<M: Message>(
         &self,
         actor: &mut A,
@@ -1552,11 +1640,21 @@ impl<A: Actor> Instance<A> {
             self.self_id().to_string(),
         );
 
+        let start = self.clock().now();
         let context = Context::new(self, headers);
         // Pass a reference to the context to the handler, so that deref
         // coercion allows the `this` argument to be treated exactly like
         // &Instance<A>.
-        actor.handle(&context, message).await
+        let result = actor.handle(&context, message).await;
+        tracing::event!(
+            target: "messages",
+            tracing::Level::TRACE,
+            dest = %self.self_id(),
+            message_type = type_info.map(|info| info.typename()).unwrap_or("unknown"),
+            elapsed_us = self.clock().now().saturating_duration_since(start).as_micros() as u64,
+            "handle_message",
+        );
+        result
     }
 
     /// Spawn on child on this instance.
@@ -1759,6 +1857,10 @@ struct InstanceCellState {
     /// The number of messages processed by this actor.
     num_processed_messages: AtomicU64,
 
+    /// The number of messages waiting in this actor's work queue,
+    /// shared with its [`Ports`].
+    num_queued_messages: Arc<AtomicU64>,
+
     /// The log recording associated with this actor. It is used to
     /// store a 'flight record' of events while the actor is running.
     recording: Recording,
@@ -1795,6 +1897,7 @@ impl InstanceCell {
         status: watch::Receiver<ActorStatus>,
         parent: Option<InstanceCell>,
         ports: Arc<dyn Any + Send + Sync>,
+        num_queued_messages: Arc<AtomicU64>,
     ) -> Self {
         let _ais = actor_id.to_string();
         let cell = Self {
@@ -1809,6 +1912,7 @@ impl InstanceCell {
                 actor_task_handle: OnceLock::new(),
                 exported_named_ports: DashMap::new(),
                 num_processed_messages: AtomicU64::new(0),
+                num_queued_messages,
                 recording: hyperactor_telemetry::recorder().record(64),
                 ports,
             }),
@@ -2021,6 +2125,7 @@ pub struct Ports<A: Actor> {
     bound: DashMap<u64, &'static str>,
     mailbox: Mailbox,
     workq: OrderedSender<WorkCell<A>>,
+    num_queued_messages: Arc<AtomicU64>,
 }
 
 /// A message's sequencer number infomation.
@@ -2059,12 +2164,17 @@ declare_attrs! {
 }
 
 impl<A: Actor> Ports<A> {
-    fn new(mailbox: Mailbox, workq: OrderedSender<WorkCell<A>>) -> Self {
+    fn new(
+        mailbox: Mailbox,
+        workq: OrderedSender<WorkCell<A>>,
+        num_queued_messages: Arc<AtomicU64>,
+    ) -> Self {
         Self {
             ports: DashMap::new(),
             bound: DashMap::new(),
             mailbox,
             workq,
+            num_queued_messages,
         }
     }
 
@@ -2085,6 +2195,7 @@ impl<A: Actor> Ports<A> {
 
                 let type_info = TypeInfo::get_by_typeid(key);
                 let workq = self.workq.clone();
+                let num_queued_messages = self.num_queued_messages.clone();
                 let actor_id = self.mailbox.actor_id().to_string();
                 let port = self.mailbox.open_enqueue_port(move |headers, msg: M| {
                     let seq_info = headers.get(SEQ_INFO).cloned();
@@ -2103,7 +2214,8 @@ impl<A: Actor> Ports<A> {
                         1,
                         hyperactor_telemetry::kv_pairs!("actor_id" => actor_id.clone()),
                     );
-                    if workq.enable_buffering {
+                    num_queued_messages.fetch_add(1, Ordering::SeqCst);
+                    let result = if workq.enable_buffering {
                         let SeqInfo { session_id, seq } =
                             seq_info.expect("SEQ_INFO must be set when buffering is enabled");
 
@@ -2118,7 +2230,11 @@ impl<A: Actor> Ports<A> {
                         })
                     } else {
                         workq.direct_send(work).map_err(anyhow::Error::from)
+                    };
+                    if result.is_err() {
+                        num_queued_messages.fetch_sub(1, Ordering::SeqCst);
                     }
+                    result
                 });
                 entry.insert(Box::new(port.clone()));
                 port
@@ -2363,6 +2479,31 @@ mod tests {
         assert_matches!(*state.borrow(), ActorStatus::Stopped);
     }
 
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_queued_messages() {
+        let proc = Proc::local();
+        let handle = proc.spawn("test", TestActor).unwrap();
+        assert_eq!(proc.queued_messages(), 0);
+
+        // Block the actor, then queue up messages behind it.
+        let (enter_tx, enter_rx) = oneshot::channel::<()>();
+        let (exit_tx, exit_rx) = oneshot::channel::<()>();
+        handle
+            .send(TestActorMessage::Wait(enter_tx, exit_rx))
+            .unwrap();
+        enter_rx.await.unwrap();
+        for _ in 0..3 {
+            handle.send(TestActorMessage::Noop()).unwrap();
+        }
+        assert_eq!(proc.queued_messages(), 3);
+
+        exit_tx.send(()).unwrap();
+        let (tx, rx) = oneshot::channel::<()>();
+        handle.send(TestActorMessage::Reply(tx)).unwrap();
+        rx.await.unwrap();
+        assert_eq!(proc.queued_messages(), 0);
+    }
+
     #[async_timed_test(timeout_secs = 30)]
     async fn test_proc_actors_messaging() {
         let proc = Proc::local();
@@ -0,0 +1,385 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! A sampling profiler for the actors in a proc.
+//!
+//! [`profile`] periodically samples what each actor in a [`Proc`] is
+//! doing: the message type (and arm) it is handling, and the spans it
+//! has entered. Samples are counted as [`FoldedStacks`], the format
+//! consumed by flamegraph tools, so that profiles of many procs can be
+//! merged into a single flamegraph.
+//!
+//! Other sources of stacks, such as an embedded Python interpreter, can
+//! contribute to profiles by registering a [`StackSampler`]:
+//!
+//! ```ignore
+//! hyperactor::submit! {
+//!     hyperactor::profiler::StackSampler {
+//!         name: "python",
+//!         sample: python_stacks,
+//!     }
+//! }
+//! ```
+//!
+//! The backtraces of all tokio tasks are sampled as well when both this
+//! crate and tokio are built with the `tokio_unstable` and
+//! `tokio_taskdump` cfgs, which tokio supports on Linux on x86, x86_64
+//! and aarch64. They are off by default, as they change the build of
+//! tokio for every crate; enable them with, for example:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg tracing_unstable --cfg tokio_unstable --cfg tokio_taskdump" cargo build
+//! ```
+//!
+//! (`RUSTFLAGS` replaces the `rustflags` of `.cargo/config.toml`, hence
+//! `tracing_unstable`.)
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate as hyperactor;
+use crate::Named;
+use crate::actor::ActorStatus;
+use crate::clock::Clock;
+use crate::clock::RealClock;
+use crate::config::PROFILER_SAMPLER_TIMEOUT;
+use crate::proc::Proc;
+
+/// Samples stacks from a source other than the actors of a proc. Each
+/// returned stack lists its frames outermost first.
+#[derive(Debug)]
+pub struct StackSampler {
+    /// The name of the sampler; this is the root frame of its stacks.
+    pub name: &'static str,
+    /// Take a sample. This is called on a blocking thread, and may block.
+    pub sample: fn() -> Vec<Vec<String>>,
+}
+
+inventory::collect!(StackSampler);
+
+/// Sampled stacks in folded form: each key is a stack of frames joined by
+/// `;`, outermost first, and each value the number of times it was
+/// sampled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Named)]
+pub struct FoldedStacks(pub BTreeMap<String, u64>);
+
+impl FoldedStacks {
+    /// Count one sample of the stack made up of `frames`.
+    pub fn add<S: AsRef<str>>(&mut self, frames: impl IntoIterator<Item = S>) {
+        self.add_n(frames, 1);
+    }
+
+    /// Count `count` samples of the stack made up of `frames`.
+    pub fn add_n<S: AsRef<str>>(&mut self, frames: impl IntoIterator<Item = S>, count: u64) {
+        // ';' separates frames, and the count follows the last space.
+        let key = frames
+            .into_iter()
+            .map(|frame| frame.as_ref().replace(';', ":").replace('\n', " "))
+            .collect::<Vec<_>>()
+            .join(";");
+        *self.0.entry(key).or_default() += count;
+    }
+
+    /// Merge the samples of `other` into these.
+    pub fn merge(&mut self, other: &FoldedStacks) {
+        for (stack, count) in &other.0 {
+            *self.0.entry(stack.clone()).or_default() += count;
+        }
+    }
+
+    /// Merge the samples of `other` into these, under an additional
+    /// root frame, e.g. the name of the proc they were taken from.
+    pub fn merge_under(&mut self, root: &str, other: &FoldedStacks) {
+        for (stack, count) in &other.0 {
+            self.add_n([root, stack.as_str()], *count);
+        }
+    }
+
+    /// The total number of samples.
+    pub fn total(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    /// Whether there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Formats the stacks one per line, as `frame;frame;frame count`.
+impl fmt::Display for FoldedStacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (stack, count) in &self.0 {
+            writeln!(f, "{} {}", stack, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Take a single sample of the actors in `proc`, adding one stack per
+/// actor to `stacks`. The stack of an actor is rooted at its name and
+/// type, followed by the message it is handling and the spans it has
+/// entered, or its status when it is not handling a message.
+pub fn sample_actors(proc: &Proc, stacks: &mut FoldedStacks) {
+    for activity in proc.actor_activity() {
+        let mut frames = vec![format!(
+            "{} ({})",
+            activity.actor_id.name(),
+            activity.type_name
+        )];
+        match &activity.status {
+            ActorStatus::Processing(_, Some((handler, arm))) => {
+                frames.push(handler.clone());
+                frames.extend(arm.clone());
+            }
+            status @ ActorStatus::Processing(_, None) => {
+                frames.push(status.arm().unwrap_or_default().to_string())
+            }
+            status => {
+                stacks.add(
+                    frames
+                        .into_iter()
+                        .chain([status.arm().unwrap_or_default().to_string()]),
+                );
+                continue;
+            }
+        }
+        // The innermost span is the most recently entered one.
+        if let Some(spans) = activity.spans.into_iter().max_by_key(Vec::len) {
+            frames.extend(spans);
+        }
+        stacks.add(frames);
+    }
+}
+
+/// Profile `proc` for `duration`, taking a sample every `interval`.
+/// Each sample covers the proc's actors, all registered
+/// [`StackSampler`]s and, if enabled, tokio task backtraces.
+///
+/// A sampler that does not return within [`PROFILER_SAMPLER_TIMEOUT`]
+/// (for example, because the Python GIL is held by a busy thread) is
+/// recorded as timed out. Its sample keeps running, and is collected by
+/// a later tick instead of starting another one.
+pub async fn profile(proc: &Proc, duration: Duration, interval: Duration) -> FoldedStacks {
+    let mut stacks = FoldedStacks::default();
+    let mut pending = HashMap::new();
+    let deadline = RealClock.now() + duration;
+    loop {
+        sample_actors(proc, &mut stacks);
+
+        // Don't wait on samplers past the end of the profile.
+        let timeout = hyperactor_config::global::get(PROFILER_SAMPLER_TIMEOUT)
+            .min(deadline.saturating_duration_since(RealClock.now()))
+            .max(interval);
+        for sampler in inventory::iter::<StackSampler> {
+            let mut sample = pending
+                .remove(sampler.name)
+                .unwrap_or_else(|| tokio::task::spawn_blocking(sampler.sample));
+            match RealClock.timeout(timeout, &mut sample).await {
+                Ok(Ok(sampled)) => {
+                    for frames in sampled {
+                        stacks.add(std::iter::once(sampler.name.to_string()).chain(frames));
+                    }
+                }
+                Ok(Err(err)) => {
+                    tracing::warn!("stack sampler {} failed: {}", sampler.name, err);
+                }
+                Err(_) => {
+                    stacks.add([sampler.name, "[sampler timed out]"]);
+                    pending.insert(sampler.name, sample);
+                }
+            }
+        }
+
+        #[cfg(all(tokio_unstable, tokio_taskdump))]
+        sample_tokio_tasks(&mut stacks).await;
+
+        if RealClock.now() + interval > deadline {
+            break;
+        }
+        RealClock.sleep(interval).await;
+    }
+    stacks
+}
+
+/// Sample the backtraces of all tasks in the current tokio runtime.
+#[cfg(all(tokio_unstable, tokio_taskdump))]
+async fn sample_tokio_tasks(stacks: &mut FoldedStacks) {
+    let dump = tokio::runtime::Handle::current().dump().await;
+    for task in dump.tasks().iter() {
+        for frames in fold_trace(&task.trace().to_string()) {
+            stacks.add(std::iter::once("tokio".to_string()).chain(frames));
+        }
+    }
+}
+
+/// Fold a tree rendered one frame per line, with children indented
+/// further than their parent, into its root-to-leaf paths.
+#[cfg_attr(not(all(tokio_unstable, tokio_taskdump)), allow(dead_code))]
+fn fold_trace(trace: &str) -> Vec<Vec<String>> {
+    let mut paths = Vec::new();
+    // The current path, with the indentation of each frame.
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut is_leaf = false;
+    for line in trace.lines() {
+        let frame = line.trim_start_matches(|c: char| {
+            c.is_whitespace() || matches!(c, '│' | '├' | '└' | '─' | '╼')
+        });
+        if frame.is_empty() {
+            continue;
+        }
+        let indent = line.chars().count() - frame.chars().count();
+        if is_leaf && path.last().is_some_and(|(last, _)| indent <= *last) {
+            paths.push(path.iter().map(|(_, frame)| frame.clone()).collect());
+        }
+        while path.last().is_some_and(|(last, _)| indent <= *last) {
+            path.pop();
+        }
+        path.push((indent, frame.trim_end().to_string()));
+        is_leaf = true;
+    }
+    if is_leaf {
+        paths.push(path.into_iter().map(|(_, frame)| frame).collect());
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::Actor;
+    use crate::Context;
+    use crate::Handler;
+
+    #[derive(Debug, Default)]
+    struct SleepActor;
+
+    impl Actor for SleepActor {}
+
+    #[derive(Debug)]
+    struct Sleep(Duration);
+
+    #[async_trait]
+    impl Handler<Sleep> for SleepActor {
+        async fn handle(
+            &mut self,
+            _cx: &Context<Self>,
+            Sleep(duration): Sleep,
+        ) -> anyhow::Result<()> {
+            RealClock.sleep(duration).await;
+            Ok(())
+        }
+    }
+
+    /// The number of calls to [`slow_sample`].
+    static SLOW_SAMPLES: AtomicUsize = AtomicUsize::new(0);
+
+    fn slow_sample() -> Vec<Vec<String>> {
+        SLOW_SAMPLES.fetch_add(1, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(100));
+        vec![vec!["sampled".to_string()]]
+    }
+
+    crate::submit! {
+        StackSampler {
+            name: "slow_test_sampler",
+            sample: slow_sample,
+        }
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        let mut stacks = FoldedStacks::default();
+        stacks.add(["a", "b;c"]);
+        stacks.add(["a", "b;c"]);
+        stacks.add(["a"]);
+        assert_eq!(stacks.to_string(), "a 1\na;b:c 2\n");
+
+        let mut merged = FoldedStacks::default();
+        merged.merge_under("proc", &stacks);
+        merged.merge(&stacks);
+        assert_eq!(merged.0["proc;a;b:c"], 2);
+        assert_eq!(merged.0["a;b:c"], 2);
+        assert_eq!(merged.total(), 6);
+    }
+
+    #[test]
+    fn test_fold_trace() {
+        let trace = "\
+╼ main at src/main.rs:1:1
+  ├╼ a at src/a.rs:1:1
+  │  └╼ b at src/b.rs:1:1
+  └╼ c at src/c.rs:1:1
+";
+        assert_eq!(
+            fold_trace(trace),
+            vec![
+                vec![
+                    "main at src/main.rs:1:1",
+                    "a at src/a.rs:1:1",
+                    "b at src/b.rs:1:1"
+                ],
+                vec!["main at src/main.rs:1:1", "c at src/c.rs:1:1"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_profile_actors() {
+        // Don't profile concurrently with the other tests, which share
+        // the slow sampler.
+        let _config = hyperactor_config::global::lock();
+        let proc = Proc::local();
+        let handle = proc.spawn("sleeper", SleepActor).unwrap();
+        handle.send(Sleep(Duration::from_secs(1))).unwrap();
+
+        let stacks = profile(&proc, Duration::from_millis(200), Duration::from_millis(20)).await;
+        let handling = stacks
+            .0
+            .iter()
+            .filter(|(stack, _)| stack.starts_with("sleeper (") && !stack.ends_with(";Idle"))
+            .map(|(_, count)| count)
+            .sum::<u64>();
+        assert!(handling > 0, "no samples of the handler in {}", stacks);
+    }
+    #[tokio::test]
+    async fn test_profile_slow_sampler() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(PROFILER_SAMPLER_TIMEOUT, Duration::from_millis(10));
+
+        let proc = Proc::local();
+        let calls = SLOW_SAMPLES.load(Ordering::SeqCst);
+        let stacks = profile(&proc, Duration::from_millis(500), Duration::from_millis(20)).await;
+        // The sampler times out, but its samples are still collected by
+        // later ticks, which don't start another sample in the meantime.
+        assert!(
+            stacks
+                .0
+                .contains_key("slow_test_sampler;[sampler timed out]"),
+            "no timeouts in {}",
+            stacks
+        );
+        assert!(
+            stacks.0.contains_key("slow_test_sampler;sampled"),
+            "no samples in {}",
+            stacks
+        );
+        let calls = SLOW_SAMPLES.load(Ordering::SeqCst) - calls;
+        assert!(calls < 10, "{} samples started", calls);
+    }
+}
@@ -26,6 +26,7 @@ use crate::Named;
 use crate::actor::ActorErrorKind;
 use crate::actor::ActorStatus;
 use crate::reference::ActorId;
+use crate::wait_graph::PendingWait;
 
 /// This is the local actor supervision event. Child actor will propagate this event to its parent.
 #[derive(Clone, Debug, Derivative, Serialize, Deserialize, Named)]
@@ -154,3 +155,95 @@ impl fmt::Display for ActorSupervisionEvent {
         Ok(())
     }
 }
+
+/// A warning that actors may be stuck waiting on each other, raised by
+/// stall detection (see [`crate::wait_graph`]). Unlike an
+/// [`ActorSupervisionEvent`], a warning does not stop any actor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Named)]
+pub enum SupervisionWarning {
+    /// The waits form a cycle: each actor is waiting on a reply from
+    /// the next, and the last on the first.
+    Deadlock {
+        /// The waits in the cycle, in order.
+        waits: Vec<PendingWait>,
+    },
+    /// An actor has been waiting on a reply for longer than
+    /// [`crate::config::STALL_WAIT_THRESHOLD`].
+    StalledWait {
+        /// The stalled wait.
+        wait: PendingWait,
+    },
+}
+
+impl SupervisionWarning {
+    /// The actors participating in the warning.
+    pub fn actor_ids(&self) -> Vec<&ActorId> {
+        match self {
+            SupervisionWarning::Deadlock { waits } => {
+                waits.iter().map(|wait| &wait.waiter).collect()
+            }
+            SupervisionWarning::StalledWait { wait } => {
+                std::iter::once(&wait.waiter).chain(&wait.awaited).collect()
+            }
+        }
+    }
+
+    /// Log the warning.
+    pub fn report(&self) {
+        let actor_ids = self
+            .actor_ids()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        tracing::warn!(
+            name = "SupervisionWarning",
+            actor_ids = actor_ids,
+            "{}",
+            self
+        );
+    }
+}
+
+impl fmt::Display for SupervisionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SupervisionWarning::Deadlock { waits } => {
+                write!(f, "possible deadlock: ")?;
+                for (i, wait) in waits.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{} waits on {}", wait.waiter, AwaitedDisplay(wait))?;
+                }
+                Ok(())
+            }
+            SupervisionWarning::StalledWait { wait } => {
+                let waited = RealClock
+                    .system_time_now()
+                    .duration_since(wait.since)
+                    .unwrap_or_default();
+                write!(
+                    f,
+                    "{} has waited on {} for {}s",
+                    wait.waiter,
+                    AwaitedDisplay(wait),
+                    waited.as_secs()
+                )
+            }
+        }
+    }
+}
+
+/// Displays what a wait is waiting on: the reply of an actor to a
+/// message, or a reply of some type.
+struct AwaitedDisplay<'a>(&'a PendingWait);
+
+impl fmt::Display for AwaitedDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.awaited {
+            Some(awaited) => write!(f, "{} for {}", awaited, self.0.message_type),
+            None => write!(f, "a {} reply", self.0.message_type),
+        }
+    }
+}
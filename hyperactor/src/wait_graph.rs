@@ -0,0 +1,408 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Detection of actors that are stuck waiting on each other.
+//!
+//! Whenever an actor awaits a reply on a once port
+//! ([`crate::mailbox::OncePortReceiver::recv`]), the wait is recorded as
+//! a [`PendingWait`] until the reply arrives. The waits of one or more
+//! procs form a [`WaitGraph`], in which each actor points to the actors
+//! it is waiting on. A cycle in this graph is a deadlock; a wait that
+//! has been pending for too long is a stall. Both are reported as
+//! [`SupervisionWarning`]s.
+//!
+//! A wait is only part of the graph if the actor expected to reply is
+//! known. The methods generated by [`crate::HandleClient`] and
+//! [`crate::RefClient`] record it; other callers can do so with
+//! [`crate::mailbox::OncePortReceiver::awaiting`]:
+//!
+//! ```ignore
+//! let (port, rx) = cx.open_once_port();
+//! other.send(cx, Request { reply: port.bind() })?;
+//! let reply = rx.awaiting(other.actor_id(), "Request").recv().await?;
+//! ```
+//!
+//! Waits on other kinds of ports can be recorded with [`track`].
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use dashmap::DashMap;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate as hyperactor;
+use crate::Named;
+use crate::clock::Clock;
+use crate::clock::RealClock;
+use crate::context;
+use crate::reference::ActorId;
+use crate::reference::ProcId;
+use crate::supervision::SupervisionWarning;
+
+/// An actor waiting on a reply from another actor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Named)]
+pub struct PendingWait {
+    /// The actor that is waiting.
+    pub waiter: ActorId,
+    /// The actor whose reply is awaited, if known.
+    pub awaited: Option<ActorId>,
+    /// The type of the message whose reply is awaited or, if the
+    /// message is not known, the type of the reply.
+    pub message_type: String,
+    /// When the wait began.
+    pub since: SystemTime,
+}
+
+/// The waits outstanding in this process, keyed by a unique id.
+static WAITS: LazyLock<DashMap<u64, PendingWait>> = LazyLock::new(DashMap::new);
+static NEXT_WAIT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Record `wait` until the returned guard is dropped.
+pub(crate) fn register(wait: PendingWait) -> WaitGuard {
+    let id = NEXT_WAIT_ID.fetch_add(1, Ordering::Relaxed);
+    WAITS.insert(id, wait);
+    WaitGuard { id }
+}
+
+/// The waits outstanding in this process by actors of `proc_id`.
+pub(crate) fn pending_waits(proc_id: &ProcId) -> Vec<PendingWait> {
+    WAITS
+        .iter()
+        .filter(|entry| entry.value().waiter.proc_id() == proc_id)
+        .map(|entry| entry.value().clone())
+        .collect()
+}
+
+/// Record that the actor of `cx` is waiting on a reply to a
+/// `message_type` message from `awaited`, until the returned guard is
+/// dropped.
+pub fn track(cx: &impl context::Actor, awaited: &ActorId, message_type: &str) -> WaitGuard {
+    register(PendingWait {
+        waiter: cx.mailbox().actor_id().clone(),
+        awaited: Some(awaited.clone()),
+        message_type: message_type.to_string(),
+        since: RealClock.system_time_now(),
+    })
+}
+
+/// Removes a recorded wait when dropped.
+#[must_use = "the wait is removed as soon as the guard is dropped"]
+pub struct WaitGuard {
+    id: u64,
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        WAITS.remove(&self.id);
+    }
+}
+
+/// The wait-for graph of a set of pending waits.
+#[derive(Debug, Clone, Default)]
+pub struct WaitGraph {
+    /// The waits of each waiting actor, oldest first, including those
+    /// on an unknown actor, which are not edges of the graph.
+    edges: BTreeMap<ActorId, Vec<PendingWait>>,
+}
+
+impl WaitGraph {
+    /// Build the graph of the provided waits, which may have been
+    /// collected from many procs.
+    pub fn new(waits: impl IntoIterator<Item = PendingWait>) -> Self {
+        let mut edges: BTreeMap<ActorId, Vec<PendingWait>> = BTreeMap::new();
+        for wait in waits {
+            edges.entry(wait.waiter.clone()).or_default().push(wait);
+        }
+        for waits in edges.values_mut() {
+            waits.sort_by(|a, b| (a.since, &a.awaited).cmp(&(b.since, &b.awaited)));
+        }
+        Self { edges }
+    }
+
+    /// The cycles in the graph, each as the waits that form it. Every
+    /// cycle is reported once, though cycles that share waits may each
+    /// be reported.
+    pub fn cycles(&self) -> Vec<Vec<PendingWait>> {
+        #[derive(PartialEq)]
+        enum Visit {
+            OnPath,
+            Done,
+        }
+
+        fn visit<'a>(
+            graph: &'a WaitGraph,
+            node: &'a ActorId,
+            visits: &mut HashMap<&'a ActorId, Visit>,
+            path: &mut Vec<&'a PendingWait>,
+            cycles: &mut Vec<Vec<PendingWait>>,
+        ) {
+            visits.insert(node, Visit::OnPath);
+            for wait in graph.edges.get(node).into_iter().flatten() {
+                let Some(awaited) = &wait.awaited else {
+                    continue;
+                };
+                match visits.get(awaited) {
+                    Some(Visit::OnPath) => {
+                        // The cycle starts with the wait on the path out of
+                        // the awaited actor; a self-wait has no such wait.
+                        let start = path
+                            .iter()
+                            .position(|on_path| &on_path.waiter == awaited)
+                            .unwrap_or(path.len());
+                        cycles.push(
+                            path[start..]
+                                .iter()
+                                .map(|&wait| wait.clone())
+                                .chain([wait.clone()])
+                                .collect(),
+                        );
+                    }
+                    Some(Visit::Done) => {}
+                    None => {
+                        path.push(wait);
+                        visit(graph, awaited, visits, path, cycles);
+                        path.pop();
+                    }
+                }
+            }
+            visits.insert(node, Visit::Done);
+        }
+
+        let mut visits = HashMap::new();
+        let mut cycles = Vec::new();
+        for node in self.edges.keys() {
+            if !visits.contains_key(node) {
+                visit(self, node, &mut visits, &mut Vec::new(), &mut cycles);
+            }
+        }
+        cycles
+    }
+
+    /// The waits that have been pending for at least `threshold` at
+    /// time `now`.
+    pub fn stalled(&self, threshold: Duration, now: SystemTime) -> Vec<PendingWait> {
+        self.edges
+            .values()
+            .flatten()
+            .filter(|wait| {
+                now.duration_since(wait.since)
+                    .is_ok_and(|waited| waited >= threshold)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The warnings for the graph: one per cycle, and one per stalled
+    /// wait that is not already part of a cycle.
+    pub fn warnings(&self, threshold: Duration, now: SystemTime) -> Vec<SupervisionWarning> {
+        let cycles = self.cycles();
+        let in_cycle: HashSet<&PendingWait> = cycles.iter().flatten().collect();
+        let stalled = self
+            .stalled(threshold, now)
+            .into_iter()
+            .filter(|wait| !in_cycle.contains(wait))
+            .map(|wait| SupervisionWarning::StalledWait { wait })
+            .collect::<Vec<_>>();
+        cycles
+            .iter()
+            .map(|waits| SupervisionWarning::Deadlock {
+                waits: waits.clone(),
+            })
+            .chain(stalled)
+            .collect()
+    }
+}
+
+/// Periodically checks waits for deadlocks and stalls, reporting each
+/// warning only once for as long as it persists.
+#[derive(Debug, Default)]
+pub struct StallDetector {
+    reported: HashSet<SupervisionWarning>,
+}
+
+impl StallDetector {
+    /// Check the provided waits, returning the warnings that were not
+    /// already returned by the previous check.
+    pub fn check(
+        &mut self,
+        waits: impl IntoIterator<Item = PendingWait>,
+        threshold: Duration,
+        now: SystemTime,
+    ) -> Vec<SupervisionWarning> {
+        let warnings = WaitGraph::new(waits).warnings(threshold, now);
+        let new = warnings
+            .iter()
+            .filter(|warning| !self.reported.contains(*warning))
+            .cloned()
+            .collect();
+        self.reported = warnings.into_iter().collect();
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id;
+    use crate::proc::Proc;
+
+    fn wait(waiter: &ActorId, awaited: &ActorId, since: SystemTime) -> PendingWait {
+        PendingWait {
+            waiter: waiter.clone(),
+            awaited: Some(awaited.clone()),
+            message_type: "Msg".to_string(),
+            since,
+        }
+    }
+
+    #[test]
+    fn test_cycles() {
+        let now = SystemTime::now();
+        let (a, b, c, d) = (
+            id!(test[0].a[0]),
+            id!(test[0].b[0]),
+            id!(test[0].c[0]),
+            id!(test[0].d[0]),
+        );
+
+        // a -> b -> c -> a, with d waiting on the cycle from outside.
+        let graph = WaitGraph::new([
+            wait(&d, &a, now),
+            wait(&a, &b, now),
+            wait(&b, &c, now),
+            wait(&c, &a, now),
+        ]);
+        assert_eq!(
+            graph.cycles(),
+            vec![vec![
+                wait(&a, &b, now),
+                wait(&b, &c, now),
+                wait(&c, &a, now)
+            ]]
+        );
+
+        // Self-waits are cycles too.
+        let graph = WaitGraph::new([wait(&d, &a, now), wait(&a, &a, now)]);
+        assert_eq!(graph.cycles(), vec![vec![wait(&a, &a, now)]]);
+
+        // A diamond is not a cycle.
+        let graph = WaitGraph::new([
+            wait(&a, &b, now),
+            wait(&a, &c, now),
+            wait(&b, &d, now),
+            wait(&c, &d, now),
+        ]);
+        assert!(graph.cycles().is_empty());
+    }
+
+    #[test]
+    fn test_warnings() {
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(120);
+        let (a, b, c) = (id!(test[0].a[0]), id!(test[0].b[0]), id!(test[0].c[0]));
+
+        let graph = WaitGraph::new([wait(&a, &b, old), wait(&b, &a, old), wait(&c, &a, old)]);
+        assert_eq!(
+            graph.warnings(Duration::from_secs(60), now),
+            vec![
+                SupervisionWarning::Deadlock {
+                    waits: vec![wait(&a, &b, old), wait(&b, &a, old)]
+                },
+                SupervisionWarning::StalledWait {
+                    wait: wait(&c, &a, old)
+                },
+            ]
+        );
+        assert_eq!(graph.stalled(Duration::from_secs(300), now), vec![]);
+    }
+
+    #[test]
+    fn test_stall_detector() {
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(120);
+        let (a, b) = (id!(test[0].a[0]), id!(test[0].b[0]));
+        let threshold = Duration::from_secs(60);
+        let mut detector = StallDetector::default();
+
+        let stalled = SupervisionWarning::StalledWait {
+            wait: wait(&a, &b, old),
+        };
+        assert_eq!(
+            detector.check([wait(&a, &b, old)], threshold, now),
+            vec![stalled.clone()]
+        );
+        // Reported only once while it persists...
+        assert_eq!(detector.check([wait(&a, &b, old)], threshold, now), vec![]);
+        // ...and again if it recurs after it was resolved.
+        assert_eq!(detector.check([], threshold, now), vec![]);
+        assert_eq!(
+            detector.check([wait(&a, &b, old)], threshold, now),
+            vec![stalled]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_track() {
+        let proc = Proc::local();
+        let (client, _) = proc.instance("client").unwrap();
+        let awaited = id!(test[0].server[0]);
+
+        let guard = track(&client, &awaited, "Request");
+        let waits = proc.pending_waits();
+        assert_eq!(waits.len(), 1);
+        assert_eq!(&waits[0].waiter, client.self_id());
+        assert_eq!(waits[0].awaited, Some(awaited));
+        assert_eq!(waits[0].message_type, "Request");
+
+        drop(guard);
+        assert!(proc.pending_waits().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_once_port_recv() {
+        let proc = Proc::local();
+        let (client, _) = proc.instance("client").unwrap();
+        let awaited = id!(test[0].server[0]);
+
+        let (port, rx) = client.open_once_port::<u64>();
+        let recv = tokio::spawn(rx.awaiting(&awaited, "Request").recv());
+        let waits = loop {
+            let waits = proc.pending_waits();
+            if !waits.is_empty() {
+                break waits;
+            }
+            tokio::task::yield_now().await;
+        };
+        assert_eq!(&waits[0].waiter, client.self_id());
+        assert_eq!(waits[0].awaited, Some(awaited));
+        assert_eq!(waits[0].message_type, "Request");
+
+        port.send(42).unwrap();
+        assert_eq!(recv.await.unwrap().unwrap(), 42);
+        assert!(proc.pending_waits().is_empty());
+
+        // Waits on an unknown actor are recorded by the type of the reply.
+        let (port, rx) = client.open_once_port::<u64>();
+        let recv = tokio::spawn(rx.recv());
+        while proc.pending_waits().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        let waits = proc.pending_waits();
+        assert_eq!(waits[0].awaited, None);
+        assert_eq!(waits[0].message_type, "u64");
+        port.send(42).unwrap();
+        recv.await.unwrap().unwrap();
+    }
+}
@@ -139,6 +139,9 @@ pub struct AttrKeyInfo {
     /// A reference to the relevant key object with the associated
     /// type parameter erased. Can be downcast to a concrete Key<T>.
     pub erased: &'static dyn ErasedKey,
+    /// The key's doc comment, one line per `///` line (with the
+    /// leading space preserved), or empty if undocumented.
+    pub doc: &'static str,
 }
 
 inventory::collect!(AttrKeyInfo);
@@ -774,6 +777,21 @@ macro_rules! assert_impl {
     };
 }
 
+/// Collect the `#[doc = "..."]` attributes among the given bracketed
+/// attribute token lists into a single newline-separated string
+/// literal. Used by `declare_attrs!` to record key documentation.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __attr_doc {
+    () => { "" };
+    ([doc = $doc:literal] $($rest:tt)*) => {
+        concat!($doc, "\n", $crate::__attr_doc!($($rest)*))
+    };
+    ([$($other:tt)*] $($rest:tt)*) => {
+        $crate::__attr_doc!($($rest)*)
+    };
+}
+
 /// Declares attribute keys using a lazy_static! style syntax.
 ///
 /// # Syntax
@@ -820,7 +838,7 @@ macro_rules! assert_impl {
 macro_rules! declare_attrs {
     // Handle multiple attribute keys with optional default values and optional meta attributes
     ($(
-        $(#[$attr:meta])*
+        $(#[$($attr:tt)*])*
         $(@meta($($meta_key:ident = $meta_value:expr),* $(,)?))*
         $vis:vis attr $name:ident: $type:ty $(= $default:expr)?;
     )*) => {
@@ -828,14 +846,14 @@ macro_rules! declare_attrs {
             $crate::declare_attrs! {
                 @single
                 $(@meta($($meta_key = $meta_value),*))*
-                $(#[$attr])* ;
+                $(#[$($attr)*])* ;
                 $vis attr $name: $type $(= $default)?;
             }
         )*
     };
 
     // Handle single attribute key with default value and meta attributes
-    (@single $(@meta($($meta_key:ident = $meta_value:expr),* $(,)?))* $(#[$attr:meta])* ; $vis:vis attr $name:ident: $type:ty = $default:expr;) => {
+    (@single $(@meta($($meta_key:ident = $meta_value:expr),* $(,)?))* $(#[$($attr:tt)*])* ; $vis:vis attr $name:ident: $type:ty = $default:expr;) => {
         $crate::assert_impl!($type, $crate::attrs::AttrValue);
 
         // Create a static default value
@@ -852,7 +870,7 @@ macro_rules! declare_attrs {
                 });
         }
 
-        $(#[$attr])*
+        $(#[$($attr)*])*
         $vis static $name: $crate::attrs::Key<$type> = {
             $crate::assert_impl!($type, $crate::attrs::AttrValue);
 
@@ -890,12 +908,13 @@ macro_rules! declare_attrs {
                 },
                 default: Some($crate::paste! { &[<$name _DEFAULT>] }),
                 erased: &$name,
+                doc: $crate::__attr_doc!($([$($attr)*])*),
             }
         }
     };
 
     // Handle single attribute key without default value but with meta attributes
-    (@single $(@meta($($meta_key:ident = $meta_value:expr),* $(,)?))* $(#[$attr:meta])* ; $vis:vis attr $name:ident: $type:ty;) => {
+    (@single $(@meta($($meta_key:ident = $meta_value:expr),* $(,)?))* $(#[$($attr:tt)*])* ; $vis:vis attr $name:ident: $type:ty;) => {
         $crate::assert_impl!($type, $crate::attrs::AttrValue);
 
         $crate::paste! {
@@ -912,7 +931,7 @@ macro_rules! declare_attrs {
             });
         }
 
-        $(#[$attr])*
+        $(#[$($attr)*])*
         $vis static $name: $crate::attrs::Key<$type> = {
             const FULL_NAME: &str = concat!(std::module_path!(), "::", stringify!($name));
             const LOWER_NAME: &str = $crate::const_ascii_lowercase!(FULL_NAME);
@@ -943,6 +962,7 @@ macro_rules! declare_attrs {
                 },
                 default: None,
                 erased: &$name,
+                doc: $crate::__attr_doc!($([$($attr)*])*),
             }
         }
     };
@@ -1223,6 +1243,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_doc_is_recorded() {
+        let doc = |name: &str| {
+            inventory::iter::<AttrKeyInfo>()
+                .find(|info| info.name == name)
+                .unwrap()
+                .doc
+        };
+        assert_eq!(
+            doc("hyperactor_config::attrs::tests::timeout_with_default"),
+            " With default...\n"
+        );
+        assert_eq!(doc("hyperactor_config::attrs::tests::test_timeout"), "");
+    }
+
     #[test]
     fn test_indexing() {
         let mut attrs = Attrs::new();
@@ -30,6 +30,22 @@
 //! updates, YAML/Env baselines) while ensuring type safety and
 //! predictable resolution order.
 //!
+//! # Inspection
+//!
+//! [`report`] describes every declared key: its effective value, the
+//! layer it came from, values it shadows in lower-priority layers,
+//! and its documentation. Unknown environment variables and YAML keys
+//! are reported as warnings, or rejected when
+//! [`CONFIG_STRICT`](crate::CONFIG_STRICT) is set.
+//!
+//! # Live updates
+//!
+//! Callers that need to react to configuration changes can
+//! [`subscribe`] to a key and receive a `watch::Receiver` that is
+//! updated whenever the key's effective value changes (through any
+//! layer). [`watch_yaml`] keeps the `File` layer in sync with a YAML
+//! file on disk.
+//!
 //! # Testing
 //!
 //! Tests can override global configuration using [`lock`]. This
@@ -44,14 +60,27 @@
 //!     // ... test logic here ...
 //! }
 //! ```
+use std::any::Any;
 use std::collections::HashMap;
+use std::fmt;
 use std::marker::PhantomData;
 use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::LazyLock;
+use std::sync::Mutex;
 use std::sync::RwLock;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use hyperactor_named::Named;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::watch;
 
 use crate::CONFIG;
 use crate::attrs::AttrKeyInfo;
@@ -67,7 +96,7 @@ use crate::from_yaml;
 /// -> File -> ClientOverride -> Default**.
 ///
 /// Smaller `priority()` number = higher precedence.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Source {
     /// Values set by the config snapshot sent from the client
     /// during proc bootstrap.
@@ -373,8 +402,18 @@ pub fn lock() -> ConfigLock {
 /// Typically invoked once at process startup to overlay config values
 /// from the environment. Repeated calls replace the existing Env
 /// layer.
-pub fn init_from_env() {
+///
+/// Unknown `HYPERACTOR_*` variables are warned about; if
+/// [`CONFIG_STRICT`](crate::CONFIG_STRICT) is set, they are an error
+/// instead, and the Env layer is left untouched.
+pub fn init_from_env() -> Result<(), anyhow::Error> {
+    crate::check_unknown(
+        "the environment",
+        &crate::unknown_env_vars(),
+        crate::is_strict(),
+    )?;
     set(Source::Env, from_env());
+    Ok(())
 }
 
 /// Initialize the global configuration from a YAML file.
@@ -392,6 +431,175 @@ pub fn init_from_yaml<P: AsRef<Path>>(path: P) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Handle to a background thread started by [`watch_yaml`] that keeps
+/// the [`Source::File`] layer in sync with a YAML file. The watcher
+/// stops when the handle is dropped.
+pub struct YamlWatcher {
+    path: PathBuf,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl YamlWatcher {
+    /// The file being watched.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for YamlWatcher {
+    fn drop(&mut self) {
+        // Dropping the sender disconnects the channel, which wakes up
+        // the watcher thread.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Initialize the [`Source::File`] layer from a YAML file, and keep
+/// re-applying it whenever the file changes.
+///
+/// The file is polled every `interval` for changes to its
+/// modification time or size. On change the file is re-read and the
+/// whole File layer is replaced, notifying [`subscribe`]rs of any
+/// keys whose effective value changed. If the new contents fail to
+/// parse, a warning is logged and the previous layer stays in place.
+///
+/// Returns an error if the initial load fails.
+pub fn watch_yaml<P: AsRef<Path>>(
+    path: P,
+    interval: Duration,
+) -> Result<YamlWatcher, anyhow::Error> {
+    let path = path.as_ref().to_path_buf();
+    let mut fingerprint = file_fingerprint(&path);
+    init_from_yaml(&path)?;
+
+    let (stop, stopped) = mpsc::channel::<()>();
+    let watched = path.clone();
+    let thread = thread::Builder::new()
+        .name("config-yaml-watcher".to_string())
+        .spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let current = file_fingerprint(&watched);
+                if current.is_none() || current == fingerprint {
+                    continue;
+                }
+                fingerprint = current;
+                match from_yaml(&watched) {
+                    Ok(attrs) => {
+                        tracing::info!(
+                            "reloaded config file {} ({} keys)",
+                            watched.display(),
+                            attrs.len()
+                        );
+                        set(Source::File, attrs);
+                    }
+                    Err(e) => tracing::warn!(
+                        "failed to reload config file {}, keeping previous values: {}",
+                        watched.display(),
+                        e
+                    ),
+                }
+            }
+        })?;
+
+    Ok(YamlWatcher {
+        path,
+        stop: Some(stop),
+        thread: Some(thread),
+    })
+}
+
+/// The (modification time, size) of a file, used to detect changes.
+fn file_fingerprint(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// A live subscription to a single key. Type-erased so that keys of
+/// different types can share one registry.
+trait Subscription: Send + Sync {
+    /// Re-resolve the key and publish its value if it changed.
+    fn refresh(&self);
+
+    /// Whether every receiver has been dropped.
+    fn is_closed(&self) -> bool;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+struct KeySubscription<T: AttrValue> {
+    key: Key<T>,
+    tx: watch::Sender<T>,
+}
+
+impl<T: AttrValue> Subscription for KeySubscription<T> {
+    fn refresh(&self) {
+        let Some(value) = try_get_cloned(self.key) else {
+            return;
+        };
+        // `AttrValue` does not require `PartialEq`, so compare the
+        // display forms instead.
+        self.tx.send_if_modified(|current| {
+            if current.display() == value.display() {
+                false
+            } else {
+                *current = value;
+                true
+            }
+        });
+    }
+
+    fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Active subscriptions, keyed by attr name.
+///
+/// Lock order: this lock may be held while acquiring [`LAYERS`], never
+/// the other way around.
+static SUBSCRIPTIONS: LazyLock<Mutex<HashMap<&'static str, Box<dyn Subscription>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Subscribe to changes of `key`'s effective value.
+///
+/// The returned receiver starts out holding the current value and is
+/// updated whenever a change to any layer (e.g. [`set`],
+/// [`create_or_merge`], a [`watch_yaml`] reload or a test override)
+/// changes the resolved value for `key`. Panics if the key has no
+/// default and is not set in any layer.
+pub fn subscribe<T: AttrValue>(key: Key<T>) -> watch::Receiver<T> {
+    let mut subscriptions = SUBSCRIPTIONS.lock().unwrap();
+    if let Some(subscription) = subscriptions
+        .get(key.name())
+        .filter(|subscription| !subscription.is_closed())
+        .and_then(|subscription| subscription.as_any().downcast_ref::<KeySubscription<T>>())
+    {
+        return subscription.tx.subscribe();
+    }
+
+    let (tx, rx) = watch::channel(get_cloned(key));
+    subscriptions.insert(key.name(), Box::new(KeySubscription { key, tx }));
+    rx
+}
+
+/// Publish new values to subscribers after a layer changed. Must not
+/// be called while holding [`LAYERS`].
+fn notify_subscribers() {
+    let mut subscriptions = SUBSCRIPTIONS.lock().unwrap();
+    subscriptions.retain(|_, subscription| !subscription.is_closed());
+    for subscription in subscriptions.values() {
+        subscription.refresh();
+    }
+}
+
 /// Get a key from the global configuration (Copy types).
 ///
 /// Resolution order: TestOverride -> Env -> Runtime -> File ->
@@ -483,13 +691,16 @@ fn make_layer(source: Source, attrs: Attrs) -> Layer {
 /// `init_from_env`, `init_from_yaml`) and by tests when overriding
 /// configuration values.
 pub fn set(source: Source, attrs: Attrs) {
-    let mut g = LAYERS.write().unwrap();
-    if let Some(l) = g.ordered.iter_mut().find(|l| layer_source(l) == source) {
-        *layer_attrs_mut(l) = attrs;
-    } else {
-        g.ordered.push(make_layer(source, attrs));
+    {
+        let mut g = LAYERS.write().unwrap();
+        if let Some(l) = g.ordered.iter_mut().find(|l| layer_source(l) == source) {
+            *layer_attrs_mut(l) = attrs;
+        } else {
+            g.ordered.push(make_layer(source, attrs));
+        }
+        g.ordered.sort_by_key(|l| priority(layer_source(l))); // TestOverride < Env < Runtime < File < ClientOverride
     }
-    g.ordered.sort_by_key(|l| priority(layer_source(l))); // TestOverride < Env < Runtime < File < ClientOverride
+    notify_subscribers();
 }
 
 /// Insert or update a configuration layer for the given [`Source`].
@@ -510,13 +721,16 @@ pub fn set(source: Source, attrs: Attrs) {
 /// By contrast, [`set`] replaces the entire layer for `source` with
 /// `attrs`, discarding any existing values in that layer.
 pub fn create_or_merge(source: Source, attrs: Attrs) {
-    let mut g = LAYERS.write().unwrap();
-    if let Some(layer) = g.ordered.iter_mut().find(|l| layer_source(l) == source) {
-        layer_attrs_mut(layer).merge(attrs);
-    } else {
-        g.ordered.push(make_layer(source, attrs));
+    {
+        let mut g = LAYERS.write().unwrap();
+        if let Some(layer) = g.ordered.iter_mut().find(|l| layer_source(l) == source) {
+            layer_attrs_mut(layer).merge(attrs);
+        } else {
+            g.ordered.push(make_layer(source, attrs));
+        }
+        g.ordered.sort_by_key(|l| priority(layer_source(l))); // TestOverride < Env < Runtime < File < ClientOverride
     }
-    g.ordered.sort_by_key(|l| priority(layer_source(l))); // TestOverride < Env < Runtime < File < ClientOverride
+    notify_subscribers();
 }
 
 /// Remove the configuration layer for the given [`Source`], if
@@ -527,8 +741,12 @@ pub fn create_or_merge(source: Source, attrs: Attrs) {
 /// and any remaining layers continue to apply in their normal
 /// priority order.
 pub fn clear(source: Source) {
-    let mut g = LAYERS.write().unwrap();
-    g.ordered.retain(|l| layer_source(l) != source);
+    LAYERS
+        .write()
+        .unwrap()
+        .ordered
+        .retain(|l| layer_source(l) != source);
+    notify_subscribers();
 }
 
 /// Return a complete, merged snapshot of the effective configuration
@@ -587,6 +805,123 @@ pub fn attrs() -> Attrs {
     merged
 }
 
+/// A declared configuration key as described by [`report`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    /// The fully qualified key name.
+    pub name: String,
+    /// The environment variable mapped to this key, if any.
+    pub env_name: Option<String>,
+    /// The `monarch.configure(...)` kwarg mapped to this key, if any.
+    pub py_name: Option<String>,
+    /// The key's documentation.
+    pub doc: String,
+    /// The effective value, or `None` if the key is unset and has no
+    /// default.
+    pub value: Option<String>,
+    /// The layer that provided `value`, or `None` if it is the
+    /// default.
+    pub source: Option<Source>,
+    /// Values in lower-priority layers that are shadowed by `source`,
+    /// in priority order.
+    pub shadowed: Vec<(Source, String)>,
+    /// The key's default, if any.
+    pub default: Option<String>,
+}
+
+/// The effective configuration of a process, produced by [`report`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConfigReport {
+    /// All CONFIG-marked keys, sorted by name.
+    pub entries: Vec<ConfigEntry>,
+    /// `HYPERACTOR_*` environment variables that do not map to any
+    /// declared key.
+    pub unknown_env_vars: Vec<String>,
+}
+
+impl Named for ConfigReport {
+    fn typename() -> &'static str {
+        "hyperactor_config::global::ConfigReport"
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            let value = entry.value.as_deref().unwrap_or("<unset>");
+            match entry.source {
+                Some(source) => writeln!(f, "{} = {} ({:?})", entry.name, value, source)?,
+                None => writeln!(f, "{} = {} (default)", entry.name, value)?,
+            }
+            for (source, shadowed) in &entry.shadowed {
+                writeln!(f, "    shadows {:?} = {}", source, shadowed)?;
+            }
+            if let Some(env_name) = &entry.env_name {
+                writeln!(f, "    env: {}", env_name)?;
+            }
+            for line in entry.doc.lines() {
+                writeln!(f, "    | {}", line)?;
+            }
+        }
+        for name in &self.unknown_env_vars {
+            writeln!(f, "warning: unknown environment variable {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe every CONFIG-marked key: its effective value, the layer
+/// that provided it, the values it shadows, and its documentation.
+///
+/// Unlike [`attrs`], values are rendered with `AttrValue::display`,
+/// so the report can be shipped to and printed by another process.
+pub fn report() -> ConfigReport {
+    let layers = LAYERS.read().unwrap();
+    let mut entries = Vec::new();
+    for info in inventory::iter::<AttrKeyInfo>() {
+        let Some(config) = info.meta.get(CONFIG) else {
+            continue;
+        };
+
+        let mut set = layers.ordered.iter().filter_map(|layer| {
+            layer_attrs(layer)
+                .get_value_by_name(info.name)
+                .map(|value| (layer_source(layer), value.display()))
+        });
+        let winner = set.next();
+        let shadowed = set.collect();
+        let default = info.default.map(|value| value.display());
+
+        entries.push(ConfigEntry {
+            name: info.name.to_string(),
+            env_name: config.env_name.clone(),
+            py_name: config.py_name.clone(),
+            doc: info
+                .doc
+                .lines()
+                .map(|line| line.strip_prefix(' ').unwrap_or(line))
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string(),
+            value: winner
+                .as_ref()
+                .map(|(_, value)| value.clone())
+                .or_else(|| default.clone()),
+            source: winner.map(|(source, _)| source),
+            shadowed,
+            default,
+        });
+    }
+    drop(layers);
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    ConfigReport {
+        entries,
+        unknown_env_vars: crate::unknown_env_vars(),
+    }
+}
+
 /// Return a snapshot of the attributes for a specific configuration
 /// source.
 ///
@@ -624,8 +959,8 @@ pub fn runtime_attrs() -> Attrs {
 /// Note: Should be called while holding [`global::lock`] in tests, to
 /// ensure no concurrent modifications happen.
 pub fn reset_to_defaults() {
-    let mut g = LAYERS.write().unwrap();
-    g.ordered.clear();
+    LAYERS.write().unwrap().ordered.clear();
+    notify_subscribers();
 }
 
 /// A guard that holds the global configuration lock and provides
@@ -715,6 +1050,9 @@ impl ConfigLock {
             unsafe { std::env::set_var(var, &top.env_str) }
         }
 
+        drop(g);
+        notify_subscribers();
+
         ConfigValueGuard {
             key,
             token,
@@ -736,10 +1074,13 @@ impl ConfigLock {
 /// itself is released.
 impl Drop for ConfigLock {
     fn drop(&mut self) {
-        let mut guard = LAYERS.write().unwrap();
-        if let Some(pos) = test_override_index(&guard) {
-            guard.ordered.remove(pos);
+        {
+            let mut guard = LAYERS.write().unwrap();
+            if let Some(pos) = test_override_index(&guard) {
+                guard.ordered.remove(pos);
+            }
         }
+        notify_subscribers();
     }
 }
 
@@ -851,6 +1192,9 @@ impl<T: 'static> Drop for ConfigValueGuard<'_, T> {
             // Now it's safe to remove the stack from the map.
             let _ = stacks.remove(key_name);
         }
+
+        drop(g);
+        notify_subscribers();
     }
 }
 
@@ -1574,4 +1918,139 @@ mod tests {
         assert_eq!(get(CODEC_MAX_FRAME_LENGTH), CODEC_MAX_FRAME_LENGTH_DEFAULT);
         assert_eq!(get(CHANNEL_MULTIPART), CHANNEL_MULTIPART_DEFAULT);
     }
+
+    #[test]
+    fn test_subscribe_sees_layer_changes() {
+        let _lock = lock();
+        reset_to_defaults();
+
+        let mut rx = subscribe(SPLIT_MAX_BUFFER_SIZE);
+        assert_eq!(*rx.borrow_and_update(), SPLIT_MAX_BUFFER_SIZE_DEFAULT);
+
+        let mut file = Attrs::new();
+        file[SPLIT_MAX_BUFFER_SIZE] = 7;
+        set(Source::File, file);
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), 7);
+
+        // Changes to other keys are not published.
+        let mut runtime = Attrs::new();
+        runtime[MESSAGE_TTL_DEFAULT] = 3;
+        create_or_merge(Source::Runtime, runtime);
+        assert!(!rx.has_changed().unwrap());
+
+        // A higher-priority layer shadows the File value.
+        let mut runtime = Attrs::new();
+        runtime[SPLIT_MAX_BUFFER_SIZE] = 9;
+        create_or_merge(Source::Runtime, runtime);
+        assert_eq!(*rx.borrow_and_update(), 9);
+
+        clear(Source::Runtime);
+        assert_eq!(*rx.borrow_and_update(), 7);
+        clear(Source::File);
+        assert_eq!(*rx.borrow_and_update(), SPLIT_MAX_BUFFER_SIZE_DEFAULT);
+
+        // A second subscriber shares the same channel.
+        let rx2 = subscribe(SPLIT_MAX_BUFFER_SIZE);
+        assert_eq!(*rx2.borrow(), SPLIT_MAX_BUFFER_SIZE_DEFAULT);
+    }
+
+    #[test]
+    fn test_subscribe_sees_test_overrides() {
+        let lock = lock();
+        reset_to_defaults();
+
+        let mut rx = subscribe(MESSAGE_TTL_DEFAULT);
+        {
+            let _guard = lock.override_key(MESSAGE_TTL_DEFAULT, 11);
+            assert_eq!(*rx.borrow_and_update(), 11);
+        }
+        assert_eq!(*rx.borrow_and_update(), MESSAGE_TTL_DEFAULT_DEFAULT);
+    }
+
+    #[test]
+    fn test_watch_yaml_reloads_file_layer() {
+        let _lock = lock();
+        reset_to_defaults();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let mut file = Attrs::new();
+        file[MESSAGE_ACK_EVERY_N_MESSAGES] = 10;
+        crate::to_yaml(&file, &path).unwrap();
+
+        let watcher = watch_yaml(&path, Duration::from_millis(10)).unwrap();
+        assert_eq!(watcher.path(), path.as_path());
+        assert_eq!(get(MESSAGE_ACK_EVERY_N_MESSAGES), 10);
+        let mut rx = subscribe(MESSAGE_ACK_EVERY_N_MESSAGES);
+
+        // Longer contents, so the change is detected even with a
+        // coarse mtime granularity.
+        file[MESSAGE_ACK_EVERY_N_MESSAGES] = 123456;
+        crate::to_yaml(&file, &path).unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while get(MESSAGE_ACK_EVERY_N_MESSAGES) != 123456 {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "config file was not reloaded"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*rx.borrow_and_update(), 123456);
+
+        // Unparseable contents leave the previous layer in place.
+        std::fs::write(&path, "not: [valid yaml").unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(get(MESSAGE_ACK_EVERY_N_MESSAGES), 123456);
+
+        drop(watcher);
+    }
+
+    #[test]
+    fn test_report_sources_and_shadowing() {
+        let _lock = lock();
+        reset_to_defaults();
+
+        let mut file = Attrs::new();
+        file[SPLIT_MAX_BUFFER_SIZE] = 7;
+        set(Source::File, file);
+        let mut runtime = Attrs::new();
+        runtime[SPLIT_MAX_BUFFER_SIZE] = 9;
+        set(Source::Runtime, runtime);
+
+        let report = report();
+        let entry = |name: &str| {
+            report
+                .entries
+                .iter()
+                .find(|entry| entry.name == name)
+                .unwrap()
+        };
+
+        let split = entry("hyperactor_config::global::tests::split_max_buffer_size");
+        assert_eq!(split.value.as_deref(), Some("9"));
+        assert_eq!(split.source, Some(Source::Runtime));
+        assert_eq!(split.shadowed, vec![(Source::File, "7".to_string())]);
+        assert_eq!(split.default.as_deref(), Some("5"));
+        assert_eq!(split.doc, "Maximum buffer size for split port messages");
+        assert_eq!(
+            split.env_name.as_deref(),
+            Some("HYPERACTOR_SPLIT_MAX_BUFFER_SIZE")
+        );
+
+        let ack = entry("hyperactor_config::global::tests::message_ack_every_n_messages");
+        assert_eq!(ack.value.as_deref(), Some("1000"));
+        assert_eq!(ack.source, None);
+        assert!(ack.shadowed.is_empty());
+
+        assert!(report.to_string().contains(
+            "hyperactor_config::global::tests::split_max_buffer_size = 9 (Runtime)\n    shadows File = 7"
+        ));
+    }
+
+    #[test]
+    fn test_watch_yaml_missing_file() {
+        assert!(watch_yaml("/nonexistent/config.yaml", Duration::from_secs(1)).is_err());
+    }
 }
@@ -13,11 +13,14 @@
 //! - `ConfigAttr`: Metadata for configuration keys
 //! - Helper functions to load/save `Attrs` (from env via `from_env`,
 //!   from YAML via `from_yaml`, and `to_yaml`)
+//! - Detection of unknown keys in the environment and YAML files
+//!   (warnings by default, errors under [`CONFIG_STRICT`])
 //! - Global layered configuration store under [`crate::global`]
 //!
 //! Individual crates should declare their own config keys using `declare_attrs!`
 //! and import `ConfigAttr`, `CONFIG`, and other infrastructure from this crate.
 
+use std::collections::HashSet;
 use std::env;
 use std::fs::File;
 use std::io::Read;
@@ -99,6 +102,89 @@ declare_attrs! {
     pub attr CONFIG: ConfigAttr;
 }
 
+declare_attrs! {
+    /// Treat unknown configuration keys as errors instead of
+    /// warnings: [`from_yaml`] rejects files with undeclared keys, and
+    /// [`global::init_from_env`] rejects unknown `HYPERACTOR_*`
+    /// environment variables.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_CONFIG_STRICT".to_string()),
+        py_name: None,
+    })
+    pub attr CONFIG_STRICT: bool = false;
+}
+
+/// Prefix of environment variables that are expected to map to
+/// configuration keys.
+const ENV_PREFIX: &str = "HYPERACTOR_";
+
+/// Variables under [`ENV_PREFIX`] that are read directly by the
+/// runtime (mostly as part of the process bootstrap protocol) rather
+/// than through configuration keys.
+const NON_CONFIG_ENV_VARS: &[&str] = &[
+    "HYPERACTOR_EXECUTION_ID",
+    "HYPERACTOR_HOST_BACKEND_ADDR",
+    "HYPERACTOR_HOST_CALLBACK_ADDR",
+    "HYPERACTOR_HOST_PROC_ID",
+    "HYPERACTOR_MESH_BOOTSTRAP_ADDR",
+    "HYPERACTOR_MESH_BOOTSTRAP_MODE",
+    "HYPERACTOR_MESH_INDEX",
+    "HYPERACTOR_MESH_ROUTER_NO_GLOBAL_FALLBACK",
+    "HYPERACTOR_OTEL_EXPORTER",
+    "HYPERACTOR_PROCESS_NAME",
+    "HYPERACTOR_SELECTION_DISABLE_ROUTING_FRAME_DEDUPLICATION",
+];
+
+/// Whether unknown keys should be rejected. Consults the environment
+/// directly, so that strictness applies while the Env layer itself
+/// is being loaded.
+pub(crate) fn is_strict() -> bool {
+    CONFIG_STRICT
+        .attrs()
+        .get(CONFIG)
+        .and_then(|cfg| cfg.env_name.as_deref())
+        .and_then(|name| env::var(name).ok())
+        .and_then(|value| <bool as AttrValue>::parse(&value).ok())
+        .unwrap_or_else(|| global::get(CONFIG_STRICT))
+}
+
+/// Report `unknown` keys found in `origin`: an error if `strict`,
+/// otherwise a warning.
+pub(crate) fn check_unknown(
+    origin: &str,
+    unknown: &[String],
+    strict: bool,
+) -> Result<(), anyhow::Error> {
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    let message = format!(
+        "unknown configuration keys in {}: {}",
+        origin,
+        unknown.join(", ")
+    );
+    if strict {
+        anyhow::bail!(message);
+    }
+    tracing::warn!("{}", message);
+    Ok(())
+}
+
+/// Environment variables under the `HYPERACTOR_` prefix that do not
+/// correspond to any declared configuration key, sorted by name.
+pub fn unknown_env_vars() -> Vec<String> {
+    let known: HashSet<&str> = inventory::iter::<AttrKeyInfo>()
+        .filter_map(|info| info.meta.get(CONFIG)?.env_name.as_deref())
+        .chain(NON_CONFIG_ENV_VARS.iter().copied())
+        .collect();
+    let mut unknown: Vec<String> = env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .filter(|name| name.starts_with(ENV_PREFIX) && !known.contains(name.as_str()))
+        .collect();
+    unknown.sort();
+    unknown
+}
+
 /// Load configuration from environment variables
 pub fn from_env() -> Attrs {
     let mut config = Attrs::new();
@@ -160,12 +246,28 @@ pub fn from_env() -> Attrs {
     config
 }
 
-/// Load configuration from a YAML file
+/// Load configuration from a YAML file.
+///
+/// Keys that do not name a declared attribute are dropped with a
+/// warning, or rejected if [`CONFIG_STRICT`] is set.
 pub fn from_yaml<P: AsRef<Path>>(path: P) -> Result<Attrs, anyhow::Error> {
+    let path = path.as_ref();
     let mut file = File::open(path)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
-    Ok(serde_yaml::from_str(&contents)?)
+
+    let mut mapping: serde_yaml::Mapping = serde_yaml::from_str(&contents)?;
+    let unknown: Vec<String> = mapping
+        .keys()
+        .filter_map(serde_yaml::Value::as_str)
+        .filter(|key| !inventory::iter::<AttrKeyInfo>().any(|info| info.name == *key))
+        .map(str::to_string)
+        .collect();
+    check_unknown(&path.display().to_string(), &unknown, is_strict())?;
+    for key in &unknown {
+        mapping.remove(key.as_str());
+    }
+    Ok(serde_yaml::from_value(serde_yaml::Value::Mapping(mapping))?)
 }
 
 /// Save configuration to a YAML file
@@ -471,4 +573,61 @@ mod tests {
 
         let _ = std::fs::remove_file(&temp_path);
     }
+
+    #[test]
+    fn test_from_yaml_unknown_keys() {
+        let config = crate::global::lock();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            indoc! {"
+                hyperactor_config::tests::usize_key: 7
+                hyperactor_config::tests::no_such_key: 1
+            "},
+        )
+        .unwrap();
+
+        // By default, unknown keys are dropped.
+        let loaded = from_yaml(&path).unwrap();
+        assert_eq!(loaded[USIZE_KEY], 7);
+        assert_eq!(loaded.len(), 1);
+
+        // In strict mode, they are rejected.
+        let _guard = config.override_key(crate::CONFIG_STRICT, true);
+        let err = from_yaml(&path).unwrap_err().to_string();
+        assert!(
+            err.contains("hyperactor_config::tests::no_such_key"),
+            "got: {err}"
+        );
+    }
+
+    #[test]
+    fn test_unknown_env_vars() {
+        let config = crate::global::lock();
+        // SAFETY: TODO: Audit that the environment access only happens in single-threaded code.
+        unsafe { std::env::set_var("HYPERACTOR_NO_SUCH_CONFIG_KEY", "1") };
+        // SAFETY: TODO: Audit that the environment access only happens in single-threaded code.
+        unsafe { std::env::set_var("HYPERACTOR_MESH_INDEX", "0") };
+
+        let unknown = crate::unknown_env_vars();
+        assert!(unknown.contains(&"HYPERACTOR_NO_SUCH_CONFIG_KEY".to_string()));
+        // Bootstrap variables are not configuration keys, but are known.
+        assert!(!unknown.contains(&"HYPERACTOR_MESH_INDEX".to_string()));
+
+        // Unknown variables are only warned about by default...
+        crate::global::init_from_env().unwrap();
+        // ...but are rejected in strict mode.
+        {
+            let _guard = config.override_key(crate::CONFIG_STRICT, true);
+            let err = crate::global::init_from_env().unwrap_err().to_string();
+            assert!(err.contains("HYPERACTOR_NO_SUCH_CONFIG_KEY"), "got: {err}");
+        }
+
+        // SAFETY: TODO: Audit that the environment access only happens in single-threaded code.
+        unsafe { std::env::remove_var("HYPERACTOR_NO_SUCH_CONFIG_KEY") };
+        // SAFETY: TODO: Audit that the environment access only happens in single-threaded code.
+        unsafe { std::env::remove_var("HYPERACTOR_MESH_INDEX") };
+        crate::global::reset_to_defaults();
+    }
 }
@@ -958,8 +958,32 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                         ));
 
                 };
+                // Calls are tracked for stall detection (see hyperactor::wait_graph).
+                let wait_message_type = if variant.is_struct() {
+                    quote! { stringify!(#enum_name) }
+                } else {
+                    let variant_name = variant.name();
+                    quote! { concat!(stringify!(#enum_name), "::", stringify!(#variant_name)) }
+                };
                 let open_port = reply_port.open_op();
                 let rx_mod = reply_port.rx_modifier();
+                let recv_reply = if reply_port.is_once {
+                    quote! {
+                        reply_receiver
+                            .awaiting(self.actor_id(), #wait_message_type)
+                            .recv()
+                            .await
+                    }
+                } else {
+                    quote! {{
+                        let _wait = hyperactor::wait_graph::track(
+                            cx,
+                            self.actor_id(),
+                            #wait_message_type,
+                        );
+                        reply_receiver.recv().await
+                    }}
+                };
                 if reply_port.is_handle {
                     impl_methods.push(quote! {
                         #[hyperactor::instrument(level=#log_level, rpc = "call", message_type=#name)]
@@ -973,7 +997,7 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                             let message = #constructor;
                             #log_message;
                             #send_message;
-                            reply_receiver.recv().await.map_err(hyperactor::anyhow::Error::from)
+                            #recv_reply.map_err(hyperactor::anyhow::Error::from)
                         }
 
                         #[hyperactor::instrument(level=#log_level, rpc = "call", message_type=#name)]
@@ -987,7 +1011,7 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                             let message = #constructor;
                             #log_message;
                             #send_message;
-                            reply_receiver.recv().await.map_err(hyperactor::anyhow::Error::from)
+                            #recv_reply.map_err(hyperactor::anyhow::Error::from)
                         }
                     });
                 } else {
@@ -1004,7 +1028,7 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                             let message = #constructor;
                             #log_message;
                             #send_message;
-                            reply_receiver.recv().await.map_err(hyperactor::anyhow::Error::from)
+                            #recv_reply.map_err(hyperactor::anyhow::Error::from)
                         }
 
                         #[hyperactor::instrument(level=#log_level, rpc="call", message_type=#name)]
@@ -1019,7 +1043,7 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                             let message = #constructor;
                             #log_message;
                             #send_message;
-                            reply_receiver.recv().await.map_err(hyperactor::anyhow::Error::from)
+                            #recv_reply.map_err(hyperactor::anyhow::Error::from)
                         }
                     });
                 }
@@ -38,6 +38,7 @@ use ndslice::Range;
 use ndslice::Selection;
 use ndslice::Shape;
 use ndslice::ShapeError;
+use ndslice::Slice;
 use ndslice::SliceError;
 use ndslice::View;
 use ndslice::reshape::Limit;
@@ -59,6 +60,7 @@ use crate::Mesh;
 use crate::comm::multicast::CastMessage;
 use crate::comm::multicast::CastMessageEnvelope;
 use crate::comm::multicast::Uslice;
+use crate::config::CAST_CHOICE_POLICY;
 use crate::config::MAX_CAST_DIMENSION_SIZE;
 use crate::metrics;
 use crate::proc_mesh::ProcMesh;
@@ -134,6 +136,7 @@ where
             selection: selection_of_cast,
         },
         message,
+        choice_policy: hyperactor_config::global::get(CAST_CHOICE_POLICY),
     };
 
     let mut headers = Attrs::new();
@@ -166,8 +169,15 @@ where
     let sel_of_root = if selection::normalize(sel_of_sliced) == normal::NormalizedSelection::True {
         // Reify this view into base.
         root_slice.reify_slice(sliced_shape.slice())?
+    } else if let Some(sel_of_root) =
+        translate_selection(sel_of_sliced, sliced_shape.slice(), root_slice)
+    {
+        // The selection maps onto the base, keeping any `Any` for the
+        // comm actors to resolve according to `CAST_CHOICE_POLICY`.
+        sel_of_root
     } else {
-        // No, fall back on `of_ranks`.
+        // No, fall back on `of_ranks`. Note that this resolves any
+        // `Any` in the selection here, at random.
         let ranks = sel_of_sliced
             .eval(&EvalOpts::strict(), sliced_shape.slice())?
             .collect::<BTreeSet<_>>();
@@ -186,6 +196,108 @@ where
     )
 }
 
+/// Translate `selection`, made over `view`, into a selection over
+/// `base`, dimension by dimension. This requires `view` to be a
+/// layout-aligned region of a contiguous `base` of the same
+/// dimensionality (as produced by `select`). Returns `None` if the
+/// view or the selection (`Where`, `First` and `Label`) cannot be
+/// translated this way.
+fn translate_selection(selection: &Selection, view: &Slice, base: &Slice) -> Option<Selection> {
+    if !base.is_contiguous()
+        || view.num_dim() != base.num_dim()
+        || view.is_empty()
+        || view
+            .strides()
+            .iter()
+            .zip(base.strides())
+            .any(|(view_stride, base_stride)| !view_stride.is_multiple_of(*base_stride))
+    {
+        return None;
+    }
+    let origin = base.coordinates(view.offset()).ok()?;
+    let view = ViewInBase(
+        (0..base.num_dim())
+            .map(|dim| {
+                (
+                    origin[dim],
+                    view.strides()[dim] / base.strides()[dim],
+                    view.sizes()[dim],
+                )
+            })
+            .collect(),
+    );
+    view.translate(selection, 0)
+}
+
+/// A view's placement in its base: per dimension, the view's first
+/// index in the base, its step, and its size.
+struct ViewInBase(Vec<(usize, usize, usize)>);
+
+impl ViewInBase {
+    /// The base indices of view indices `min..max` by `step` along
+    /// `dim`.
+    fn range(&self, dim: usize, min: usize, max: usize, step: usize) -> Range {
+        let (start, view_step, _) = self.0[dim];
+        Range(
+            start + min * view_step,
+            Some(start + (max - 1) * view_step + 1),
+            view_step * step,
+        )
+    }
+
+    /// The whole view, from `dim` on.
+    fn region(&self, dim: usize) -> Selection {
+        (dim..self.0.len())
+            .rev()
+            .fold(Selection::True, |inner, dim| {
+                Selection::Range(self.range(dim, 0, self.0[dim].2, 1), Box::new(inner))
+            })
+    }
+
+    fn translate(&self, selection: &Selection, dim: usize) -> Option<Selection> {
+        if dim == self.0.len() {
+            return matches!(selection, Selection::True | Selection::False)
+                .then(|| selection.clone());
+        }
+        let size = self.0[dim].2;
+        let translate =
+            |selection: &Selection, dim: usize| self.translate(selection, dim).map(Box::new);
+        Some(match selection {
+            Selection::False => Selection::False,
+            Selection::True => self.region(dim),
+            Selection::All(inner) => {
+                Selection::Range(self.range(dim, 0, size, 1), translate(inner, dim + 1)?)
+            }
+            Selection::Range(Range(min, end, step), inner) => {
+                let (min, max, step) = (*min, end.map_or(size, |end| end.min(size)), *step);
+                if min >= max {
+                    return Some(Selection::False);
+                }
+                Selection::Range(self.range(dim, min, max, step), translate(inner, dim + 1)?)
+            }
+            // Keep the choice, but among the view's indices only.
+            Selection::Any(inner) => Selection::Intersection(
+                Box::new(Selection::Range(
+                    self.range(dim, 0, size, 1),
+                    Box::new(Selection::True),
+                )),
+                Box::new(Selection::Any(translate(inner, dim + 1)?)),
+            ),
+            Selection::Union(a, b) => Selection::Union(translate(a, dim)?, translate(b, dim)?),
+            Selection::Intersection(a, b) => {
+                Selection::Intersection(translate(a, dim)?, translate(b, dim)?)
+            }
+            Selection::Difference(a, b) => {
+                Selection::Difference(translate(a, dim)?, translate(b, dim)?)
+            }
+            Selection::Complement(inner) => {
+                Selection::Difference(Box::new(self.region(dim)), translate(inner, dim)?)
+            }
+            Selection::Where(..) | Selection::First(_) | Selection::Label(..) => return None,
+        })
+    }
+}
+
 /// A mesh of actors, all of which reside on the same [`ProcMesh`].
 #[async_trait]
 pub trait ActorMesh: Mesh<Id = ActorMeshId> {
@@ -872,6 +984,45 @@ mod tests {
     use super::*;
     use crate::proc_mesh::ProcEvent;
 
+    #[test]
+    fn test_translate_selection() {
+        use ndslice::dsl::*;
+
+        let base_shape = ndslice::shape!(x = 4, y = 6);
+        let base = base_shape.slice();
+        let view_shape = ndslice::select!(base_shape, x = 1..3, y = 1..6).unwrap();
+        let view = view_shape.slice();
+
+        for selection in [
+            true_(),
+            all(range(1..3, true_())),
+            range(1, complement(range(0..2, true_()))),
+            union(range(0, all(true_())), all(range(4, true_()))),
+        ] {
+            let expected = selection
+                .eval(&EvalOpts::strict(), view)
+                .unwrap()
+                .collect::<BTreeSet<_>>();
+            let translated = translate_selection(&selection, view, base).unwrap();
+            let actual = translated
+                .eval(&EvalOpts::strict(), base)
+                .unwrap()
+                .collect::<BTreeSet<_>>();
+            assert_eq!(actual, expected, "{}", selection);
+        }
+
+        // `Any` picks one row of the view.
+        let translated = translate_selection(&any(all(true_())), view, base).unwrap();
+        let selected = translated
+            .eval(&EvalOpts::strict(), base)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(selected.len(), 5);
+        assert!(selected.iter().all(|rank| view.iter().any(|r| r == *rank)));
+
+        assert!(translate_selection(&first(all(true_())), view, base).is_none());
+    }
+
     // These tests are parametric over allocators.
     #[macro_export]
     macro_rules! actor_mesh_test_suite {
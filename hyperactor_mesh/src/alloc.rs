@@ -107,6 +107,32 @@ declare_attrs! {
         py_name: None,
     })
     pub attr REMOTE_ALLOC_ALLOWED_PORT_RANGE: Range<u16>;
+
+    /// Path to a token file whose first entry, a `<principal> <token>`
+    /// line, is presented as the credential on remote allocation
+    /// requests.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_REMOTE_ALLOC_TOKEN_FILE".to_string()),
+        py_name: None,
+    })
+    pub attr REMOTE_ALLOC_TOKEN_FILE: String;
+
+    /// Path to a file holding the secret shared with remote allocators.
+    /// When set, remote allocation requests are signed with HMAC-SHA256
+    /// using this secret. Takes precedence over `REMOTE_ALLOC_TOKEN_FILE`.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_REMOTE_ALLOC_SECRET_FILE".to_string()),
+        py_name: None,
+    })
+    pub attr REMOTE_ALLOC_SECRET_FILE: String;
+
+    /// The principal named in HMAC-signed remote allocation requests.
+    /// Defaults to the `USER` environment variable.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_REMOTE_ALLOC_PRINCIPAL".to_string()),
+        py_name: None,
+    })
+    pub attr REMOTE_ALLOC_PRINCIPAL: String;
 }
 
 /// Errors that occur during allocation operations.
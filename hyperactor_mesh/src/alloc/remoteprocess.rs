@@ -6,6 +6,8 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+pub mod auth;
+
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
@@ -69,6 +71,9 @@ use crate::alloc::ProcessAllocator;
 use crate::alloc::REMOTE_ALLOC_BOOTSTRAP_ADDR;
 use crate::alloc::process::CLIENT_TRACE_ID_LABEL;
 use crate::alloc::process::ClientContext;
+use crate::alloc::remoteprocess::auth::AllocCredential;
+use crate::alloc::remoteprocess::auth::AllocPolicy;
+use crate::alloc::remoteprocess::auth::AllocRequest;
 use crate::alloc::serve_with_config;
 use crate::alloc::with_unspecified_port_or_any;
 use crate::shortuuid::ShortUuid;
@@ -93,6 +98,9 @@ pub enum RemoteProcessAllocatorMessage {
         client_context: Option<ClientContext>,
         /// The address allocator should use for its forwarder.
         forwarder_addr: ChannelAddr,
+        /// Credential authenticating the client, checked by allocators
+        /// configured with an [`AllocPolicy`] authenticator.
+        credential: Option<AllocCredential>,
     },
     /// Stop allocation.
     Stop,
@@ -101,6 +109,34 @@ pub enum RemoteProcessAllocatorMessage {
     HeartBeat,
 }
 
+impl RemoteProcessAllocatorMessage {
+    /// Attach the client's credential (see [`auth::client_credential`])
+    /// to an `Allocate` message that is sent over `transport`.
+    fn sign(&mut self, transport: &ChannelTransport) -> Result<(), anyhow::Error> {
+        if let Self::Allocate {
+            alloc_key,
+            extent,
+            bootstrap_addr,
+            hosts,
+            client_context,
+            forwarder_addr,
+            credential,
+        } = self
+        {
+            let request = AllocRequest {
+                alloc_key,
+                extent,
+                bootstrap_addr,
+                hosts,
+                client_context: client_context.as_ref(),
+                forwarder_addr,
+            };
+            *credential = auth::client_credential(request, transport)?;
+        }
+        Ok(())
+    }
+}
+
 /// Control message sent from local allocator to remote allocator
 /// relaying process state updates.
 /// AsRefStr allows us to log the values
@@ -122,8 +158,13 @@ pub enum RemoteProcessProcStateMessage {
 /// Allocator with a service frontend that wraps ProcessAllocator.
 pub struct RemoteProcessAllocator {
     cancel_token: CancellationToken,
+    policy: AllocPolicy,
 }
 
+/// Tracing target for audit records of allocation requests: who asked
+/// for what, and whether the request was accepted.
+pub const AUDIT_TARGET: &str = "monarch_alloc_audit";
+
 async fn conditional_sleeper<F: futures::Future<Output = ()>>(t: Option<F>) {
     match t {
         Some(timer) => timer.await,
@@ -134,8 +175,15 @@ async fn conditional_sleeper<F: futures::Future<Output = ()>>(t: Option<F>) {
 impl RemoteProcessAllocator {
     /// Create a new allocator. It will not start until start() is called.
     pub fn new() -> Arc<Self> {
+        Self::with_policy(AllocPolicy::default())
+    }
+
+    /// Create a new allocator that authenticates allocation requests and
+    /// restricts the programs it launches according to `policy`.
+    pub fn with_policy(policy: AllocPolicy) -> Arc<Self> {
         Arc::new(Self {
             cancel_token: CancellationToken::new(),
+            policy,
         })
     }
 
@@ -159,6 +207,8 @@ impl RemoteProcessAllocator {
     /// At any point, client can send Stop message to serve_addr to stop the allocator.
     /// If timeout is Some, the allocator will exit if no client connects within
     /// that timeout, and no child allocation is running.
+    ///
+    /// Fails immediately if cmd is not allowed by the allocator's policy.
     #[hyperactor::instrument]
     pub async fn start(
         &self,
@@ -166,8 +216,10 @@ impl RemoteProcessAllocator {
         serve_addr: ChannelAddr,
         timeout: Option<Duration>,
     ) -> Result<(), anyhow::Error> {
+        self.policy.check_command(&cmd)?;
+        let program = format!("{:?}", cmd.as_std());
         let process_allocator = ProcessAllocator::new(cmd);
-        self.start_with_allocator(serve_addr, process_allocator, timeout)
+        self.serve(serve_addr, process_allocator, timeout, Some(program))
             .await
     }
 
@@ -176,16 +228,34 @@ impl RemoteProcessAllocator {
     /// Used for testing.
     #[hyperactor::instrument(fields(addr=serve_addr.to_string()))]
     pub async fn start_with_allocator<A: Allocator + Send + Sync + 'static>(
+        &self,
+        serve_addr: ChannelAddr,
+        process_allocator: A,
+        timeout: Option<Duration>,
+    ) -> Result<(), anyhow::Error>
+    where
+        <A as Allocator>::Alloc: Send,
+        <A as Allocator>::Alloc: Sync,
+    {
+        self.serve(serve_addr, process_allocator, timeout, None)
+            .await
+    }
+
+    /// Serve allocation requests; `program` describes what the allocator
+    /// launches, for audit logging.
+    async fn serve<A: Allocator + Send + Sync + 'static>(
         &self,
         serve_addr: ChannelAddr,
         mut process_allocator: A,
         timeout: Option<Duration>,
+        program: Option<String>,
     ) -> Result<(), anyhow::Error>
     where
         <A as Allocator>::Alloc: Send,
         <A as Allocator>::Alloc: Sync,
     {
         tracing::info!("starting remote allocator on: {}", serve_addr);
+        let transport = serve_addr.transport();
         let (_, mut rx) = channel::serve(serve_addr.clone()).map_err(anyhow::Error::from)?;
 
         struct ActiveAllocation {
@@ -224,8 +294,47 @@ impl RemoteProcessAllocator {
                             hosts,
                             client_context,
                             forwarder_addr,
+                            credential,
                         }) => {
                             tracing::info!("received allocation request for {} with extent {}", alloc_key, extent);
+                            // Authenticate before touching the running allocation, so that
+                            // rejected requests cannot preempt it.
+                            let request = AllocRequest {
+                                alloc_key: &alloc_key,
+                                extent: &extent,
+                                bootstrap_addr: &bootstrap_addr,
+                                hosts: &hosts,
+                                client_context: client_context.as_ref(),
+                                forwarder_addr: &forwarder_addr,
+                            };
+                            let principal = match self.policy.authenticate(credential.as_ref(), request, &transport) {
+                                Ok(principal) => principal,
+                                Err(e) => {
+                                    tracing::warn!(
+                                        target: AUDIT_TARGET,
+                                        claimed_principal = credential.as_ref().map(|c| c.principal()),
+                                        alloc_key = %alloc_key,
+                                        extent = %extent,
+                                        program = program.as_deref(),
+                                        bootstrap_addr = %bootstrap_addr,
+                                        error = %e,
+                                        "allocation request rejected",
+                                    );
+                                    // The bootstrap address comes from an unauthenticated
+                                    // request, so it is never dialed: the request is dropped.
+                                    continue;
+                                }
+                            };
+                            tracing::info!(
+                                target: AUDIT_TARGET,
+                                principal = principal.as_deref(),
+                                alloc_key = %alloc_key,
+                                extent = %extent,
+                                program = program.as_deref(),
+                                bootstrap_addr = %bootstrap_addr,
+                                monarch_client_trace_id = client_context.as_ref().map(|c| c.trace_id.to_string()),
+                                "allocation request accepted",
+                            );
                             ensure_previous_alloc_stopped(&mut active_allocation).await;
 
                             // Create the corresponding local allocation spec.
@@ -862,7 +971,7 @@ impl RemoteProcessAlloc {
 
                     let trace_id = hyperactor_telemetry::trace::get_or_create_trace_id();
                     let client_context = Some(ClientContext { trace_id });
-                    let message = RemoteProcessAllocatorMessage::Allocate {
+                    let mut message = RemoteProcessAllocatorMessage::Allocate {
                         alloc_key: alloc_key.clone(),
                         extent: region.extent(),
                         bootstrap_addr: self.bootstrap_addr.clone(),
@@ -874,7 +983,9 @@ impl RemoteProcessAlloc {
                         // alloc is a public IP address. In some environment, that
                         // could lead to port unreachable error.
                         forwarder_addr: with_unspecified_port_or_any(&remote_addr),
+                        credential: None,
                     };
+                    message.sign(&remote_addr.transport())?;
                     tracing::info!(
                         name = message.as_ref(),
                         "sending allocate message to workers"
@@ -947,7 +1058,7 @@ impl RemoteProcessAlloc {
 
                     let trace_id = hyperactor_telemetry::trace::get_or_create_trace_id();
                     let client_context = Some(ClientContext { trace_id });
-                    let message = RemoteProcessAllocatorMessage::Allocate {
+                    let mut message = RemoteProcessAllocatorMessage::Allocate {
                         alloc_key: alloc_key.clone(),
                         extent: region.extent(),
                         bootstrap_addr: self.bootstrap_addr.clone(),
@@ -959,7 +1070,9 @@ impl RemoteProcessAlloc {
                         // alloc is a public IP address. In some environment, that
                         // could lead to port unreachable error.
                         forwarder_addr: with_unspecified_port_or_any(&remote_addr),
+                        credential: None,
                     };
+                    message.sign(&remote_addr.transport())?;
                     tracing::info!(
                         name = message.as_ref(),
                         "sending allocate message to workers"
@@ -1512,6 +1625,7 @@ mod test {
             hosts: vec![],
             client_context: None,
             forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+            credential: None,
         })
         .await
         .unwrap();
@@ -1666,6 +1780,7 @@ mod test {
             hosts: vec![],
             client_context: None,
             forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+            credential: None,
         })
         .await
         .unwrap();
@@ -1693,6 +1808,122 @@ mod test {
         handle.await.unwrap().unwrap();
     }
 
+    #[timed_test::async_timed_test(timeout_secs = 15)]
+    async fn test_authenticated_allocate() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(
+            hyperactor::config::REMOTE_ALLOCATOR_HEARTBEAT_INTERVAL,
+            Duration::from_millis(100),
+        );
+        hyperactor_telemetry::initialize_logging_for_test();
+        let serve_addr = ChannelAddr::any(ChannelTransport::Unix);
+        let bootstrap_addr = ChannelAddr::any(ChannelTransport::Unix);
+        let (_, mut rx) = channel::serve(bootstrap_addr.clone()).unwrap();
+
+        let extent = extent!(host = 1, gpu = 2);
+        let tx = channel::dial(serve_addr.clone()).unwrap();
+
+        let world_id: WorldId = id!(test_world_id);
+        let mut alloc = MockAllocWrapper::new_block_next(
+            MockAlloc::new(),
+            // block after all created, all running
+            extent.num_ranks() * 2,
+        );
+        let next_tx = alloc.notify_tx();
+        alloc.alloc.expect_world_id().return_const(world_id.clone());
+        alloc.alloc.expect_extent().return_const(extent.clone());
+
+        set_procstate_expectations(&mut alloc.alloc, extent.clone());
+
+        alloc.alloc.expect_next().return_const(None);
+        alloc.alloc.expect_stop().times(1).return_once(|| Ok(()));
+
+        // Only the authenticated request may reach the allocator.
+        let mut allocator = MockAllocator::new();
+        allocator
+            .expect_allocate()
+            .times(1)
+            .return_once(|_| Ok(alloc));
+
+        let remote_allocator = RemoteProcessAllocator::with_policy(
+            auth::AllocPolicy::default()
+                .with_authenticator(auth::AllocAuthenticator::new().with_token("alice", "s3cret")),
+        );
+        let handle = tokio::spawn({
+            let remote_allocator = remote_allocator.clone();
+            async move {
+                remote_allocator
+                    .start_with_allocator(serve_addr, allocator, None)
+                    .await
+            }
+        });
+
+        let allocate =
+            |alloc_key: &ShortUuid, credential| RemoteProcessAllocatorMessage::Allocate {
+                alloc_key: alloc_key.clone(),
+                extent: extent.clone(),
+                bootstrap_addr: bootstrap_addr.clone(),
+                hosts: vec![],
+                client_context: None,
+                forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+                credential,
+            };
+        // Rejected requests are dropped without a reply, so the first
+        // message the client sees is for the authenticated request.
+        tx.send(allocate(&ShortUuid::generate(), None))
+            .await
+            .unwrap();
+        tx.send(allocate(
+            &ShortUuid::generate(),
+            Some(auth::AllocCredential::token("alice", "guess")),
+        ))
+        .await
+        .unwrap();
+
+        let alloc_key = ShortUuid::generate();
+        tx.send(allocate(
+            &alloc_key,
+            Some(auth::AllocCredential::token("alice", "s3cret")),
+        ))
+        .await
+        .unwrap();
+
+        // Allocated
+        let m = rx.recv().await.unwrap();
+        assert_matches!(
+            m,
+            RemoteProcessProcStateMessage::Allocated {  world_id: got_world_id, alloc_key: got_alloc_key }
+            if world_id == got_world_id && alloc_key == got_alloc_key
+        );
+
+        read_all_created(&mut rx, extent.num_ranks()).await;
+        read_all_running(&mut rx, extent.num_ranks()).await;
+
+        tx.send(RemoteProcessAllocatorMessage::Stop).await.unwrap();
+        next_tx.send(()).unwrap();
+
+        read_all_stopped(&mut rx, extent.num_ranks()).await;
+
+        remote_allocator.terminate();
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_start_rejects_disallowed_command() {
+        let remote_allocator = RemoteProcessAllocator::with_policy(
+            auth::AllocPolicy::default().allow(auth::AllowedCommand::program("/bin/true")),
+        );
+        let err = remote_allocator
+            .start(
+                Command::new("/bin/false"),
+                ChannelAddr::any(ChannelTransport::Unix),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not allowed"), "{}", err);
+    }
+
     #[timed_test::async_timed_test(timeout_secs = 15)]
     async fn test_realloc() {
         let config = hyperactor_config::global::lock();
@@ -1771,6 +2002,7 @@ mod test {
             hosts: vec![],
             client_context: None,
             forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+            credential: None,
         })
         .await
         .unwrap();
@@ -1796,6 +2028,7 @@ mod test {
             hosts: vec![],
             client_context: None,
             forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+            credential: None,
         })
         .await
         .unwrap();
@@ -1894,6 +2127,7 @@ mod test {
             hosts: vec![],
             client_context: None,
             forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+            credential: None,
         })
         .await
         .unwrap();
@@ -1986,6 +2220,7 @@ mod test {
             hosts: vec![],
             client_context: None,
             forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+            credential: None,
         })
         .await
         .unwrap();
@@ -2081,6 +2316,7 @@ mod test {
                 trace_id: test_trace_id.to_string(),
             }),
             forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+            credential: None,
         })
         .await
         .unwrap();
@@ -2156,6 +2392,7 @@ mod test {
             hosts: vec![],
             client_context: None,
             forwarder_addr: with_unspecified_port_or_any(&tx.addr()),
+            credential: None,
         })
         .await
         .unwrap();
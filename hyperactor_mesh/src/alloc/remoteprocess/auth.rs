@@ -0,0 +1,803 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Authentication and launch policy for [`RemoteProcessAllocator`].
+//!
+//! A remote allocator launches its configured program on behalf of
+//! whoever sends it an `Allocate` message. An [`AllocPolicy`] lets the
+//! allocator:
+//!
+//! * require an [`AllocCredential`] on every allocation request, either
+//!   a bearer token from a token file, or an HMAC-SHA256 tag computed
+//!   with a shared secret over the request;
+//! * restrict the programs (and arguments) it is willing to launch.
+//!
+//! HMAC credentials cover every field of the request (see
+//! [`AllocRequest`]), carry a timestamp that must fall within
+//! [`AllocAuthenticator::max_skew`] of the allocator's clock, and a
+//! nonce that may only be used once within that window. This gives
+//! replay protection without an extra round trip on the one-way
+//! allocator channel. Since they reveal nothing about the secret, they
+//! may be sent over any transport; bearer tokens are refused over
+//! plaintext transports (see [`is_plaintext`]).
+//!
+//! [`RemoteProcessAllocator`]: super::RemoteProcessAllocator
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use hmac::Hmac;
+use hmac::Mac;
+use hyperactor::channel::ChannelAddr;
+use hyperactor::channel::ChannelTransport;
+use hyperactor::clock::Clock;
+use hyperactor::clock::RealClock;
+use ndslice::view::Extent;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::process::Command;
+
+use crate::alloc::REMOTE_ALLOC_PRINCIPAL;
+use crate::alloc::REMOTE_ALLOC_SECRET_FILE;
+use crate::alloc::REMOTE_ALLOC_TOKEN_FILE;
+use crate::alloc::process::ClientContext;
+use crate::shortuuid::ShortUuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The fields of an allocation request that an HMAC credential covers:
+/// every field of `Allocate` but the credential itself.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AllocRequest<'a> {
+    /// The key used to identify the allocation.
+    pub alloc_key: &'a ShortUuid,
+    /// The extent to allocate.
+    pub extent: &'a Extent,
+    /// The address the allocator sends updates to.
+    pub bootstrap_addr: &'a ChannelAddr,
+    /// The ordered list of hosts in the allocation.
+    pub hosts: &'a [String],
+    /// The client context passed to the allocation.
+    pub client_context: Option<&'a ClientContext>,
+    /// The address the allocator uses for its forwarder.
+    pub forwarder_addr: &'a ChannelAddr,
+}
+
+/// Whether messages sent over `transport` can be read by others on the
+/// network. Bearer tokens are never sent over such transports.
+pub fn is_plaintext(transport: &ChannelTransport) -> bool {
+    match transport {
+        ChannelTransport::Tcp(_) => true,
+        ChannelTransport::Sim(transport) => is_plaintext(transport),
+        _ => false,
+    }
+}
+
+/// Credential presented by a client with an allocation request.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AllocCredential {
+    /// A bearer token, as listed in the allocator's token file.
+    Token {
+        /// The principal the token was issued to.
+        principal: String,
+        /// The token itself.
+        token: String,
+    },
+    /// An HMAC-SHA256 tag over the request, keyed by a secret shared
+    /// between the client and the allocator.
+    Hmac {
+        /// The principal making the request.
+        principal: String,
+        /// Milliseconds since the Unix epoch at which the tag was computed.
+        issued_at_ms: u64,
+        /// Single-use nonce.
+        nonce: String,
+        /// The tag; see [`AllocCredential::hmac`].
+        mac: Vec<u8>,
+    },
+}
+
+impl AllocCredential {
+    /// Create a token credential.
+    pub fn token(principal: impl Into<String>, token: impl Into<String>) -> Self {
+        Self::Token {
+            principal: principal.into(),
+            token: token.into(),
+        }
+    }
+
+    /// Create an HMAC credential for `request`, signed with `secret` at
+    /// the current time.
+    pub fn hmac(principal: impl Into<String>, secret: &[u8], request: AllocRequest<'_>) -> Self {
+        let principal = principal.into();
+        let issued_at_ms = unix_millis(RealClock.system_time_now());
+        let nonce = ShortUuid::generate().to_string();
+        let mac = hmac_tag(secret, &principal, issued_at_ms, &nonce, request);
+        Self::Hmac {
+            principal,
+            issued_at_ms,
+            nonce,
+            mac,
+        }
+    }
+
+    /// The principal on whose behalf the request is made. This is only
+    /// trustworthy once the credential has been verified.
+    pub fn principal(&self) -> &str {
+        match self {
+            Self::Token { principal, .. } | Self::Hmac { principal, .. } => principal,
+        }
+    }
+}
+
+// Secrets are never printed.
+impl fmt::Debug for AllocCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Token { principal, .. } => f
+                .debug_struct("Token")
+                .field("principal", principal)
+                .finish_non_exhaustive(),
+            Self::Hmac {
+                principal,
+                issued_at_ms,
+                nonce,
+                ..
+            } => f
+                .debug_struct("Hmac")
+                .field("principal", principal)
+                .field("issued_at_ms", issued_at_ms)
+                .field("nonce", nonce)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// The tag is computed over the newline-separated principal, timestamp,
+/// and nonce, followed by the bincode encoding of the request.
+fn hmac_tag(
+    secret: &[u8],
+    principal: &str,
+    issued_at_ms: u64,
+    nonce: &str,
+    request: AllocRequest<'_>,
+) -> Vec<u8> {
+    hmac_for(secret, principal, issued_at_ms, nonce, request)
+        .finalize()
+        .into_bytes()
+        .to_vec()
+}
+
+fn hmac_for(
+    secret: &[u8],
+    principal: &str,
+    issued_at_ms: u64,
+    nonce: &str,
+    request: AllocRequest<'_>,
+) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(principal.as_bytes());
+    mac.update(b"\n");
+    mac.update(issued_at_ms.to_string().as_bytes());
+    mac.update(b"\n");
+    mac.update(nonce.as_bytes());
+    mac.update(b"\n");
+    mac.update(&bincode::serialize(&request).expect("requests are serializable"));
+    mac
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Compare two byte strings in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Errors produced when an allocation request is refused.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AuthError {
+    /// The request carried no credential, but one is required.
+    #[error("missing credential")]
+    MissingCredential,
+
+    /// The token is not in the allocator's token file, or does not belong
+    /// to the claimed principal.
+    #[error("invalid token for principal {0}")]
+    InvalidToken(String),
+
+    /// The HMAC tag does not verify.
+    #[error("invalid signature for principal {0}")]
+    InvalidSignature(String),
+
+    /// The credential's timestamp is outside the allowed window.
+    #[error("credential for principal {0} is expired or from the future")]
+    Expired(String),
+
+    /// The credential's nonce was already used.
+    #[error("credential for principal {0} was replayed")]
+    Replayed(String),
+
+    /// The credential kind is not accepted by this allocator.
+    #[error("{0} credentials are not accepted")]
+    Unsupported(String),
+
+    /// A bearer token was sent over a plaintext transport.
+    #[error("token for principal {0} was sent over a plaintext transport")]
+    Plaintext(String),
+
+    /// The program is not on the allow-list.
+    #[error("launching {0} is not allowed")]
+    CommandNotAllowed(String),
+}
+
+/// Verifies [`AllocCredential`]s against a token file and/or a shared
+/// HMAC secret.
+pub struct AllocAuthenticator {
+    /// Token to principal.
+    tokens: HashMap<String, String>,
+    secret: Option<Vec<u8>>,
+    max_skew: Duration,
+    /// Nonces seen within the skew window, with their timestamps.
+    seen_nonces: Mutex<HashMap<String, u64>>,
+}
+
+impl AllocAuthenticator {
+    /// An authenticator that accepts no credentials until tokens or a
+    /// secret are added.
+    pub fn new() -> Self {
+        Self {
+            tokens: HashMap::new(),
+            secret: None,
+            max_skew: Duration::from_secs(300),
+            seen_nonces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Accept `token` as a credential for `principal`.
+    pub fn with_token(mut self, principal: impl Into<String>, token: impl Into<String>) -> Self {
+        self.tokens.insert(token.into(), principal.into());
+        self
+    }
+
+    /// Accept the tokens listed in the token file at `path`. See
+    /// [`read_token_file`] for the format.
+    pub fn with_token_file(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        for (principal, token) in read_token_file(path)? {
+            self.tokens.insert(token, principal);
+        }
+        Ok(self)
+    }
+
+    /// Accept HMAC credentials signed with `secret`.
+    pub fn with_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Accept HMAC credentials signed with the secret stored in the file
+    /// at `path`. See [`read_secret_file`].
+    pub fn with_secret_file(self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(self.with_secret(read_secret_file(path)?))
+    }
+
+    /// Set the maximum difference between an HMAC credential's timestamp
+    /// and the allocator's clock. Defaults to 5 minutes.
+    pub fn with_max_skew(mut self, max_skew: Duration) -> Self {
+        self.max_skew = max_skew;
+        self
+    }
+
+    /// The maximum accepted clock skew for HMAC credentials.
+    pub fn max_skew(&self) -> Duration {
+        self.max_skew
+    }
+
+    /// Verify `credential` for `request`, returning the authenticated
+    /// principal.
+    pub fn verify(
+        &self,
+        credential: Option<&AllocCredential>,
+        request: AllocRequest<'_>,
+    ) -> Result<String, AuthError> {
+        match credential.ok_or(AuthError::MissingCredential)? {
+            AllocCredential::Token { principal, token } => {
+                if self.tokens.is_empty() {
+                    return Err(AuthError::Unsupported("token".to_string()));
+                }
+                // Compare against every entry so that timing does not
+                // reveal which tokens exist.
+                let mut valid = false;
+                for (known, owner) in &self.tokens {
+                    valid |=
+                        constant_time_eq(known.as_bytes(), token.as_bytes()) && owner == principal;
+                }
+                if valid {
+                    Ok(principal.clone())
+                } else {
+                    Err(AuthError::InvalidToken(principal.clone()))
+                }
+            }
+            AllocCredential::Hmac {
+                principal,
+                issued_at_ms,
+                nonce,
+                mac,
+            } => {
+                let secret = self
+                    .secret
+                    .as_ref()
+                    .ok_or_else(|| AuthError::Unsupported("HMAC".to_string()))?;
+                hmac_for(secret, principal, *issued_at_ms, nonce, request)
+                    .verify_slice(mac)
+                    .map_err(|_| AuthError::InvalidSignature(principal.clone()))?;
+
+                let now = unix_millis(RealClock.system_time_now());
+                let skew = self.max_skew.as_millis() as u64;
+                if now.abs_diff(*issued_at_ms) > skew {
+                    return Err(AuthError::Expired(principal.clone()));
+                }
+
+                let mut seen = self.seen_nonces.lock().unwrap();
+                seen.retain(|_, issued| now.abs_diff(*issued) <= skew);
+                if seen.insert(nonce.clone(), *issued_at_ms).is_some() {
+                    return Err(AuthError::Replayed(principal.clone()));
+                }
+                Ok(principal.clone())
+            }
+        }
+    }
+}
+
+impl Default for AllocAuthenticator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A program the allocator may launch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedCommand {
+    /// The program path, compared verbatim.
+    pub program: PathBuf,
+    /// The exact arguments the program must be launched with. `None`
+    /// allows any arguments.
+    pub args: Option<Vec<String>>,
+}
+
+impl AllowedCommand {
+    /// Allow `program` with any arguments.
+    pub fn program(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: None,
+        }
+    }
+
+    /// Allow `program` with exactly `args`.
+    pub fn exact(program: impl Into<PathBuf>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Some(args),
+        }
+    }
+
+    fn permits(&self, cmd: &Command) -> bool {
+        let cmd = cmd.as_std();
+        if Path::new(cmd.get_program()) != self.program {
+            return false;
+        }
+        match &self.args {
+            None => true,
+            Some(args) => cmd
+                .get_args()
+                .map(|arg| arg.to_string_lossy())
+                .eq(args.iter().map(|arg| arg.as_str().into())),
+        }
+    }
+}
+
+/// Policy applied by a [`RemoteProcessAllocator`] to incoming
+/// allocation requests. The default policy accepts every request.
+///
+/// [`RemoteProcessAllocator`]: super::RemoteProcessAllocator
+#[derive(Default)]
+pub struct AllocPolicy {
+    authenticator: Option<AllocAuthenticator>,
+    allowed_commands: Vec<AllowedCommand>,
+}
+
+impl AllocPolicy {
+    /// Require requests to carry a credential accepted by `authenticator`.
+    pub fn with_authenticator(mut self, authenticator: AllocAuthenticator) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    /// Add `allowed` to the allow-list. Once any command is allowed, the
+    /// allocator refuses to launch commands that are not on the list.
+    pub fn allow(mut self, allowed: AllowedCommand) -> Self {
+        self.allowed_commands.push(allowed);
+        self
+    }
+
+    /// Authenticate `request`, received over `transport`, returning the
+    /// principal. Without an authenticator, the claimed principal (if
+    /// any) is returned unverified. Tokens received over a plaintext
+    /// transport are refused either way, since they may have been seen
+    /// by others.
+    pub fn authenticate(
+        &self,
+        credential: Option<&AllocCredential>,
+        request: AllocRequest<'_>,
+        transport: &ChannelTransport,
+    ) -> Result<Option<String>, AuthError> {
+        if let Some(AllocCredential::Token { principal, .. }) = credential
+            && is_plaintext(transport)
+        {
+            return Err(AuthError::Plaintext(principal.clone()));
+        }
+        match &self.authenticator {
+            Some(authenticator) => authenticator.verify(credential, request).map(Some),
+            None => Ok(credential.map(|c| c.principal().to_string())),
+        }
+    }
+
+    /// Check that `cmd` is on the allow-list.
+    pub fn check_command(&self, cmd: &Command) -> Result<(), AuthError> {
+        if self.allowed_commands.is_empty()
+            || self
+                .allowed_commands
+                .iter()
+                .any(|allowed| allowed.permits(cmd))
+        {
+            Ok(())
+        } else {
+            Err(AuthError::CommandNotAllowed(format!("{:?}", cmd.as_std())))
+        }
+    }
+}
+
+/// Read a token file. Each non-empty line not starting with `#` holds a
+/// principal and its token, separated by whitespace.
+pub fn read_token_file(path: impl AsRef<Path>) -> anyhow::Result<Vec<(String, String)>> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("reading token file {}: {}", path.display(), e))?;
+    let mut entries = Vec::new();
+    for (lineno, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [principal, token] => entries.push((principal.to_string(), token.to_string())),
+            _ => anyhow::bail!(
+                "{}:{}: expected `<principal> <token>`",
+                path.display(),
+                lineno + 1
+            ),
+        }
+    }
+    Ok(entries)
+}
+
+/// Read a shared secret from a file, ignoring surrounding whitespace.
+pub fn read_secret_file(path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+    let path = path.as_ref();
+    let contents = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("reading secret file {}: {}", path.display(), e))?;
+    let secret = contents.trim_ascii();
+    anyhow::ensure!(
+        !secret.is_empty(),
+        "secret file {} is empty",
+        path.display()
+    );
+    Ok(secret.to_vec())
+}
+
+/// The credential a client presents with `request`, sent over
+/// `transport`, as configured by `REMOTE_ALLOC_SECRET_FILE` or
+/// `REMOTE_ALLOC_TOKEN_FILE`. Returns `None` if neither is set, and
+/// fails if a token would be sent over a plaintext transport.
+pub(crate) fn client_credential(
+    request: AllocRequest<'_>,
+    transport: &ChannelTransport,
+) -> anyhow::Result<Option<AllocCredential>> {
+    if let Some(path) = hyperactor_config::global::try_get_cloned(REMOTE_ALLOC_SECRET_FILE) {
+        let secret = read_secret_file(path)?;
+        let principal = hyperactor_config::global::try_get_cloned(REMOTE_ALLOC_PRINCIPAL)
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_else(|| "unknown".to_string());
+        return Ok(Some(AllocCredential::hmac(principal, &secret, request)));
+    }
+    if let Some(path) = hyperactor_config::global::try_get_cloned(REMOTE_ALLOC_TOKEN_FILE) {
+        anyhow::ensure!(
+            !is_plaintext(transport),
+            "refusing to send a token over plaintext transport {}; use TLS, or an HMAC secret",
+            transport
+        );
+        let (principal, token) = read_token_file(&path)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("token file {} has no entries", path))?;
+        return Ok(Some(AllocCredential::token(principal, token)));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use hyperactor::channel::TcpMode;
+    use ndslice::extent;
+
+    use super::*;
+
+    /// Owns the fields of a test allocation request.
+    struct Fields {
+        alloc_key: ShortUuid,
+        extent: Extent,
+        bootstrap_addr: ChannelAddr,
+        hosts: Vec<String>,
+        forwarder_addr: ChannelAddr,
+    }
+
+    impl Fields {
+        fn new() -> Self {
+            Self {
+                alloc_key: ShortUuid::generate(),
+                extent: extent!(host = 1, gpu = 2),
+                bootstrap_addr: ChannelAddr::any(ChannelTransport::Unix),
+                hosts: vec!["host0".to_string()],
+                forwarder_addr: ChannelAddr::any(ChannelTransport::Unix),
+            }
+        }
+
+        fn request(&self) -> AllocRequest<'_> {
+            AllocRequest {
+                alloc_key: &self.alloc_key,
+                extent: &self.extent,
+                bootstrap_addr: &self.bootstrap_addr,
+                hosts: &self.hosts,
+                client_context: None,
+                forwarder_addr: &self.forwarder_addr,
+            }
+        }
+    }
+
+    #[test]
+    fn test_token() {
+        let auth = AllocAuthenticator::new().with_token("alice", "s3cret");
+        let fields = Fields::new();
+
+        let cred = AllocCredential::token("alice", "s3cret");
+        assert_eq!(auth.verify(Some(&cred), fields.request()).unwrap(), "alice");
+
+        let cred = AllocCredential::token("bob", "s3cret");
+        assert!(matches!(
+            auth.verify(Some(&cred), fields.request()),
+            Err(AuthError::InvalidToken(_))
+        ));
+        let cred = AllocCredential::token("alice", "guess");
+        assert!(matches!(
+            auth.verify(Some(&cred), fields.request()),
+            Err(AuthError::InvalidToken(_))
+        ));
+        assert!(matches!(
+            auth.verify(None, fields.request()),
+            Err(AuthError::MissingCredential)
+        ));
+    }
+
+    #[test]
+    fn test_hmac() {
+        let auth = AllocAuthenticator::new().with_secret(b"shared".to_vec());
+        let fields = Fields::new();
+
+        let cred = AllocCredential::hmac("alice", b"shared", fields.request());
+        assert_eq!(auth.verify(Some(&cred), fields.request()).unwrap(), "alice");
+        // Nonces are single-use.
+        assert!(matches!(
+            auth.verify(Some(&cred), fields.request()),
+            Err(AuthError::Replayed(_))
+        ));
+
+        let cred = AllocCredential::hmac("alice", b"wrong", fields.request());
+        assert!(matches!(
+            auth.verify(Some(&cred), fields.request()),
+            Err(AuthError::InvalidSignature(_))
+        ));
+
+        // Tokens are not accepted without a token file.
+        let cred = AllocCredential::token("alice", "shared");
+        assert!(matches!(
+            auth.verify(Some(&cred), fields.request()),
+            Err(AuthError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_hmac_covers_request() {
+        let auth = AllocAuthenticator::new().with_secret(b"shared".to_vec());
+        let fields = Fields::new();
+        let other_key = ShortUuid::generate();
+        let other_extent = extent!(host = 2, gpu = 2);
+        let other_addr = ChannelAddr::any(ChannelTransport::Local);
+        let context = ClientContext {
+            trace_id: "trace".to_string(),
+        };
+        let request = fields.request();
+        for tampered in [
+            AllocRequest {
+                alloc_key: &other_key,
+                ..request
+            },
+            AllocRequest {
+                extent: &other_extent,
+                ..request
+            },
+            AllocRequest {
+                bootstrap_addr: &other_addr,
+                ..request
+            },
+            AllocRequest {
+                hosts: &[],
+                ..request
+            },
+            AllocRequest {
+                client_context: Some(&context),
+                ..request
+            },
+            AllocRequest {
+                forwarder_addr: &other_addr,
+                ..request
+            },
+        ] {
+            let cred = AllocCredential::hmac("alice", b"shared", request);
+            assert!(matches!(
+                auth.verify(Some(&cred), tampered),
+                Err(AuthError::InvalidSignature(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_hmac_expired() {
+        let auth = AllocAuthenticator::new()
+            .with_secret(b"shared".to_vec())
+            .with_max_skew(Duration::from_secs(60));
+        let fields = Fields::new();
+
+        let issued_at_ms = unix_millis(RealClock.system_time_now()) - 120_000;
+        let nonce = "n".to_string();
+        let mac = hmac_tag(b"shared", "alice", issued_at_ms, &nonce, fields.request());
+        let cred = AllocCredential::Hmac {
+            principal: "alice".to_string(),
+            issued_at_ms,
+            nonce,
+            mac,
+        };
+        assert!(matches!(
+            auth.verify(Some(&cred), fields.request()),
+            Err(AuthError::Expired(_))
+        ));
+    }
+
+    #[test]
+    fn test_plaintext() {
+        let policy = AllocPolicy::default().with_authenticator(
+            AllocAuthenticator::new()
+                .with_token("alice", "s3cret")
+                .with_secret(b"shared".to_vec()),
+        );
+        let fields = Fields::new();
+        let tcp = ChannelTransport::Tcp(TcpMode::Hostname);
+
+        // Tokens are refused over plaintext transports, even without an
+        // authenticator...
+        let cred = AllocCredential::token("alice", "s3cret");
+        assert_eq!(
+            policy.authenticate(Some(&cred), fields.request(), &tcp),
+            Err(AuthError::Plaintext("alice".to_string()))
+        );
+        assert_eq!(
+            AllocPolicy::default().authenticate(Some(&cred), fields.request(), &tcp),
+            Err(AuthError::Plaintext("alice".to_string()))
+        );
+        assert_eq!(
+            policy.authenticate(Some(&cred), fields.request(), &ChannelTransport::Unix),
+            Ok(Some("alice".to_string()))
+        );
+
+        // ...but HMAC credentials are not.
+        let cred = AllocCredential::hmac("alice", b"shared", fields.request());
+        assert_eq!(
+            policy.authenticate(Some(&cred), fields.request(), &tcp),
+            Ok(Some("alice".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_credential_debug_hides_secrets() {
+        let cred = AllocCredential::token("alice", "s3cret");
+        let debug = format!("{:?}", cred);
+        assert!(debug.contains("alice"));
+        assert!(!debug.contains("s3cret"));
+    }
+
+    #[test]
+    fn test_files() {
+        let mut tokens = tempfile::NamedTempFile::new().unwrap();
+        writeln!(tokens, "# comment\nalice tok1\n\nbob   tok2").unwrap();
+        assert_eq!(
+            read_token_file(tokens.path()).unwrap(),
+            vec![
+                ("alice".to_string(), "tok1".to_string()),
+                ("bob".to_string(), "tok2".to_string())
+            ]
+        );
+        let auth = AllocAuthenticator::new()
+            .with_token_file(tokens.path())
+            .unwrap();
+        let fields = Fields::new();
+        assert_eq!(
+            auth.verify(
+                Some(&AllocCredential::token("bob", "tok2")),
+                fields.request()
+            )
+            .unwrap(),
+            "bob"
+        );
+
+        let mut bad = tempfile::NamedTempFile::new().unwrap();
+        writeln!(bad, "alice").unwrap();
+        assert!(read_token_file(bad.path()).is_err());
+
+        let mut secret = tempfile::NamedTempFile::new().unwrap();
+        writeln!(secret, "  shared  ").unwrap();
+        assert_eq!(read_secret_file(secret.path()).unwrap(), b"shared");
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let mut cmd = Command::new("/usr/bin/monarch_bootstrap");
+        assert!(AllocPolicy::default().check_command(&cmd).is_ok());
+
+        let policy = AllocPolicy::default().allow(AllowedCommand::program("/usr/bin/other"));
+        assert!(matches!(
+            policy.check_command(&cmd),
+            Err(AuthError::CommandNotAllowed(_))
+        ));
+
+        let policy =
+            AllocPolicy::default().allow(AllowedCommand::program("/usr/bin/monarch_bootstrap"));
+        cmd.arg("--verbose");
+        assert!(policy.check_command(&cmd).is_ok());
+
+        let policy = AllocPolicy::default()
+            .allow(AllowedCommand::exact("/usr/bin/monarch_bootstrap", vec![]));
+        assert!(policy.check_command(&cmd).is_err());
+        let policy = AllocPolicy::default().allow(AllowedCommand::exact(
+            "/usr/bin/monarch_bootstrap",
+            vec!["--verbose".to_string()],
+        ));
+        assert!(policy.check_command(&cmd).is_ok());
+    }
+}
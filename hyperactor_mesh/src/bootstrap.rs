@@ -513,14 +513,28 @@ impl Bootstrap {
                 let agent_handle = ok!(ProcMeshAgent::boot_v1(proc.clone())
                     .map_err(|e| HostError::AgentSpawnFailure(proc_id, e)));
 
+                // Preserve the proc's recent history if it is terminated.
+                let _flight_recorder_guard =
+                    hyperactor::register_signal_cleanup_scoped(Box::pin(async {
+                        dump_flight_recorder();
+                    }));
+
                 let span = entered.exit();
 
                 // Finally serve the proc on the same transport as the backend address,
                 // and call back.
                 let (proc_addr, proc_rx) = ok!(channel::serve(serve_addr));
                 proc.clone().serve(proc_rx);
+                // Report where a failure dumps the flight recorder, so that the
+                // parent can find the dump once this process has exited.
+                let flight_recorder_path = hyperactor_telemetry::recorder::flight_recorder_path()
+                    .map(|path| path.display().to_string());
                 ok!(ok!(channel::dial(callback_addr))
-                    .send((proc_addr, agent_handle.bind::<ProcMeshAgent>()))
+                    .send((
+                        proc_addr,
+                        agent_handle.bind::<ProcMeshAgent>(),
+                        flight_recorder_path,
+                    ))
                     .instrument(span)
                     .await
                     .map_err(ChannelError::from));
@@ -548,6 +562,19 @@ impl Bootstrap {
     }
 }
 
+/// Dump this process's flight recorder (see
+/// [`hyperactor_telemetry::recorder::FlightRecorder`]), so that its
+/// recent history survives the process. The parent learns the dump's
+/// path from the bootstrap callback and reports it in the proc's
+/// terminal [`ProcStatus`].
+fn dump_flight_recorder() {
+    match hyperactor_telemetry::recorder::dump_flight_recorder() {
+        Ok(Some(path)) => tracing::info!("dumped flight recorder to {}", path.display()),
+        Ok(None) => (),
+        Err(err) => tracing::warn!("failed to dump flight recorder: {}", err),
+    }
+}
+
 /// Install "kill me if parent dies" and close the race window.
 pub fn install_pdeathsig_kill() -> io::Result<()> {
     #[cfg(target_os = "linux")]
@@ -612,10 +639,19 @@ pub enum ProcStatus {
     Stopped {
         exit_code: i32,
         stderr_tail: Vec<String>,
+        /// The flight recorder dump left behind by the process, if
+        /// any (see [`hyperactor_telemetry::recorder::FlightRecorder`]).
+        flight_recording: Option<PathBuf>,
     },
     /// The process was killed by a signal (e.g. SIGKILL).
     /// (Process-level: abnormal termination.)
-    Killed { signal: i32, core_dumped: bool },
+    Killed {
+        signal: i32,
+        core_dumped: bool,
+        /// The flight recorder dump left behind by the process, if
+        /// any.
+        flight_recording: Option<PathBuf>,
+    },
     /// The proc or its process failed for some other reason
     /// (bootstrap error, unexpected condition, etc.). (Both levels:
     /// catch-all failure.)
@@ -665,22 +701,41 @@ impl std::fmt::Display for ProcStatus {
                     .unwrap_or_default();
                 write!(f, "Stopping[{pid}]{uptime}")
             }
-            ProcStatus::Stopped { exit_code, .. } => write!(f, "Stopped(exit={exit_code})"),
+            ProcStatus::Stopped {
+                exit_code,
+                flight_recording,
+                ..
+            } => {
+                write!(f, "Stopped(exit={exit_code})")?;
+                write_flight_recording(f, flight_recording)
+            }
             ProcStatus::Killed {
                 signal,
                 core_dumped,
+                flight_recording,
             } => {
                 if *core_dumped {
-                    write!(f, "Killed(sig={signal}, core)")
+                    write!(f, "Killed(sig={signal}, core)")?;
                 } else {
-                    write!(f, "Killed(sig={signal})")
+                    write!(f, "Killed(sig={signal})")?;
                 }
+                write_flight_recording(f, flight_recording)
             }
             ProcStatus::Failed { reason } => write!(f, "Failed({reason})"),
         }
     }
 }
 
+fn write_flight_recording(
+    f: &mut std::fmt::Formatter<'_>,
+    flight_recording: &Option<PathBuf>,
+) -> std::fmt::Result {
+    match flight_recording {
+        Some(path) => write!(f, "; flight recording: {}", path.display()),
+        None => Ok(()),
+    }
+}
+
 /// Error returned by [`BootstrapProcHandle::ready`].
 #[derive(Debug, Clone)]
 pub enum ReadyError {
@@ -769,6 +824,9 @@ pub struct BootstrapProcHandle {
     /// `borrow()` the current status and `changed().await` future
     /// transitions independently.
     rx: tokio::sync::watch::Receiver<ProcStatus>,
+    /// Where the process dumps its flight recorder if it fails, as
+    /// reported by the process in its bootstrap callback.
+    flight_recorder_path: Arc<std::sync::Mutex<Option<PathBuf>>>,
 }
 
 impl fmt::Debug for BootstrapProcHandle {
@@ -814,6 +872,7 @@ impl BootstrapProcHandle {
             stderr_fwder: Arc::new(std::sync::Mutex::new(None)),
             tx,
             rx,
+            flight_recorder_path: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 
@@ -1021,9 +1080,31 @@ impl BootstrapProcHandle {
         })
     }
 
+    /// Record where the process dumps its flight recorder if it
+    /// fails.
+    pub(crate) fn set_flight_recorder_path(&self, path: PathBuf) {
+        *self
+            .flight_recorder_path
+            .lock()
+            .expect("flight recorder path mutex poisoned") = Some(path);
+    }
+
+    /// The flight recorder dump left behind by the exited process, if
+    /// it reported a dump path and wrote a dump there. The path is
+    /// named by the process's pid and start time, so it is never that
+    /// of an earlier process or of an on-demand snapshot.
+    fn flight_recording(&self) -> Option<PathBuf> {
+        self.flight_recorder_path
+            .lock()
+            .expect("flight recorder path mutex poisoned")
+            .clone()
+            .filter(|path| path.exists())
+    }
+
     /// Record that the process has exited normally with the given
     /// exit code.
     pub(crate) fn mark_stopped(&self, exit_code: i32, stderr_tail: Vec<String>) -> bool {
+        let flight_recording = self.flight_recording();
         self.transition(|st| match *st {
             ProcStatus::Starting
             | ProcStatus::Running { .. }
@@ -1032,6 +1113,7 @@ impl BootstrapProcHandle {
                 *st = ProcStatus::Stopped {
                     exit_code,
                     stderr_tail,
+                    flight_recording,
                 };
                 true
             }
@@ -1048,6 +1130,7 @@ impl BootstrapProcHandle {
     /// Record that the process was killed by the given signal (e.g.
     /// SIGKILL, SIGTERM).
     pub(crate) fn mark_killed(&self, signal: i32, core_dumped: bool) -> bool {
+        let flight_recording = self.flight_recording();
         self.transition(|st| match *st {
             ProcStatus::Starting
             | ProcStatus::Running { .. }
@@ -1056,6 +1139,7 @@ impl BootstrapProcHandle {
                 *st = ProcStatus::Killed {
                     signal,
                     core_dumped,
+                    flight_recording,
                 };
                 true
             }
@@ -2010,7 +2094,10 @@ impl ProcManager for BootstrapProcManager {
         let pid_table = Arc::clone(&self.pid_table);
         tokio::spawn(async move {
             match callback_rx.recv().await {
-                Ok((addr, agent)) => {
+                Ok((addr, agent, flight_recorder_path)) => {
+                    if let Some(path) = flight_recorder_path {
+                        h.set_flight_recorder_path(PathBuf::from(path));
+                    }
                     let pid = match h.pid() {
                         Some(p) => p,
                         None => {
@@ -2176,6 +2263,7 @@ async fn bootstrap_v0_proc_mesh(config: Option<Attrs>) -> anyhow::Error {
         let procs = Arc::new(tokio::sync::Mutex::new(Vec::<Proc>::new()));
         let procs_for_cleanup = procs.clone();
         let _cleanup_guard = hyperactor::register_signal_cleanup_scoped(Box::pin(async move {
+            dump_flight_recorder();
             for proc_to_stop in procs_for_cleanup.lock().await.iter_mut() {
                 if let Err(err) = proc_to_stop
                     .destroy_and_wait::<()>(Duration::from_millis(10), None)
@@ -2716,6 +2804,8 @@ mod tests {
         tx.post(LogMessage::Log {
             hostname: "testhost".into(),
             pid: 12345,
+            proc_id: None,
+            point: None,
             output_target: OutputTarget::Stdout,
             payload: Serialized::serialize(&"hello from child".to_string()).unwrap(),
         });
@@ -2804,11 +2894,32 @@ mod tests {
                 h.status(),
                 ProcStatus::Killed {
                     signal: 9,
-                    core_dumped: true
+                    core_dumped: true,
+                    ..
                 }
             ));
         }
 
+        #[tokio::test]
+        async fn killed_reports_flight_recording() {
+            let h = handle_for_test();
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("flight_recorder.jsonl");
+            std::fs::write(&path, "{}\n").unwrap();
+            h.set_flight_recorder_path(path.clone());
+            assert!(h.mark_running(1, RealClock.system_time_now()));
+            assert!(h.mark_killed(15, false));
+            let st = h.status();
+
+            match &st {
+                ProcStatus::Killed {
+                    flight_recording, ..
+                } => assert_eq!(flight_recording.as_ref(), Some(&path)),
+                other => panic!("expected Killed, got {other:?}"),
+            }
+            assert!(st.to_string().contains(&path.display().to_string()));
+        }
+
         #[tokio::test]
         async fn running_to_failed_ok() {
             let h = handle_for_test();
@@ -3383,6 +3494,7 @@ mod tests {
         let st = ProcStatus::Stopped {
             exit_code: 7,
             stderr_tail: Vec::new(),
+            flight_recording: None,
         };
         let s = format!("{}", st);
         assert!(s.contains("Stopped"));
@@ -3409,6 +3521,7 @@ mod tests {
             ProcStatus::Killed {
                 signal: 9,
                 core_dumped: false,
+                flight_recording: Some(PathBuf::from("/tmp/flight_recorder_42.jsonl")),
             },
             ProcStatus::Failed {
                 reason: "boom".into(),
@@ -3848,6 +3961,7 @@ mod tests {
             ProcStatus::Stopped {
                 exit_code,
                 stderr_tail,
+                ..
             } => {
                 assert_eq!(
                     exit_code, 7,
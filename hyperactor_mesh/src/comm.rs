@@ -9,11 +9,13 @@
 use crate::comm::multicast::CAST_ORIGINATING_SENDER;
 use crate::reference::ActorMeshId;
 use crate::resource;
+pub mod choice;
 pub mod multicast;
 
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -32,16 +34,23 @@ use hyperactor::mailbox::MailboxSender;
 use hyperactor::mailbox::Undeliverable;
 use hyperactor::mailbox::UndeliverableMailboxSender;
 use hyperactor::mailbox::UndeliverableMessageError;
+use hyperactor::mailbox::headers;
 use hyperactor::mailbox::monitored_return_handle;
 use hyperactor::reference::UnboundPort;
+use hyperactor_config::attrs::Attrs;
 use ndslice::selection::routing::RoutingFrame;
+use rand::seq::IteratorRandom;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::comm::choice::ChoicePolicy;
+use crate::comm::choice::ChoiceState;
 use crate::comm::multicast::CastMessage;
 use crate::comm::multicast::CastMessageEnvelope;
 use crate::comm::multicast::ForwardMessage;
 use crate::comm::multicast::set_cast_info_on_headers;
+use crate::config::COMM_LOAD_REPORT_FANOUT;
+use crate::config::COMM_LOAD_REPORT_INTERVAL;
 
 /// Parameters to initialize the CommActor
 #[derive(Debug, Clone, Serialize, Deserialize, Named, Default)]
@@ -58,6 +67,8 @@ struct Buffered {
     next_steps: HashMap<usize, Vec<RoutingFrame>>,
     /// The message to deliver.
     message: CastMessageEnvelope,
+    /// How to resolve `Any` selections downstream.
+    choice_policy: ChoicePolicy,
 }
 
 /// Bookkeeping to handle sequence numbers and in-order delivery for messages
@@ -82,6 +93,7 @@ struct ReceiveState {
         CommActorMode,
         CastMessage,
         ForwardMessage,
+        LoadReport,
     ],
 )]
 pub struct CommActor {
@@ -92,8 +104,33 @@ pub struct CommActor {
 
     /// The comm actor's mode.
     mode: CommActorMode,
+
+    /// State used to resolve `Any` selections.
+    choices: ChoiceState,
+    /// Whether a load report is scheduled.
+    reporting_load: bool,
+}
+
+/// Reports the load (e.g., queue depth) of the actors on a rank to a
+/// comm actor, for use by [`ChoicePolicy::LeastLoaded`]. Reports
+/// replace earlier ones for the same rank. Every
+/// [`COMM_LOAD_REPORT_INTERVAL`](crate::config::COMM_LOAD_REPORT_INTERVAL),
+/// comm actors in mesh mode send them to
+/// [`COMM_LOAD_REPORT_FANOUT`](crate::config::COMM_LOAD_REPORT_FANOUT)
+/// peers picked at random, so that each rank learns the load of the
+/// others over successive intervals.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct LoadReport {
+    /// The rank whose load is reported.
+    pub rank: usize,
+    /// The reported load.
+    pub load: u64,
 }
 
+/// Local message scheduling a comm actor's next load report.
+#[derive(Debug)]
+struct LoadReportTick;
+
 /// Configuration for how a `CommActor` determines its own rank and locates peers.
 ///
 /// - In `Mesh` mode, the comm actor is assigned an explicit rank and a mapping to each peer by rank.
@@ -231,13 +268,15 @@ impl Actor for CommActor {
 impl CommActor {
     /// Forward the message to the comm actor on the given peer rank.
     fn forward(
-        cx: &Instance<Self>,
+        cx: &Context<Self>,
         mode: &CommActorMode,
         rank: usize,
         message: ForwardMessage,
     ) -> Result<()> {
         let child = mode.peer_for_rank(cx.self_id(), rank)?;
-        child.send(cx, message)?;
+        let mut headers = Attrs::new();
+        headers::set_relayed_from(&mut headers, cx.headers());
+        child.send_with_headers(cx, headers, message)?;
         Ok(())
     }
 
@@ -248,6 +287,7 @@ impl CommActor {
         next_steps: HashMap<usize, Vec<RoutingFrame>>,
         sender: ActorId,
         mut message: CastMessageEnvelope,
+        choice_policy: ChoicePolicy,
         seq: usize,
         last_seqs: &mut HashMap<usize, usize>,
     ) -> Result<()> {
@@ -288,6 +328,7 @@ impl CommActor {
                 .point_of_rank(cast_rank)
                 .expect("rank out of bounds");
             let mut headers = cx.headers().clone();
+            headers::set_relayed_from(&mut headers, cx.headers());
             set_cast_info_on_headers(&mut headers, point, message.sender().clone());
             cx.post(
                 cx.self_id()
@@ -312,6 +353,7 @@ impl CommActor {
                         dests,
                         sender: sender.clone(),
                         message: message.clone(),
+                        choice_policy,
                         seq,
                         last_seq: *last_seq,
                     },
@@ -327,8 +369,16 @@ impl CommActor {
 
 #[async_trait]
 impl Handler<CommActorMode> for CommActor {
-    async fn handle(&mut self, _cx: &Context<Self>, mode: CommActorMode) -> Result<()> {
+    async fn handle(&mut self, cx: &Context<Self>, mode: CommActorMode) -> Result<()> {
         self.mode = mode;
+        // Only mesh mode knows its peers, so only it can report load.
+        if matches!(self.mode, CommActorMode::Mesh(..))
+            && !self.reporting_load
+            && !hyperactor_config::global::get(COMM_LOAD_REPORT_INTERVAL).is_zero()
+        {
+            self.reporting_load = true;
+            cx.self_message_with_delay(LoadReportTick, Duration::ZERO)?;
+        }
         Ok(())
     }
 }
@@ -354,6 +404,7 @@ impl Handler<CastMessage> for CommActor {
             dests: vec![frame],
             sender: cx.self_id().clone(),
             message: cast_message.message,
+            choice_policy: cast_message.choice_policy,
             seq: *seq,
             last_seq,
         };
@@ -381,15 +432,17 @@ impl Handler<ForwardMessage> for CommActor {
             sender,
             dests,
             message,
+            choice_policy,
             seq,
             last_seq,
         } = fwd_message;
 
         // Resolve/dedup routing frames.
         let rank = self.mode.self_rank(cx.self_id())?;
+        let choices = &mut self.choices;
         let (deliver_here, next_steps) =
-            ndslice::selection::routing::resolve_routing(rank, dests, &mut |_| {
-                panic!("Choice encountered in CommActor routing")
+            ndslice::selection::routing::resolve_routing(rank, dests, &mut |choice| {
+                choices.choose(choice_policy, choice)
             })?;
 
         let recv_state = self.recv_state.entry(message.stream_key()).or_default();
@@ -404,6 +457,7 @@ impl Handler<ForwardMessage> for CommActor {
                     next_steps,
                     sender.clone(),
                     message,
+                    choice_policy,
                     seq,
                     &mut recv_state.last_seqs,
                 )?;
@@ -416,6 +470,7 @@ impl Handler<ForwardMessage> for CommActor {
                     deliver_here,
                     next_steps,
                     message,
+                    choice_policy,
                 }) = recv_state.buffer.remove(&recv_state.seq)
                 {
                     Self::handle_message(
@@ -425,6 +480,7 @@ impl Handler<ForwardMessage> for CommActor {
                         next_steps,
                         sender.clone(),
                         message,
+                        choice_policy,
                         seq,
                         &mut recv_state.last_seqs,
                     )?;
@@ -448,6 +504,7 @@ impl Handler<ForwardMessage> for CommActor {
                         deliver_here,
                         next_steps,
                         message,
+                        choice_policy,
                     },
                 );
             }
@@ -461,6 +518,49 @@ impl Handler<ForwardMessage> for CommActor {
     }
 }
 
+#[async_trait]
+impl Handler<LoadReportTick> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, _: LoadReportTick) -> Result<()> {
+        let CommActorMode::Mesh(rank, peers) = &self.mode else {
+            self.reporting_load = false;
+            return Ok(());
+        };
+        let load = cx.proc().queued_messages();
+        self.choices.record_load(*rank, load);
+        // Peers are picked anew each interval; reporting to all of them
+        // would take a number of messages quadratic in the mesh size.
+        let fanout = hyperactor_config::global::get(COMM_LOAD_REPORT_FANOUT);
+        let targets = peers
+            .iter()
+            .filter(|(peer_rank, _)| *peer_rank != rank)
+            .choose_multiple(&mut rand::thread_rng(), fanout);
+        for (peer_rank, peer) in targets {
+            // Reports are superseded by later ones, so losing one is harmless.
+            if let Err(err) = peer.send(cx, LoadReport { rank: *rank, load }) {
+                tracing::warn!(
+                    actor = %cx.self_id(),
+                    "failed to report load to rank {}: {}",
+                    peer_rank,
+                    err
+                );
+            }
+        }
+        cx.self_message_with_delay(
+            LoadReportTick,
+            hyperactor_config::global::get(COMM_LOAD_REPORT_INTERVAL),
+        )?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<LoadReport> for CommActor {
+    async fn handle(&mut self, _cx: &Context<Self>, report: LoadReport) -> Result<()> {
+        self.choices.record_load(report.rank, report.load);
+        Ok(())
+    }
+}
+
 pub mod test_utils {
     use anyhow::Result;
     use async_trait::async_trait;
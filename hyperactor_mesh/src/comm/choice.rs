@@ -0,0 +1,256 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Policies used by the comm actor to resolve routing choices.
+//!
+//! A [`Selection::Any`](ndslice::selection::Selection::Any) in a cast
+//! selection asks the comm actors to deliver to exactly one index
+//! along a dimension. The routing layer surfaces each such decision
+//! as a [`Choice`]; the comm actor responsible for that hop resolves
+//! it according to the cast's [`ChoicePolicy`]. Casting to
+//! `sel!(?, *)` over a mesh of replicas thus load balances a request
+//! over them.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use hyperactor::Named;
+use hyperactor_config::attrs::AttrValue;
+use ndslice::selection::routing::Choice;
+use ndslice::selection::routing::random_choice;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// How a comm actor picks among the candidates of a [`Choice`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ChoicePolicy {
+    /// Pick a candidate uniformly at random.
+    #[default]
+    Random,
+    /// Cycle through the candidates, remembering the position per
+    /// decision point across casts.
+    RoundRobin,
+    /// Pick the candidate whose next hop has reported the lowest
+    /// load (see [`LoadReport`](crate::comm::LoadReport)). Ties are
+    /// broken by index. Comm actors only exchange reports when
+    /// [`COMM_LOAD_REPORT_INTERVAL`](crate::config::COMM_LOAD_REPORT_INTERVAL)
+    /// is set; otherwise each relies on its own estimates.
+    LeastLoaded,
+    /// Prefer the candidate on the comm actor's own coordinate, so
+    /// that the message doesn't leave this branch of the tree; fall
+    /// back on a random candidate otherwise.
+    Locality,
+}
+
+impl fmt::Display for ChoicePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChoicePolicy::Random => write!(f, "random"),
+            ChoicePolicy::RoundRobin => write!(f, "round_robin"),
+            ChoicePolicy::LeastLoaded => write!(f, "least_loaded"),
+            ChoicePolicy::Locality => write!(f, "locality"),
+        }
+    }
+}
+
+impl std::str::FromStr for ChoicePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "random" => Ok(ChoicePolicy::Random),
+            "round_robin" => Ok(ChoicePolicy::RoundRobin),
+            "least_loaded" => Ok(ChoicePolicy::LeastLoaded),
+            "locality" | "local" => Ok(ChoicePolicy::Locality),
+            _ => Err(anyhow::anyhow!("unknown choice policy: {}", s)),
+        }
+    }
+}
+
+impl Named for ChoicePolicy {
+    fn typename() -> &'static str {
+        "hyperactor_mesh::comm::choice::ChoicePolicy"
+    }
+}
+
+impl AttrValue for ChoicePolicy {
+    fn display(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        s.parse()
+    }
+}
+
+/// Per-comm-actor state used to resolve choices.
+#[derive(Debug, Default)]
+pub(crate) struct ChoiceState {
+    /// Round-robin cursors, keyed by the decision point: the
+    /// dimension being chosen, and the coordinates that precede it.
+    cursors: HashMap<(usize, Vec<usize>), usize>,
+    /// The last reported (or optimistically estimated) load of each
+    /// rank.
+    loads: HashMap<usize, u64>,
+}
+
+impl ChoiceState {
+    /// Resolve `choice` according to `policy`, returning one of its
+    /// candidates.
+    pub(crate) fn choose(&mut self, policy: ChoicePolicy, choice: &Choice) -> usize {
+        let candidates = choice.candidates();
+        let frame = choice.frame();
+        match policy {
+            ChoicePolicy::Random => random_choice(choice),
+            ChoicePolicy::RoundRobin => {
+                let cursor = self
+                    .cursors
+                    .entry((frame.dim, frame.here[..frame.dim].to_vec()))
+                    .or_default();
+                let index = candidates[*cursor % candidates.len()];
+                *cursor = cursor.wrapping_add(1);
+                index
+            }
+            ChoicePolicy::LeastLoaded => {
+                let (index, rank) = candidates
+                    .iter()
+                    .map(|&i| {
+                        let mut here = frame.here.clone();
+                        here[frame.dim] = i;
+                        (i, frame.slice.location(&here).ok())
+                    })
+                    // A candidate we cannot locate is never preferred.
+                    .min_by_key(|(_, rank)| rank.map_or(u64::MAX, |rank| self.load(rank)))
+                    .expect("choices have at least one candidate");
+                // Until the next report arrives, assume that the
+                // message we're about to send adds to the load.
+                if let Some(rank) = rank {
+                    *self.loads.entry(rank).or_default() += 1;
+                }
+                index
+            }
+            ChoicePolicy::Locality => {
+                let local = frame.here[frame.dim];
+                if candidates.contains(&local) {
+                    local
+                } else {
+                    random_choice(choice)
+                }
+            }
+        }
+    }
+
+    /// Record the load reported for `rank`.
+    pub(crate) fn record_load(&mut self, rank: usize, load: u64) {
+        self.loads.insert(rank, load);
+    }
+
+    fn load(&self, rank: usize) -> u64 {
+        self.loads.get(&rank).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndslice::Slice;
+    use ndslice::selection::dsl::*;
+    use ndslice::selection::routing::RoutingFrame;
+    use ndslice::selection::routing::resolve_routing;
+
+    use super::*;
+
+    /// Route `any(true)` over a 1-d slice of `size` from rank 0,
+    /// returning the chosen rank.
+    fn route_any(state: &mut ChoiceState, policy: ChoicePolicy, size: usize) -> usize {
+        let slice = Slice::new_row_major(vec![size]);
+        let frame = RoutingFrame::root(any(true_()), slice);
+        let (deliver_here, next_steps) =
+            resolve_routing(0, vec![frame], &mut |choice| state.choose(policy, choice)).unwrap();
+        if deliver_here {
+            assert!(next_steps.is_empty());
+            return 0;
+        }
+        assert_eq!(next_steps.len(), 1);
+        next_steps.into_keys().next().unwrap()
+    }
+
+    #[test]
+    fn test_choice_policy_parse() {
+        for policy in [
+            ChoicePolicy::Random,
+            ChoicePolicy::RoundRobin,
+            ChoicePolicy::LeastLoaded,
+            ChoicePolicy::Locality,
+        ] {
+            assert_eq!(policy.to_string().parse::<ChoicePolicy>().unwrap(), policy);
+        }
+        assert_eq!(
+            "round-robin".parse::<ChoicePolicy>().unwrap(),
+            ChoicePolicy::RoundRobin
+        );
+        assert!("fastest".parse::<ChoicePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_random() {
+        let mut state = ChoiceState::default();
+        for _ in 0..16 {
+            assert!(route_any(&mut state, ChoicePolicy::Random, 4) < 4);
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut state = ChoiceState::default();
+        let chosen: Vec<_> = (0..6)
+            .map(|_| route_any(&mut state, ChoicePolicy::RoundRobin, 4))
+            .collect();
+        assert_eq!(chosen, vec![0, 1, 2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn test_least_loaded() {
+        let mut state = ChoiceState::default();
+        state.record_load(0, 5);
+        state.record_load(1, 2);
+        state.record_load(2, 3);
+        state.record_load(3, 4);
+
+        // Rank 1 is least loaded, until our own sends catch up with
+        // rank 2 (ties go to the lower index).
+        assert_eq!(route_any(&mut state, ChoicePolicy::LeastLoaded, 4), 1);
+        assert_eq!(route_any(&mut state, ChoicePolicy::LeastLoaded, 4), 1);
+        assert_eq!(route_any(&mut state, ChoicePolicy::LeastLoaded, 4), 2);
+
+        // A fresh report overrides the estimate.
+        state.record_load(3, 0);
+        assert_eq!(route_any(&mut state, ChoicePolicy::LeastLoaded, 4), 3);
+    }
+
+    #[test]
+    fn test_locality() {
+        let mut state = ChoiceState::default();
+        for _ in 0..4 {
+            assert_eq!(route_any(&mut state, ChoicePolicy::Locality, 4), 0);
+        }
+
+        // From rank 6 ([1, 2]), choosing a host in the second zone
+        // stays local.
+        let slice = Slice::new_row_major(vec![2, 4]);
+        let mut frame = RoutingFrame::root(range(1, any(true_())), slice);
+        frame.here = vec![1, 2];
+        frame.dim = 1;
+        frame.selection = any(true_());
+        let (deliver_here, next_steps) = resolve_routing(6, vec![frame], &mut |choice| {
+            state.choose(ChoicePolicy::Locality, choice)
+        })
+        .unwrap();
+        assert!(deliver_here);
+        assert!(next_steps.is_empty());
+    }
+}
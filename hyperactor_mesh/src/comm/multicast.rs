@@ -30,6 +30,7 @@ use ndslice::selection::routing::RoutingFrame;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::comm::choice::ChoicePolicy;
 use crate::reference::ActorMeshId;
 
 /// A union of slices that can be used to represent arbitrary subset of
@@ -211,6 +212,9 @@ pub struct CastMessage {
     pub dest: Uslice,
     /// The message to cast.
     pub message: CastMessageEnvelope,
+    /// How the comm actors resolve `Any` selections in `dest`.
+    #[serde(default)]
+    pub choice_policy: ChoicePolicy,
 }
 
 /// Forward a message to procs of next hops. This is used by comm actor to
@@ -228,6 +232,9 @@ pub(crate) struct ForwardMessage {
     pub(crate) last_seq: usize,
     /// The message to distribute.
     pub(crate) message: CastMessageEnvelope,
+    /// How to resolve `Any` selections in `dests`.
+    #[serde(default)]
+    pub(crate) choice_policy: ChoicePolicy,
 }
 
 declare_attrs! {
@@ -11,10 +11,14 @@
 //! This module provides hyperactor_mesh-specific configuration attributes that extend
 //! the base hyperactor configuration system.
 
+use std::time::Duration;
+
 use hyperactor_config::CONFIG;
 use hyperactor_config::ConfigAttr;
 use hyperactor_config::attrs::declare_attrs;
 
+use crate::comm::choice::ChoicePolicy;
+
 // Declare hyperactor_mesh-specific configuration keys
 declare_attrs! {
     /// The maximium for a dimension size allowed for a folded shape
@@ -26,4 +30,76 @@ declare_attrs! {
         py_name: None,
     })
     pub attr MAX_CAST_DIMENSION_SIZE: usize = usize::MAX;
+
+    /// How comm actors resolve `Any` selections when casting:
+    /// "random" (default), "round_robin", "least_loaded" or
+    /// "locality". See [`ChoicePolicy`].
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_CAST_CHOICE_POLICY".to_string()),
+        py_name: None,
+    })
+    pub attr CAST_CHOICE_POLICY: ChoicePolicy = ChoicePolicy::Random;
+
+    /// How often comm actors report their proc's queue depth to
+    /// their peers, for use by [`ChoicePolicy::LeastLoaded`]. Each
+    /// report goes to [`COMM_LOAD_REPORT_FANOUT`] peers picked at
+    /// random. Zero (the default) disables reporting.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_COMM_LOAD_REPORT_INTERVAL".to_string()),
+        py_name: None,
+    })
+    pub attr COMM_LOAD_REPORT_INTERVAL: Duration = Duration::ZERO;
+
+    /// How many peers each comm actor reports its load to every
+    /// [`COMM_LOAD_REPORT_INTERVAL`], bounding the number of reports
+    /// per interval to this many per rank regardless of the size of
+    /// the mesh.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_COMM_LOAD_REPORT_FANOUT".to_string()),
+        py_name: None,
+    })
+    pub attr COMM_LOAD_REPORT_FANOUT: usize = 4;
+
+    /// The receive window of an actor connection (see
+    /// [`crate::connect`]): the number of bytes a writer may have in
+    /// flight before it has to wait for the reader to catch up. A
+    /// window of 0 is treated as 1.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_CONNECTION_WINDOW_SIZE".to_string()),
+        py_name: None,
+    })
+    pub attr CONNECTION_WINDOW_SIZE: usize = 4 * 1024 * 1024;
+
+    /// The largest data frame an actor connection sends in a single
+    /// message. Larger writes are split.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_CONNECTION_MAX_FRAME_SIZE".to_string()),
+        py_name: None,
+    })
+    pub attr CONNECTION_MAX_FRAME_SIZE: usize = 256 * 1024;
+
+    /// Whether procs run a [`crate::port_forward::PortForwardActor`],
+    /// which lets clients reach services on the proc's host.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_PORT_FORWARD_ENABLED".to_string()),
+        py_name: None,
+    })
+    pub attr PORT_FORWARD_ENABLED: bool = false;
+
+    /// The comma-separated addresses (`PORT`, `HOST:PORT` or
+    /// `unix:PATH`) that port forwarders may dial. Nothing is allowed
+    /// by default.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_PORT_FORWARD_ALLOWED_TARGETS".to_string()),
+        py_name: None,
+    })
+    pub attr PORT_FORWARD_ALLOWED_TARGETS: String = String::new();
+
+    /// How often metrics agents report their proc's metrics to the
+    /// root of a [`crate::metrics_aggregator::MetricsService`].
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_METRICS_AGGREGATION_INTERVAL".to_string()),
+        py_name: None,
+    })
+    pub attr METRICS_AGGREGATION_INTERVAL: Duration = Duration::from_secs(10);
 }
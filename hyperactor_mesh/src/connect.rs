@@ -35,6 +35,21 @@
 //!
 //! Servers forward `Connect` messages to the `accept()` helper function to finish setting up the
 //! connection, which returns the `ActorConnection` they can use.
+//!
+//! # Flow Control
+//!
+//! Connections use credit-based flow control. When the connection is set up, each side's reader
+//! grants the peer's writer a window of [`CONNECTION_WINDOW_SIZE`] bytes, and it returns credit as
+//! data is consumed. A writer that has used up its credit returns `Poll::Pending` until more
+//! arrives, so that a slow reader applies backpressure rather than letting the writer buffer
+//! without limit. Writes are split into frames of at most [`CONNECTION_MAX_FRAME_SIZE`] bytes.
+//!
+//! # Half-Close
+//!
+//! As with a `TcpStream`, the two directions close independently. Shutting down (or dropping) a
+//! write half sends EOF: the peer reads any remaining data and then `Ok(0)`, while the other
+//! direction stays open. Dropping a read half before EOF tells the peer, whose subsequent writes
+//! fail with `BrokenPipe`.
 
 use std::io::Cursor;
 use std::pin::Pin;
@@ -48,6 +63,7 @@ use futures::stream::FusedStream;
 use futures::task::Context;
 use futures::task::Poll;
 use hyperactor::ActorId;
+use hyperactor::Instance;
 use hyperactor::Named;
 use hyperactor::OncePortRef;
 use hyperactor::PortRef;
@@ -69,6 +85,9 @@ use tokio::io::AsyncRead;
 use tokio::io::AsyncWrite;
 use tokio_util::io::StreamReader;
 
+use crate::config::CONNECTION_MAX_FRAME_SIZE;
+use crate::config::CONNECTION_WINDOW_SIZE;
+
 // Timeout for establishing a connection, used by both client and server.
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
@@ -81,11 +100,116 @@ enum Io {
     Eof,
 }
 
+/// Flow control messages, sent from a reader back to the peer's writer.
+#[derive(Debug, Serialize, Deserialize, Named, Clone)]
+enum Credit {
+    // The reader consumed this many bytes, which may now be sent.
+    Grant(usize),
+    // The reader was dropped before EOF; nothing more will be read.
+    Closed,
+}
+
+/// The reader's side of flow control: returns credit to the peer's writer
+/// as data is consumed.
+struct CreditReturn {
+    // Credit is sent from a dedicated child instance, so that grants which
+    // arrive after the peer has gone away don't come back as undeliverable
+    // to the actor that owns the connection.
+    instance: Instance<()>,
+    port: PortRef<Credit>,
+    // Consumed bytes that haven't been returned yet.
+    pending: usize,
+    // Credit is returned in batches of at least this many bytes.
+    threshold: usize,
+}
+
+impl CreditReturn {
+    fn new(instance: Instance<()>, port: PortRef<Credit>, window: usize) -> Self {
+        Self {
+            instance,
+            port,
+            pending: 0,
+            threshold: (window / 4).max(1),
+        }
+    }
+
+    fn consumed(&mut self, len: usize) {
+        self.pending += len;
+        if self.pending >= self.threshold {
+            let _ = self.port.send(&self.instance, Credit::Grant(self.pending));
+            self.pending = 0;
+        }
+    }
+
+    fn close(&self) {
+        let _ = self.port.send(&self.instance, Credit::Closed);
+    }
+}
+
+/// The writer's side of flow control: tracks the credit granted by the peer's
+/// reader.
+struct WriteCredit {
+    port: PortReceiver<Credit>,
+    // The number of bytes we may send before we need more credit.
+    available: usize,
+    // Whether the peer's reader has gone away.
+    peer_closed: bool,
+}
+
+impl WriteCredit {
+    fn new(port: PortReceiver<Credit>, window: usize) -> Self {
+        Self {
+            port,
+            available: window,
+            peer_closed: false,
+        }
+    }
+
+    fn apply(&mut self, credit: Credit) {
+        match credit {
+            Credit::Grant(len) => self.available += len,
+            Credit::Closed => self.peer_closed = true,
+        }
+    }
+
+    /// Poll for available credit, waiting for the peer to grant some if we
+    /// have none left.
+    fn poll_available(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<usize>> {
+        while let Some(credit) = self.port.try_recv().map_err(std::io::Error::other)? {
+            self.apply(credit);
+        }
+        loop {
+            if self.peer_closed {
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "connection closed by peer",
+                )));
+            }
+            if self.available > 0 {
+                return Poll::Ready(Ok(self.available));
+            }
+            let credit = futures::ready!(Box::pin(self.port.recv()).as_mut().poll(cx))
+                .map_err(std::io::Error::other)?;
+            self.apply(credit);
+        }
+    }
+}
+
 struct OwnedReadHalfStream {
     port: PortReceiver<Io>,
+    credit: CreditReturn,
     exhausted: bool,
 }
 
+impl Drop for OwnedReadHalfStream {
+    fn drop(&mut self) {
+        // Once we've seen EOF, the peer has stopped writing anyway.
+        if !self.exhausted {
+            self.credit.close();
+        }
+    }
+}
+
 /// Wrap a `PortReceiver<IoMsg>` as a `AsyncRead`.
 pub struct OwnedReadHalf {
     peer: ActorId,
@@ -102,6 +226,8 @@ pub struct OwnedWriteHalf<C: context::Actor> {
     port: PortRef<Io>,
     #[pin]
     shutdown: bool,
+    credit: WriteCredit,
+    max_frame_size: usize,
 }
 
 /// A duplex bytestream connection between two actors.  Can generally be used like a `TcpStream`.
@@ -124,11 +250,12 @@ impl<C: context::Actor> ActorConnection<C> {
 }
 
 impl OwnedReadHalf {
-    fn new(peer: ActorId, port: PortReceiver<Io>) -> Self {
+    fn new(peer: ActorId, port: PortReceiver<Io>, credit: CreditReturn) -> Self {
         Self {
             peer,
             inner: StreamReader::new(OwnedReadHalfStream {
                 port,
+                credit,
                 exhausted: false,
             }),
         }
@@ -147,12 +274,14 @@ impl OwnedReadHalf {
 }
 
 impl<C: context::Actor> OwnedWriteHalf<C> {
-    fn new(peer: ActorId, caps: C, port: PortRef<Io>) -> Self {
+    fn new(peer: ActorId, caps: C, port: PortRef<Io>, credit: WriteCredit) -> Self {
         Self {
             peer,
             caps,
             port,
             shutdown: false,
+            credit,
+            max_frame_size: hyperactor_config::global::get(CONNECTION_MAX_FRAME_SIZE).max(1),
         }
     }
 
@@ -172,7 +301,7 @@ impl<C: context::Actor> OwnedWriteHalf<C> {
 impl<C: context::Actor> PinnedDrop for OwnedWriteHalf<C> {
     fn drop(self: Pin<&mut Self>) {
         let this = self.project();
-        if !*this.shutdown {
+        if !*this.shutdown && !this.credit.peer_closed {
             let _ = this.port.send(&*this.caps, Io::Eof);
         }
     }
@@ -227,7 +356,12 @@ impl Stream for OwnedReadHalfStream {
         let result = futures::ready!(Box::pin(self.port.recv()).as_mut().poll(cx));
         match result {
             Err(err) => Poll::Ready(Some(Err(std::io::Error::other(err)))),
-            Ok(Io::Data(buf)) => Poll::Ready(Some(Ok(Cursor::new(buf)))),
+            Ok(Io::Data(buf)) => {
+                // The reader only pulls the next frame once it has consumed
+                // the previous one, so this is when the credit is returned.
+                self.credit.consumed(buf.len());
+                Poll::Ready(Some(Ok(Cursor::new(buf))))
+            }
             // Break out of stream when we see EOF.
             Ok(Io::Eof) => {
                 self.exhausted = true;
@@ -256,7 +390,7 @@ impl AsyncRead for OwnedReadHalf {
 impl<C: context::Actor> AsyncWrite for OwnedWriteHalf<C> {
     fn poll_write(
         self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<Result<usize, std::io::Error>> {
         let this = self.project();
@@ -266,8 +400,16 @@ impl<C: context::Actor> AsyncWrite for OwnedWriteHalf<C> {
                 "write after shutdown",
             )));
         }
-        match this.port.send(&*this.caps, Io::Data(buf.into())) {
-            Ok(()) => Poll::Ready(Ok(buf.len())),
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let available = futures::ready!(this.credit.poll_available(cx))?;
+        let len = buf.len().min(available).min(*this.max_frame_size);
+        match this.port.send(&*this.caps, Io::Data(buf[..len].into())) {
+            Ok(()) => {
+                this.credit.available -= len;
+                Poll::Ready(Ok(len))
+            }
             Err(e) => Poll::Ready(Err(std::io::Error::other(e))),
         }
     }
@@ -280,7 +422,16 @@ impl<C: context::Actor> AsyncWrite for OwnedWriteHalf<C> {
         self: Pin<&mut Self>,
         _cx: &mut Context<'_>,
     ) -> Poll<Result<(), std::io::Error>> {
-        // Send EOF on shutdown.
+        // Like `TcpStream`, shutting down more than once is fine.
+        if self.shutdown {
+            return Poll::Ready(Ok(()));
+        }
+        // Send EOF on shutdown, unless the peer is no longer reading.
+        if self.credit.peer_closed {
+            let mut this = self.project();
+            *this.shutdown = true;
+            return Poll::Ready(Ok(()));
+        }
         match self.port.send(&self.caps, Io::Eof) {
             Ok(()) => {
                 let mut this = self.project();
@@ -296,6 +447,8 @@ impl<C: context::Actor> AsyncWrite for OwnedWriteHalf<C> {
 pub struct ConnectionCompleter<C> {
     caps: C,
     conn: PortReceiver<Io>,
+    credit: PortReceiver<Credit>,
+    window: usize,
     port: OncePortReceiver<Accept>,
 }
 
@@ -306,9 +459,19 @@ impl<C: context::Actor> ConnectionCompleter<C> {
         let accept = RealClock
             .timeout(CONNECT_TIMEOUT, self.port.recv())
             .await??;
+        let (instance, _handle) = self.caps.instance().child()?;
         Ok(ActorConnection {
-            reader: OwnedReadHalf::new(accept.id.clone(), self.conn),
-            writer: OwnedWriteHalf::new(accept.id, self.caps, accept.conn),
+            reader: OwnedReadHalf::new(
+                accept.id.clone(),
+                self.conn,
+                CreditReturn::new(instance, accept.credit, self.window),
+            ),
+            writer: OwnedWriteHalf::new(
+                accept.id,
+                self.caps,
+                accept.conn,
+                WriteCredit::new(self.credit, accept.window),
+            ),
         })
     }
 }
@@ -319,6 +482,10 @@ pub struct Connect {
     /// The ID of the client initiating the connection.
     id: ActorId,
     conn: PortRef<Io>,
+    /// The port the server returns credit to as it consumes the client's data.
+    credit: PortRef<Credit>,
+    /// The number of bytes the server may send before the client returns credit.
+    window: usize,
     /// The port the server can use to complete the connection.
     return_conn: OncePortRef<Accept>,
 }
@@ -327,17 +494,23 @@ impl Connect {
     /// Allocate a new `Connect` message and return the associated `ConnectionCompleter` that can be used
     /// to finish setting up the connection.
     pub fn allocate<C: context::Actor>(id: ActorId, caps: C) -> (Self, ConnectionCompleter<C>) {
+        let window = hyperactor_config::global::get(CONNECTION_WINDOW_SIZE).max(1);
         let (conn_tx, conn_rx) = open_port::<Io>(&caps);
+        let (credit_tx, credit_rx) = open_port::<Credit>(&caps);
         let (return_tx, return_rx) = open_once_port::<Accept>(&caps);
         (
             Self {
                 id,
                 conn: conn_tx.bind(),
+                credit: credit_tx.bind(),
+                window,
                 return_conn: return_tx.bind(),
             },
             ConnectionCompleter {
                 caps,
                 conn: conn_rx,
+                credit: credit_rx,
+                window,
                 port: return_rx,
             },
         )
@@ -352,11 +525,16 @@ struct Accept {
     id: ActorId,
     /// The port the client will use to send data over the connection to the server.
     conn: PortRef<Io>,
+    /// The port the client returns credit to as it consumes the server's data.
+    credit: PortRef<Credit>,
+    /// The number of bytes the client may send before the server returns credit.
+    window: usize,
 }
 
 impl Bind for Connect {
     fn bind(&mut self, bindings: &mut Bindings) -> Result<()> {
         self.conn.bind(bindings)?;
+        self.credit.bind(bindings)?;
         self.return_conn.bind(bindings)
     }
 }
@@ -364,6 +542,7 @@ impl Bind for Connect {
 impl Unbind for Connect {
     fn unbind(&self, bindings: &mut Bindings) -> Result<()> {
         self.conn.unbind(bindings)?;
+        self.credit.unbind(bindings)?;
         self.return_conn.unbind(bindings)
     }
 }
@@ -375,17 +554,31 @@ pub async fn accept<C: context::Actor>(
     self_id: ActorId,
     message: Connect,
 ) -> Result<ActorConnection<C>> {
+    let window = hyperactor_config::global::get(CONNECTION_WINDOW_SIZE).max(1);
     let (tx, rx) = open_port::<Io>(&caps);
+    let (credit_tx, credit_rx) = open_port::<Credit>(&caps);
+    let (instance, _handle) = caps.instance().child()?;
     message.return_conn.send(
         &caps,
         Accept {
             id: self_id,
             conn: tx.bind(),
+            credit: credit_tx.bind(),
+            window,
         },
     )?;
     Ok(ActorConnection {
-        reader: OwnedReadHalf::new(message.id.clone(), rx),
-        writer: OwnedWriteHalf::new(message.id, caps, message.conn),
+        reader: OwnedReadHalf::new(
+            message.id.clone(),
+            rx,
+            CreditReturn::new(instance, message.credit, window),
+        ),
+        writer: OwnedWriteHalf::new(
+            message.id,
+            caps,
+            message.conn,
+            WriteCredit::new(credit_rx, message.window),
+        ),
     })
 }
 
@@ -393,6 +586,7 @@ pub async fn accept<C: context::Actor>(
 mod tests {
     use anyhow::Result;
     use async_trait::async_trait;
+    use futures::FutureExt;
     use futures::try_join;
     use hyperactor::Actor;
     use hyperactor::Context;
@@ -508,4 +702,159 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_backpressure() -> Result<()> {
+        let config = hyperactor_config::global::lock();
+        let _window = config.override_key(CONNECTION_WINDOW_SIZE, 8);
+        let _frame = config.override_key(CONNECTION_MAX_FRAME_SIZE, 4);
+
+        let proc = Proc::local();
+        let (client, _client_handle) = proc.instance("client")?;
+
+        let (connect, completer) =
+            Connect::allocate(client.self_id().clone(), client.clone_for_py());
+        let (mut rd, _) = accept(client.clone_for_py(), client.self_id().clone(), connect)
+            .await?
+            .into_split();
+        let (_, mut wr) = completer.complete().await?.into_split();
+
+        // Writes are split into frames, and stall once the window is used up.
+        let send = [7u8; 16];
+        assert_eq!(wr.write(&send).now_or_never().unwrap()?, 4);
+        assert_eq!(wr.write(&send).now_or_never().unwrap()?, 4);
+        assert!(wr.write(&send).now_or_never().is_none());
+
+        // Reading returns credit, which unblocks the writer.
+        let mut recv = [0u8; 4];
+        rd.read_exact(&mut recv).await?;
+        assert_eq!(recv, [7u8; 4]);
+        assert_eq!(wr.write(&send).await?, 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_large_transfer() -> Result<()> {
+        let config = hyperactor_config::global::lock();
+        let _window = config.override_key(CONNECTION_WINDOW_SIZE, 64);
+        let _frame = config.override_key(CONNECTION_MAX_FRAME_SIZE, 16);
+
+        let proc = Proc::local();
+        let (client, _client_handle) = proc.instance("client")?;
+        let (connect, completer) = Connect::allocate(client.self_id().clone(), client);
+        let actor = proc.spawn("actor", EchoActor {})?;
+        actor.send(connect)?;
+        let (mut rd, mut wr) = completer.complete().await?.into_split();
+
+        let send: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+        try_join!(
+            async {
+                wr.write_all(&send).await?;
+                wr.shutdown().await?;
+                anyhow::Ok(())
+            },
+            async {
+                let mut recv = vec![];
+                rd.read_to_end(&mut recv).await?;
+                assert_eq!(send, recv);
+                anyhow::Ok(())
+            },
+        )?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zero_window() -> Result<()> {
+        let config = hyperactor_config::global::lock();
+        let _window = config.override_key(CONNECTION_WINDOW_SIZE, 0);
+
+        let proc = Proc::local();
+        let (client, _client_handle) = proc.instance("client")?;
+        let (connect, completer) = Connect::allocate(client.self_id().clone(), client);
+        let actor = proc.spawn("actor", EchoActor {})?;
+        actor.send(connect)?;
+        let (mut rd, mut wr) = completer.complete().await?.into_split();
+
+        // A zero window is treated as a single byte, so data still flows.
+        let send = b"hello";
+        try_join!(
+            async {
+                wr.write_all(send).await?;
+                wr.shutdown().await?;
+                anyhow::Ok(())
+            },
+            async {
+                let mut recv = vec![];
+                rd.read_to_end(&mut recv).await?;
+                assert_eq!(recv, send);
+                anyhow::Ok(())
+            },
+        )?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_after_reader_dropped() -> Result<()> {
+        let config = hyperactor_config::global::lock();
+        let _window = config.override_key(CONNECTION_WINDOW_SIZE, 8);
+
+        let proc = Proc::local();
+        let (client, _client_handle) = proc.instance("client")?;
+
+        let (connect, completer) =
+            Connect::allocate(client.self_id().clone(), client.clone_for_py());
+        let (rd, _) = accept(client.clone_for_py(), client.self_id().clone(), connect)
+            .await?
+            .into_split();
+        let (_, mut wr) = completer.complete().await?.into_split();
+
+        drop(rd);
+
+        // Like a `TcpStream` whose peer has closed, writes eventually fail.
+        let err = wr.write_all(&[0u8; 64]).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+
+        // Shutting down is still fine.
+        wr.shutdown().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_half_close() -> Result<()> {
+        let proc = Proc::local();
+        let (client, _client_handle) = proc.instance("client")?;
+
+        let (connect, completer) =
+            Connect::allocate(client.self_id().clone(), client.clone_for_py());
+        let (mut server_rd, mut server_wr) =
+            accept(client.clone_for_py(), client.self_id().clone(), connect)
+                .await?
+                .into_split();
+        let (mut client_rd, mut client_wr) = completer.complete().await?.into_split();
+
+        // The client closes its side, possibly more than once...
+        client_wr.write_all(b"request").await?;
+        client_wr.shutdown().await?;
+        client_wr.shutdown().await?;
+        let err = client_wr.write_all(b"more").await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+
+        let mut recv = vec![];
+        server_rd.read_to_end(&mut recv).await?;
+        assert_eq!(recv, b"request");
+
+        // ...while the server can still respond.
+        server_wr.write_all(b"response").await?;
+        server_wr.shutdown().await?;
+        let mut recv = vec![];
+        client_rd.read_to_end(&mut recv).await?;
+        assert_eq!(recv, b"response");
+
+        // Reads past EOF keep returning EOF.
+        assert_eq!(server_rd.read(&mut [0u8; 8]).await?, 0);
+
+        Ok(())
+    }
 }
@@ -26,6 +26,8 @@ pub mod logging;
 pub mod mesh;
 pub mod mesh_selection;
 mod metrics;
+pub mod metrics_aggregator;
+pub mod port_forward;
 pub mod proc_mesh;
 pub mod reference;
 pub mod resource;
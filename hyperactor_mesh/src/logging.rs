@@ -6,9 +6,11 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::fmt;
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -19,6 +21,8 @@ use anyhow::Result;
 use async_trait::async_trait;
 use chrono::DateTime;
 use chrono::Local;
+use chrono::SecondsFormat;
+use chrono::Utc;
 use hostname;
 use hyperactor::Actor;
 use hyperactor::ActorRef;
@@ -29,6 +33,7 @@ use hyperactor::Handler;
 use hyperactor::Instance;
 use hyperactor::Named;
 use hyperactor::OncePortRef;
+use hyperactor::ProcId;
 use hyperactor::RefClient;
 use hyperactor::Unbind;
 use hyperactor::channel;
@@ -44,9 +49,11 @@ use hyperactor::clock::RealClock;
 use hyperactor::data::Serialized;
 use hyperactor_config::CONFIG;
 use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::AttrValue;
 use hyperactor_config::attrs::declare_attrs;
 use hyperactor_telemetry::env;
 use hyperactor_telemetry::log_file_path;
+use ndslice::Point;
 use serde::Deserialize;
 use serde::Serialize;
 use tokio::io;
@@ -61,9 +68,14 @@ use tokio::task::JoinHandle;
 use tracing::Level;
 
 use crate::bootstrap::BOOTSTRAP_LOG_CHANNEL;
+use crate::comm::multicast::CAST_POINT;
 use crate::shortuuid::ShortUuid;
 
 mod line_prefixing_writer;
+mod rotating_file;
+
+use rotating_file::RotatingFile;
+use rotating_file::RotationPolicy;
 
 pub(crate) const DEFAULT_AGGREGATE_WINDOW_SEC: u64 = 5;
 const MAX_LINE_SIZE: usize = 4 * 1024;
@@ -91,6 +103,261 @@ declare_attrs! {
         py_name: None,
     })
     pub attr PREFIX_WITH_RANK: bool = true;
+
+    /// How the log client renders forwarded log lines: "text"
+    /// (default) or "json" (newline-delimited [`LogRecord`]s).
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_LOG_OUTPUT_FORMAT".to_string()),
+        py_name: None,
+    })
+    pub attr LOG_OUTPUT_FORMAT: LogOutputFormat = LogOutputFormat::Text;
+
+    /// If non-empty, JSON log records are appended to this file
+    /// instead of being written to stdout. Ignored in text mode.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_LOG_OUTPUT_FILE".to_string()),
+        py_name: None,
+    })
+    pub attr LOG_OUTPUT_FILE: String = String::new();
+
+    /// Template for the names of per-host aggregated log files.
+    /// Supported placeholders: `{prefix}` (the environment's log file
+    /// prefix), `{job}` (the execution id), `{host}`, `{proc}` (pid
+    /// of the process owning the appender), `{uuid}` (unique per
+    /// appender) and `{stream}` (`stdout` or `stderr`). Templates
+    /// must contain `{uuid}` and `{stream}`, so that no two streams
+    /// write to the same file.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_LOG_FILE_NAME_TEMPLATE".to_string()),
+        py_name: None,
+    })
+    pub attr LOG_FILE_NAME_TEMPLATE: LogFileNameTemplate = LogFileNameTemplate::default();
+
+    /// Rotate aggregated log files before they grow past this many
+    /// bytes. 0 disables size-based rotation.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_LOG_FILE_MAX_SIZE".to_string()),
+        py_name: None,
+    })
+    pub attr LOG_FILE_MAX_SIZE: usize = 0;
+
+    /// Rotate aggregated log files after they have been open this
+    /// long. Rotation happens on the first write after the interval
+    /// has elapsed, so an idle file is not rotated until it is
+    /// written to again. 0 disables time-based rotation.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_LOG_FILE_ROTATE_INTERVAL".to_string()),
+        py_name: None,
+    })
+    pub attr LOG_FILE_ROTATE_INTERVAL: Duration = Duration::ZERO;
+
+    /// Number of rotated log file segments to keep per stream.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_LOG_FILE_RETENTION".to_string()),
+        py_name: None,
+    })
+    pub attr LOG_FILE_RETENTION: usize = 5;
+
+    /// If enabled, rotated log file segments are gzip-compressed.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_LOG_FILE_COMPRESS".to_string()),
+        py_name: None,
+    })
+    pub attr LOG_FILE_COMPRESS: bool = false;
+}
+
+/// Output format used by [`LogClientActor`] for forwarded log lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LogOutputFormat {
+    /// Human-readable `"[hostname pid] line"` text; aggregated
+    /// summaries are ANSI-decorated.
+    #[default]
+    Text,
+    /// Newline-delimited JSON, one [`LogRecord`] per line.
+    Json,
+}
+
+impl fmt::Display for LogOutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogOutputFormat::Text => write!(f, "text"),
+            LogOutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl std::str::FromStr for LogOutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" | "plain" => Ok(LogOutputFormat::Text),
+            "json" | "jsonl" | "ndjson" => Ok(LogOutputFormat::Json),
+            _ => Err(anyhow::anyhow!("unknown log output format: {}", s)),
+        }
+    }
+}
+
+impl Named for LogOutputFormat {
+    fn typename() -> &'static str {
+        "hyperactor_mesh::logging::LogOutputFormat"
+    }
+}
+
+impl AttrValue for LogOutputFormat {
+    fn display(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        s.parse()
+    }
+}
+
+/// The template for the names of aggregated log files; see
+/// [`LOG_FILE_NAME_TEMPLATE`]. Templates without the `{uuid}` and
+/// `{stream}` placeholders are rejected, as the stdout and stderr files
+/// of an appender, or the files of several appenders, would collide.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct LogFileNameTemplate(String);
+
+impl LogFileNameTemplate {
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for LogFileNameTemplate {
+    fn default() -> Self {
+        Self("{prefix}_{host}_{uuid}.{stream}".to_string())
+    }
+}
+
+impl fmt::Display for LogFileNameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for LogFileNameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        for placeholder in ["{uuid}", "{stream}"] {
+            if !s.contains(placeholder) {
+                anyhow::bail!("log file name template {} lacks {}", s, placeholder);
+            }
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for LogFileNameTemplate {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<LogFileNameTemplate> for String {
+    fn from(template: LogFileNameTemplate) -> Self {
+        template.0
+    }
+}
+
+impl Named for LogFileNameTemplate {
+    fn typename() -> &'static str {
+        "hyperactor_mesh::logging::LogFileNameTemplate"
+    }
+}
+
+impl AttrValue for LogFileNameTemplate {
+    fn display(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        s.parse()
+    }
+}
+
+/// A single structured log record, emitted by [`LogClientActor`] as
+/// one JSON object per line when the output format is
+/// [`LogOutputFormat::Json`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogRecord {
+    /// A line forwarded from a remote process.
+    Line {
+        /// The hostname of the process that generated the line.
+        hostname: String,
+        /// The pid of the process that generated the line.
+        pid: u32,
+        /// The proc that forwarded the line, if known.
+        proc_id: Option<String>,
+        /// The rank of the forwarding proc in its mesh, if known.
+        rank: Option<usize>,
+        /// The coordinates of the forwarding proc, keyed by
+        /// dimension label, if known.
+        point: Option<BTreeMap<String, usize>>,
+        /// The stream the line was written to ("stdout" or "stderr").
+        stream: String,
+        /// RFC 3339 time at which the client received the line.
+        timestamp: String,
+        /// The line itself, without a trailing newline.
+        line: String,
+    },
+    /// A group of similar lines collapsed by edit-distance
+    /// aggregation.
+    Aggregate {
+        /// The stream the lines were written to.
+        stream: String,
+        /// RFC 3339 start of the aggregation window.
+        window_start: String,
+        /// RFC 3339 end of the aggregation window.
+        window_end: String,
+        /// Number of lines collapsed into this record.
+        count: u64,
+        /// The representative line for the group.
+        line: String,
+    },
+}
+
+impl LogRecord {
+    fn line(
+        hostname: &str,
+        pid: u32,
+        proc_id: Option<&ProcId>,
+        point: Option<&Point>,
+        output_target: OutputTarget,
+        line: String,
+    ) -> Self {
+        LogRecord::Line {
+            hostname: hostname.to_string(),
+            pid,
+            proc_id: proc_id.map(|proc_id| proc_id.to_string()),
+            rank: point.map(|point| point.rank()),
+            point: point.map(|point| {
+                point
+                    .extent()
+                    .labels()
+                    .iter()
+                    .cloned()
+                    .zip(point.coords())
+                    .collect()
+            }),
+            stream: output_target.to_string(),
+            timestamp: format_rfc3339(RealClock.system_time_now()),
+            line,
+        }
+    }
+}
+
+fn format_rfc3339(time: SystemTime) -> String {
+    let datetime: DateTime<Utc> = time.into();
+    datetime.to_rfc3339_opts(SecondsFormat::Micros, true)
 }
 
 /// Calculate the Levenshtein distance between two strings
@@ -235,6 +502,23 @@ impl Aggregator {
     fn is_empty(&self) -> bool {
         self.lines.is_empty()
     }
+
+    /// Summarize the current window as one [`LogRecord::Aggregate`]
+    /// per group of similar lines.
+    fn records(&self, output_target: OutputTarget) -> Vec<LogRecord> {
+        let window_start = format_rfc3339(self.start_time);
+        let window_end = format_rfc3339(RealClock.system_time_now());
+        self.lines
+            .iter()
+            .map(|line| LogRecord::Aggregate {
+                stream: output_target.to_string(),
+                window_start: window_start.clone(),
+                window_end: window_end.clone(),
+                count: line.count,
+                line: line.content.clone(),
+            })
+            .collect()
+    }
 }
 
 // Helper function to format SystemTime
@@ -290,6 +574,11 @@ pub enum LogMessage {
         hostname: String,
         /// The pid of the process that generated the log
         pid: u32,
+        /// The proc that forwarded the log. Filled in by the
+        /// [`LogForwardActor`]; `None` when sent by the proc manager.
+        proc_id: Option<ProcId>,
+        /// The point of the forwarding proc in its mesh, if known.
+        point: Option<Point>,
         /// The target output stream (stdout or stderr)
         output_target: OutputTarget,
         /// The log payload as bytes
@@ -321,6 +610,19 @@ pub enum LogClientMessage {
         aggregate_window_sec: Option<u64>,
     },
 
+    /// Change how forwarded lines are rendered, replying with an error
+    /// if the requested file could not be opened, in which case the
+    /// output is left unchanged.
+    SetOutput {
+        /// The output format to use from now on.
+        format: LogOutputFormat,
+        /// For JSON output, a file to append records to. If None,
+        /// records are written to stdout.
+        path: Option<String>,
+        #[reply]
+        result: OncePortRef<Result<(), String>>,
+    },
+
     /// Synchronously flush all the logs from all the procs. This is for client to call.
     StartSyncFlush {
         /// Expect these many procs to ack the flush message.
@@ -352,6 +654,15 @@ pub enum OutputTarget {
     Stderr,
 }
 
+impl fmt::Display for OutputTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputTarget::Stdout => write!(f, "stdout"),
+            OutputTarget::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub enum Stream {
     /// Standard output stream
@@ -394,6 +705,8 @@ impl LogSender for LocalLogSender {
             self.tx.post(LogMessage::Log {
                 hostname: self.hostname.clone(),
                 pid: self.pid,
+                proc_id: None,
+                point: None,
                 output_target: target,
                 payload: Serialized::serialize(&payload)?,
             });
@@ -439,25 +752,26 @@ impl fmt::Debug for FileAppender {
 }
 
 impl FileAppender {
-    /// Create a new FileAppender with aggregated log files for stdout and stderr
+    /// Create a new FileAppender with aggregated log files for stdout and stderr.
+    /// Files are named after [`LOG_FILE_NAME_TEMPLATE`] and rotated according to
+    /// the `LOG_FILE_*` config. Rotation only affects the files on disk; the
+    /// in-memory tails kept by [`StreamFwder`] are fed from the child's stream
+    /// and are unaffected.
     /// Returns None if file creation fails
     pub fn new() -> Option<Self> {
         let stop = Arc::new(Notify::new());
-        // TODO make it configurable
-        let file_name_tag = hostname::get()
-            .unwrap_or_else(|_| "unknown_host".into())
-            .into_string()
-            .unwrap_or("unknown_host".to_string());
+        let file_name_vars = FileNameVars::current();
 
         // Create stdout file and task
-        let (stdout_path, stdout_writer) =
-            match get_unique_local_log_destination(&file_name_tag, OutputTarget::Stdout) {
+        let stdout_writer =
+            match get_unique_local_log_destination(&file_name_vars, OutputTarget::Stdout) {
                 Some(writer) => writer,
                 None => {
                     tracing::warn!("failed to create stdout file");
                     return None;
                 }
             };
+        let stdout_path = stdout_writer.path().to_path_buf();
         let (stdout_addr, stdout_rx) = {
             let _guard = tracing::span!(Level::INFO, "appender", file = "stdout").entered();
             match channel::serve(ChannelAddr::any(ChannelTransport::Unix)) {
@@ -477,14 +791,15 @@ impl FileAppender {
         ));
 
         // Create stderr file and task
-        let (stderr_path, stderr_writer) =
-            match get_unique_local_log_destination(&file_name_tag, OutputTarget::Stderr) {
+        let stderr_writer =
+            match get_unique_local_log_destination(&file_name_vars, OutputTarget::Stderr) {
                 Some(writer) => writer,
                 None => {
                     tracing::warn!("failed to create stderr file");
                     return None;
                 }
             };
+        let stderr_path = stderr_writer.path().to_path_buf();
         let (stderr_addr, stderr_rx) = {
             let _guard = tracing::span!(Level::INFO, "appender", file = "stderr").entered();
             match channel::serve(ChannelAddr::any(ChannelTransport::Unix)) {
@@ -538,7 +853,7 @@ impl Drop for FileAppender {
 /// Task that receives lines from StreamFwds and writes them to the aggregated file
 async fn file_monitor_task(
     mut rx: ChannelRx<FileMonitorMessage>,
-    mut writer: Box<dyn io::AsyncWrite + Send + Unpin + 'static>,
+    mut writer: RotatingFile,
     target: OutputTarget,
     stop: Arc<Notify>,
 ) {
@@ -549,12 +864,8 @@ async fn file_monitor_task(
                     Ok(msg) => {
                         // Write lines to aggregated file
                         for line in &msg.lines {
-                            if let Err(e) = writer.write_all(line.as_bytes()).await {
+                            if let Err(e) = writer.write_line(line.as_bytes()).await {
                                 tracing::warn!("FileMonitor: failed to write line to file: {}", e);
-                                continue;
-                            }
-                            if let Err(e) = writer.write_all(b"\n").await {
-                                tracing::warn!("FileMonitor: failed to write newline to file: {}", e);
                             }
                         }
                         if let Err(e) = writer.flush().await {
@@ -582,45 +893,69 @@ async fn file_monitor_task(
     tracing::debug!("FileMonitor task for {:?} exiting", target);
 }
 
+/// Values substituted into [`LOG_FILE_NAME_TEMPLATE`], shared by the
+/// stdout and stderr files of one appender.
+struct FileNameVars {
+    job: String,
+    host: String,
+    proc: String,
+    uuid: String,
+}
+
+impl FileNameVars {
+    fn current() -> Self {
+        Self {
+            job: env::execution_id(),
+            host: hostname::get()
+                .unwrap_or_else(|_| "unknown_host".into())
+                .into_string()
+                .unwrap_or("unknown_host".to_string()),
+            proc: std::process::id().to_string(),
+            uuid: ShortUuid::generate().to_string(),
+        }
+    }
+
+    fn file_name(&self, template: &str, prefix: &str, output_target: OutputTarget) -> String {
+        rotating_file::expand_template(
+            template,
+            &[
+                ("prefix", prefix),
+                ("job", &self.job),
+                ("host", &self.host),
+                ("proc", &self.proc),
+                ("uuid", &self.uuid),
+                ("stream", &output_target.to_string()),
+            ],
+        )
+    }
+}
+
 fn create_unique_file_writer(
-    file_name_tag: &str,
+    file_name_vars: &FileNameVars,
     output_target: OutputTarget,
     env: env::Env,
-) -> Result<(PathBuf, Box<dyn io::AsyncWrite + Send + Unpin + 'static>)> {
-    let suffix = match output_target {
-        OutputTarget::Stderr => "stderr",
-        OutputTarget::Stdout => "stdout",
-    };
-    let (path, filename) = log_file_path(env, None)?;
-    let path = Path::new(&path);
-    let mut full_path = PathBuf::from(path);
-
-    let uuid = ShortUuid::generate();
-
-    full_path.push(format!(
-        "{}_{}_{}.{}",
-        filename, file_name_tag, uuid, suffix
-    ));
-    let file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(full_path.clone())?;
-    let tokio_file = tokio::fs::File::from_std(file);
-    // TODO: should we buffer this?
-    Ok((full_path, Box::new(tokio_file)))
+) -> Result<RotatingFile> {
+    let (path, prefix) = log_file_path(env, None)?;
+    let template = hyperactor_config::global::get_cloned(LOG_FILE_NAME_TEMPLATE);
+    let mut full_path = PathBuf::from(Path::new(&path));
+    full_path.push(file_name_vars.file_name(template.as_str(), &prefix, output_target));
+    Ok(RotatingFile::open(
+        full_path,
+        RotationPolicy::from_config(),
+    )?)
 }
 
 fn get_unique_local_log_destination(
-    file_name_tag: &str,
+    file_name_vars: &FileNameVars,
     output_target: OutputTarget,
-) -> Option<(PathBuf, Box<dyn io::AsyncWrite + Send + Unpin + 'static>)> {
+) -> Option<RotatingFile> {
     let env: env::Env = env::Env::current();
     if env == env::Env::Local && !hyperactor_config::global::get(FORCE_FILE_LOG) {
         tracing::debug!("not creating log file because of env type");
         None
     } else {
-        match create_unique_file_writer(file_name_tag, output_target, env) {
-            Ok((a, b)) => Some((a, b)),
+        match create_unique_file_writer(file_name_vars, output_target, env) {
+            Ok(writer) => Some(writer),
             Err(e) => {
                 tracing::warn!("failed to create unique file writer: {}", e);
                 None
@@ -993,6 +1328,9 @@ pub struct LogForwardActor {
     next_flush_deadline: SystemTime,
     logging_client_ref: ActorRef<LogClientActor>,
     stream_to_client: bool,
+    /// This proc's point in the mesh, learned from the first cast
+    /// message received.
+    point: Option<Point>,
 }
 
 #[async_trait]
@@ -1057,6 +1395,7 @@ impl hyperactor::RemoteSpawn for LogForwardActor {
             next_flush_deadline: now,
             logging_client_ref,
             stream_to_client: true,
+            point: None,
         })
     }
 }
@@ -1096,12 +1435,16 @@ impl LogForwardMessageHandler for LogForwardActor {
             Ok(LogMessage::Log {
                 hostname,
                 pid,
+                proc_id,
+                point,
                 output_target,
                 payload,
             }) => {
                 if self.stream_to_client {
+                    let proc_id = proc_id.or_else(|| Some(ctx.self_id().proc_id().clone()));
+                    let point = point.or_else(|| self.point.clone());
                     self.logging_client_ref
-                        .log(ctx, hostname, pid, output_target, payload)
+                        .log(ctx, hostname, pid, proc_id, point, output_target, payload)
                         .await?;
                 }
             }
@@ -1118,18 +1461,20 @@ impl LogForwardMessageHandler for LogForwardActor {
 
     async fn set_mode(
         &mut self,
-        _ctx: &Context<Self>,
+        ctx: &Context<Self>,
         stream_to_client: bool,
     ) -> Result<(), anyhow::Error> {
+        self.record_point(ctx);
         self.stream_to_client = stream_to_client;
         Ok(())
     }
 
     async fn force_sync_flush(
         &mut self,
-        _cx: &Context<Self>,
+        cx: &Context<Self>,
         version: u64,
     ) -> Result<(), anyhow::Error> {
+        self.record_point(cx);
         self.flush_tx
             .lock()
             .await
@@ -1141,6 +1486,15 @@ impl LogForwardMessageHandler for LogForwardActor {
     }
 }
 
+impl LogForwardActor {
+    /// Remember this proc's point if the current message was cast.
+    fn record_point(&mut self, cx: &Context<Self>) {
+        if self.point.is_none() {
+            self.point = cx.headers().get(CAST_POINT).cloned();
+        }
+    }
+}
+
 /// Deserialize a serialized message and split it into UTF-8 lines
 fn deserialize_message_lines(
     serialized_message: &hyperactor::data::Serialized,
@@ -1175,6 +1529,9 @@ fn deserialize_message_lines(
 pub struct LogClientActor {
     aggregate_window_sec: Option<u64>,
     aggregators: HashMap<OutputTarget, Aggregator>,
+    output_format: LogOutputFormat,
+    // Destination for JSON records; stdout if None.
+    json_file: Option<std::fs::File>,
     last_flush_time: SystemTime,
     next_flush_deadline: Option<SystemTime>,
 
@@ -1191,9 +1548,30 @@ impl Default for LogClientActor {
         aggregators.insert(OutputTarget::Stderr, Aggregator::new());
         aggregators.insert(OutputTarget::Stdout, Aggregator::new());
 
+        let output_format = hyperactor_config::global::get(LOG_OUTPUT_FORMAT);
+        let output_path = hyperactor_config::global::get_cloned(LOG_OUTPUT_FILE);
+        let json_file = match output_format {
+            LogOutputFormat::Json if !output_path.is_empty() => {
+                match open_json_file(&output_path) {
+                    Ok(file) => Some(file),
+                    Err(e) => {
+                        tracing::warn!(
+                            "failed to open log output file {}, using stdout: {}",
+                            output_path,
+                            e
+                        );
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
         Self {
             aggregate_window_sec: Some(DEFAULT_AGGREGATE_WINDOW_SEC),
             aggregators,
+            output_format,
+            json_file,
             last_flush_time: RealClock.system_time_now(),
             next_flush_deadline: None,
             current_flush_version: 0,
@@ -1203,17 +1581,30 @@ impl Default for LogClientActor {
     }
 }
 
+/// Open `path` for appending JSON log records.
+fn open_json_file(path: &str) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
 impl LogClientActor {
     fn print_aggregators(&mut self) {
         for (output_target, aggregator) in self.aggregators.iter_mut() {
             if aggregator.is_empty() {
                 continue;
             }
-            match output_target {
-                OutputTarget::Stdout => {
+            match (self.output_format, output_target) {
+                (LogOutputFormat::Json, _) => {
+                    for record in aggregator.records(*output_target) {
+                        Self::write_record(&mut self.json_file, &record);
+                    }
+                }
+                (LogOutputFormat::Text, OutputTarget::Stdout) => {
                     println!("{}", aggregator);
                 }
-                OutputTarget::Stderr => {
+                (LogOutputFormat::Text, OutputTarget::Stderr) => {
                     eprintln!("{}", aggregator);
                 }
             }
@@ -1223,6 +1614,48 @@ impl LogClientActor {
         }
     }
 
+    /// Write one JSON record to the configured sink.
+    fn write_record(json_file: &mut Option<std::fs::File>, record: &LogRecord) {
+        let message = match serde_json::to_string(record) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::error!("failed to serialize log record: {}", e);
+                return;
+            }
+        };
+
+        #[cfg(test)]
+        crate::logging::test_tap::push(&message);
+
+        match json_file {
+            Some(file) => {
+                if let Err(e) = writeln!(file, "{}", message) {
+                    tracing::warn!("failed to write log record to file: {}", e);
+                }
+            }
+            None => println!("{}", message),
+        }
+    }
+
+    /// Emit a single forwarded line in the configured output format.
+    fn emit_log_line(
+        &mut self,
+        hostname: &str,
+        pid: u32,
+        proc_id: Option<&ProcId>,
+        point: Option<&Point>,
+        output_target: OutputTarget,
+        line: String,
+    ) {
+        match self.output_format {
+            LogOutputFormat::Text => Self::print_log_line(hostname, pid, output_target, line),
+            LogOutputFormat::Json => {
+                let record = LogRecord::line(hostname, pid, proc_id, point, output_target, line);
+                Self::write_record(&mut self.json_file, &record);
+            }
+        }
+    }
+
     fn print_log_line(hostname: &str, pid: u32, output_target: OutputTarget, line: String) {
         let message = format!("[{} {}] {}", hostname, pid, line);
 
@@ -1260,18 +1693,22 @@ impl LogMessageHandler for LogClientActor {
         cx: &Context<Self>,
         hostname: String,
         pid: u32,
+        proc_id: Option<ProcId>,
+        point: Option<Point>,
         output_target: OutputTarget,
         payload: Serialized,
     ) -> Result<(), anyhow::Error> {
         // Deserialize the message and process line by line with UTF-8
         let message_line_groups = deserialize_message_lines(&payload)?;
         let hostname = hostname.as_str();
+        let proc_id = proc_id.as_ref();
+        let point = point.as_ref();
 
         let message_lines: Vec<String> = message_line_groups.into_iter().flatten().collect();
         match self.aggregate_window_sec {
             None => {
                 for line in message_lines {
-                    Self::print_log_line(hostname, pid, output_target, line);
+                    self.emit_log_line(hostname, pid, proc_id, point, output_target, line);
                 }
                 self.last_flush_time = RealClock.system_time_now();
             }
@@ -1281,12 +1718,12 @@ impl LogMessageHandler for LogClientActor {
                         if let Err(e) = aggregator.add_line(&line) {
                             tracing::error!("error adding log line: {}", e);
                             // For the sake of completeness, flush the log lines.
-                            Self::print_log_line(hostname, pid, output_target, line);
+                            self.emit_log_line(hostname, pid, proc_id, point, output_target, line);
                         }
                     } else {
                         tracing::error!("unknown output target: {:?}", output_target);
                         // For the sake of completeness, flush the log lines.
-                        Self::print_log_line(hostname, pid, output_target, line);
+                        self.emit_log_line(hostname, pid, proc_id, point, output_target, line);
                     }
                 }
 
@@ -1383,6 +1820,31 @@ impl LogClientMessageHandler for LogClientActor {
         Ok(())
     }
 
+    async fn set_output(
+        &mut self,
+        _cx: &Context<Self>,
+        format: LogOutputFormat,
+        path: Option<String>,
+    ) -> Result<Result<(), String>, anyhow::Error> {
+        let json_file = match (format, path) {
+            (LogOutputFormat::Json, Some(path)) => match open_json_file(&path) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    return Ok(Err(format!(
+                        "failed to open log output file {}: {}",
+                        path, e
+                    )));
+                }
+            },
+            _ => None,
+        };
+        // Whatever was aggregated so far is rendered in the old format.
+        self.print_aggregators();
+        self.output_format = format;
+        self.json_file = json_file;
+        Ok(Ok(()))
+    }
+
     async fn start_sync_flush(
         &mut self,
         cx: &Context<Self>,
@@ -1615,6 +2077,8 @@ mod tests {
         tx.post(LogMessage::Log {
             hostname: "my_host".into(),
             pid: 1,
+            proc_id: None,
+            point: None,
             output_target: OutputTarget::Stderr,
             payload: Serialized::serialize(&"will not stream".to_string()).unwrap(),
         });
@@ -1624,6 +2088,8 @@ mod tests {
         tx.post(LogMessage::Log {
             hostname: "my_host".into(),
             pid: 1,
+            proc_id: None,
+            point: None,
             output_target: OutputTarget::Stderr,
             payload: Serialized::serialize(&"will stream".to_string()).unwrap(),
         });
@@ -2013,6 +2479,143 @@ mod tests {
         assert_eq!(stderr_deserialized, OutputTarget::Stderr);
     }
 
+    #[test]
+    fn test_log_file_name_template_parse() {
+        assert_eq!(
+            "{prefix}_{host}_{uuid}.{stream}"
+                .parse::<LogFileNameTemplate>()
+                .unwrap(),
+            LogFileNameTemplate::default()
+        );
+        assert!(
+            "{job}_{uuid}.{stream}"
+                .parse::<LogFileNameTemplate>()
+                .is_ok()
+        );
+        // Without either placeholder, streams or appenders share a file.
+        assert!(
+            "{prefix}_{host}.{stream}"
+                .parse::<LogFileNameTemplate>()
+                .is_err()
+        );
+        assert!(
+            "{prefix}_{host}_{uuid}"
+                .parse::<LogFileNameTemplate>()
+                .is_err()
+        );
+        assert!(serde_json::from_str::<LogFileNameTemplate>("\"{prefix}.log\"").is_err());
+    }
+
+    #[test]
+    fn test_log_output_format_parse() {
+        assert_eq!(
+            "json".parse::<LogOutputFormat>().unwrap(),
+            LogOutputFormat::Json
+        );
+        assert_eq!(
+            "TEXT".parse::<LogOutputFormat>().unwrap(),
+            LogOutputFormat::Text
+        );
+        assert!("yaml".parse::<LogOutputFormat>().is_err());
+        assert_eq!(
+            <LogOutputFormat as AttrValue>::parse(&LogOutputFormat::Json.display()).unwrap(),
+            LogOutputFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_log_record_line_json() {
+        let point = ndslice::extent!(host = 2, gpu = 4)
+            .point(vec![1, 3])
+            .unwrap();
+        let proc_id = id!(world[3]);
+        let record = LogRecord::line(
+            "my_host",
+            42,
+            Some(&proc_id),
+            Some(&point),
+            OutputTarget::Stderr,
+            "hello".to_string(),
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&record).unwrap()).unwrap();
+        assert_eq!(json["type"], "line");
+        assert_eq!(json["hostname"], "my_host");
+        assert_eq!(json["pid"], 42);
+        assert_eq!(json["proc_id"], proc_id.to_string());
+        assert_eq!(json["rank"], 7);
+        assert_eq!(json["point"]["host"], 1);
+        assert_eq!(json["point"]["gpu"], 3);
+        assert_eq!(json["stream"], "stderr");
+        assert_eq!(json["line"], "hello");
+        assert!(json["timestamp"].as_str().unwrap().ends_with('Z'));
+
+        // Records round-trip.
+        let parsed: LogRecord = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, record);
+    }
+
+    #[tokio::test]
+    async fn test_set_output_bad_path() {
+        let proc = Proc::local();
+        let (client, _handle) = proc.instance("client").unwrap();
+        let log_client = proc
+            .spawn("log_client", LogClientActor::new(()).await.unwrap())
+            .unwrap();
+
+        // A file that cannot be opened is reported to the caller
+        // instead of failing the actor.
+        let result = log_client
+            .set_output(
+                &client,
+                LogOutputFormat::Json,
+                Some("/nonexistent/dir/logs.jsonl".to_string()),
+            )
+            .await
+            .unwrap();
+        assert!(result.unwrap_err().contains("/nonexistent/dir/logs.jsonl"));
+
+        // The actor is still serving messages.
+        let (reply, _reply_rx) = client.open_once_port::<()>();
+        let (version, version_rx) = client.open_once_port::<u64>();
+        log_client
+            .send(LogClientMessage::StartSyncFlush {
+                expected_procs: 0,
+                reply: reply.bind(),
+                version: version.bind(),
+            })
+            .unwrap();
+        assert_eq!(version_rx.recv().await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_aggregator_records() {
+        let mut aggregator = Aggregator::new();
+        aggregator.add_line("Test error message 1").unwrap();
+        aggregator.add_line("Test error message 2").unwrap();
+        aggregator.add_line("Something else entirely").unwrap();
+
+        let records = aggregator.records(OutputTarget::Stdout);
+        assert_eq!(records.len(), 2);
+        match &records[0] {
+            LogRecord::Aggregate {
+                stream,
+                count,
+                line,
+                ..
+            } => {
+                assert_eq!(stream, "stdout");
+                assert_eq!(*count, 2);
+                assert_eq!(line, "Test error message 1");
+            }
+            other => panic!("unexpected record: {:?}", other),
+        }
+        let json = serde_json::to_string(&records[1]).unwrap();
+        assert!(json.contains("\"type\":\"aggregate\""));
+        assert!(!json.contains("\x1b"));
+    }
+
     #[test]
     fn test_log_line_display_formatting() {
         let log_line = LogLine::new("Test message".to_string());
@@ -0,0 +1,325 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Size- and time-bounded log files used by [`super::FileAppender`].
+//!
+//! The active segment is always written at the configured path.
+//! When it is rotated, it is renamed to `<path>.1` (optionally
+//! compressed to `<path>.1.gz`), older segments shift up by one, and
+//! anything beyond the retention count is deleted.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use hyperactor::clock::Clock;
+use hyperactor::clock::RealClock;
+use tokio::io;
+use tokio::io::AsyncWriteExt;
+
+use super::LOG_FILE_COMPRESS;
+use super::LOG_FILE_MAX_SIZE;
+use super::LOG_FILE_RETENTION;
+use super::LOG_FILE_ROTATE_INTERVAL;
+
+/// When and how a [`RotatingFile`] rotates its segments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RotationPolicy {
+    /// Rotate before a write would grow the active segment past this
+    /// many bytes. Zero disables size-based rotation.
+    pub(crate) max_bytes: u64,
+    /// Rotate once the active segment has been open this long. The
+    /// age is only checked on writes, so an idle segment is rotated by
+    /// the next write rather than when it expires.
+    pub(crate) max_age: Option<Duration>,
+    /// Number of rotated segments to keep.
+    pub(crate) retention: usize,
+    /// Whether rotated segments are gzip-compressed.
+    pub(crate) compress: bool,
+}
+
+impl RotationPolicy {
+    /// The policy described by the global configuration.
+    pub(crate) fn from_config() -> Self {
+        let max_age = hyperactor_config::global::get(LOG_FILE_ROTATE_INTERVAL);
+        Self {
+            max_bytes: hyperactor_config::global::get(LOG_FILE_MAX_SIZE) as u64,
+            max_age: (!max_age.is_zero()).then_some(max_age),
+            retention: hyperactor_config::global::get(LOG_FILE_RETENTION),
+            compress: hyperactor_config::global::get(LOG_FILE_COMPRESS),
+        }
+    }
+}
+
+/// An append-only log file that rotates itself according to a
+/// [`RotationPolicy`].
+pub(crate) struct RotatingFile {
+    path: PathBuf,
+    file: tokio::fs::File,
+    written: u64,
+    opened_at: SystemTime,
+    policy: RotationPolicy,
+}
+
+impl RotatingFile {
+    /// Open (or create) the active segment at `path`.
+    pub(crate) fn open(path: PathBuf, policy: RotationPolicy) -> io::Result<Self> {
+        let file = open_append(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            file: tokio::fs::File::from_std(file),
+            path,
+            written,
+            opened_at: RealClock.system_time_now(),
+            policy,
+        })
+    }
+
+    /// The path of the active segment.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `line` followed by a newline, rotating first if the
+    /// policy requires it.
+    pub(crate) async fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.should_rotate(len) {
+            self.rotate().await?;
+        }
+        self.file.write_all(line).await?;
+        self.file.write_all(b"\n").await?;
+        self.written += len;
+        Ok(())
+    }
+
+    pub(crate) async fn flush(&mut self) -> io::Result<()> {
+        self.file.flush().await
+    }
+
+    fn should_rotate(&self, incoming: u64) -> bool {
+        // Never rotate an empty segment; a single oversized line
+        // still has to go somewhere.
+        if self.written == 0 {
+            return false;
+        }
+        let too_big = self.policy.max_bytes > 0 && self.written + incoming > self.policy.max_bytes;
+        let too_old = self.policy.max_age.is_some_and(|max_age| {
+            RealClock
+                .system_time_now()
+                .duration_since(self.opened_at)
+                .unwrap_or_default()
+                >= max_age
+        });
+        too_big || too_old
+    }
+
+    async fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush().await?;
+        let path = self.path.clone();
+        let policy = self.policy.clone();
+        tokio::task::spawn_blocking(move || shift_segments(&path, &policy))
+            .await
+            .map_err(io::Error::other)??;
+
+        self.file = tokio::fs::File::from_std(open_append(&self.path)?);
+        self.written = 0;
+        self.opened_at = RealClock.system_time_now();
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// The path of the `index`-th rotated segment of `path`.
+pub(crate) fn segment_path(path: &Path, index: usize, compressed: bool) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", index));
+    if compressed {
+        name.push(".gz");
+    }
+    PathBuf::from(name)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Move the active segment to `<path>.1`, shifting older segments and
+/// dropping those beyond the retention count.
+fn shift_segments(path: &Path, policy: &RotationPolicy) -> io::Result<()> {
+    if policy.retention == 0 {
+        return remove_if_exists(path);
+    }
+
+    for compressed in [false, true] {
+        remove_if_exists(&segment_path(path, policy.retention, compressed))?;
+    }
+    for index in (1..policy.retention).rev() {
+        for compressed in [false, true] {
+            let from = segment_path(path, index, compressed);
+            if from.exists() {
+                fs::rename(&from, segment_path(path, index + 1, compressed))?;
+            }
+        }
+    }
+
+    let first = segment_path(path, 1, false);
+    fs::rename(path, &first)?;
+    if policy.compress {
+        compress(&first)?;
+    }
+    Ok(())
+}
+
+/// Gzip `path` into `<path>.gz` and remove the original.
+fn compress(path: &Path) -> io::Result<()> {
+    let mut gz_path = OsString::from(path.as_os_str());
+    gz_path.push(".gz");
+
+    let mut input = fs::File::open(path)?;
+    let mut encoder = GzEncoder::new(fs::File::create(&gz_path)?, Compression::default());
+    std::io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(path)
+}
+
+/// Expand `{key}` placeholders in a file name template. Unknown
+/// placeholders are left as is.
+pub(crate) fn expand_template(template: &str, vars: &[(&str, &str)]) -> String {
+    vars.iter()
+        .fold(template.to_string(), |name, (key, value)| {
+            name.replace(&format!("{{{}}}", key), value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn policy(max_bytes: u64, retention: usize, compress: bool) -> RotationPolicy {
+        RotationPolicy {
+            max_bytes,
+            max_age: None,
+            retention,
+            compress,
+        }
+    }
+
+    #[test]
+    fn test_expand_template() {
+        let name = expand_template(
+            "{prefix}_{host}_{proc}.{stream}{unknown}",
+            &[
+                ("prefix", "monarch_log"),
+                ("host", "h0"),
+                ("proc", "123"),
+                ("stream", "stdout"),
+            ],
+        );
+        assert_eq!(name, "monarch_log_h0_123.stdout{unknown}");
+    }
+
+    #[tokio::test]
+    async fn test_rotate_by_size_with_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.stdout");
+        let mut file = RotatingFile::open(path.clone(), policy(10, 2, false)).unwrap();
+
+        for line in ["aaaaaaa", "bbbbbbb", "ccccccc", "ddddddd"] {
+            file.write_line(line.as_bytes()).await.unwrap();
+        }
+        file.flush().await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ddddddd\n");
+        assert_eq!(
+            fs::read_to_string(segment_path(&path, 1, false)).unwrap(),
+            "ccccccc\n"
+        );
+        assert_eq!(
+            fs::read_to_string(segment_path(&path, 2, false)).unwrap(),
+            "bbbbbbb\n"
+        );
+        // The oldest segment fell out of retention.
+        assert!(!segment_path(&path, 3, false).exists());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_with_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.stderr");
+        let mut file = RotatingFile::open(path.clone(), policy(8, 3, true)).unwrap();
+
+        file.write_line(b"first").await.unwrap();
+        file.write_line(b"second").await.unwrap();
+        file.flush().await.unwrap();
+
+        assert!(!segment_path(&path, 1, false).exists());
+        let mut decoded = String::new();
+        GzDecoder::new(fs::File::open(segment_path(&path, 1, true)).unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "first\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+    }
+
+    #[tokio::test]
+    async fn test_rotate_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.stdout");
+        let mut file = RotatingFile::open(
+            path.clone(),
+            RotationPolicy {
+                max_bytes: 0,
+                max_age: Some(Duration::from_millis(10)),
+                retention: 1,
+                compress: false,
+            },
+        )
+        .unwrap();
+
+        file.write_line(b"old").await.unwrap();
+        RealClock.sleep(Duration::from_millis(20)).await;
+        file.write_line(b"new").await.unwrap();
+        file.flush().await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(
+            fs::read_to_string(segment_path(&path, 1, false)).unwrap(),
+            "old\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_zero_retention_discards_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.stdout");
+        let mut file = RotatingFile::open(path.clone(), policy(4, 0, false)).unwrap();
+
+        file.write_line(b"abc").await.unwrap();
+        file.write_line(b"def").await.unwrap();
+        file.flush().await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "def\n");
+        assert!(!segment_path(&path, 1, false).exists());
+    }
+}
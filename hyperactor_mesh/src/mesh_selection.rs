@@ -57,11 +57,12 @@ fn verify_dimension_sizes(origin: &Shape, target: &Shape) -> Result<(), anyhow::
 /// Given a set of coordinates, create a set of single-value ranges for each dimension..
 fn exact_mapping<'a>(
     target_selection: &Selection,
+    target_extent: &[usize],
     coords: &'a [(String, usize)],
     origin_rank: &usize,
 ) -> Result<Vec<(&'a str, Range)>, anyhow::Error> {
     let coord_dim = coords.iter().map(|(_, d)| *d).collect::<Vec<_>>();
-    if target_selection.contains(&coord_dim) {
+    if target_selection.contains(&coord_dim, target_extent) {
         Ok(coords
             .iter()
             .map(|(label, index)| (label.as_str(), Range::from(*index)))
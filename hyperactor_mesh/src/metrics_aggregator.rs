@@ -0,0 +1,592 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Job-wide metrics aggregation.
+//!
+//! A [`MetricsAgent`] (named [`METRICS_AGENT_NAME`]) runs on every proc
+//! of a mesh. Once the root agent (on rank 0) is asked to
+//! [`ServeMetrics`], it opens an accumulating port and casts
+//! [`StartReporting`] to all agents. Each agent then periodically
+//! sends a [`hyperactor_telemetry::metrics_snapshot`] of its process to
+//! that port. Reports are reduced as they travel up the comm actor
+//! tree, so the root receives a bounded number of messages regardless
+//! of the size of the mesh.
+//!
+//! The root keeps the latest snapshot of every rank and serves
+//! [`JobMetrics`]: the job-wide totals, and the totals along each
+//! dimension of the mesh (e.g. per host). Snapshots are process-wide,
+//! so ranks whose procs share a process are counted once:
+//!
+//! ```text
+//! hyper metrics <root-proc-id>
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use hyperactor::Actor;
+use hyperactor::ActorRef;
+use hyperactor::Bind;
+use hyperactor::Context;
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::Named;
+use hyperactor::OncePortRef;
+use hyperactor::PortRef;
+use hyperactor::ProcId;
+use hyperactor::RefClient;
+use hyperactor::Unbind;
+use hyperactor::accum::Accumulator;
+use hyperactor::accum::CommReducer;
+use hyperactor::accum::ReducerFactory;
+use hyperactor::accum::ReducerOpts;
+use hyperactor::accum::ReducerSpec;
+use hyperactor::context;
+use hyperactor::context::Mailbox as _;
+use hyperactor::mailbox::PortReceiver;
+use hyperactor_telemetry::metrics_snapshot;
+use hyperactor_telemetry::metrics_snapshot::MetricsSnapshot;
+use ndslice::Extent;
+use ndslice::view::Ranked;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::comm::multicast::CastInfo;
+use crate::config::METRICS_AGGREGATION_INTERVAL;
+use crate::supervision::SupervisionFailureMessage;
+use crate::v1::ActorMesh;
+use crate::v1::ActorMeshRef;
+use crate::v1::ProcMeshRef;
+
+/// The name under which the metrics agent is spawned on every proc.
+pub const METRICS_AGENT_NAME: &str = "metrics_agent";
+
+/// The metrics snapshot reported by a single rank.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RankSnapshot {
+    /// Incremented with every report, so that a newer snapshot of a
+    /// rank always replaces an older one, whatever order they are
+    /// reduced in.
+    pub seq: u64,
+    /// The process of the rank, as `host:pid`. Ranks whose procs share
+    /// a process report the same snapshot.
+    pub process: String,
+    /// The snapshot itself.
+    pub snapshot: MetricsSnapshot,
+}
+
+/// The latest snapshots of a set of ranks. This is both the update
+/// sent by the agents and the state accumulated by the root.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Named)]
+pub struct RankSnapshots(pub BTreeMap<usize, RankSnapshot>);
+
+impl RankSnapshots {
+    /// Merge `other` into these snapshots, keeping the latest snapshot
+    /// of each rank.
+    pub fn merge(&mut self, other: RankSnapshots) {
+        for (rank, snapshot) in other.0 {
+            match self.0.get(&rank) {
+                Some(existing) if existing.seq >= snapshot.seq => {}
+                _ => {
+                    self.0.insert(rank, snapshot);
+                }
+            }
+        }
+    }
+}
+
+/// Reduces [`RankSnapshots`] along the comm actor tree.
+#[derive(Named)]
+struct RankSnapshotsReducer;
+
+impl CommReducer for RankSnapshotsReducer {
+    type Update = RankSnapshots;
+
+    fn reduce(&self, mut left: Self::Update, right: Self::Update) -> Result<Self::Update> {
+        left.merge(right);
+        Ok(left)
+    }
+}
+
+hyperactor::submit! {
+    ReducerFactory {
+        typehash_f: <RankSnapshotsReducer as Named>::typehash,
+        builder_f: |_| Ok(Box::new(RankSnapshotsReducer)),
+    }
+}
+
+/// Accumulates [`RankSnapshots`] at the root.
+struct RankSnapshotsAccumulator;
+
+impl Accumulator for RankSnapshotsAccumulator {
+    type State = RankSnapshots;
+    type Update = RankSnapshots;
+
+    fn accumulate(&self, state: &mut Self::State, update: Self::Update) -> Result<()> {
+        state.merge(update);
+        Ok(())
+    }
+
+    fn reducer_spec(&self) -> Option<ReducerSpec> {
+        Some(ReducerSpec {
+            typehash: <RankSnapshotsReducer as Named>::typehash(),
+            builder_params: None,
+        })
+    }
+}
+
+/// Job-wide metrics, as served by the root agent.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Named)]
+pub struct JobMetrics {
+    /// The number of ranks in the mesh.
+    pub num_ranks: usize,
+    /// The number of ranks that have reported at least once.
+    pub reporting_ranks: usize,
+    /// The number of distinct processes of the reporting ranks.
+    pub reporting_processes: usize,
+    /// The metrics of all reporting processes combined.
+    pub total: MetricsSnapshot,
+    /// For each dimension of the mesh, the combined metrics of the
+    /// ranks at each of its coordinates. A process is counted at the
+    /// coordinates of its lowest rank.
+    pub by_dimension: BTreeMap<String, BTreeMap<usize, MetricsSnapshot>>,
+}
+
+impl JobMetrics {
+    /// Aggregate the per-rank `snapshots` of a mesh with the given
+    /// extent. Ranks outside of the extent are ignored.
+    pub fn aggregate(extent: &Extent, snapshots: &RankSnapshots) -> Self {
+        let mut metrics = JobMetrics {
+            num_ranks: extent.num_ranks(),
+            ..Default::default()
+        };
+        let mut processes = HashSet::new();
+        for (
+            rank,
+            RankSnapshot {
+                process, snapshot, ..
+            },
+        ) in &snapshots.0
+        {
+            let Ok(point) = extent.point_of_rank(*rank) else {
+                continue;
+            };
+            metrics.reporting_ranks += 1;
+            // Ranks are visited in order, so a process is counted at its
+            // lowest rank.
+            if !processes.insert(process) {
+                continue;
+            }
+            metrics.reporting_processes += 1;
+            metrics.total.merge(snapshot);
+            for (label, coord) in extent.labels().iter().zip(point.coords()) {
+                metrics
+                    .by_dimension
+                    .entry(label.clone())
+                    .or_default()
+                    .entry(coord)
+                    .or_default()
+                    .merge(snapshot);
+            }
+        }
+        metrics
+    }
+}
+
+/// Start reporting metrics to `report` every `interval`. This is cast
+/// by the root agent to every agent in the mesh.
+#[derive(Debug, Clone, Named, Serialize, Deserialize, Bind, Unbind)]
+pub struct StartReporting {
+    /// The interval between reports.
+    pub interval: Duration,
+    /// The root's accumulating port.
+    #[binding(include)]
+    pub report: PortRef<RankSnapshots>,
+}
+
+/// Make this agent the root of the aggregation over `agents`,
+/// which must include it.
+#[derive(Debug, Clone, Named, Serialize, Deserialize)]
+pub struct ServeMetrics {
+    /// The agents to aggregate metrics from.
+    pub agents: ActorMeshRef<MetricsAgent>,
+    /// The interval at which agents report their metrics.
+    pub interval: Duration,
+}
+
+/// Queries served by the root agent.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    Handler,
+    HandleClient,
+    RefClient,
+    Named
+)]
+pub enum MetricsQuery {
+    /// Get the job-wide metrics, or an error if this agent is not
+    /// serving metrics.
+    GetJobMetrics {
+        #[reply]
+        metrics: OncePortRef<Result<JobMetrics, String>>,
+    },
+}
+
+/// Local message scheduling an agent's next report.
+#[derive(Debug)]
+struct ReportTick;
+
+#[derive(Debug)]
+struct Reporting {
+    rank: usize,
+    interval: Duration,
+    report: PortRef<RankSnapshots>,
+    process: String,
+    seq: u64,
+}
+
+#[derive(Debug)]
+struct Root {
+    extent: Extent,
+    receiver: PortReceiver<RankSnapshots>,
+    snapshots: RankSnapshots,
+}
+
+/// Reports the metrics of its proc and, on the root, serves job-wide
+/// aggregates. See the module documentation.
+#[derive(Debug, Default)]
+#[hyperactor::export(
+    spawn = true,
+    handlers = [
+        StartReporting { cast = true },
+        ServeMetrics,
+        MetricsQuery,
+    ]
+)]
+pub struct MetricsAgent {
+    reporting: Option<Reporting>,
+    root: Option<Root>,
+}
+
+impl Actor for MetricsAgent {}
+
+#[async_trait]
+impl Handler<StartReporting> for MetricsAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        StartReporting { interval, report }: StartReporting,
+    ) -> Result<()> {
+        // Only schedule a tick if one isn't already pending; a repeated
+        // cast just updates where and how often we report. The sequence
+        // number carries over so that new reports still supersede old ones.
+        let previous = self.reporting.take();
+        self.reporting = Some(Reporting {
+            rank: cx.cast_point().rank(),
+            interval,
+            report,
+            process: format!(
+                "{}:{}",
+                hostname::get()
+                    .unwrap_or_else(|_| "unknown_host".into())
+                    .to_string_lossy(),
+                std::process::id()
+            ),
+            seq: previous.as_ref().map_or(0, |reporting| reporting.seq),
+        });
+        if previous.is_none() {
+            cx.self_message_with_delay(ReportTick, Duration::ZERO)?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<ReportTick> for MetricsAgent {
+    async fn handle(&mut self, cx: &Context<Self>, _: ReportTick) -> Result<()> {
+        let Some(reporting) = self.reporting.as_mut() else {
+            return Ok(());
+        };
+        reporting.seq += 1;
+        let update = RankSnapshots(BTreeMap::from([(
+            reporting.rank,
+            RankSnapshot {
+                seq: reporting.seq,
+                process: reporting.process.clone(),
+                snapshot: metrics_snapshot::snapshot(),
+            },
+        )]));
+        // A lost report is superseded by the next one.
+        if let Err(err) = reporting.report.send(cx, update) {
+            tracing::warn!(
+                actor = %cx.self_id(),
+                "failed to report metrics: {}",
+                err
+            );
+        }
+        cx.self_message_with_delay(ReportTick, reporting.interval)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<ServeMetrics> for MetricsAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        ServeMetrics { agents, interval }: ServeMetrics,
+    ) -> Result<()> {
+        let (port, receiver) = cx.mailbox().open_accum_port_opts(
+            RankSnapshotsAccumulator,
+            Some(ReducerOpts {
+                max_update_interval: Some(interval),
+                initial_update_interval: None,
+            }),
+        );
+        agents.cast(
+            cx,
+            StartReporting {
+                interval,
+                report: port.bind(),
+            },
+        )?;
+        self.root = Some(Root {
+            extent: agents.region().extent(),
+            receiver,
+            snapshots: RankSnapshots::default(),
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+#[hyperactor::forward(MetricsQuery)]
+impl MetricsQueryHandler for MetricsAgent {
+    async fn get_job_metrics(
+        &mut self,
+        _cx: &Context<Self>,
+    ) -> Result<Result<JobMetrics, String>, anyhow::Error> {
+        let Some(root) = self.root.as_mut() else {
+            return Ok(Err("this agent is not serving metrics".to_string()));
+        };
+        // The receiver coalesces, so this yields the latest accumulated
+        // state, if it changed since the last query.
+        if let Some(snapshots) = root.receiver.try_recv()? {
+            root.snapshots = snapshots;
+        }
+        Ok(Ok(JobMetrics::aggregate(&root.extent, &root.snapshots)))
+    }
+}
+
+/// A running metrics aggregation service.
+pub struct MetricsService {
+    agents: ActorMesh<MetricsAgent>,
+    root: ActorRef<MetricsAgent>,
+}
+
+impl MetricsService {
+    /// Spawn a [`MetricsAgent`] on every proc of `proc_mesh`, and start
+    /// aggregating their metrics at rank 0, with agents reporting every
+    /// [`METRICS_AGGREGATION_INTERVAL`].
+    ///
+    /// There is no host mesh counterpart: metrics are kept per process,
+    /// and the actors of a host mesh run in the procs of the proc meshes
+    /// spawned on it, each in its own process. Spawn the service on such
+    /// a proc mesh instead; its extent includes the host dimensions, so
+    /// [`JobMetrics::by_dimension`] breaks the metrics down per host.
+    pub async fn spawn<C: context::Actor>(cx: &C, proc_mesh: &ProcMeshRef) -> Result<Self>
+    where
+        C::A: Handler<SupervisionFailureMessage>,
+    {
+        let agents: ActorMesh<MetricsAgent> =
+            proc_mesh.spawn_service(cx, METRICS_AGENT_NAME, &()).await?;
+        let root = agents
+            .get(0)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("cannot serve metrics for an empty mesh"))?;
+        root.send(
+            cx,
+            ServeMetrics {
+                agents: (*agents).clone(),
+                interval: hyperactor_config::global::get(METRICS_AGGREGATION_INTERVAL),
+            },
+        )?;
+        Ok(Self { agents, root })
+    }
+
+    /// The agents of this service.
+    pub fn agents(&self) -> &ActorMesh<MetricsAgent> {
+        &self.agents
+    }
+
+    /// The root agent, which serves [`MetricsQuery`].
+    pub fn root(&self) -> &ActorRef<MetricsAgent> {
+        &self.root
+    }
+
+    /// The proc of the root agent; this is what `hyper metrics` takes.
+    pub fn root_proc(&self) -> &ProcId {
+        self.root.actor_id().proc_id()
+    }
+
+    /// Get the current job-wide metrics.
+    pub async fn job_metrics(&self, cx: &impl context::Actor) -> Result<JobMetrics> {
+        self.root
+            .get_job_metrics(cx)
+            .await?
+            .map_err(anyhow::Error::msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor_telemetry::metrics_snapshot::HistogramSnapshot;
+    use ndslice::extent;
+
+    use super::*;
+    use crate::v1::testing;
+
+    fn snapshot(counter: f64) -> MetricsSnapshot {
+        MetricsSnapshot {
+            counters: BTreeMap::from([("messages".to_string(), counter)]),
+            histograms: BTreeMap::from([(
+                "latency".to_string(),
+                HistogramSnapshot {
+                    count: 1,
+                    sum: counter,
+                    min: Some(counter),
+                    max: Some(counter),
+                    bounds: Vec::new(),
+                    bucket_counts: Vec::new(),
+                },
+            )]),
+        }
+    }
+
+    fn reported(rank: usize, seq: u64, counter: f64) -> RankSnapshots {
+        reported_by(rank, &format!("host:{}", rank), seq, counter)
+    }
+
+    fn reported_by(rank: usize, process: &str, seq: u64, counter: f64) -> RankSnapshots {
+        RankSnapshots(BTreeMap::from([(
+            rank,
+            RankSnapshot {
+                seq,
+                process: process.to_string(),
+                snapshot: snapshot(counter),
+            },
+        )]))
+    }
+
+    #[test]
+    fn test_merge_keeps_latest() {
+        let mut snapshots = reported(0, 2, 2.0);
+        snapshots.merge(reported(0, 1, 1.0));
+        snapshots.merge(reported(1, 1, 10.0));
+        assert_eq!(snapshots.0[&0].seq, 2);
+        assert_eq!(snapshots.0[&0].snapshot, snapshot(2.0));
+        assert_eq!(snapshots.0[&1].seq, 1);
+
+        snapshots.merge(reported(0, 3, 3.0));
+        assert_eq!(snapshots.0[&0].snapshot, snapshot(3.0));
+
+        // The reducer is order independent.
+        let left = RankSnapshotsReducer
+            .reduce(reported(0, 1, 1.0), reported(0, 2, 2.0))
+            .unwrap();
+        let right = RankSnapshotsReducer
+            .reduce(reported(0, 2, 2.0), reported(0, 1, 1.0))
+            .unwrap();
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let extent = extent!(hosts = 2, gpus = 2);
+        let mut snapshots = RankSnapshots::default();
+        for rank in 0..3 {
+            snapshots.merge(reported(rank, 1, (rank + 1) as f64));
+        }
+        // Out of the extent.
+        snapshots.merge(reported(7, 1, 100.0));
+
+        let metrics = JobMetrics::aggregate(&extent, &snapshots);
+        assert_eq!(metrics.num_ranks, 4);
+        assert_eq!(metrics.reporting_ranks, 3);
+        assert_eq!(metrics.total.counters["messages"], 6.0);
+        assert_eq!(metrics.total.histograms["latency"].count, 3);
+
+        let hosts = &metrics.by_dimension["hosts"];
+        assert_eq!(hosts[&0].counters["messages"], 3.0);
+        assert_eq!(hosts[&1].counters["messages"], 3.0);
+        let gpus = &metrics.by_dimension["gpus"];
+        assert_eq!(gpus[&0].counters["messages"], 4.0);
+        assert_eq!(gpus[&1].counters["messages"], 2.0);
+        assert_eq!(gpus[&1].histograms["latency"].max, Some(2.0));
+    }
+
+    #[test]
+    fn test_aggregate_shared_process() {
+        let extent = extent!(hosts = 2, gpus = 2);
+        let mut snapshots = RankSnapshots::default();
+        // The procs of each host share a process, and report the same
+        // snapshot.
+        for rank in 0..4 {
+            let host = rank / 2;
+            snapshots.merge(reported_by(
+                rank,
+                &format!("host{}:1", host),
+                1,
+                (host + 1) as f64,
+            ));
+        }
+
+        let metrics = JobMetrics::aggregate(&extent, &snapshots);
+        assert_eq!(metrics.reporting_ranks, 4);
+        assert_eq!(metrics.reporting_processes, 2);
+        assert_eq!(metrics.total.counters["messages"], 3.0);
+        let hosts = &metrics.by_dimension["hosts"];
+        assert_eq!(hosts[&0].counters["messages"], 1.0);
+        assert_eq!(hosts[&1].counters["messages"], 2.0);
+        // Each process is counted at its lowest rank.
+        let gpus = &metrics.by_dimension["gpus"];
+        assert_eq!(gpus[&0].counters["messages"], 3.0);
+        assert!(!gpus.contains_key(&1));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_service() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(METRICS_AGGREGATION_INTERVAL, Duration::from_millis(50));
+
+        let (proc_mesh, instance, _router) = testing::local_proc_mesh(extent!(replica = 3)).await;
+        let service = MetricsService::spawn(instance, &proc_mesh).await.unwrap();
+
+        let metrics = loop {
+            let metrics = service.job_metrics(instance).await.unwrap();
+            if metrics.reporting_ranks == 3 {
+                break metrics;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        assert_eq!(metrics.num_ranks, 3);
+        // The local procs share this process, which is counted once.
+        assert_eq!(metrics.reporting_processes, 1);
+        assert_eq!(metrics.by_dimension["replica"].len(), 1);
+
+        // Only the root serves metrics.
+        let other = service.agents().get(1).cloned().unwrap();
+        assert!(other.get_job_metrics(instance).await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,535 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! TCP and Unix socket port forwarding through the mesh.
+//!
+//! When [`PORT_FORWARD_ENABLED`] is set, every proc runs a
+//! [`PortForwardActor`] (named [`PORT_FORWARD_ACTOR_NAME`]). When it
+//! receives a [`ForwardConnect`], it dials the requested address on
+//! its own host and splices the resulting socket onto an
+//! [`ActorConnection`](crate::connect::ActorConnection). Only the
+//! addresses listed in [`PORT_FORWARD_ALLOWED_TARGETS`] are dialed.
+//!
+//! On the client side, a [`PortForwardListener`] accepts local
+//! connections and tunnels each of them through its own
+//! `ActorConnection` to a remote forwarder. This gives access to
+//! services (TensorBoard, debuggers, Jupyter, ...) running next to a
+//! worker without setting up separate SSH tunnels:
+//!
+//! ```text
+//! hyper port-forward <proc-id> 6006:6006
+//! hyper port-forward <proc-id> 5678:unix:/tmp/debug.sock
+//! ```
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::Context as _;
+use anyhow::Result;
+use async_trait::async_trait;
+use hyperactor::Actor;
+use hyperactor::ActorRef;
+use hyperactor::Bind;
+use hyperactor::Context;
+use hyperactor::Handler;
+use hyperactor::Instance;
+use hyperactor::Named;
+use hyperactor::Unbind;
+use hyperactor::context;
+use hyperactor::proc::Proc;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::net::UnixListener;
+use tokio::net::UnixStream;
+
+use crate::config::PORT_FORWARD_ALLOWED_TARGETS;
+use crate::config::PORT_FORWARD_ENABLED;
+use crate::connect::Connect;
+use crate::connect::accept;
+
+/// The name under which the port forwarding actor is spawned on
+/// every proc.
+pub const PORT_FORWARD_ACTOR_NAME: &str = "port_forward";
+
+/// Spawn the [`PortForwardActor`] on `proc`, if
+/// [`PORT_FORWARD_ENABLED`] is set.
+pub(crate) fn spawn_if_enabled(proc: &Proc) -> Result<()> {
+    if hyperactor_config::global::get(PORT_FORWARD_ENABLED) {
+        proc.spawn(PORT_FORWARD_ACTOR_NAME, PortForwardActor)?;
+    }
+    Ok(())
+}
+
+/// Whether `target` is listed in [`PORT_FORWARD_ALLOWED_TARGETS`].
+fn is_allowed(target: &ForwardAddr) -> bool {
+    hyperactor_config::global::get(PORT_FORWARD_ALLOWED_TARGETS)
+        .split(',')
+        .map(str::trim)
+        .filter(|allowed| !allowed.is_empty())
+        .filter_map(|allowed| allowed.parse::<ForwardAddr>().ok())
+        .any(|allowed| &allowed == target)
+}
+
+/// An address to listen on, or to dial.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForwardAddr {
+    /// A TCP address in `host:port` form.
+    Tcp(String),
+    /// A Unix domain socket path.
+    Unix(PathBuf),
+}
+
+impl fmt::Display for ForwardAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForwardAddr::Tcp(addr) => write!(f, "{}", addr),
+            ForwardAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl FromStr for ForwardAddr {
+    type Err = anyhow::Error;
+
+    /// Parse `unix:PATH`, `HOST:PORT` or a bare `PORT`, which refers
+    /// to localhost.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            anyhow::ensure!(!path.is_empty(), "empty unix socket path");
+            return Ok(ForwardAddr::Unix(PathBuf::from(path)));
+        }
+        if let Ok(port) = s.parse::<u16>() {
+            return Ok(ForwardAddr::Tcp(format!("localhost:{}", port)));
+        }
+        match s.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() => {
+                port.parse::<u16>()
+                    .with_context(|| format!("invalid port in address {}", s))?;
+                Ok(ForwardAddr::Tcp(s.to_string()))
+            }
+            _ => anyhow::bail!(
+                "invalid address {}: expected PORT, HOST:PORT or unix:PATH",
+                s
+            ),
+        }
+    }
+}
+
+/// A `<local>:<remote>` forwarding specification, as accepted by
+/// `hyper port-forward`.
+///
+/// The local side is a port (bound on localhost) or `unix:PATH`; the
+/// remote side is anything accepted by [`ForwardAddr`]. For example
+/// `8888:8888`, `6006:gpu-host:6006` or `5678:unix:/tmp/debug.sock`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortForwardSpec {
+    /// The address to listen on locally.
+    pub local: ForwardAddr,
+    /// The address dialed by the remote forwarder.
+    pub remote: ForwardAddr,
+}
+
+impl FromStr for PortForwardSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (local, remote) = match s.strip_prefix("unix:") {
+            Some(rest) => {
+                let (path, remote) = rest
+                    .split_once(':')
+                    .with_context(|| format!("missing remote address in {}", s))?;
+                (format!("unix:{}", path), remote)
+            }
+            None => {
+                let (port, remote) = s
+                    .split_once(':')
+                    .with_context(|| format!("missing remote address in {}", s))?;
+                let port: u16 = port
+                    .parse()
+                    .with_context(|| format!("invalid local port in {}", s))?;
+                (format!("127.0.0.1:{}", port), remote)
+            }
+        };
+        Ok(Self {
+            local: local.parse()?,
+            remote: remote.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for PortForwardSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.local, self.remote)
+    }
+}
+
+/// Ask a [`PortForwardActor`] to dial `target` and connect it to the
+/// client through `connect`.
+#[derive(Debug, Clone, Named, Serialize, Deserialize, Bind, Unbind)]
+pub struct ForwardConnect {
+    /// The address to dial on the forwarder's host.
+    pub target: ForwardAddr,
+    /// The connection to splice the dialed socket onto.
+    #[binding(include)]
+    pub connect: Connect,
+}
+
+/// Dials local addresses on behalf of remote clients. See the
+/// [module documentation](self).
+#[derive(Debug, Default)]
+#[hyperactor::export(handlers = [ForwardConnect])]
+pub struct PortForwardActor;
+
+impl Actor for PortForwardActor {}
+
+#[async_trait]
+impl Handler<ForwardConnect> for PortForwardActor {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        ForwardConnect { target, connect }: ForwardConnect,
+    ) -> Result<(), anyhow::Error> {
+        // Each connection gets its own child instance, so that it can
+        // outlive this handler without holding up the actor.
+        let (instance, _handle) = cx.child()?;
+        tokio::spawn(async move {
+            if let Err(err) = serve_connection(instance, target.clone(), connect).await {
+                tracing::warn!("port forward to {} failed: {:#}", target, err);
+            }
+        });
+        Ok(())
+    }
+}
+
+async fn serve_connection(
+    instance: Instance<()>,
+    target: ForwardAddr,
+    connect: Connect,
+) -> Result<()> {
+    let self_id = instance.self_id().clone();
+    // Dial first so that a bad target doesn't leave the client with
+    // a half-open connection: on failure, we accept and immediately
+    // close it.
+    let dialed = if is_allowed(&target) {
+        dial(&target).await
+    } else {
+        Err(anyhow::anyhow!("{} is not an allowed target", target))
+    };
+    match dialed {
+        Ok(Dialed::Tcp(mut socket)) => {
+            let mut conn = accept(instance, self_id, connect).await?;
+            tokio::io::copy_bidirectional(&mut socket, &mut conn).await?;
+        }
+        Ok(Dialed::Unix(mut socket)) => {
+            let mut conn = accept(instance, self_id, connect).await?;
+            tokio::io::copy_bidirectional(&mut socket, &mut conn).await?;
+        }
+        Err(err) => {
+            let mut conn = accept(instance, self_id, connect).await?;
+            conn.shutdown().await?;
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+enum Dialed {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+async fn dial(target: &ForwardAddr) -> Result<Dialed> {
+    Ok(match target {
+        ForwardAddr::Tcp(addr) => Dialed::Tcp(
+            TcpStream::connect(addr)
+                .await
+                .with_context(|| format!("connecting to {}", addr))?,
+        ),
+        ForwardAddr::Unix(path) => Dialed::Unix(
+            UnixStream::connect(path)
+                .await
+                .with_context(|| format!("connecting to {}", path.display()))?,
+        ),
+    })
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// The client side of a port forward: accepts local connections and
+/// tunnels each of them to a remote [`PortForwardActor`].
+pub struct PortForwardListener {
+    listener: Listener,
+}
+
+impl PortForwardListener {
+    /// Listen on `local`.
+    pub async fn bind(local: &ForwardAddr) -> Result<Self> {
+        let listener = match local {
+            ForwardAddr::Tcp(addr) => Listener::Tcp(
+                TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("binding {}", addr))?,
+            ),
+            ForwardAddr::Unix(path) => Listener::Unix(
+                UnixListener::bind(path).with_context(|| format!("binding {}", path.display()))?,
+            ),
+        };
+        Ok(Self { listener })
+    }
+
+    /// The bound TCP address, if listening on TCP.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.listener {
+            Listener::Tcp(listener) => listener.local_addr().ok(),
+            Listener::Unix(_) => None,
+        }
+    }
+
+    /// Accept connections forever, forwarding each of them through
+    /// `forwarder` to `remote`. A connection that cannot be tunneled
+    /// is dropped; the listener keeps accepting.
+    pub async fn serve(
+        self,
+        cx: &impl context::Actor,
+        forwarder: ActorRef<PortForwardActor>,
+        remote: ForwardAddr,
+    ) -> Result<()> {
+        loop {
+            let tunneled = match &self.listener {
+                Listener::Tcp(listener) => {
+                    let (socket, _) = listener.accept().await?;
+                    tunnel(cx, &forwarder, &remote, socket)
+                }
+                Listener::Unix(listener) => {
+                    let (socket, _) = listener.accept().await?;
+                    tunnel(cx, &forwarder, &remote, socket)
+                }
+            };
+            if let Err(err) = tunneled {
+                tracing::warn!("failed to tunnel a connection to {}: {:#}", remote, err);
+            }
+        }
+    }
+}
+
+/// Tunnel `socket` to `remote` through `forwarder`, in the background.
+fn tunnel<S>(
+    cx: &impl context::Actor,
+    forwarder: &ActorRef<PortForwardActor>,
+    remote: &ForwardAddr,
+    mut socket: S,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (instance, _handle) = cx.instance().child()?;
+    let (connect, completer) = Connect::allocate(instance.self_id().clone(), instance);
+    forwarder.send(
+        cx,
+        ForwardConnect {
+            target: remote.clone(),
+            connect,
+        },
+    )?;
+    let remote = remote.clone();
+    tokio::spawn(async move {
+        let res = async {
+            let mut conn = completer.complete().await?;
+            tokio::io::copy_bidirectional(&mut socket, &mut conn).await?;
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(err) = res {
+            tracing::warn!("port forward to {} failed: {:#}", remote, err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_addr() {
+        assert_eq!(
+            "6006".parse::<ForwardAddr>().unwrap(),
+            ForwardAddr::Tcp("localhost:6006".to_string())
+        );
+        assert_eq!(
+            "gpu-host:6006".parse::<ForwardAddr>().unwrap(),
+            ForwardAddr::Tcp("gpu-host:6006".to_string())
+        );
+        assert_eq!(
+            "[::1]:6006".parse::<ForwardAddr>().unwrap(),
+            ForwardAddr::Tcp("[::1]:6006".to_string())
+        );
+        assert_eq!(
+            "unix:/tmp/debug.sock".parse::<ForwardAddr>().unwrap(),
+            ForwardAddr::Unix(PathBuf::from("/tmp/debug.sock"))
+        );
+        assert!("gpu-host".parse::<ForwardAddr>().is_err());
+        assert!("gpu-host:http".parse::<ForwardAddr>().is_err());
+        assert!("unix:".parse::<ForwardAddr>().is_err());
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        assert_eq!(
+            "8888:6006".parse::<PortForwardSpec>().unwrap(),
+            PortForwardSpec {
+                local: ForwardAddr::Tcp("127.0.0.1:8888".to_string()),
+                remote: ForwardAddr::Tcp("localhost:6006".to_string()),
+            }
+        );
+        assert_eq!(
+            "8888:gpu-host:6006".parse::<PortForwardSpec>().unwrap(),
+            PortForwardSpec {
+                local: ForwardAddr::Tcp("127.0.0.1:8888".to_string()),
+                remote: ForwardAddr::Tcp("gpu-host:6006".to_string()),
+            }
+        );
+        assert_eq!(
+            "unix:/tmp/a.sock:unix:/tmp/b.sock"
+                .parse::<PortForwardSpec>()
+                .unwrap(),
+            PortForwardSpec {
+                local: ForwardAddr::Unix(PathBuf::from("/tmp/a.sock")),
+                remote: ForwardAddr::Unix(PathBuf::from("/tmp/b.sock")),
+            }
+        );
+        assert!("8888".parse::<PortForwardSpec>().is_err());
+        assert!("http:6006".parse::<PortForwardSpec>().is_err());
+    }
+
+    /// Accept a single connection on `listener` and echo it back.
+    async fn echo_once(listener: Listener) -> Result<()> {
+        match listener {
+            Listener::Tcp(listener) => {
+                let (socket, _) = listener.accept().await?;
+                let (mut rd, mut wr) = socket.into_split();
+                tokio::io::copy(&mut rd, &mut wr).await?;
+                wr.shutdown().await?;
+            }
+            Listener::Unix(listener) => {
+                let (socket, _) = listener.accept().await?;
+                let (mut rd, mut wr) = socket.into_split();
+                tokio::io::copy(&mut rd, &mut wr).await?;
+                wr.shutdown().await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn roundtrip(socket: &mut TcpStream, data: &[u8]) -> Result<Vec<u8>> {
+        socket.write_all(data).await?;
+        socket.shutdown().await?;
+        let mut recv = vec![];
+        socket.read_to_end(&mut recv).await?;
+        Ok(recv)
+    }
+
+    /// Spawn a forwarder on a local proc, and serve a local TCP
+    /// listener forwarding to `remote` through it. Returns the proc,
+    /// which must be kept alive, and the listener's address.
+    async fn forward_to(remote: ForwardAddr) -> Result<(Proc, SocketAddr)> {
+        let proc = Proc::local();
+        let (client, _client_handle) = proc.instance("client")?;
+        let forwarder = proc.spawn(PORT_FORWARD_ACTOR_NAME, PortForwardActor)?;
+
+        let listener =
+            PortForwardListener::bind(&ForwardAddr::Tcp("127.0.0.1:0".to_string())).await?;
+        let local = listener.local_addr().unwrap();
+        let forwarder = forwarder.bind::<PortForwardActor>();
+        tokio::spawn(async move { listener.serve(&client, forwarder, remote).await });
+        Ok((proc, local))
+    }
+
+    #[tokio::test]
+    async fn test_forward_tcp() -> Result<()> {
+        let echo = TcpListener::bind("127.0.0.1:0").await?;
+        let remote = ForwardAddr::Tcp(echo.local_addr()?.to_string());
+        let echo = tokio::spawn(echo_once(Listener::Tcp(echo)));
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(PORT_FORWARD_ALLOWED_TARGETS, remote.to_string());
+        let (_proc, local) = forward_to(remote).await?;
+
+        let mut socket = TcpStream::connect(local).await?;
+        assert_eq!(roundtrip(&mut socket, b"hello").await?, b"hello");
+        echo.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forward_unix() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("echo.sock");
+        let echo = tokio::spawn(echo_once(Listener::Unix(UnixListener::bind(&path)?)));
+        let remote = ForwardAddr::Unix(path);
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(PORT_FORWARD_ALLOWED_TARGETS, remote.to_string());
+        let (_proc, local) = forward_to(remote).await?;
+
+        let mut socket = TcpStream::connect(local).await?;
+        assert_eq!(roundtrip(&mut socket, b"world").await?, b"world");
+        echo.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forward_unreachable() -> Result<()> {
+        // Find a port that nothing is listening on.
+        let unused = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+        let remote = ForwardAddr::Tcp(unused.to_string());
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(PORT_FORWARD_ALLOWED_TARGETS, remote.to_string());
+        let (_proc, local) = forward_to(remote).await?;
+
+        // The forwarder closes the tunnel when it can't dial the
+        // target; the listener keeps serving.
+        for _ in 0..2 {
+            let mut socket = TcpStream::connect(local).await?;
+            let mut recv = vec![];
+            socket.read_to_end(&mut recv).await?;
+            assert!(recv.is_empty());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forward_not_allowed() -> Result<()> {
+        let echo = TcpListener::bind("127.0.0.1:0").await?;
+        let remote = ForwardAddr::Tcp(echo.local_addr()?.to_string());
+
+        // Nothing is allowed by default.
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(PORT_FORWARD_ALLOWED_TARGETS, String::new());
+        let (_proc, local) = forward_to(remote).await?;
+
+        let mut socket = TcpStream::connect(local).await?;
+        let mut recv = vec![];
+        socket.read_to_end(&mut recv).await?;
+        assert!(recv.is_empty());
+        Ok(())
+    }
+}
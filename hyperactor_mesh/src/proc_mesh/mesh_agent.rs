@@ -15,6 +15,7 @@ use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::RwLock;
 use std::sync::RwLockWriteGuard;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use enum_as_inner::EnumAsInner;
@@ -49,11 +50,21 @@ use hyperactor::mailbox::MailboxSender;
 use hyperactor::mailbox::MessageEnvelope;
 use hyperactor::mailbox::Undeliverable;
 use hyperactor::proc::Proc;
+use hyperactor::profiler;
+use hyperactor::profiler::FoldedStacks;
 use hyperactor::supervision::ActorSupervisionEvent;
+use hyperactor::supervision::SupervisionWarning;
+use hyperactor::wait_graph::PendingWait;
+use hyperactor::wait_graph::StallDetector;
+use hyperactor_config::attrs::Attrs;
+use hyperactor_config::global::ConfigReport;
+use hyperactor_config::global::Source;
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::actor_mesh::CAST_ACTOR_MESH_ID;
+use crate::comm::multicast::CastInfo;
+use crate::port_forward;
 use crate::proc_mesh::SupervisionEventState;
 use crate::reference::ActorMeshId;
 use crate::resource;
@@ -221,6 +232,12 @@ pub(crate) fn update_event_actor_id(mut event: ActorSupervisionEvent) -> ActorSu
         resource::StopAll { cast = true },
         resource::GetState<ActorState> { cast = true },
         resource::GetRankStatus { cast = true },
+        SetRuntimeConfig { cast = true },
+        ConfigMessage,
+        FlightRecorderMessage,
+        Profile { cast = true },
+        GetPendingWaits { cast = true },
+        SubscribeWarnings { cast = true },
     ]
 )]
 pub struct ProcMeshAgent {
@@ -235,6 +252,10 @@ pub struct ProcMeshAgent {
     /// If record_supervision_events is true, then this will contain the list
     /// of all events that were received.
     supervision_events: HashMap<ActorId, Vec<ActorSupervisionEvent>>,
+    /// Checks the proc's reply waits for deadlocks and stalls.
+    stall_detector: StallDetector,
+    /// The subscribers to the warnings raised by `stall_detector`.
+    warning_subscribers: Vec<PortRef<SupervisionWarning>>,
 }
 
 impl ProcMeshAgent {
@@ -256,8 +277,11 @@ impl ProcMeshAgent {
             actor_states: HashMap::new(),
             record_supervision_events: false,
             supervision_events: HashMap::new(),
+            stall_detector: StallDetector::default(),
+            warning_subscribers: Vec::new(),
         };
         let handle = proc.spawn::<Self>("mesh", agent)?;
+        port_forward::spawn_if_enabled(&proc)?;
         Ok((proc, handle))
     }
 
@@ -269,8 +293,12 @@ impl ProcMeshAgent {
             actor_states: HashMap::new(),
             record_supervision_events: true,
             supervision_events: HashMap::new(),
+            stall_detector: StallDetector::default(),
+            warning_subscribers: Vec::new(),
         };
-        proc.spawn::<Self>("agent", agent)
+        let handle = proc.spawn::<Self>("agent", agent)?;
+        port_forward::spawn_if_enabled(&proc)?;
+        Ok(handle)
     }
 
     async fn destroy_and_wait_except_current<'a>(
@@ -288,6 +316,10 @@ impl ProcMeshAgent {
 impl Actor for ProcMeshAgent {
     async fn init(&mut self, this: &Instance<Self>) -> Result<(), anyhow::Error> {
         self.proc.set_supervision_coordinator(this.port())?;
+        let interval = hyperactor_config::global::get(hyperactor::config::STALL_DETECTION_INTERVAL);
+        if !interval.is_zero() {
+            this.self_message_with_delay(CheckStalls, interval)?;
+        }
         Ok(())
     }
 }
@@ -774,6 +806,295 @@ impl Handler<resource::GetState<ActorState>> for ProcMeshAgent {
     }
 }
 
+/// Merge `attrs` into the proc's [`Source::Runtime`] config layer, so
+/// that settings can be tuned on a live job. The agent acknowledges on
+/// `reply` once the values are installed. Values set through the
+/// environment still take precedence over the Runtime layer.
+#[derive(Debug, Clone, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct SetRuntimeConfig {
+    /// The values to install.
+    pub attrs: Attrs,
+    /// Acknowledged once the values are installed.
+    #[binding(include)]
+    pub reply: PortRef<()>,
+}
+
+#[async_trait]
+impl Handler<SetRuntimeConfig> for ProcMeshAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        SetRuntimeConfig { attrs, reply }: SetRuntimeConfig,
+    ) -> anyhow::Result<()> {
+        tracing::info!(
+            actor = %cx.self_id(),
+            "installing runtime config override: {}",
+            attrs
+        );
+        hyperactor_config::global::create_or_merge(Source::Runtime, attrs);
+        // As with the other queries, a lost reply must not stop the agent.
+        if let Err(e) = reply.send(cx, ()) {
+            tracing::warn!(
+                actor = %cx.self_id(),
+                "failed to send SetRuntimeConfig reply to {} due to error: {}",
+                reply.port_id().actor_id(),
+                e
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Queries about the proc's configuration.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    Handler,
+    HandleClient,
+    RefClient,
+    Named
+)]
+pub enum ConfigMessage {
+    /// Describe the proc's effective configuration, including where
+    /// each value came from.
+    Report {
+        #[reply]
+        report: OncePortRef<ConfigReport>,
+    },
+}
+
+#[async_trait]
+#[hyperactor::forward(ConfigMessage)]
+impl ConfigMessageHandler for ProcMeshAgent {
+    async fn report(&mut self, _cx: &Context<Self>) -> Result<ConfigReport, anyhow::Error> {
+        Ok(hyperactor_config::global::report())
+    }
+}
+
+/// Requests for the process's flight recorder
+/// (see [`hyperactor_telemetry::recorder::FlightRecorder`]).
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    Handler,
+    HandleClient,
+    RefClient,
+    Named
+)]
+pub enum FlightRecorderMessage {
+    /// Dump the flight recorder to a file, replying with its path, or
+    /// with an error if the flight recorder is disabled or could not be
+    /// written.
+    Dump {
+        #[reply]
+        path: OncePortRef<Result<String, String>>,
+    },
+}
+
+#[async_trait]
+#[hyperactor::forward(FlightRecorderMessage)]
+impl FlightRecorderMessageHandler for ProcMeshAgent {
+    async fn dump(&mut self, cx: &Context<Self>) -> Result<Result<String, String>, anyhow::Error> {
+        Ok(
+            match hyperactor_telemetry::recorder::snapshot_flight_recorder() {
+                Ok(Some(path)) => {
+                    tracing::info!(
+                        actor = %cx.self_id(),
+                        "dumped flight recorder to {}",
+                        path.display()
+                    );
+                    Ok(path.display().to_string())
+                }
+                Ok(None) => Err("the flight recorder is not enabled".to_string()),
+                Err(err) => Err(format!("failed to dump flight recorder: {}", err)),
+            },
+        )
+    }
+}
+
+/// Profile the proc for `duration`, taking a sample every `interval`,
+/// and reply with the rank of the proc and the sampled stacks (see
+/// [`hyperactor::profiler`]). Profiling runs in the background, so the
+/// agent keeps handling messages in the meantime.
+#[derive(Debug, Clone, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct Profile {
+    /// How long to profile for.
+    pub duration: Duration,
+    /// The interval between samples.
+    pub interval: Duration,
+    /// Receives the rank of the proc and its stacks.
+    #[binding(include)]
+    pub reply: PortRef<(usize, FoldedStacks)>,
+}
+
+#[async_trait]
+impl Handler<Profile> for ProcMeshAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        Profile {
+            duration,
+            interval,
+            reply,
+        }: Profile,
+    ) -> anyhow::Result<()> {
+        let rank = cx.cast_point().rank();
+        let proc = self.proc.clone();
+        let done = cx.port::<ProfileDone>();
+        tokio::spawn(async move {
+            let stacks = profiler::profile(&proc, duration, interval).await;
+            // The agent only goes away with the proc.
+            let _ = done.send(ProfileDone {
+                rank,
+                stacks,
+                reply,
+            });
+        });
+        Ok(())
+    }
+}
+
+/// The stacks reported for a proc that did not reply to [`Profile`]
+/// in time: a single `[no response]` sample, so that such procs stand
+/// out when profiles are merged into a flamegraph.
+pub(crate) fn unresponsive_profile() -> FoldedStacks {
+    let mut stacks = FoldedStacks::default();
+    stacks.add(["[no response]"]);
+    stacks
+}
+
+/// A local message delivering a finished profile, to be sent to its
+/// requester.
+#[derive(Debug)]
+struct ProfileDone {
+    rank: usize,
+    stacks: FoldedStacks,
+    reply: PortRef<(usize, FoldedStacks)>,
+}
+
+#[async_trait]
+impl Handler<ProfileDone> for ProcMeshAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        ProfileDone {
+            rank,
+            stacks,
+            reply,
+        }: ProfileDone,
+    ) -> anyhow::Result<()> {
+        if let Err(e) = reply.send(cx, (rank, stacks)) {
+            tracing::warn!(
+                actor = %cx.self_id(),
+                "failed to send Profile reply to {} due to error: {}",
+                reply.port_id().actor_id(),
+                e
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A local message that periodically checks the proc's reply waits for
+/// deadlocks and stalls. Any new ones are logged and sent, as supervision
+/// warnings, to the subscribers of [`SubscribeWarnings`].
+#[derive(Debug)]
+struct CheckStalls;
+
+#[async_trait]
+impl Handler<CheckStalls> for ProcMeshAgent {
+    async fn handle(&mut self, cx: &Context<Self>, _: CheckStalls) -> anyhow::Result<()> {
+        let warnings = self.stall_detector.check(
+            self.proc.pending_waits(),
+            hyperactor_config::global::get(hyperactor::config::STALL_WAIT_THRESHOLD),
+            RealClock.system_time_now(),
+        );
+        for warning in warnings {
+            warning.report();
+            // Subscribers that cannot be sent to are dropped.
+            self.warning_subscribers.retain(|subscriber| {
+                match subscriber.send(cx, warning.clone()) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::warn!(
+                            actor = %cx.self_id(),
+                            "failed to send supervision warning to {}; unsubscribing: {}",
+                            subscriber.port_id().actor_id(),
+                            e
+                        );
+                        false
+                    }
+                }
+            });
+        }
+        let interval = hyperactor_config::global::get(hyperactor::config::STALL_DETECTION_INTERVAL);
+        if !interval.is_zero() {
+            cx.self_message_with_delay(CheckStalls, interval)?;
+        }
+        Ok(())
+    }
+}
+
+/// Subscribe to the supervision warnings raised by the proc's stall
+/// detection (see [`hyperactor::wait_graph`]). Every new warning is sent
+/// to `subscriber` until a send to it fails.
+#[derive(Debug, Clone, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct SubscribeWarnings {
+    /// Receives the warnings.
+    #[binding(include)]
+    pub subscriber: PortRef<SupervisionWarning>,
+}
+
+#[async_trait]
+impl Handler<SubscribeWarnings> for ProcMeshAgent {
+    async fn handle(
+        &mut self,
+        _cx: &Context<Self>,
+        SubscribeWarnings { mut subscriber }: SubscribeWarnings,
+    ) -> anyhow::Result<()> {
+        // A subscriber that goes away must not take the agent with it.
+        subscriber.return_undeliverable(false);
+        self.warning_subscribers.push(subscriber);
+        Ok(())
+    }
+}
+
+/// Get the reply waits currently outstanding in the proc, so that a
+/// wait-for graph can be built across a mesh (see
+/// [`hyperactor::wait_graph`]). The reply carries the rank of the proc.
+#[derive(Debug, Clone, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct GetPendingWaits {
+    /// Receives the rank of the proc and its waits.
+    #[binding(include)]
+    pub reply: PortRef<(usize, Vec<PendingWait>)>,
+}
+
+#[async_trait]
+impl Handler<GetPendingWaits> for ProcMeshAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        GetPendingWaits { reply }: GetPendingWaits,
+    ) -> anyhow::Result<()> {
+        let rank = cx.cast_point().rank();
+        if let Err(e) = reply.send(cx, (rank, self.proc.pending_waits())) {
+            tracing::warn!(
+                actor = %cx.self_id(),
+                "failed to send GetPendingWaits reply to {} due to error: {}",
+                reply.port_id().actor_id(),
+                e
+            );
+        }
+        Ok(())
+    }
+}
+
 /// A local handler to get a new client instance on the proc.
 /// This is used to create root client instances.
 #[derive(Debug, hyperactor::Handler, hyperactor::HandleClient)]
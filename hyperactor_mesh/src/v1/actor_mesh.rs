@@ -6,6 +6,8 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeSet;
+use std::collections::HashSet;
 use std::fmt;
 use std::hash::Hash;
 use std::hash::Hasher;
@@ -25,6 +27,8 @@ use hyperactor_config::attrs::Attrs;
 use hyperactor_mesh_macros::sel;
 use ndslice::Selection;
 use ndslice::ViewExt as _;
+use ndslice::selection::EvalOpts;
+use ndslice::selection::Labels;
 use ndslice::view;
 use ndslice::view::Region;
 use ndslice::view::View;
@@ -177,6 +181,39 @@ impl<A: Referable> ActorMeshRef<A> {
         self.cast_with_selection(cx, sel!(*), message)
     }
 
+    /// Cast a message to the actors in this mesh selected by `sel`,
+    /// resolving [`Selection::Label`]s against `labels`, which are keyed
+    /// by the dimensions of this mesh's region. For example, with the
+    /// hosts of a `hosts x gpus` mesh labeled by GPU type,
+    /// `sel!(["h100"]*, *)` casts to every actor on an "h100" host.
+    ///
+    /// Labels are resolved to concrete ranks here, before the cast is
+    /// routed.
+    #[allow(clippy::result_large_err)]
+    pub fn cast_with_labels<M>(
+        &self,
+        cx: &impl context::Actor,
+        sel: Selection,
+        labels: &Labels,
+        message: M,
+    ) -> v1::Result<()>
+    where
+        A: RemoteHandles<M> + RemoteHandles<IndexedErasedUnbound<M>>,
+        M: Castable + RemoteMessage + Clone, // Clone is required until we are fully onto comm actor
+    {
+        let region = view::Ranked::region(self);
+        let ranks = sel
+            .eval_with_labels(&EvalOpts::strict(), region.slice(), labels)
+            .map_err(|e| Error::CastingError(self.name.clone(), e.into()))?
+            .collect::<BTreeSet<_>>();
+        if ranks.is_empty() {
+            return Ok(());
+        }
+        let sel = Selection::of_ranks(region.slice(), &ranks)
+            .map_err(|e| Error::CastingError(self.name.clone(), e.into()))?;
+        self.cast_with_selection(cx, sel, message)
+    }
+
     /// Cast a message to the actors in this mesh according to the provided selection.
     /// This should *only* be used for temporary support for selections in the tensor
     /// engine. If you use this for anything else, you will be fired (you too, OSS
@@ -209,7 +246,18 @@ impl<A: Referable> ActorMeshRef<A> {
         if let Some(root_comm_actor) = self.proc_mesh.root_comm_actor() {
             self.cast_v0(cx, message, sel, root_comm_actor)
         } else {
+            let slice = view::Ranked::region(self).slice().clone();
+            let selected = sel
+                .eval(&EvalOpts::lenient(), &slice)
+                .map_err(|e| Error::CastingError(self.name.clone(), e.into()))?
+                .collect::<HashSet<_>>();
             for (point, actor) in self.iter() {
+                if !slice
+                    .location(&point.coords())
+                    .is_ok_and(|location| selected.contains(&location))
+                {
+                    continue;
+                }
                 let create_rank = point.rank();
                 let mut headers = Attrs::new();
                 headers.set(
@@ -451,6 +499,7 @@ mod tests {
     use std::assert_matches::assert_matches;
     use std::collections::HashSet;
 
+    use hyperactor::Instance;
     use hyperactor::actor::ActorStatus;
     use hyperactor::clock::Clock;
     use hyperactor::clock::RealClock;
@@ -823,6 +872,83 @@ mod tests {
         let _ = host_mesh.shutdown(&instance).await;
     }
 
+    #[async_timed_test(timeout_secs = 30)]
+    #[cfg(fbcode_build)]
+    async fn test_cast_with_labels() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::bootstrap::MESH_BOOTSTRAP_ENABLE_PDEATHSIG, false);
+
+        let instance = testing::instance();
+        let mut host_mesh = testing::host_mesh(extent!(host = 4)).await;
+        let proc_mesh = host_mesh
+            .spawn(instance, "test", Extent::unity())
+            .await
+            .unwrap();
+        let actor_mesh: ActorMesh<testactor::TestActor> =
+            proc_mesh.spawn(instance, "test", &()).await.unwrap();
+        assert_cast_with_labels(instance, &actor_mesh).await;
+
+        let _ = host_mesh.shutdown(&instance).await;
+    }
+
+    #[tokio::test]
+    async fn test_cast_with_labels_local() {
+        let (proc_mesh, instance, _router) = testing::local_proc_mesh(extent!(host = 4)).await;
+        let actor_mesh: ActorMesh<testactor::TestActor> =
+            proc_mesh.spawn(instance, "test", &()).await.unwrap();
+        assert_cast_with_labels(instance, &actor_mesh).await;
+    }
+
+    /// Casts to the "h100" hosts of `actor_mesh`, a mesh over 4 hosts
+    /// of which hosts 1 and 3 are labeled "h100", and checks that
+    /// exactly their actors receive the message.
+    async fn assert_cast_with_labels(
+        instance: &Instance<testing::TestRootClient>,
+        actor_mesh: &ActorMesh<testactor::TestActor>,
+    ) {
+        use hyperactor_mesh_macros::sel;
+        use ndslice::selection::Labels;
+
+        // Hosts 1 and 3 are "h100", the others "a100".
+        let labels = (0..4).fold(Labels::new(), |labels, host| {
+            labels.with(0, host, if host % 2 == 1 { "h100" } else { "a100" })
+        });
+
+        let (cast_info, mut cast_info_rx) = instance.mailbox().open_port();
+        actor_mesh
+            .cast_with_labels(
+                instance,
+                sel!(["h100"]*),
+                &labels,
+                testactor::GetCastInfo {
+                    cast_info: cast_info.bind(),
+                },
+            )
+            .unwrap();
+
+        let mut point_to_actor: HashSet<_> = actor_mesh
+            .iter()
+            .filter(|(point, _)| point.coord(0) % 2 == 1)
+            .collect();
+        assert_eq!(point_to_actor.len(), 2);
+        while !point_to_actor.is_empty() {
+            let (point, origin_actor_ref, _sender_actor_id) = cast_info_rx.recv().await.unwrap();
+            let key = (point, origin_actor_ref);
+            assert!(
+                point_to_actor.remove(&key),
+                "key {:?} not selected or removed twice",
+                key
+            );
+        }
+        // Nothing is delivered to the "a100" hosts.
+        assert!(
+            RealClock
+                .timeout(Duration::from_millis(100), cast_info_rx.recv())
+                .await
+                .is_err()
+        );
+    }
+
     /// Test that undeliverable messages are properly returned to the
     /// sender when communication to a proc is broken.
     ///
@@ -33,6 +33,7 @@ use hyperactor::Named;
 use hyperactor::ProcId;
 use hyperactor::channel::ChannelAddr;
 use hyperactor::context;
+use hyperactor::profiler::FoldedStacks;
 use ndslice::Extent;
 use ndslice::Region;
 use ndslice::ViewExt;
@@ -48,10 +49,14 @@ use crate::alloc::Alloc;
 use crate::bootstrap::BootstrapCommand;
 use crate::bootstrap::BootstrapProcManager;
 use crate::proc_mesh::DEFAULT_TRANSPORT;
+use crate::proc_mesh::mesh_agent::ProcMeshAgent;
+use crate::proc_mesh::mesh_agent::Profile;
+use crate::proc_mesh::mesh_agent::unresponsive_profile;
 use crate::resource;
 use crate::resource::CreateOrUpdateClient;
 use crate::resource::GetRankStatus;
 use crate::resource::GetRankStatusClient;
+use crate::resource::ListClient;
 use crate::resource::ProcSpec;
 use crate::resource::RankedValues;
 use crate::resource::Status;
@@ -67,6 +72,7 @@ use crate::v1::host_mesh::mesh_agent::ProcState;
 use crate::v1::host_mesh::mesh_agent::ShutdownHostClient;
 use crate::v1::mesh_controller::HostMeshController;
 use crate::v1::mesh_controller::ProcMeshController;
+use crate::v1::proc_mesh::PROFILE_MAX_IDLE;
 use crate::v1::proc_mesh::ProcRef;
 
 declare_attrs! {
@@ -1244,6 +1250,92 @@ impl HostMeshRef {
             .collect_mesh::<ValueMesh<_>>(region)?;
         Ok(vm)
     }
+
+    /// Profile every proc on the hosts of this mesh for `duration`,
+    /// sampling what its actors are doing every `interval` (see
+    /// [`hyperactor::profiler`]). The stacks of each host merge those
+    /// of its procs, each rooted at the name of the proc. Hosts are
+    /// profiled concurrently, and must list and profile their procs
+    /// within `duration` plus [`PROFILE_MAX_IDLE`]. Procs that
+    /// do not reply in time, and hosts that do not list their procs in
+    /// time, are reported with a single `[no response]` sample.
+    pub async fn profile(
+        &self,
+        cx: &impl context::Actor,
+        duration: Duration,
+        interval: Duration,
+    ) -> v1::Result<ValueMesh<FoldedStacks>> {
+        let deadline =
+            RealClock.now() + duration + hyperactor_config::global::get(PROFILE_MAX_IDLE);
+        let hosts = futures::future::try_join_all(self.ranks.iter().enumerate().map(
+            |(host_rank, host)| async move {
+                let remaining = deadline.saturating_duration_since(RealClock.now());
+                let proc_names = match RealClock
+                    .timeout(remaining, host.mesh_agent().list(cx))
+                    .await
+                {
+                    Ok(proc_names) => proc_names.map_err(|e| {
+                        v1::Error::CallError(host.mesh_agent().actor_id().clone(), e.into())
+                    })?,
+                    Err(_) => {
+                        tracing::warn!("no proc list from host rank {} in time", host_rank);
+                        return Ok(unresponsive_profile());
+                    }
+                };
+                // Note that we don't send 1 message per host agent, we send 1
+                // message per proc, each with its own reply port.
+                let mut pending = Vec::new();
+                for proc_name in proc_names {
+                    let (port, rx) = cx.mailbox().open_port::<(usize, FoldedStacks)>();
+                    let mut reply = port.bind();
+                    reply.return_undeliverable(false);
+                    let agent: ActorRef<ProcMeshAgent> =
+                        ActorRef::attest(host.named_proc(&proc_name).actor_id("agent", 0));
+                    agent
+                        .send(
+                            cx,
+                            Profile {
+                                duration,
+                                interval,
+                                reply,
+                            },
+                        )
+                        .map_err(|e| v1::Error::CallError(agent.actor_id().clone(), e.into()))?;
+                    pending.push((proc_name, rx));
+                }
+
+                let replies = futures::future::join_all(pending.into_iter().map(
+                    |(proc_name, mut rx)| async move {
+                        let remaining = deadline.saturating_duration_since(RealClock.now());
+                        let stacks = match RealClock.timeout(remaining, rx.recv()).await {
+                            Ok(Ok((_, stacks))) => stacks,
+                            _ => {
+                                tracing::warn!(
+                                    "no profile from proc {} on host rank {} in time",
+                                    proc_name,
+                                    host_rank,
+                                );
+                                unresponsive_profile()
+                            }
+                        };
+                        (proc_name, stacks)
+                    },
+                ))
+                .await;
+                let mut stacks = FoldedStacks::default();
+                for (proc_name, proc_stacks) in replies {
+                    stacks.merge_under(&proc_name.to_string(), &proc_stacks);
+                }
+                Ok::<_, v1::Error>(stacks)
+            },
+        ))
+        .await?;
+
+        let vm = hosts
+            .into_iter()
+            .collect_mesh::<ValueMesh<_>>(self.region.clone())?;
+        Ok(vm)
+    }
 }
 
 impl view::Ranked for HostMeshRef {
@@ -23,7 +23,9 @@ use hyperactor::ActorId;
 use hyperactor::ActorRef;
 use hyperactor::Handler;
 use hyperactor::Named;
+use hyperactor::PortRef;
 use hyperactor::ProcId;
+use hyperactor::RemoteHandles;
 use hyperactor::RemoteMessage;
 use hyperactor::RemoteSpawn;
 use hyperactor::accum::ReducerOpts;
@@ -37,9 +39,16 @@ use hyperactor::clock::RealClock;
 use hyperactor::context;
 use hyperactor::mailbox::DialMailboxRouter;
 use hyperactor::mailbox::MailboxServer;
+use hyperactor::mailbox::PortReceiver;
+use hyperactor::message::Castable;
+use hyperactor::message::IndexedErasedUnbound;
+use hyperactor::profiler::FoldedStacks;
 use hyperactor::supervision::ActorSupervisionEvent;
+use hyperactor::supervision::SupervisionWarning;
+use hyperactor::wait_graph::WaitGraph;
 use hyperactor_config::CONFIG;
 use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::Attrs;
 use hyperactor_config::attrs::declare_attrs;
 use ndslice::Extent;
 use ndslice::ViewExt as _;
@@ -93,6 +102,22 @@ declare_attrs! {
         py_name: None,
     })
     pub attr GET_ACTOR_STATE_MAX_IDLE: Duration = Duration::from_mins(1);
+
+    /// The maximum idle time between acknowledgements while setting
+    /// the runtime config of a proc mesh.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_SET_RUNTIME_CONFIG_MAX_IDLE".to_string()),
+        py_name: None,
+    })
+    pub attr SET_RUNTIME_CONFIG_MAX_IDLE: Duration = Duration::from_secs(30);
+
+    /// How long, beyond the requested duration, to wait for the
+    /// profiles of procs.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("HYPERACTOR_MESH_PROFILE_MAX_IDLE".to_string()),
+        py_name: None,
+    })
+    pub attr PROFILE_MAX_IDLE: Duration = Duration::from_secs(30);
 }
 
 /// A reference to a single [`hyperactor::Proc`].
@@ -762,6 +787,149 @@ impl ProcMeshRef {
         ActorMeshRef::new(Name::new_reserved(agent_name).unwrap(), self.clone())
     }
 
+    /// Merge `attrs` into the Runtime config layer of every proc in this
+    /// mesh, returning once all procs have acknowledged. Useful to tune
+    /// settings (e.g. logging) on a running job without restarting it.
+    pub async fn set_runtime_config(
+        &self,
+        cx: &impl context::Actor,
+        attrs: Attrs,
+    ) -> v1::Result<()> {
+        let agent_mesh = self.agent_mesh();
+        let (port, mut rx) = cx.mailbox().open_port::<()>();
+        agent_mesh.cast(
+            cx,
+            mesh_agent::SetRuntimeConfig {
+                attrs,
+                reply: port.bind(),
+            },
+        )?;
+        let timeout = hyperactor_config::global::get(SET_RUNTIME_CONFIG_MAX_IDLE);
+        for received in 0..self.ranks.len() {
+            RealClock.timeout(timeout, rx.recv()).await.map_err(|_| {
+                Error::Other(anyhow::anyhow!(
+                    "timeout after {:?} waiting for runtime config acks from mesh {}: got {} of {}",
+                    timeout,
+                    agent_mesh,
+                    received,
+                    self.ranks.len(),
+                ))
+            })??;
+        }
+        Ok(())
+    }
+
+    /// Cast the message built by `message` to the agents of this mesh,
+    /// and collect the replies they send to the port it is given, each
+    /// tagged with the rank of its proc. Ranks that do not reply within
+    /// `timeout` are `None`.
+    async fn cast_and_collect<M, R>(
+        &self,
+        cx: &impl context::Actor,
+        timeout: Duration,
+        message: impl FnOnce(PortRef<(usize, R)>) -> M,
+    ) -> v1::Result<Vec<Option<R>>>
+    where
+        ProcMeshAgent: RemoteHandles<M> + RemoteHandles<IndexedErasedUnbound<M>>,
+        M: Castable + RemoteMessage + Clone,
+        R: RemoteMessage,
+    {
+        let agent_mesh = self.agent_mesh();
+        let (port, mut rx) = cx.mailbox().open_port::<(usize, R)>();
+        agent_mesh.cast(cx, message(port.bind()))?;
+        let mut replies: Vec<Option<R>> = (0..self.ranks.len()).map(|_| None).collect();
+        let mut received = 0;
+        let deadline = RealClock.now() + timeout;
+        while received < replies.len() {
+            let remaining = deadline.saturating_duration_since(RealClock.now());
+            let Ok(reply) = RealClock.timeout(remaining, rx.recv()).await else {
+                tracing::warn!(
+                    "timeout waiting for replies to {} from proc mesh agents in mesh {}: got {} of {}",
+                    type_name::<M>(),
+                    agent_mesh,
+                    received,
+                    replies.len(),
+                );
+                break;
+            };
+            let (rank, value) = reply?;
+            if let Some(slot @ None) = replies.get_mut(rank) {
+                *slot = Some(value);
+                received += 1;
+            }
+        }
+        Ok(replies)
+    }
+
+    /// Profile every proc in this mesh for `duration`, sampling what
+    /// its actors are doing every `interval` (see
+    /// [`hyperactor::profiler`]). Procs that do not reply in time are
+    /// reported with a single `[no response]` sample.
+    pub async fn profile(
+        &self,
+        cx: &impl context::Actor,
+        duration: Duration,
+        interval: Duration,
+    ) -> v1::Result<ValueMesh<FoldedStacks>> {
+        let profiles = self
+            .cast_and_collect(
+                cx,
+                duration + hyperactor_config::global::get(PROFILE_MAX_IDLE),
+                |reply| mesh_agent::Profile {
+                    duration,
+                    interval,
+                    reply,
+                },
+            )
+            .await?;
+        let vm = profiles
+            .into_iter()
+            .map(|stacks| stacks.unwrap_or_else(mesh_agent::unresponsive_profile))
+            .collect_mesh::<ValueMesh<_>>(self.region.clone())?;
+        Ok(vm)
+    }
+
+    /// Check the actors of this mesh for deadlocks and stalled waits,
+    /// by building the wait-for graph of the reply waits outstanding
+    /// across all of its procs (see [`hyperactor::wait_graph`]). Waits
+    /// pending for at least `threshold` are reported as stalled. Procs
+    /// that do not reply in time are left out of the graph.
+    pub async fn detect_stalls(
+        &self,
+        cx: &impl context::Actor,
+        threshold: Duration,
+    ) -> v1::Result<Vec<SupervisionWarning>> {
+        let waits = self
+            .cast_and_collect(
+                cx,
+                hyperactor_config::global::get(GET_ACTOR_STATE_MAX_IDLE),
+                |reply| mesh_agent::GetPendingWaits { reply },
+            )
+            .await?;
+        Ok(WaitGraph::new(waits.into_iter().flatten().flatten())
+            .warnings(threshold, RealClock.system_time_now()))
+    }
+
+    /// Subscribe to the supervision warnings raised by the stall
+    /// detection of the procs in this mesh, which check their reply
+    /// waits every [`hyperactor::config::STALL_DETECTION_INTERVAL`]
+    /// (see [`hyperactor::wait_graph`]). Each new warning is delivered
+    /// to the returned receiver.
+    #[allow(clippy::result_large_err)]
+    pub fn subscribe_warnings(
+        &self,
+        cx: &impl context::Actor,
+    ) -> v1::Result<PortReceiver<SupervisionWarning>> {
+        let (port, rx) = cx.mailbox().open_port();
+        self.agent_mesh().cast(
+            cx,
+            mesh_agent::SubscribeWarnings {
+                subscriber: port.bind(),
+            },
+        )?;
+        Ok(rx)
+    }
+
     /// The supervision events of procs in this mesh.
     pub async fn actor_states(
         &self,
@@ -1282,6 +1450,74 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_proc_mesh_profile() {
+        let (mesh, actor, _router) = testing::local_proc_mesh(extent!(replica = 2)).await;
+        let profiles = mesh
+            .profile(
+                &actor,
+                std::time::Duration::from_millis(100),
+                std::time::Duration::from_millis(10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(profiles.extent(), extent!(replica = 2));
+        // Every proc replied, with samples of at least its own agent.
+        for stacks in profiles.values() {
+            assert!(stacks.total() > 0);
+            assert!(
+                !stacks.0.contains_key("[no response]"),
+                "unexpected profile: {}",
+                stacks
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proc_mesh_detect_stalls() {
+        let (mesh, actor, _router) = testing::local_proc_mesh(extent!(replica = 2)).await;
+        // Idle agents are not waiting on anything, even with no threshold.
+        let warnings = mesh
+            .detect_stalls(&actor, std::time::Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_proc_mesh_subscribe_warnings() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(
+            hyperactor::config::STALL_DETECTION_INTERVAL,
+            std::time::Duration::from_millis(50),
+        );
+        let (mesh, actor, _router) = testing::local_proc_mesh(extent!(replica = 2)).await;
+        let mut warnings = mesh.subscribe_warnings(&actor).unwrap();
+        let actors: ActorMesh<testactor::TestActor> =
+            mesh.spawn(&actor, "stalled", &()).await.unwrap();
+        actors.cast(&actor, testactor::WaitOnSelf).unwrap();
+
+        // Every actor is reported waiting on itself, once.
+        let mut waiters = HashSet::new();
+        for _ in 0..2 {
+            let warning = warnings.recv().await.unwrap();
+            let SupervisionWarning::Deadlock { waits } = &warning else {
+                panic!("unexpected warning: {}", warning);
+            };
+            assert_eq!(waits.len(), 1);
+            assert_eq!(waits[0].awaited.as_ref(), Some(&waits[0].waiter));
+            waiters.insert(waits[0].waiter.clone());
+        }
+        assert_eq!(waiters.len(), 2);
+
+        // The same deadlocks are found on demand.
+        let detected = mesh
+            .detect_stalls(&actor, std::time::Duration::MAX)
+            .await
+            .unwrap();
+        assert_eq!(detected.len(), 2);
+    }
+
     #[async_timed_test(timeout_secs = 30)]
     #[cfg(fbcode_build)]
     async fn test_spawn_actor() {
@@ -1295,6 +1531,39 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    #[cfg(fbcode_build)]
+    async fn test_set_runtime_config() {
+        let instance = testing::instance();
+        // The procs of the process-backed mesh do not share this
+        // process's configuration, so the change must be carried to them.
+        let proc_mesh = testing::proc_meshes(instance, extent!(replicas = 2))
+            .await
+            .pop()
+            .unwrap();
+        let actor_mesh: crate::v1::ActorMesh<testactor::TestActor> =
+            proc_mesh.spawn(instance, "test", &()).await.unwrap();
+
+        let threshold = std::time::Duration::from_secs(123);
+        let mut attrs = hyperactor_config::attrs::Attrs::new();
+        attrs.set(hyperactor::config::STALL_WAIT_THRESHOLD, threshold);
+        proc_mesh.set_runtime_config(instance, attrs).await.unwrap();
+
+        let (tx, mut rx) = instance.open_port();
+        actor_mesh
+            .cast(instance, testactor::GetConfigAttrs(tx.bind()))
+            .unwrap();
+        for _ in 0..2 {
+            let attrs =
+                bincode::deserialize::<hyperactor_config::attrs::Attrs>(&rx.recv().await.unwrap())
+                    .unwrap();
+            assert_eq!(
+                *attrs.get(hyperactor::config::STALL_WAIT_THRESHOLD).unwrap(),
+                threshold
+            );
+        }
+    }
+
     #[tokio::test]
     #[cfg(fbcode_build)]
     async fn test_failing_spawn_actor() {
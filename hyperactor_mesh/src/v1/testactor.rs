@@ -62,6 +62,7 @@ use crate::v1::testing;
         Forward,
         GetConfigAttrs { cast = true },
         SetConfigAttrs { cast = true },
+        WaitOnSelf { cast = true },
     ]
 )]
 pub struct TestActor;
@@ -207,6 +208,21 @@ impl Handler<Forward> for TestActor {
     }
 }
 
+/// A message that makes the recipient wait on a reply from itself,
+/// which never comes: a deadlock for stall detection to report.
+#[derive(Debug, Clone, Named, Bind, Unbind, Serialize, Deserialize)]
+pub struct WaitOnSelf;
+
+#[async_trait]
+impl Handler<WaitOnSelf> for TestActor {
+    async fn handle(&mut self, cx: &Context<Self>, _: WaitOnSelf) -> Result<(), anyhow::Error> {
+        // Keep the port open, so that the wait never ends.
+        let (_port, rx) = cx.open_once_port::<()>();
+        rx.awaiting(cx.self_id(), "WaitOnSelf").recv().await?;
+        Ok(())
+    }
+}
+
 /// Just return the cast info of the sender.
 #[derive(
     Debug,
@@ -14,6 +14,9 @@
 //! - SQLite: Query database and compare rows
 //! - Scuba: Mock client and compare logged samples
 //!
+//! It then runs sampling workloads through the unified implementation with
+//! sampling and rate limiting enabled, and checks the rows that reach SQLite.
+//!
 //! Usage:
 //!   buck2 run //monarch/hyperactor_telemetry:correctness_test
 
@@ -442,6 +445,145 @@ fn workload_events_in_spans() {
     }
 }
 
+fn workload_head_sampling() {
+    for i in 0..20 {
+        tracing::info!(iteration = i, "kept event");
+        tracing::info!(target: "sampled_out", iteration = i, "dropped event");
+        let _span = tracing::info_span!("sampled_span", iteration = i).entered();
+        tracing::info!(iteration = i, "dropped event in span");
+    }
+}
+
+fn workload_tail_sampling() {
+    for i in 0..10 {
+        let _span = tracing::info_span!("handle_request", iteration = i).entered();
+        tracing::info!("fast request");
+    }
+    for i in 0..2 {
+        let _span = tracing::info_span!("handle_request", iteration = i).entered();
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        tracing::info!("slow request");
+    }
+    for i in 0..3 {
+        let _span = tracing::info_span!("handle_request", iteration = i).entered();
+        tracing::error!("failed request");
+    }
+}
+
+fn workload_sink_rate_limit() {
+    for i in 0..500 {
+        tracing::info!(iteration = i, "rate limited event");
+    }
+}
+
+/// A workload run only through the unified implementation with sampling
+/// configured, and the number of `log_events` rows it should produce.
+struct SamplingTest {
+    name: &'static str,
+    env: &'static [(&'static str, &'static str)],
+    expected_log_events: std::ops::RangeInclusive<i64>,
+}
+
+const SAMPLING_TESTS: &[SamplingTest] = &[
+    SamplingTest {
+        name: "head_sampling",
+        env: &[(
+            "MONARCH_TRACE_SAMPLING_RULES",
+            "sampled_out=0,sampled_span=0",
+        )],
+        expected_log_events: 20..=20,
+    },
+    SamplingTest {
+        name: "tail_sampling",
+        env: &[
+            ("MONARCH_TRACE_TAIL_SAMPLING_SPANS", "handle_request"),
+            ("MONARCH_TRACE_TAIL_SAMPLING_THRESHOLD", "50ms"),
+        ],
+        expected_log_events: 5..=5,
+    },
+    SamplingTest {
+        name: "sink_rate_limit",
+        env: &[("MONARCH_TRACE_SINK_RATE_LIMITS", "SqliteSink=50")],
+        // A full bucket, plus whatever is refilled while the worker drains.
+        expected_log_events: 50..=100,
+    },
+];
+
+/// Run the sampling tests, returning whether all of them passed.
+fn run_sampling_tests(exe: &str) -> Result<bool> {
+    let username = whoami::username();
+    let mut all_passed = true;
+
+    for test in SAMPLING_TESTS {
+        println!("\n{}", "=".repeat(80));
+        println!("Running sampling test: {}", test_name_to_display(test.name));
+        println!("{}", "=".repeat(80));
+
+        let mut command = std::process::Command::new(exe);
+        command
+            .arg(test.name)
+            .arg("--unified")
+            .env("TEST_LOG_PREFIX", "test")
+            .env("MONARCH_LOG_SUFFIX", format!("{}_unified", test.name))
+            .env("ENABLE_SQLITE_TRACING", "1");
+        for (key, value) in test.env {
+            command.env(key, value);
+        }
+        let status = command.status()?;
+
+        let db = PathBuf::from(format!("/tmp/{}/test_{}_unified.db", username, test.name));
+        let result = if !status.success() {
+            Err(anyhow::anyhow!("UNIFIED implementation FAILED"))
+        } else if !db.exists() {
+            Err(anyhow::anyhow!("database not found: {}", db.display()))
+        } else {
+            rusqlite::Connection::open(&db)
+                .and_then(|conn| {
+                    // Only count the workload's own events.
+                    conn.query_row(
+                        "SELECT COUNT(*) FROM log_events WHERE module_path = ?1",
+                        [module_path!()],
+                        |row| row.get::<_, i64>(0),
+                    )
+                })
+                .map_err(anyhow::Error::from)
+                .and_then(|rows| {
+                    println!("  log_events rows: {}", rows);
+                    if test.expected_log_events.contains(&rows) {
+                        Ok(())
+                    } else {
+                        Err(anyhow::anyhow!(
+                            "expected {:?} log_events rows, got {}",
+                            test.expected_log_events,
+                            rows
+                        ))
+                    }
+                })
+        };
+
+        match result {
+            Ok(()) => println!("\n✓ Test PASSED: {}", test_name_to_display(test.name)),
+            Err(e) => {
+                println!(
+                    "\n✗ Test FAILED: {}: {}",
+                    test_name_to_display(test.name),
+                    e
+                );
+                all_passed = false;
+            }
+        }
+
+        let _ = std::fs::remove_file(&db);
+        let _ = std::fs::remove_file(format!("/tmp/{}/test_{}_unified.log", username, test.name));
+        let _ = std::fs::remove_file(format!(
+            "/tmp/{}/test_{}_unified_scuba_tracing.json",
+            username, test.name
+        ));
+    }
+
+    Ok(all_passed)
+}
+
 fn main() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
 
@@ -617,6 +759,10 @@ fn main() -> Result<()> {
         let _ = std::fs::remove_file(&unified_log);
     }
 
+    if !run_sampling_tests(&args[0])? {
+        all_passed = false;
+    }
+
     println!("\n\n{}", "=".repeat(80));
     if all_passed {
         println!("All tests completed successfully!");
@@ -666,6 +812,9 @@ fn run_single_test(test_name: &str, impl_type: &str) -> Result<()> {
         "events_with_fields" => workload_events_with_fields,
         "mixed_log_levels" => workload_mixed_log_levels,
         "events_in_spans" => workload_events_in_spans,
+        "head_sampling" => workload_head_sampling,
+        "tail_sampling" => workload_tail_sampling,
+        "sink_rate_limit" => workload_sink_rate_limit,
         _ => {
             return Err(anyhow::anyhow!("Unknown test: {}", test_name));
         }
@@ -754,6 +903,9 @@ fn test_name_to_display(test_name: &str) -> &str {
         "events_with_fields" => "Events with many fields",
         "mixed_log_levels" => "Mixed log levels",
         "events_in_spans" => "Events in spans",
+        "head_sampling" => "Head-based sampling",
+        "tail_sampling" => "Tail-based sampling",
+        "sink_rate_limit" => "Per-sink rate limit",
         _ => test_name,
     }
 }
@@ -42,6 +42,30 @@ declare_attrs! {
     })
     pub attr ENABLE_RECORDER_TRACING: bool = true;
 
+    /// Number of recent spans and events kept by the process-wide
+    /// flight recorder, which is dumped when the process fails.
+    /// 0 disables the flight recorder.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_FLIGHT_RECORDER_CAPACITY".to_string()),
+        py_name: Some("flight_recorder_capacity".to_string()),
+    })
+    pub attr FLIGHT_RECORDER_CAPACITY: usize = 4096;
+
+    /// The most verbose level captured by the flight recorder.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_FLIGHT_RECORDER_LEVEL".to_string()),
+        py_name: Some("flight_recorder_level".to_string()),
+    })
+    pub attr FLIGHT_RECORDER_LEVEL: String = "debug".to_string();
+
+    /// Directory that flight recorder dumps are written to.
+    /// Defaults to `monarch_flight_recorder` in the system temp directory.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_FLIGHT_RECORDER_DIR".to_string()),
+        py_name: Some("flight_recorder_dir".to_string()),
+    })
+    pub attr FLIGHT_RECORDER_DIR: String = String::new();
+
     /// Enable the SQLite tracing layer.
     /// When true, SQLite tracing is enabled.
     @meta(CONFIG = ConfigAttr {
@@ -80,6 +104,43 @@ declare_attrs! {
     })
     pub attr USE_UNIFIED_LAYER: bool = false;
 
+    /// Head-based sampling rules for the unified tracing layer, as a
+    /// comma-separated list of `pattern=rate`, e.g.
+    /// "handle_message=0.1,hyperactor::mailbox=0.01". A pattern matches
+    /// a span name exactly, or a target and its submodules. A sampled-out
+    /// span drops its descendants and the events within it.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_TRACE_SAMPLING_RULES".to_string()),
+        py_name: Some("trace_sampling_rules".to_string()),
+    })
+    pub attr TRACE_SAMPLING_RULES: String = String::new();
+
+    /// Comma-separated span names that are tail sampled by the unified
+    /// tracing layer: each such span, with everything inside it, is only
+    /// exported if it ran for at least `TRACE_TAIL_SAMPLING_THRESHOLD` or
+    /// recorded an error.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_TRACE_TAIL_SAMPLING_SPANS".to_string()),
+        py_name: Some("trace_tail_sampling_spans".to_string()),
+    })
+    pub attr TRACE_TAIL_SAMPLING_SPANS: String = String::new();
+
+    /// Minimum duration for a tail sampled span to be exported.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_TRACE_TAIL_SAMPLING_THRESHOLD".to_string()),
+        py_name: Some("trace_tail_sampling_threshold".to_string()),
+    })
+    pub attr TRACE_TAIL_SAMPLING_THRESHOLD: Duration = Duration::from_millis(100);
+
+    /// Per-sink rate limits for the unified tracing layer, as a
+    /// comma-separated list of `SinkName=events_per_second`, e.g.
+    /// "SqliteSink=1000". Events over the limit are dropped and counted.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_TRACE_SINK_RATE_LIMITS".to_string()),
+        py_name: Some("trace_sink_rate_limits".to_string()),
+    })
+    pub attr TRACE_SINK_RATE_LIMITS: String = String::new();
+
     // Suffix to append to log filenames for test isolation
     @meta(CONFIG = ConfigAttr {
         env_name: Some("MONARCH_LOG_SUFFIX".to_string()),
@@ -54,15 +54,18 @@ mod config;
 pub mod in_memory_reader;
 #[cfg(fbcode_build)]
 mod meta;
+pub mod metrics_snapshot;
 mod otel;
 mod pool;
 pub mod recorder;
+pub mod sampling;
 pub mod sinks;
 mod spool;
 pub mod sqlite;
 pub mod task;
 pub mod trace;
 pub mod trace_dispatcher;
+pub mod trace_query;
 use std::io::Write;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -96,6 +99,8 @@ use crate::config::ENABLE_OTEL_METRICS;
 use crate::config::ENABLE_OTEL_TRACING;
 use crate::config::ENABLE_RECORDER_TRACING;
 use crate::config::ENABLE_SQLITE_TRACING;
+use crate::config::FLIGHT_RECORDER_CAPACITY;
+use crate::config::FLIGHT_RECORDER_LEVEL;
 use crate::config::MONARCH_FILE_LOG_LEVEL;
 use crate::config::MONARCH_LOG_SUFFIX;
 use crate::config::USE_UNIFIED_LAYER;
@@ -277,6 +282,30 @@ pub fn recorder() -> &'static Recorder {
     RECORDER.get_or_init(Recorder::new)
 }
 
+/// Enable the flight recorder of the [`recorder`] singleton, as
+/// configured by `FLIGHT_RECORDER_CAPACITY` and `FLIGHT_RECORDER_LEVEL`.
+fn enable_flight_recorder() {
+    let cap = hyperactor_config::global::get(FLIGHT_RECORDER_CAPACITY);
+    if cap == 0 {
+        return;
+    }
+    let level = hyperactor_config::global::get_cloned(FLIGHT_RECORDER_LEVEL);
+    let level = LevelFilter::from_str(&level).unwrap_or_else(|_| {
+        eprintln!("invalid flight recorder level {level:?}; using debug");
+        LevelFilter::DEBUG
+    });
+    recorder().enable_flight_recorder(cap.max(2), level);
+}
+
+/// The sampling configuration of the unified layer. An invalid
+/// configuration is reported and disables sampling.
+fn sampling_config() -> sampling::SamplingConfig {
+    sampling::SamplingConfig::from_config().unwrap_or_else(|e| {
+        eprintln!("invalid trace sampling configuration, sampling disabled: {e:#}");
+        sampling::SamplingConfig::default()
+    })
+}
+
 /// Hotswap the telemetry clock at runtime. This allows changing the clock implementation
 /// after initialization, which is useful for testing or switching between real and simulated time.
 pub fn swap_telemetry_clock(clock: impl TelemetryClock + Send + 'static) {
@@ -665,6 +694,9 @@ fn initialize_logging_with_log_prefix_impl(
     let use_unified = hyperactor_config::global::get(USE_UNIFIED_LAYER);
 
     swap_telemetry_clock(clock);
+    if hyperactor_config::global::get(ENABLE_RECORDER_TRACING) {
+        enable_flight_recorder();
+    }
     let file_log_level = match env::Env::current() {
         env::Env::Local => LOG_LEVEL_INFO,
         env::Env::MastEmulator => LOG_LEVEL_INFO,
@@ -755,14 +787,22 @@ fn initialize_logging_with_log_prefix_impl(
                 }
             }
 
+            let registered = trace_dispatcher::registered_sinks();
+            if !registered.is_empty() {
+                max_level = Some(tracing::level_filters::LevelFilter::TRACE);
+                sinks.extend(registered);
+            }
+
             if let Err(err) = Registry::default()
                 .with(if hyperactor_config::global::get(ENABLE_RECORDER_TRACING) {
                     Some(recorder().layer())
                 } else {
                     None
                 })
-                .with(trace_dispatcher::TraceEventDispatcher::new(
-                    sinks, max_level,
+                .with(trace_dispatcher::TraceEventDispatcher::new_with_sampling(
+                    sinks,
+                    max_level,
+                    sampling_config(),
                 ))
                 .try_init()
             {
@@ -912,9 +952,17 @@ fn initialize_logging_with_log_prefix_impl(
                 file_log_level,
             )));
 
+            let registered = trace_dispatcher::registered_sinks();
+            if !registered.is_empty() {
+                max_level = Some(tracing::level_filters::LevelFilter::TRACE);
+                sinks.extend(registered);
+            }
+
             if let Err(err) = registry
-                .with(trace_dispatcher::TraceEventDispatcher::new(
-                    sinks, max_level,
+                .with(trace_dispatcher::TraceEventDispatcher::new_with_sampling(
+                    sinks,
+                    max_level,
+                    sampling_config(),
                 ))
                 .try_init()
             {
@@ -952,6 +1000,10 @@ fn initialize_logging_with_log_prefix_impl(
             }
         }
 
+        if hyperactor_config::global::get(ENABLE_OTEL_METRICS) {
+            otel::init_metrics();
+        }
+
         Box::new(EmptyTestHandle)
     }
 }
@@ -0,0 +1,367 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Point-in-time snapshots of this process's metrics.
+//!
+//! Telemetry attaches a cumulative in-memory reader to the process
+//! meter provider, alongside any exporters, so that the current value
+//! of every counter and histogram declared with the `declare_static_*`
+//! macros can be read with [`snapshot`]. Snapshots are serializable and can be
+//! merged, which lets them be aggregated across processes.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::OnceLock;
+
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::ManualReader;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::metrics::data::Histogram;
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::data::Sum;
+use opentelemetry_sdk::metrics::reader::MetricReader;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::in_memory_reader::InMemoryReader;
+
+/// The values of a histogram.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistogramSnapshot {
+    /// The number of recorded values.
+    pub count: u64,
+    /// The sum of the recorded values.
+    pub sum: f64,
+    /// The smallest recorded value.
+    pub min: Option<f64>,
+    /// The largest recorded value.
+    pub max: Option<f64>,
+    /// The upper bounds of the buckets, excluding the final, unbounded
+    /// bucket.
+    pub bounds: Vec<f64>,
+    /// The number of values in each bucket; one more than `bounds`.
+    pub bucket_counts: Vec<u64>,
+}
+
+impl HistogramSnapshot {
+    /// Merge `other` into this histogram. Buckets are only kept if both
+    /// histograms use the same bounds.
+    pub fn merge(&mut self, other: &HistogramSnapshot) {
+        if self.count == 0 {
+            *self = other.clone();
+            return;
+        }
+        if other.count == 0 {
+            return;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        if self.bounds == other.bounds && self.bucket_counts.len() == other.bucket_counts.len() {
+            for (count, other) in self.bucket_counts.iter_mut().zip(&other.bucket_counts) {
+                *count += other;
+            }
+        } else {
+            self.bounds.clear();
+            self.bucket_counts.clear();
+        }
+    }
+
+    /// The mean of the recorded values.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// An estimate of the `q` quantile: the upper bound of the bucket
+    /// containing it, clamped to the largest recorded value.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 || self.bucket_counts.is_empty() {
+            return None;
+        }
+        let target = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in self.bucket_counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                let bound = self.bounds.get(i).copied().unwrap_or(f64::INFINITY);
+                return Some(match self.max {
+                    Some(max) => bound.min(max),
+                    None => bound,
+                });
+            }
+        }
+        self.max
+    }
+}
+
+/// The counters and histograms of a process at a point in time. Metrics
+/// recorded with attributes are keyed as `name{key=value,...}`, with
+/// the attributes sorted by key.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// Counters and up-down counters.
+    pub counters: BTreeMap<String, f64>,
+    /// Histograms, including timers.
+    pub histograms: BTreeMap<String, HistogramSnapshot>,
+}
+
+impl MetricsSnapshot {
+    /// Whether the snapshot has no metrics.
+    pub fn is_empty(&self) -> bool {
+        self.counters.is_empty() && self.histograms.is_empty()
+    }
+
+    /// Merge `other` into this snapshot, adding counters and merging
+    /// histograms.
+    pub fn merge(&mut self, other: &MetricsSnapshot) {
+        for (name, value) in &other.counters {
+            *self.counters.entry(name.clone()).or_default() += value;
+        }
+        for (name, histogram) in &other.histograms {
+            self.histograms
+                .entry(name.clone())
+                .or_default()
+                .merge(histogram);
+        }
+    }
+
+    /// Build a snapshot from metrics collected by a reader.
+    pub fn from_resource_metrics(rm: &ResourceMetrics) -> Self {
+        let mut snapshot = Self::default();
+        for scope in &rm.scope_metrics {
+            for metric in &scope.metrics {
+                let data = metric.data.as_any();
+                if let Some(sum) = data.downcast_ref::<Sum<u64>>() {
+                    for point in &sum.data_points {
+                        snapshot.add_counter(&metric.name, &point.attributes, point.value as f64);
+                    }
+                } else if let Some(sum) = data.downcast_ref::<Sum<i64>>() {
+                    for point in &sum.data_points {
+                        snapshot.add_counter(&metric.name, &point.attributes, point.value as f64);
+                    }
+                } else if let Some(sum) = data.downcast_ref::<Sum<f64>>() {
+                    for point in &sum.data_points {
+                        snapshot.add_counter(&metric.name, &point.attributes, point.value);
+                    }
+                } else if let Some(histogram) = data.downcast_ref::<Histogram<u64>>() {
+                    for point in &histogram.data_points {
+                        snapshot.add_histogram(
+                            &metric.name,
+                            &point.attributes,
+                            HistogramSnapshot {
+                                count: point.count,
+                                sum: point.sum as f64,
+                                min: point.min.map(|min| min as f64),
+                                max: point.max.map(|max| max as f64),
+                                bounds: point.bounds.clone(),
+                                bucket_counts: point.bucket_counts.clone(),
+                            },
+                        );
+                    }
+                } else if let Some(histogram) = data.downcast_ref::<Histogram<f64>>() {
+                    for point in &histogram.data_points {
+                        snapshot.add_histogram(
+                            &metric.name,
+                            &point.attributes,
+                            HistogramSnapshot {
+                                count: point.count,
+                                sum: point.sum,
+                                min: point.min,
+                                max: point.max,
+                                bounds: point.bounds.clone(),
+                                bucket_counts: point.bucket_counts.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        snapshot
+    }
+
+    fn add_counter(&mut self, name: &str, attributes: &[opentelemetry::KeyValue], value: f64) {
+        *self
+            .counters
+            .entry(metric_key(name, attributes))
+            .or_default() += value;
+    }
+
+    fn add_histogram(
+        &mut self,
+        name: &str,
+        attributes: &[opentelemetry::KeyValue],
+        histogram: HistogramSnapshot,
+    ) {
+        self.histograms
+            .entry(metric_key(name, attributes))
+            .or_default()
+            .merge(&histogram);
+    }
+}
+
+fn metric_key(name: &str, attributes: &[opentelemetry::KeyValue]) -> String {
+    if attributes.is_empty() {
+        return name.to_string();
+    }
+    let mut attributes: Vec<String> = attributes
+        .iter()
+        .map(|kv| format!("{}={}", kv.key, kv.value))
+        .collect();
+    attributes.sort();
+    format!("{}{{{}}}", name, attributes.join(","))
+}
+
+fn manual_reader() -> &'static Arc<ManualReader> {
+    static READER: OnceLock<Arc<ManualReader>> = OnceLock::new();
+    READER.get_or_init(|| {
+        Arc::new(
+            ManualReader::builder()
+                .with_temporality(Temporality::Cumulative)
+                .build(),
+        )
+    })
+}
+
+/// The reader that feeds [`snapshot`]. It must be attached to the
+/// process meter provider, whichever exporters that provider has.
+pub(crate) fn reader() -> InMemoryReader {
+    InMemoryReader::new(Arc::clone(manual_reader()))
+}
+
+/// The process meter provider when no exporter is configured, which
+/// only feeds [`snapshot`].
+#[cfg(not(fbcode_build))]
+pub(crate) fn meter_provider() -> SdkMeterProvider {
+    static PROVIDER: OnceLock<SdkMeterProvider> = OnceLock::new();
+    PROVIDER
+        .get_or_init(|| SdkMeterProvider::builder().with_reader(reader()).build())
+        .clone()
+}
+
+/// Snapshot the current value of this process's metrics. The snapshot is
+/// empty if telemetry has not installed its meter provider.
+pub fn snapshot() -> MetricsSnapshot {
+    let mut rm = ResourceMetrics {
+        resource: Resource::builder_empty().build(),
+        scope_metrics: Vec::new(),
+    };
+    if manual_reader().collect(&mut rm).is_err() {
+        return MetricsSnapshot::default();
+    }
+    MetricsSnapshot::from_resource_metrics(&rm)
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::metrics::MeterProvider;
+
+    use super::*;
+
+    fn histogram(values: &[f64], bounds: &[f64]) -> HistogramSnapshot {
+        let mut bucket_counts = vec![0; bounds.len() + 1];
+        for value in values {
+            let bucket = bounds
+                .iter()
+                .position(|bound| value <= bound)
+                .unwrap_or(bounds.len());
+            bucket_counts[bucket] += 1;
+        }
+        HistogramSnapshot {
+            count: values.len() as u64,
+            sum: values.iter().sum(),
+            min: values.iter().copied().reduce(f64::min),
+            max: values.iter().copied().reduce(f64::max),
+            bounds: bounds.to_vec(),
+            bucket_counts,
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = MetricsSnapshot::default();
+        a.counters.insert("messages".to_string(), 3.0);
+        a.histograms
+            .insert("latency".to_string(), histogram(&[1.0, 5.0], &[2.0, 10.0]));
+        let mut b = MetricsSnapshot::default();
+        b.counters.insert("messages".to_string(), 4.0);
+        b.counters.insert("errors".to_string(), 1.0);
+        b.histograms
+            .insert("latency".to_string(), histogram(&[20.0], &[2.0, 10.0]));
+
+        a.merge(&b);
+        assert_eq!(a.counters["messages"], 7.0);
+        assert_eq!(a.counters["errors"], 1.0);
+        assert_eq!(
+            a.histograms["latency"],
+            histogram(&[1.0, 5.0, 20.0], &[2.0, 10.0])
+        );
+
+        // Histograms with different bounds keep their summary only.
+        a.merge(&MetricsSnapshot {
+            histograms: [("latency".to_string(), histogram(&[3.0], &[5.0]))].into(),
+            ..Default::default()
+        });
+        let latency = &a.histograms["latency"];
+        assert_eq!(latency.count, 4);
+        assert_eq!(latency.sum, 29.0);
+        assert_eq!(latency.min, Some(1.0));
+        assert_eq!(latency.max, Some(20.0));
+        assert!(latency.bucket_counts.is_empty());
+        assert_eq!(latency.quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_quantile() {
+        let latency = histogram(&[1.0, 1.5, 5.0, 7.0], &[2.0, 10.0]);
+        assert_eq!(latency.mean(), Some(3.625));
+        assert_eq!(latency.quantile(0.5), Some(2.0));
+        // The last bucket is clamped to the largest value.
+        assert_eq!(latency.quantile(1.0), Some(7.0));
+        assert_eq!(HistogramSnapshot::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_from_resource_metrics() {
+        let reader = Arc::new(
+            ManualReader::builder()
+                .with_temporality(Temporality::Cumulative)
+                .build(),
+        );
+        let provider = SdkMeterProvider::builder()
+            .with_reader(InMemoryReader::new(Arc::clone(&reader)))
+            .build();
+        let meter = provider.meter("snapshot_test");
+        let counter = meter.u64_counter("requests").build();
+        counter.add(2, &[]);
+        counter.add(3, &[opentelemetry::KeyValue::new("status", "error")]);
+        let histogram = meter.f64_histogram("latency").build();
+        histogram.record(4.0, &[]);
+        histogram.record(6.0, &[]);
+
+        let mut rm = ResourceMetrics {
+            resource: Resource::builder_empty().build(),
+            scope_metrics: Vec::new(),
+        };
+        reader.collect(&mut rm).unwrap();
+        let snapshot = MetricsSnapshot::from_resource_metrics(&rm);
+        assert_eq!(snapshot.counters["requests"], 2.0);
+        assert_eq!(snapshot.counters["requests{status=error}"], 3.0);
+        let latency = &snapshot.histograms["latency"];
+        assert_eq!(latency.count, 2);
+        assert_eq!(latency.sum, 10.0);
+        assert_eq!(latency.mean(), Some(5.0));
+    }
+}
@@ -24,6 +24,14 @@ pub fn tracing_layer<
 pub fn init_metrics() {
     #[cfg(fbcode_build)]
     {
-        opentelemetry::global::set_meter_provider(crate::meta::meter_provider());
+        // Also attach the snapshot reader, so that metrics snapshots work
+        // alongside the exporters.
+        opentelemetry::global::set_meter_provider(crate::meta::meter_provider(
+            crate::metrics_snapshot::reader(),
+        ));
+    }
+    #[cfg(not(fbcode_build))]
+    {
+        opentelemetry::global::set_meter_provider(crate::metrics_snapshot::meter_provider());
     }
 }
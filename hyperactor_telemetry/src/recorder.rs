@@ -10,12 +10,18 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::io::BufWriter;
+use std::io::Write;
 use std::mem::take;
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::OnceLock;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use dashmap::DashMap;
 use serde::Deserialize;
@@ -26,6 +32,7 @@ use tracing::Span;
 use tracing::Subscriber;
 use tracing::field::Field;
 use tracing::field::Visit;
+use tracing::level_filters::LevelFilter;
 use tracing::span;
 use tracing::span::Attributes;
 use tracing::span::Id;
@@ -35,6 +42,7 @@ use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::registry::Scope;
 
 use crate::SPAN_FIELD_RECORDING;
+use crate::config::FLIGHT_RECORDER_DIR;
 use crate::pool::Pool;
 use crate::spool::Spool;
 
@@ -444,6 +452,148 @@ struct RecordingState {
     spool: Spool<Event>,
 }
 
+/// A process-wide flight recorder: a bounded log of the most recent spans
+/// and events across all threads and actors, recorded regardless of the
+/// span they occur in. Unlike a [`Recording`], which is attached to failures
+/// of individual actors, the flight recorder is meant to be dumped when the
+/// whole process fails, so that its recent history outlives it.
+#[derive(Debug)]
+pub struct FlightRecorder {
+    spool: Spool<Event>,
+    seq: AtomicUsize,
+    level: LevelFilter,
+}
+
+impl FlightRecorder {
+    fn new(cap: usize, level: LevelFilter) -> Self {
+        assert!(cap > 1, "capacity must be > 1");
+        Self {
+            spool: Spool::new(cap),
+            seq: AtomicUsize::new(0),
+            level,
+        }
+    }
+
+    fn record(
+        &self,
+        pool: &Pool<Event>,
+        metadata: &'static Metadata<'static>,
+        record: impl FnOnce(&mut Event),
+    ) {
+        if *metadata.level() > self.level {
+            return;
+        }
+        let mut recorded = pool.get();
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        recorded.reset(SystemTime::now(), metadata, seq);
+        record(&mut recorded);
+        self.spool.push(recorded);
+    }
+
+    /// Retrieve the recorded spans and events, oldest first.
+    pub fn tail(&self) -> Vec<Event> {
+        self.spool.tail()
+    }
+
+    /// Write the recorded spans and events to `path`, one JSON object
+    /// per line, oldest first. The file is replaced atomically.
+    pub fn dump(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(std::fs::File::create(&tmp)?);
+        for event in self.tail() {
+            let metadata = event.metadata;
+            let line = serde_json::json!({
+                "seq": event.seq,
+                "time_us": event
+                    .time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_micros() as u64,
+                "kind": if metadata.is_span() { "span" } else { "event" },
+                "level": metadata.level().as_str(),
+                "target": metadata.target(),
+                "name": metadata.name(),
+                "file": metadata.file(),
+                "line": metadata.line(),
+                "fields": event.json_value(),
+            });
+            serde_json::to_writer(&mut writer, &line)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&tmp, path)
+    }
+}
+
+/// When this process enabled its flight recorder. Together with the pid,
+/// it names the process's dumps, so that they are never mistaken for those
+/// of an earlier process with the same pid.
+static PROCESS_START: OnceLock<SystemTime> = OnceLock::new();
+
+/// The prefix of the names of this process's flight recorder dumps.
+fn dump_prefix() -> PathBuf {
+    let dir = hyperactor_config::global::try_get_cloned(FLIGHT_RECORDER_DIR)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("monarch_flight_recorder"));
+    let start = PROCESS_START
+        .get_or_init(SystemTime::now)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    dir.join(format!("flight_recorder_{}_{}", std::process::id(), start))
+}
+
+/// The file that this process's flight recorder is dumped to when the
+/// process fails (see [`dump_flight_recorder`]), or `None` if the flight
+/// recorder is not enabled. A child reports this path to its parent, so
+/// that the parent can find the dump after the child has exited.
+pub fn flight_recorder_path() -> Option<PathBuf> {
+    crate::recorder().flight_recorder()?;
+    let mut path = dump_prefix().into_os_string();
+    path.push(".jsonl");
+    Some(path.into())
+}
+
+/// Dump the flight recorder of the global [`crate::recorder`] to
+/// [`flight_recorder_path`]. This is meant for a failing process; use
+/// [`snapshot_flight_recorder`] to dump a live one. Returns the path of the
+/// dump, or `None` if the flight recorder is not enabled.
+pub fn dump_flight_recorder() -> std::io::Result<Option<PathBuf>> {
+    let (Some(flight_recorder), Some(path)) =
+        (crate::recorder().flight_recorder(), flight_recorder_path())
+    else {
+        return Ok(None);
+    };
+    flight_recorder.dump(&path)?;
+    Ok(Some(path))
+}
+
+/// Dump the flight recorder of the global [`crate::recorder`] on demand.
+/// Each snapshot is written to its own file, distinct from
+/// [`flight_recorder_path`], so that it is not reported as the dump of a
+/// failed process. Returns the path of the snapshot, or `None` if the
+/// flight recorder is not enabled.
+pub fn snapshot_flight_recorder() -> std::io::Result<Option<PathBuf>> {
+    let Some(flight_recorder) = crate::recorder().flight_recorder() else {
+        return Ok(None);
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros();
+    let mut path = dump_prefix().into_os_string();
+    path.push(format!("_snapshot_{now}.jsonl"));
+    let path = PathBuf::from(path);
+    flight_recorder.dump(&path)?;
+    Ok(Some(path))
+}
+
 /// A recorder captures events from a [`tracing::span`] and records them
 /// to a [`mpsc::UnboundedSender`]. In order to record events, the recorder's
 /// layer ([`Recorder::layer`]) must be installed into the relevant tracing
@@ -457,6 +607,7 @@ struct RecorderState {
     key: Key,
     recordings: Arc<DashMap<KeyRef, Arc<RecordingState>>>,
     pool: Pool<Event>,
+    flight_recorder: OnceLock<FlightRecorder>,
 }
 
 impl Recorder {
@@ -466,6 +617,7 @@ impl Recorder {
             key: Key::new(),
             recordings: Arc::new(DashMap::new()),
             pool: Pool::new(1024),
+            flight_recorder: OnceLock::new(),
         });
         Self { state }
     }
@@ -476,6 +628,21 @@ impl Recorder {
         Recording::new(cap, Arc::clone(&self.state))
     }
 
+    /// Enable this recorder's [`FlightRecorder`], keeping the last `cap`
+    /// spans and events at or above `level`. Only the first call has an
+    /// effect; subsequent calls return the existing flight recorder.
+    pub fn enable_flight_recorder(&self, cap: usize, level: LevelFilter) -> &FlightRecorder {
+        PROCESS_START.get_or_init(SystemTime::now);
+        self.state
+            .flight_recorder
+            .get_or_init(|| FlightRecorder::new(cap, level))
+    }
+
+    /// This recorder's [`FlightRecorder`], if it has been enabled.
+    pub fn flight_recorder(&self) -> Option<&FlightRecorder> {
+        self.state.flight_recorder.get()
+    }
+
     /// The layer associated with this recorder. This layer must be
     /// installed into the relevant tracing subscriber in order to
     /// record events.
@@ -526,6 +693,12 @@ where
 
         attrs.record(&mut visitor);
 
+        if let Some(flight_recorder) = self.state.flight_recorder.get() {
+            flight_recorder.record(&self.state.pool, attrs.metadata(), |recorded| {
+                attrs.record(recorded)
+            });
+        }
+
         if let Some(keys) = visitor.keys() {
             if let Some(span) = ctx.span(id) {
                 let mut extensions: tracing_subscriber::registry::ExtensionsMut<'_> =
@@ -536,6 +709,12 @@ where
     }
 
     fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        if let Some(flight_recorder) = self.state.flight_recorder.get() {
+            flight_recorder.record(&self.state.pool, event.metadata(), |recorded| {
+                event.record(recorded)
+            });
+        }
+
         let Some(scope) = ctx.event_scope(event) else {
             return;
         };
@@ -730,4 +909,55 @@ mod tests {
             }
         });
     }
+
+    #[test]
+    fn test_flight_recorder() {
+        let recorder = Recorder::new();
+        recorder.enable_flight_recorder(4, LevelFilter::DEBUG);
+        tracing::subscriber::with_default(Registry::default().with(recorder.layer()), || {
+            let span = span!(Level::INFO, "work", step = 1u64);
+            let _guard = span.enter();
+            for i in 0..5 {
+                info!("event {}", i);
+            }
+            tracing::trace!("too verbose");
+        });
+
+        let flight_recorder = recorder.flight_recorder().unwrap();
+        let tail = flight_recorder.tail();
+        assert_eq!(tail.len(), 4);
+        assert_eq!(tail[0].json_value(), json!({"message": "event 1"}));
+        assert_eq!(tail[3].json_value(), json!({"message": "event 4"}));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dumps").join("flight.jsonl");
+        flight_recorder.dump(&path).unwrap();
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["kind"], "event");
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[3]["fields"]["message"], "event 4");
+    }
+
+    #[test]
+    fn test_flight_recorder_records_spans() {
+        let recorder = Recorder::new();
+        recorder.enable_flight_recorder(10, LevelFilter::INFO);
+        // Outside of any recording span, but still captured.
+        tracing::subscriber::with_default(Registry::default().with(recorder.layer()), || {
+            let _span = span!(Level::INFO, "work", step = 1u64);
+            tracing::debug!("filtered");
+            info!("kept");
+        });
+
+        let tail = recorder.flight_recorder().unwrap().tail();
+        assert_eq!(tail.len(), 2);
+        assert!(tail[0].metadata.is_span());
+        assert_eq!(tail[0].json_value(), json!({"step": 1}));
+        assert_eq!(tail[1].json_value(), json!({"message": "kept"}));
+    }
 }
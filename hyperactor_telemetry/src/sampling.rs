@@ -0,0 +1,659 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Sampling and rate limiting for the [`TraceEventDispatcher`].
+//!
+//! Three independent mechanisms, all disabled by default:
+//!
+//! - Head-based sampling ([`TRACE_SAMPLING_RULES`]) decides when a span is
+//!   created whether it is kept. A sampled-out span takes its descendants
+//!   and the events inside it with it. Events are also sampled by target.
+//!   Decisions are made on the application thread, so sampled-out spans
+//!   and events never reach the queue.
+//! - Tail-based sampling ([`TRACE_TAIL_SAMPLING_SPANS`]) buffers the named
+//!   spans, with everything inside them, on the worker thread until they
+//!   close. A trace is exported only if it ran for at least
+//!   [`TRACE_TAIL_SAMPLING_THRESHOLD`] or recorded an error.
+//! - Per-sink rate limits ([`TRACE_SINK_RATE_LIMITS`]) cap the spans and
+//!   events per second delivered to a sink.
+//!
+//! Dropped events are counted in the `telemetry.trace_events_sampled_out`
+//! and `telemetry.trace_events_rate_limited` metrics.
+//!
+//! [`TraceEventDispatcher`]: crate::trace_dispatcher::TraceEventDispatcher
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context as _;
+
+use crate::config::TRACE_SAMPLING_RULES;
+use crate::config::TRACE_SINK_RATE_LIMITS;
+use crate::config::TRACE_TAIL_SAMPLING_SPANS;
+use crate::config::TRACE_TAIL_SAMPLING_THRESHOLD;
+use crate::trace_dispatcher::TraceEvent;
+
+crate::declare_static_counter!(
+    TRACE_EVENTS_SAMPLED_OUT,
+    "telemetry.trace_events_sampled_out"
+);
+crate::declare_static_counter!(
+    TRACE_EVENTS_RATE_LIMITED,
+    "telemetry.trace_events_rate_limited"
+);
+
+/// The most events buffered for a single tail sampled trace. Traces that
+/// grow beyond this are exported regardless of their outcome.
+const MAX_TAIL_BUFFERED_EVENTS: usize = 10_000;
+
+/// A head-based sampling rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingRule {
+    /// A span name, or a target. Targets also match their submodules.
+    pub pattern: String,
+    /// The fraction of matching spans and events to keep, in `[0, 1]`.
+    pub rate: f64,
+}
+
+impl SamplingRule {
+    fn matches_target(&self, target: &str) -> bool {
+        target
+            .strip_prefix(self.pattern.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    }
+}
+
+/// Sampling and rate limiting configuration for the dispatcher. The
+/// default configuration keeps everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SamplingConfig {
+    /// Head-based sampling rules. The first matching rule applies.
+    pub head_rules: Vec<SamplingRule>,
+    /// Names of the spans that are tail sampled.
+    pub tail_spans: Vec<String>,
+    /// Minimum duration for a tail sampled span to be kept.
+    pub tail_threshold: Duration,
+    /// Maximum events per second, by sink name.
+    pub sink_rate_limits: HashMap<String, f64>,
+}
+
+impl SamplingConfig {
+    /// The sampling configuration given by the global config.
+    pub fn from_config() -> anyhow::Result<Self> {
+        Ok(Self {
+            head_rules: parse_rules(&hyperactor_config::global::get_cloned(TRACE_SAMPLING_RULES))?,
+            tail_spans: hyperactor_config::global::get_cloned(TRACE_TAIL_SAMPLING_SPANS)
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect(),
+            tail_threshold: hyperactor_config::global::get(TRACE_TAIL_SAMPLING_THRESHOLD),
+            sink_rate_limits: parse_assignments(&hyperactor_config::global::get_cloned(
+                TRACE_SINK_RATE_LIMITS,
+            ))?
+            .into_iter()
+            .map(|(sink, limit)| {
+                anyhow::ensure!(limit > 0.0, "rate limit for {} must be positive", sink);
+                Ok((sink, limit))
+            })
+            .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    pub(crate) fn has_head_rules(&self) -> bool {
+        !self.head_rules.is_empty()
+    }
+
+    /// Decide whether a new span is kept. Rules match the span name, or
+    /// its target.
+    pub(crate) fn sample_span(&self, name: &str, target: &str) -> bool {
+        self.head_rules
+            .iter()
+            .find(|rule| rule.pattern == name || rule.matches_target(target))
+            .is_none_or(|rule| sample(rule.rate))
+    }
+
+    /// Decide whether an event is kept. Rules match the event target.
+    pub(crate) fn sample_event(&self, target: &str) -> bool {
+        self.head_rules
+            .iter()
+            .find(|rule| rule.matches_target(target))
+            .is_none_or(|rule| sample(rule.rate))
+    }
+}
+
+fn sample(rate: f64) -> bool {
+    rand::random::<f64>() < rate
+}
+
+fn parse_assignments(spec: &str) -> anyhow::Result<Vec<(String, f64)>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (key, value) = entry
+                .split_once('=')
+                .with_context(|| format!("expected key=value, got {:?}", entry))?;
+            let value = value
+                .trim()
+                .parse::<f64>()
+                .with_context(|| format!("invalid number in {:?}", entry))?;
+            Ok((key.trim().to_string(), value))
+        })
+        .collect()
+}
+
+fn parse_rules(spec: &str) -> anyhow::Result<Vec<SamplingRule>> {
+    parse_assignments(spec)?
+        .into_iter()
+        .map(|(pattern, rate)| {
+            anyhow::ensure!(
+                (0.0..=1.0).contains(&rate),
+                "sampling rate for {} must be between 0 and 1",
+                pattern
+            );
+            Ok(SamplingRule { pattern, rate })
+        })
+        .collect()
+}
+
+/// A trace rooted at a tail sampled span.
+struct TailTrace {
+    start: std::time::SystemTime,
+    errored: bool,
+    /// Set once the trace outgrew its buffer; its events are then
+    /// dispatched as they arrive.
+    passthrough: bool,
+    buffered: Vec<TraceEvent>,
+}
+
+/// Buffers the traces of tail sampled spans on the worker thread until
+/// their root closes.
+pub(crate) struct TailSampler {
+    spans: HashSet<String>,
+    threshold: Duration,
+    /// Open traces, by root span id.
+    traces: HashMap<u64, TailTrace>,
+    /// The root of each open span that belongs to a trace.
+    roots: HashMap<u64, u64>,
+}
+
+impl TailSampler {
+    pub(crate) fn new(config: &SamplingConfig) -> Option<Self> {
+        if config.tail_spans.is_empty() {
+            return None;
+        }
+        Some(Self {
+            spans: config.tail_spans.iter().cloned().collect(),
+            threshold: config.tail_threshold,
+            traces: HashMap::new(),
+            roots: HashMap::new(),
+        })
+    }
+
+    /// Process `event`, appending the events that are ready to be
+    /// dispatched to `out`. Returns the number of events sampled out.
+    pub(crate) fn process(&mut self, event: TraceEvent, out: &mut Vec<TraceEvent>) -> u64 {
+        let root = match &event {
+            TraceEvent::NewSpan {
+                id,
+                name,
+                parent_id,
+                timestamp,
+                ..
+            } => {
+                if let Some(root) = parent_id.and_then(|parent| self.roots.get(&parent).copied()) {
+                    self.roots.insert(*id, root);
+                    root
+                } else if self.spans.contains(*name) {
+                    self.roots.insert(*id, *id);
+                    self.traces.insert(
+                        *id,
+                        TailTrace {
+                            start: *timestamp,
+                            errored: false,
+                            passthrough: false,
+                            buffered: Vec::new(),
+                        },
+                    );
+                    *id
+                } else {
+                    out.push(event);
+                    return 0;
+                }
+            }
+            TraceEvent::SpanEnter { id, .. } | TraceEvent::SpanExit { id, .. } => {
+                match self.roots.get(id) {
+                    Some(root) => *root,
+                    None => {
+                        out.push(event);
+                        return 0;
+                    }
+                }
+            }
+            TraceEvent::SpanClose { id, timestamp } => match self.roots.remove(id) {
+                Some(root) if root == *id => {
+                    let end = *timestamp;
+                    let mut trace = self
+                        .traces
+                        .remove(&root)
+                        .expect("open trace for tracked root");
+                    trace.buffered.push(event);
+                    let elapsed = end.duration_since(trace.start).unwrap_or_default();
+                    if trace.passthrough || trace.errored || elapsed >= self.threshold {
+                        out.append(&mut trace.buffered);
+                        return 0;
+                    }
+                    return trace.buffered.len() as u64;
+                }
+                Some(root) => root,
+                None => {
+                    out.push(event);
+                    return 0;
+                }
+            },
+            TraceEvent::Event {
+                parent_span, level, ..
+            } => match parent_span.and_then(|parent| self.roots.get(&parent).copied()) {
+                Some(root) => {
+                    if *level == tracing::Level::ERROR {
+                        if let Some(trace) = self.traces.get_mut(&root) {
+                            trace.errored = true;
+                        }
+                    }
+                    root
+                }
+                None => {
+                    out.push(event);
+                    return 0;
+                }
+            },
+        };
+
+        let trace = self
+            .traces
+            .get_mut(&root)
+            .expect("open trace for tracked span");
+        if trace.passthrough {
+            out.push(event);
+        } else {
+            trace.buffered.push(event);
+            if trace.buffered.len() > MAX_TAIL_BUFFERED_EVENTS {
+                trace.passthrough = true;
+                out.append(&mut trace.buffered);
+            }
+        }
+        0
+    }
+
+    /// Release all open traces, e.g. on shutdown.
+    pub(crate) fn drain(&mut self, out: &mut Vec<TraceEvent>) {
+        self.roots.clear();
+        for (_, mut trace) in self.traces.drain() {
+            out.append(&mut trace.buffered);
+        }
+    }
+}
+
+/// A token bucket limiting the spans and events delivered to one sink.
+/// Spans and events inside a rate limited span are dropped with it, so
+/// that sinks never see children of spans they did not receive.
+pub(crate) struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+    dropped_spans: HashSet<u64>,
+    /// Spans and events dropped since the last call to `take_dropped`.
+    dropped: u64,
+}
+
+impl RateLimiter {
+    /// A limiter admitting `rate` events per second, with bursts of up to
+    /// one second's worth of events.
+    pub(crate) fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+            dropped_spans: HashSet::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Whether `event` may be delivered to the sink.
+    pub(crate) fn admit(&mut self, event: &TraceEvent) -> bool {
+        match event {
+            TraceEvent::NewSpan { id, parent_id, .. } => {
+                let parent_dropped =
+                    parent_id.is_some_and(|parent| self.dropped_spans.contains(&parent));
+                if parent_dropped || !self.take() {
+                    self.dropped_spans.insert(*id);
+                    self.dropped += 1;
+                    return false;
+                }
+                true
+            }
+            TraceEvent::SpanEnter { id, .. } | TraceEvent::SpanExit { id, .. } => {
+                !self.dropped_spans.contains(id)
+            }
+            TraceEvent::SpanClose { id, .. } => !self.dropped_spans.remove(id),
+            TraceEvent::Event { parent_span, .. } => {
+                let parent_dropped =
+                    parent_span.is_some_and(|parent| self.dropped_spans.contains(&parent));
+                if parent_dropped || !self.take() {
+                    self.dropped += 1;
+                    return false;
+                }
+                true
+            }
+        }
+    }
+
+    fn take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The number of spans and events dropped since the last call.
+    pub(crate) fn take_dropped(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped)
+    }
+}
+
+/// Record `count` sampled-out events by `sampler` ("head" or "tail").
+pub(crate) fn record_sampled_out(sampler: &'static str, count: u64) {
+    if count > 0 {
+        TRACE_EVENTS_SAMPLED_OUT.add(count, crate::kv_pairs!("sampler" => sampler));
+    }
+}
+
+/// Record `count` events dropped by the rate limit of `sink`.
+pub(crate) fn record_rate_limited(sink: &str, count: u64) {
+    if count > 0 {
+        TRACE_EVENTS_RATE_LIMITED.add(count, crate::kv_pairs!("sink" => sink.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::time::SystemTime;
+
+    use indexmap::IndexMap;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::trace_dispatcher::TraceEventDispatcher;
+    use crate::trace_dispatcher::TraceEventSink;
+
+    fn new_span(id: u64, name: &'static str, parent_id: Option<u64>, at: SystemTime) -> TraceEvent {
+        TraceEvent::NewSpan {
+            id,
+            name,
+            target: "sampling_test",
+            level: tracing::Level::INFO,
+            fields: IndexMap::new(),
+            timestamp: at,
+            parent_id,
+            thread_name: String::new(),
+            file: None,
+            line: None,
+        }
+    }
+
+    fn event(parent_span: Option<u64>, level: tracing::Level) -> TraceEvent {
+        TraceEvent::Event {
+            name: "event",
+            target: "sampling_test",
+            level,
+            fields: IndexMap::new(),
+            timestamp: SystemTime::now(),
+            parent_span,
+            thread_id: String::new(),
+            thread_name: String::new(),
+            module_path: None,
+            file: None,
+            line: None,
+        }
+    }
+
+    fn close(id: u64, at: SystemTime) -> TraceEvent {
+        TraceEvent::SpanClose { id, timestamp: at }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = hyperactor_config::global::lock();
+        assert_eq!(
+            SamplingConfig::from_config().unwrap(),
+            SamplingConfig {
+                tail_threshold: Duration::from_millis(100),
+                ..Default::default()
+            }
+        );
+
+        let _rules = config.override_key(
+            TRACE_SAMPLING_RULES,
+            "handle_message=0.5, hyperactor::mailbox=0".to_string(),
+        );
+        let _spans = config.override_key(TRACE_TAIL_SAMPLING_SPANS, "handle_message,".to_string());
+        let _limits = config.override_key(TRACE_SINK_RATE_LIMITS, "SqliteSink=1000".to_string());
+        let sampling = SamplingConfig::from_config().unwrap();
+        assert_eq!(
+            sampling.head_rules,
+            vec![
+                SamplingRule {
+                    pattern: "handle_message".to_string(),
+                    rate: 0.5,
+                },
+                SamplingRule {
+                    pattern: "hyperactor::mailbox".to_string(),
+                    rate: 0.0,
+                },
+            ]
+        );
+        assert_eq!(sampling.tail_spans, vec!["handle_message".to_string()]);
+        assert_eq!(sampling.sink_rate_limits["SqliteSink"], 1000.0);
+
+        let _bad = config.override_key(TRACE_SAMPLING_RULES, "handle_message=2".to_string());
+        assert!(SamplingConfig::from_config().is_err());
+    }
+
+    #[test]
+    fn test_head_rules() {
+        let sampling = SamplingConfig {
+            head_rules: parse_rules("noisy=0,hyperactor::mailbox=0,hyperactor=1").unwrap(),
+            ..Default::default()
+        };
+        assert!(!sampling.sample_span("noisy", "anything"));
+        assert!(!sampling.sample_span("post", "hyperactor::mailbox::durable"));
+        assert!(sampling.sample_span("post", "hyperactor::mailboxes"));
+        assert!(sampling.sample_span("post", "hyperactor::proc"));
+        assert!(sampling.sample_span("other", "elsewhere"));
+        // Event rules only match targets.
+        assert!(sampling.sample_event("noisy"));
+        assert!(!sampling.sample_event("hyperactor::mailbox"));
+    }
+
+    #[test]
+    fn test_tail_sampling() {
+        let mut sampler = TailSampler::new(&SamplingConfig {
+            tail_spans: vec!["handle_message".to_string()],
+            tail_threshold: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .unwrap();
+        let start = SystemTime::now();
+        let fast = start + Duration::from_millis(10);
+        let slow = start + Duration::from_millis(200);
+        let mut out = Vec::new();
+
+        // Unrelated spans pass straight through.
+        assert_eq!(
+            sampler.process(new_span(1, "other", None, start), &mut out),
+            0
+        );
+        assert_eq!(out.len(), 1);
+        out.clear();
+
+        // A fast trace is dropped along with its children.
+        sampler.process(new_span(2, "handle_message", None, start), &mut out);
+        sampler.process(new_span(3, "child", Some(2), start), &mut out);
+        sampler.process(event(Some(3), tracing::Level::INFO), &mut out);
+        sampler.process(close(3, fast), &mut out);
+        assert!(out.is_empty());
+        assert_eq!(sampler.process(close(2, fast), &mut out), 5);
+        assert!(out.is_empty());
+
+        // A slow trace is kept.
+        sampler.process(new_span(4, "handle_message", None, start), &mut out);
+        sampler.process(event(Some(4), tracing::Level::INFO), &mut out);
+        assert_eq!(sampler.process(close(4, slow), &mut out), 0);
+        assert_eq!(out.len(), 3);
+        out.clear();
+
+        // A fast trace that errored is kept.
+        sampler.process(new_span(5, "handle_message", None, start), &mut out);
+        sampler.process(new_span(6, "child", Some(5), start), &mut out);
+        sampler.process(event(Some(6), tracing::Level::ERROR), &mut out);
+        sampler.process(close(6, fast), &mut out);
+        assert_eq!(sampler.process(close(5, fast), &mut out), 0);
+        assert_eq!(out.len(), 5);
+        assert!(sampler.roots.is_empty());
+        assert!(sampler.traces.is_empty());
+    }
+
+    #[test]
+    fn test_tail_sampling_overflow() {
+        let mut sampler = TailSampler::new(&SamplingConfig {
+            tail_spans: vec!["handle_message".to_string()],
+            tail_threshold: Duration::from_secs(60),
+            ..Default::default()
+        })
+        .unwrap();
+        let start = SystemTime::now();
+        let mut out = Vec::new();
+        sampler.process(new_span(1, "handle_message", None, start), &mut out);
+        for _ in 0..MAX_TAIL_BUFFERED_EVENTS {
+            sampler.process(event(Some(1), tracing::Level::INFO), &mut out);
+        }
+        assert_eq!(out.len(), MAX_TAIL_BUFFERED_EVENTS + 1);
+        sampler.process(event(Some(1), tracing::Level::INFO), &mut out);
+        assert_eq!(sampler.process(close(1, start), &mut out), 0);
+        assert_eq!(out.len(), MAX_TAIL_BUFFERED_EVENTS + 3);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let start = SystemTime::now();
+        let mut limiter = RateLimiter::new(2.0);
+        assert!(limiter.admit(&event(None, tracing::Level::INFO)));
+        assert!(limiter.admit(&new_span(1, "span", None, start)));
+        // The bucket is empty: the span is dropped with its children.
+        assert!(!limiter.admit(&new_span(2, "span", None, start)));
+        assert!(!limiter.admit(&TraceEvent::SpanEnter {
+            id: 2,
+            timestamp: start
+        }));
+        assert!(!limiter.admit(&new_span(3, "child", Some(2), start)));
+        assert!(!limiter.admit(&event(Some(3), tracing::Level::INFO)));
+        assert!(!limiter.admit(&close(3, start)));
+        assert!(!limiter.admit(&close(2, start)));
+        // Span lifecycle events of admitted spans are not limited.
+        assert!(limiter.admit(&close(1, start)));
+        assert_eq!(limiter.take_dropped(), 3);
+        assert_eq!(limiter.take_dropped(), 0);
+        assert!(limiter.dropped_spans.is_empty());
+
+        std::thread::sleep(Duration::from_millis(600));
+        assert!(limiter.admit(&event(None, tracing::Level::INFO)));
+    }
+
+    #[derive(Clone, Default)]
+    struct CollectingSink(Arc<Mutex<Vec<TraceEvent>>>);
+
+    impl TraceEventSink for CollectingSink {
+        fn consume(&mut self, event: &TraceEvent) -> Result<(), anyhow::Error> {
+            self.0.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), anyhow::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dispatcher_sampling() {
+        let sink = CollectingSink::default();
+        let sampling = SamplingConfig {
+            head_rules: parse_rules("dropped=0,noisy=0").unwrap(),
+            tail_spans: vec!["handle_message".to_string()],
+            tail_threshold: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let subscriber = tracing_subscriber::registry().with(
+            TraceEventDispatcher::new_with_sampling(vec![Box::new(sink.clone())], None, sampling),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("kept").in_scope(|| {
+                tracing::info!("in kept");
+            });
+            tracing::info_span!("dropped").in_scope(|| {
+                tracing::info_span!("child").in_scope(|| {
+                    tracing::info!("in dropped");
+                });
+            });
+            tracing::info!(target: "noisy", "noisy");
+            tracing::info_span!("handle_message").in_scope(|| {
+                tracing::info!("fast");
+            });
+            tracing::info_span!("handle_message").in_scope(|| {
+                tracing::error!("failed");
+            });
+        });
+
+        let events = sink.0.lock().unwrap();
+        let spans: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::NewSpan { name, .. } => Some(*name),
+                _ => None,
+            })
+            .collect();
+        let messages: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::Event { fields, .. } => Some(format!("{:?}", fields["message"])),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spans, vec!["kept", "handle_message"]);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("in kept"));
+        assert!(messages[1].contains("failed"));
+        // Every span that was dispatched was also closed.
+        let closes = events
+            .iter()
+            .filter(|event| matches!(event, TraceEvent::SpanClose { .. }))
+            .count();
+        assert_eq!(closes, 2);
+    }
+}
@@ -0,0 +1,307 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! JSON-lines sink for trace events.
+//!
+//! Writes one JSON object per trace event. This is the reference
+//! implementation of an externally pluggable sink: it only uses the public
+//! [`TraceEventSink`] API, and registers itself with the dispatcher through
+//! a [`TraceEventSinkFactory`] enabled by `JSONL_TRACE_FILE`.
+
+use std::fs::OpenOptions;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::declare_attrs;
+use indexmap::IndexMap;
+use serde_json::Value;
+use serde_json::json;
+use tracing_subscriber::filter::Targets;
+
+use crate::trace_dispatcher::FieldValue;
+use crate::trace_dispatcher::TraceEvent;
+use crate::trace_dispatcher::TraceEventSink;
+use crate::trace_dispatcher::TraceEventSinkFactory;
+
+declare_attrs! {
+    /// If set, trace events are written as JSON lines to this file.
+    /// `{pid}` in the path is replaced with the process id.
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_JSONL_TRACE_FILE".to_string()),
+        py_name: Some("jsonl_trace_file".to_string()),
+    })
+    pub attr JSONL_TRACE_FILE: String;
+
+    /// Target filter for the JSON-lines sink, in `tracing` directive
+    /// syntax, e.g. "info,hyperactor::mailbox=debug".
+    @meta(CONFIG = ConfigAttr {
+        env_name: Some("MONARCH_JSONL_TRACE_FILTER".to_string()),
+        py_name: Some("jsonl_trace_filter".to_string()),
+    })
+    pub attr JSONL_TRACE_FILTER: String = "info".to_string();
+}
+
+inventory::submit! {
+    TraceEventSinkFactory {
+        name: "jsonl",
+        create: create_from_config,
+    }
+}
+
+fn create_from_config() -> anyhow::Result<Option<Box<dyn TraceEventSink>>> {
+    let Some(path) = hyperactor_config::global::try_get_cloned(JSONL_TRACE_FILE) else {
+        return Ok(None);
+    };
+    let path = path.replace("{pid}", &std::process::id().to_string());
+    let targets = Targets::from_str(&hyperactor_config::global::get_cloned(JSONL_TRACE_FILTER))?;
+    Ok(Some(Box::new(
+        JsonLinesSink::new_with_file(path)?.with_target_filter(targets),
+    )))
+}
+
+/// Sink that writes each trace event as a single line of JSON.
+pub struct JsonLinesSink {
+    writer: Box<dyn Write + Send>,
+    targets: Option<Targets>,
+    line: Vec<u8>,
+}
+
+impl JsonLinesSink {
+    /// Create a sink writing to `writer`.
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer,
+            targets: None,
+            line: Vec::new(),
+        }
+    }
+
+    /// Create a sink appending to the file at `path`.
+    pub fn new_with_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        Ok(Self::new(Box::new(BufWriter::new(file))))
+    }
+
+    /// Only consume spans and events matching `targets`.
+    pub fn with_target_filter(mut self, targets: Targets) -> Self {
+        self.targets = Some(targets);
+        self
+    }
+}
+
+fn timestamp_us(timestamp: &SystemTime) -> u64 {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+fn fields_to_json(fields: &IndexMap<String, FieldValue>) -> Value {
+    fields
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                FieldValue::Bool(b) => json!(b),
+                FieldValue::I64(i) => json!(i),
+                FieldValue::U64(u) => json!(u),
+                FieldValue::F64(f) => json!(f),
+                FieldValue::Str(s) | FieldValue::Debug(s) => json!(s),
+            };
+            (key.clone(), value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// The JSON representation of `event`.
+pub fn to_json(event: &TraceEvent) -> Value {
+    match event {
+        TraceEvent::NewSpan {
+            id,
+            name,
+            target,
+            level,
+            fields,
+            timestamp,
+            parent_id,
+            thread_name,
+            file,
+            line,
+        } => json!({
+            "type": "new_span",
+            "timestamp_us": timestamp_us(timestamp),
+            "id": id,
+            "name": name,
+            "target": target,
+            "level": level.as_str(),
+            "fields": fields_to_json(fields),
+            "parent_id": parent_id,
+            "thread_name": thread_name,
+            "file": file,
+            "line": line,
+        }),
+        TraceEvent::SpanEnter { id, timestamp } => json!({
+            "type": "span_enter",
+            "timestamp_us": timestamp_us(timestamp),
+            "id": id,
+        }),
+        TraceEvent::SpanExit { id, timestamp } => json!({
+            "type": "span_exit",
+            "timestamp_us": timestamp_us(timestamp),
+            "id": id,
+        }),
+        TraceEvent::SpanClose { id, timestamp } => json!({
+            "type": "span_close",
+            "timestamp_us": timestamp_us(timestamp),
+            "id": id,
+        }),
+        TraceEvent::Event {
+            name,
+            target,
+            level,
+            fields,
+            timestamp,
+            parent_span,
+            thread_id,
+            thread_name,
+            module_path,
+            file,
+            line,
+        } => json!({
+            "type": "event",
+            "timestamp_us": timestamp_us(timestamp),
+            "name": name,
+            "target": target,
+            "level": level.as_str(),
+            "fields": fields_to_json(fields),
+            "parent_span": parent_span,
+            "thread_id": thread_id,
+            "thread_name": thread_name,
+            "module_path": module_path,
+            "file": file,
+            "line": line,
+        }),
+    }
+}
+
+impl TraceEventSink for JsonLinesSink {
+    fn consume(&mut self, event: &TraceEvent) -> Result<(), anyhow::Error> {
+        self.line.clear();
+        serde_json::to_writer(&mut self.line, &to_json(event))?;
+        self.line.push(b'\n');
+        self.writer.write_all(&self.line)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "JsonLinesSink"
+    }
+
+    fn target_filter(&self) -> Option<&Targets> {
+        self.targets.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::trace_dispatcher::TraceEventDispatcher;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_jsonl_sink() {
+        let buf = SharedBuf::default();
+        let sink = JsonLinesSink::new(Box::new(buf.clone()))
+            .with_target_filter(Targets::from_str("jsonl_test=debug").unwrap());
+        let subscriber = tracing_subscriber::registry()
+            .with(TraceEventDispatcher::new(vec![Box::new(sink)], None));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!(target: "jsonl_test", "work", step = 3u64);
+            let _guard = span.enter();
+            tracing::info!(target: "jsonl_test", ok = true, "hello");
+            tracing::info!(target: "elsewhere", "filtered out");
+            tracing::trace!(target: "jsonl_test", "too verbose");
+        });
+        // Dropping the subscriber joins the worker, flushing the sink.
+
+        let contents = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let types: Vec<&str> = lines
+            .iter()
+            .map(|line| line["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            vec!["new_span", "span_enter", "event", "span_exit", "span_close"]
+        );
+
+        assert_eq!(lines[0]["name"], "work");
+        assert_eq!(lines[0]["level"], "DEBUG");
+        assert_eq!(lines[0]["fields"]["step"], 3);
+        assert_eq!(lines[2]["fields"]["message"], "hello");
+        assert_eq!(lines[2]["fields"]["ok"], true);
+        assert_eq!(lines[2]["parent_span"], lines[0]["id"]);
+    }
+
+    #[test]
+    fn test_jsonl_factory() {
+        let factory = inventory::iter::<TraceEventSinkFactory>
+            .into_iter()
+            .find(|factory| factory.name == "jsonl")
+            .expect("jsonl factory is registered");
+
+        let config = hyperactor_config::global::lock();
+        assert!((factory.create)().unwrap().is_none());
+
+        let path = std::env::temp_dir().join("jsonl_factory_test_{pid}.jsonl");
+        let _guard = config.override_key(JSONL_TRACE_FILE, path.display().to_string());
+        let mut sink = (factory.create)().unwrap().expect("sink is enabled");
+        sink.flush().unwrap();
+        drop(sink);
+
+        let resolved =
+            std::env::temp_dir().join(format!("jsonl_factory_test_{}.jsonl", std::process::id()));
+        assert!(resolved.exists());
+        std::fs::remove_file(resolved).unwrap();
+    }
+}
@@ -7,9 +7,10 @@
  */
 
 //! Exporters for the unified telemetry layer.
-//! Each exporter implements the TraceExporter trait and handles
+//! Each exporter implements the TraceEventSink trait and handles
 //! writing events to a specific backend (SQLite, Scuba, glog, etc).
 
 pub mod glog;
+pub mod jsonl;
 pub mod perfetto;
 pub mod sqlite;
@@ -104,6 +104,7 @@ impl SqliteSink {
             // We only batch Event variants in consume(), so this match is guaranteed to succeed
             let TraceEvent::Event {
                 target,
+                level,
                 fields,
                 timestamp,
                 module_path,
@@ -143,6 +144,11 @@ impl SqliteSink {
                     .insert("file".to_string(), JValue::String(f.to_string()));
             }
 
+            visitor.0.insert(
+                "level".to_string(),
+                JValue::String(level.as_str().to_string()),
+            );
+
             for (key, value) in fields {
                 let json_value = match value {
                     FieldValue::Bool(b) => JValue::Bool(*b),
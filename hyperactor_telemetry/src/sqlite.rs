@@ -127,6 +127,7 @@ lazy_static! {
             "name",
             "supervised_actor",
             "actor_status",
+            "time_us",
             "module_path",
             "line",
             "file",
@@ -142,6 +143,9 @@ lazy_static! {
             "src",
             "dest",
             "payload",
+            "sender",
+            "message_type",
+            "elapsed_us",
             "module_path",
             "line",
             "file",
@@ -224,6 +228,16 @@ macro_rules! insert_event {
         );
         v.0.insert("line".to_string(), meta.line().into());
         v.0.insert("file".to_string(), meta.file().map(String::from).into());
+        v.0.entry("level".to_string())
+            .or_insert_with(|| meta.level().as_str().into());
+        v.0.entry("time_us".to_string()).or_insert_with(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros()
+                .to_string()
+                .into()
+        });
         $conn.prepare_cached(&$table.insert_stmt)?.execute(
             serde_rusqlite::to_params_named_with_fields(v, $table.columns)?
                 .to_slice()
@@ -9,6 +9,15 @@
 //! Unified telemetry layer that captures trace events once and fans out to multiple exporters
 //! on a background thread, eliminating redundant capture and moving work off the application
 //! thread.
+//!
+//! Crates outside of `hyperactor_telemetry` can add their own exporters by implementing
+//! [`TraceEventSink`] and registering a [`TraceEventSinkFactory`] with `inventory::submit!`.
+//! Registered factories are invoked when logging is initialized with the unified layer; each
+//! decides from the current configuration whether its sink is enabled. See
+//! [`crate::sinks::jsonl`] for a reference implementation.
+//!
+//! The dispatcher can sample and rate limit the events it captures; see
+//! [`crate::sampling`].
 
 use std::sync::Arc;
 use std::sync::atomic::AtomicU64;
@@ -26,13 +35,18 @@ use tracing_subscriber::layer::Context;
 use tracing_subscriber::layer::Layer;
 use tracing_subscriber::registry::LookupSpan;
 
+use crate::sampling;
+use crate::sampling::RateLimiter;
+use crate::sampling::SamplingConfig;
+use crate::sampling::TailSampler;
+
 const QUEUE_CAPACITY: usize = 100_000;
 
 /// Unified representation of a trace event captured from the tracing layer.
 /// This is captured once on the application thread, then sent to the background
 /// worker for fan-out to multiple exporters.
 #[derive(Debug, Clone)]
-pub(crate) enum TraceEvent {
+pub enum TraceEvent {
     /// A new span was created (on_new_span)
     NewSpan {
         id: u64,
@@ -70,7 +84,7 @@ pub(crate) enum TraceEvent {
 
 /// Simplified field value representation for trace events
 #[derive(Debug, Clone)]
-pub(crate) enum FieldValue {
+pub enum FieldValue {
     Bool(bool),
     I64(i64),
     U64(u64),
@@ -82,7 +96,7 @@ pub(crate) enum FieldValue {
 /// Trait for sinks that receive trace events from the dispatcher.
 /// Implementations run on the background worker thread and can perform
 /// expensive I/O operations without blocking the application.
-pub(crate) trait TraceEventSink: Send + 'static {
+pub trait TraceEventSink: Send + 'static {
     /// Consume a single event. Called on background thread.
     fn consume(&mut self, event: &TraceEvent) -> Result<(), anyhow::Error>;
 
@@ -119,6 +133,42 @@ pub(crate) trait TraceEventSink: Send + 'static {
     }
 }
 
+/// A factory for [`TraceEventSink`]s, registered with `inventory::submit!`:
+///
+/// ```ignore
+/// inventory::submit! {
+///     TraceEventSinkFactory {
+///         name: "my_backend",
+///         create: || Ok(Some(Box::new(MyBackendSink::new()?))),
+///     }
+/// }
+/// ```
+pub struct TraceEventSinkFactory {
+    /// The name of the sink, used in diagnostics.
+    pub name: &'static str,
+    /// Create the sink. Returns `None` if the sink is not enabled by the
+    /// current configuration.
+    pub create: fn() -> anyhow::Result<Option<Box<dyn TraceEventSink>>>,
+}
+
+inventory::collect!(TraceEventSinkFactory);
+
+/// Create the sinks of all registered [`TraceEventSinkFactory`]s that are
+/// enabled. Factories that fail are reported and skipped.
+pub(crate) fn registered_sinks() -> Vec<Box<dyn TraceEventSink>> {
+    let mut sinks = Vec::new();
+    for factory in inventory::iter::<TraceEventSinkFactory> {
+        match (factory.create)() {
+            Ok(Some(sink)) => sinks.push(sink),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("[telemetry] failed to create sink {}: {}", factory.name, e);
+            }
+        }
+    }
+    sinks
+}
+
 /// The trace event dispatcher that captures events once and dispatches to multiple sinks
 /// on a background thread.
 pub struct TraceEventDispatcher {
@@ -128,8 +178,15 @@ pub struct TraceEventDispatcher {
     _worker_handle: WorkerHandle,
     max_level: Option<tracing::level_filters::LevelFilter>,
     dropped_events: Arc<AtomicU64>,
+    sampling: SamplingConfig,
+    /// Spans and events dropped by head-based sampling, reported to
+    /// metrics by the worker.
+    sampled_out: Arc<AtomicU64>,
 }
 
+/// Span extension marking a span dropped by head-based sampling.
+struct SampledOut;
+
 struct WorkerHandle {
     join_handle: Option<JoinHandle<()>>,
 }
@@ -144,19 +201,39 @@ impl TraceEventDispatcher {
     /// # Arguments
     /// * `sinks` - List of sinks to dispatch events to.
     /// * `max_level` - Maximum level filter hint (None for no filtering)
-    pub(crate) fn new(
+    pub fn new(
+        sinks: Vec<Box<dyn TraceEventSink>>,
+        max_level: Option<tracing::level_filters::LevelFilter>,
+    ) -> Self {
+        Self::new_with_sampling(sinks, max_level, SamplingConfig::default())
+    }
+
+    /// Create a new trace event dispatcher that samples and rate limits
+    /// events according to `sampling`.
+    pub fn new_with_sampling(
         sinks: Vec<Box<dyn TraceEventSink>>,
         max_level: Option<tracing::level_filters::LevelFilter>,
+        sampling: SamplingConfig,
     ) -> Self {
         let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
         let (dropped_sender, dropped_receiver) = mpsc::channel();
         let dropped_events = Arc::new(AtomicU64::new(0));
         let dropped_events_worker = Arc::clone(&dropped_events);
+        let sampled_out = Arc::new(AtomicU64::new(0));
+        let sampled_out_worker = Arc::clone(&sampled_out);
+        let sampling_worker = sampling.clone();
 
         let worker_handle = std::thread::Builder::new()
             .name("telemetry-worker".into())
             .spawn(move || {
-                worker_loop(receiver, dropped_receiver, sinks, dropped_events_worker);
+                worker_loop(
+                    receiver,
+                    dropped_receiver,
+                    sinks,
+                    dropped_events_worker,
+                    sampling_worker,
+                    sampled_out_worker,
+                );
             })
             .expect("failed to spawn telemetry worker thread");
 
@@ -168,9 +245,26 @@ impl TraceEventDispatcher {
             },
             max_level,
             dropped_events,
+            sampling,
+            sampled_out,
         }
     }
 
+    /// Whether the span `id` was dropped by head-based sampling.
+    fn is_sampled_out<S>(&self, id: &Id, ctx: &Context<'_, S>) -> bool
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        self.sampling.has_head_rules()
+            && ctx
+                .span(id)
+                .is_some_and(|span| span.extensions().get::<SampledOut>().is_some())
+    }
+
+    fn record_sampled_out(&self) {
+        self.sampled_out.fetch_add(1, Ordering::Relaxed);
+    }
+
     fn send_event(&self, event: TraceEvent) {
         if let Some(sender) = &self.sender {
             if let Err(mpsc::TrySendError::Full(_)) = sender.try_send(event) {
@@ -258,16 +352,32 @@ where
 {
     fn on_new_span(&self, attrs: &tracing::span::Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
         let metadata = attrs.metadata();
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.clone()),
+            None => ctx.current_span().id().cloned(),
+        };
+
+        if self.sampling.has_head_rules()
+            && (parent
+                .as_ref()
+                .is_some_and(|parent| self.is_sampled_out(parent, &ctx))
+                || !self
+                    .sampling
+                    .sample_span(metadata.name(), metadata.target()))
+        {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(SampledOut);
+            }
+            self.record_sampled_out();
+            return;
+        }
+
         let mut fields = IndexMap::new();
 
         let mut visitor = FieldVisitor(&mut fields);
         attrs.record(&mut visitor);
 
-        let parent_id = if let Some(parent) = attrs.parent() {
-            Some(parent.into_u64())
-        } else {
-            ctx.current_span().id().map(|id| id.into_u64())
-        };
+        let parent_id = parent.map(|parent| parent.into_u64());
 
         let thread_name = std::thread::current()
             .name()
@@ -290,7 +400,10 @@ where
         self.send_event(event);
     }
 
-    fn on_enter(&self, id: &Id, _ctx: Context<'_, S>) {
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if self.is_sampled_out(id, &ctx) {
+            return;
+        }
         let event = TraceEvent::SpanEnter {
             id: id.into_u64(),
             timestamp: SystemTime::now(),
@@ -299,7 +412,10 @@ where
         self.send_event(event);
     }
 
-    fn on_exit(&self, id: &Id, _ctx: Context<'_, S>) {
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if self.is_sampled_out(id, &ctx) {
+            return;
+        }
         let event = TraceEvent::SpanExit {
             id: id.into_u64(),
             timestamp: SystemTime::now(),
@@ -310,6 +426,16 @@ where
 
     fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
         let metadata = event.metadata();
+        if self.sampling.has_head_rules()
+            && (ctx
+                .event_span(event)
+                .is_some_and(|span| span.extensions().get::<SampledOut>().is_some())
+                || !self.sampling.sample_event(metadata.target()))
+        {
+            self.record_sampled_out();
+            return;
+        }
+
         let mut fields = IndexMap::new();
         let mut visitor = FieldVisitor(&mut fields);
         event.record(&mut visitor);
@@ -357,7 +483,10 @@ where
         self.send_event(trace_event);
     }
 
-    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if self.is_sampled_out(&id, &ctx) {
+            return;
+        }
         let event = TraceEvent::SpanClose {
             id: id.into_u64(),
             timestamp: SystemTime::now(),
@@ -407,30 +536,61 @@ impl<'a> tracing::field::Visit for FieldVisitor<'a> {
     }
 }
 
-/// Background worker loop that receives events from both regular and priority channels,
-/// and dispatches them to sinks. Priority events are processed first.
-/// Runs until both senders are dropped.
-fn worker_loop(
-    receiver: mpsc::Receiver<TraceEvent>,
-    dropped_receiver: mpsc::Receiver<TraceEvent>,
-    mut sinks: Vec<Box<dyn TraceEventSink>>,
-    dropped_events: Arc<AtomicU64>,
-) {
-    const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
-    const FLUSH_EVENT_COUNT: usize = 1000;
-    let mut last_flush = std::time::Instant::now();
-    let mut events_since_flush = 0;
+/// The sinks of a worker, with the sampling and rate limiting applied to
+/// the events dispatched to them.
+struct WorkerSinks {
+    sinks: Vec<Box<dyn TraceEventSink>>,
+    /// The rate limiter of each sink, if it has one.
+    limiters: Vec<Option<RateLimiter>>,
+    tail_sampler: Option<TailSampler>,
+    /// Scratch space for the events released by the tail sampler.
+    ready: Vec<TraceEvent>,
+    /// Events dropped by head-based sampling on the application threads.
+    head_sampled_out: Arc<AtomicU64>,
+    tail_sampled_out: u64,
+}
 
-    fn flush_sinks(sinks: &mut [Box<dyn TraceEventSink>]) {
-        for sink in sinks {
-            if let Err(e) = sink.flush() {
-                eprintln!("[telemetry] sink {} failed to flush: {}", sink.name(), e);
+impl WorkerSinks {
+    fn new(
+        sinks: Vec<Box<dyn TraceEventSink>>,
+        sampling: &SamplingConfig,
+        head_sampled_out: Arc<AtomicU64>,
+    ) -> Self {
+        let limiters = sinks
+            .iter()
+            .map(|sink| {
+                sampling
+                    .sink_rate_limits
+                    .get(sink.name())
+                    .map(|rate| RateLimiter::new(*rate))
+            })
+            .collect();
+        Self {
+            sinks,
+            limiters,
+            tail_sampler: TailSampler::new(sampling),
+            ready: Vec::new(),
+            head_sampled_out,
+            tail_sampled_out: 0,
+        }
+    }
+
+    fn dispatch(&mut self, event: TraceEvent) {
+        match &mut self.tail_sampler {
+            Some(tail_sampler) => {
+                self.tail_sampled_out += tail_sampler.process(event, &mut self.ready);
+                let mut ready = std::mem::take(&mut self.ready);
+                for event in ready.drain(..) {
+                    self.dispatch_to_sinks(event);
+                }
+                self.ready = ready;
             }
+            None => self.dispatch_to_sinks(event),
         }
     }
 
-    fn dispatch_to_sinks(sinks: &mut [Box<dyn TraceEventSink>], event: TraceEvent) {
-        for sink in sinks {
+    fn dispatch_to_sinks(&mut self, event: TraceEvent) {
+        for (sink, limiter) in self.sinks.iter_mut().zip(&mut self.limiters) {
             if match &event {
                 TraceEvent::NewSpan { target, level, .. }
                 | TraceEvent::Event { target, level, .. } => match sink.target_filter() {
@@ -438,7 +598,8 @@ fn worker_loop(
                     None => true,
                 },
                 _ => true,
-            } {
+            } && limiter.as_mut().is_none_or(|limiter| limiter.admit(&event))
+            {
                 if let Err(e) = sink.consume(&event) {
                     eprintln!(
                         "[telemetry] sink {} failed to consume event: {}",
@@ -450,26 +611,71 @@ fn worker_loop(
         }
     }
 
+    fn flush(&mut self) {
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.flush() {
+                eprintln!("[telemetry] sink {} failed to flush: {}", sink.name(), e);
+            }
+        }
+
+        sampling::record_sampled_out("head", self.head_sampled_out.swap(0, Ordering::Relaxed));
+        sampling::record_sampled_out("tail", std::mem::take(&mut self.tail_sampled_out));
+        for (sink, limiter) in self.sinks.iter().zip(&mut self.limiters) {
+            if let Some(limiter) = limiter {
+                sampling::record_rate_limited(sink.name(), limiter.take_dropped());
+            }
+        }
+    }
+
+    /// Dispatch the traces still held by the tail sampler and flush.
+    fn finish(&mut self) {
+        if let Some(tail_sampler) = &mut self.tail_sampler {
+            tail_sampler.drain(&mut self.ready);
+            for event in std::mem::take(&mut self.ready) {
+                self.dispatch_to_sinks(event);
+            }
+        }
+        self.flush();
+    }
+}
+
+/// Background worker loop that receives events from both regular and priority channels,
+/// and dispatches them to sinks. Priority events are processed first.
+/// Runs until both senders are dropped.
+fn worker_loop(
+    receiver: mpsc::Receiver<TraceEvent>,
+    dropped_receiver: mpsc::Receiver<TraceEvent>,
+    sinks: Vec<Box<dyn TraceEventSink>>,
+    dropped_events: Arc<AtomicU64>,
+    sampling: SamplingConfig,
+    sampled_out: Arc<AtomicU64>,
+) {
+    const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+    const FLUSH_EVENT_COUNT: usize = 1000;
+    let mut last_flush = std::time::Instant::now();
+    let mut events_since_flush = 0;
+    let mut sinks = WorkerSinks::new(sinks, &sampling, sampled_out);
+
     loop {
         while let Ok(event) = dropped_receiver.try_recv() {
-            dispatch_to_sinks(&mut sinks, event);
+            sinks.dispatch(event);
             events_since_flush += 1;
         }
 
         match receiver.recv_timeout(FLUSH_INTERVAL) {
             Ok(event) => {
-                dispatch_to_sinks(&mut sinks, event);
+                sinks.dispatch(event);
                 events_since_flush += 1;
 
                 if events_since_flush >= FLUSH_EVENT_COUNT || last_flush.elapsed() >= FLUSH_INTERVAL
                 {
-                    flush_sinks(&mut sinks);
+                    sinks.flush();
                     last_flush = std::time::Instant::now();
                     events_since_flush = 0;
                 }
             }
             Err(mpsc::RecvTimeoutError::Timeout) => {
-                flush_sinks(&mut sinks);
+                sinks.flush();
                 last_flush = std::time::Instant::now();
                 events_since_flush = 0;
             }
@@ -480,13 +686,13 @@ fn worker_loop(
     }
 
     while let Ok(event) = dropped_receiver.try_recv() {
-        dispatch_to_sinks(&mut sinks, event);
+        sinks.dispatch(event);
     }
     while let Ok(event) = receiver.try_recv() {
-        dispatch_to_sinks(&mut sinks, event);
+        sinks.dispatch(event);
     }
 
-    flush_sinks(&mut sinks);
+    sinks.finish();
 
     let total_dropped = dropped_events.load(Ordering::Relaxed);
     if total_dropped > 0 {
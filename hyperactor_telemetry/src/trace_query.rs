@@ -0,0 +1,413 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Offline queries over the sqlite trace databases written by
+//! [`crate::sqlite::SqliteTracing`] and the sqlite sink.
+//!
+//! Each proc writes its own database. [`TraceDb::open`] loads any number of
+//! them into a single in-memory database with the same tables, plus a
+//! leading `proc` column naming the database each row came from, so that
+//! [`Report`]s and ad-hoc SQL can be run across a whole job at once.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use rusqlite::Connection;
+use rusqlite::types::ValueRef;
+use serde_json::Value;
+
+use crate::sqlite::TableName;
+
+/// The tables copied from each trace database.
+const TABLES: [TableName; 3] = [
+    TableName::ActorLifecycle,
+    TableName::Messages,
+    TableName::LogEvents,
+];
+
+/// Actor statuses after which an actor is no longer running.
+const TERMINAL_STATUSES: &[&str] = &["Stopped", "Failed"];
+
+/// A set of per-proc trace databases, merged for querying.
+pub struct TraceDb {
+    conn: Connection,
+    procs: Vec<String>,
+}
+
+impl TraceDb {
+    /// Load the trace databases at `paths`. A directory contributes every
+    /// `*.db` file directly inside it. Rows are labeled with the file stem
+    /// of the database they came from.
+    pub fn open(paths: &[impl AsRef<Path>]) -> Result<Self> {
+        let mut files = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            if path.is_dir() {
+                let mut entries = std::fs::read_dir(path)
+                    .with_context(|| format!("reading {}", path.display()))?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<std::io::Result<Vec<PathBuf>>>()?;
+                entries.retain(|entry| entry.extension().is_some_and(|ext| ext == "db"));
+                entries.sort();
+                files.extend(entries);
+            } else {
+                files.push(path.to_path_buf());
+            }
+        }
+        anyhow::ensure!(!files.is_empty(), "no trace databases found");
+
+        let conn = Connection::open_in_memory()?;
+        for table in TABLES {
+            let columns = table
+                .get_table()
+                .columns
+                .iter()
+                .map(|col| format!("{col} TEXT"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            conn.execute(
+                &format!(
+                    "create table {} (proc TEXT, seq INTEGER, {columns})",
+                    table.as_str()
+                ),
+                [],
+            )?;
+        }
+
+        let mut db = Self {
+            conn,
+            procs: Vec::new(),
+        };
+        for file in files {
+            db.load(&file)
+                .with_context(|| format!("loading {}", file.display()))?;
+        }
+        Ok(db)
+    }
+
+    /// Copy the tables of the database at `path` into the merged database.
+    /// Tables or columns missing from `path` (e.g., from an older schema)
+    /// are skipped or left NULL.
+    fn load(&mut self, path: &Path) -> Result<()> {
+        anyhow::ensure!(path.is_file(), "no such file");
+        let proc = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+
+        self.conn.execute(
+            "attach database ?1 as src",
+            [path.to_string_lossy().as_ref()],
+        )?;
+        let result = (|| {
+            let tx = self.conn.transaction()?;
+            for table in TABLES {
+                let name = table.as_str();
+                let present: Vec<String> = tx
+                    .prepare("select name from pragma_table_info(?1, 'src')")?
+                    .query_map([name], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+                if present.is_empty() {
+                    continue;
+                }
+                let columns = std::iter::once("seq")
+                    .chain(table.get_table().columns.iter().copied())
+                    .filter(|col| present.iter().any(|p| p == col))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                tx.execute(
+                    &format!(
+                        "insert into main.{name} (proc, {columns}) \
+                         select ?1, {columns} from src.{name}"
+                    ),
+                    [&proc],
+                )?;
+            }
+            tx.commit()
+        })();
+        self.conn.execute("detach database src", [])?;
+        result?;
+
+        self.procs.push(proc);
+        Ok(())
+    }
+
+    /// The labels of the loaded databases, in load order.
+    pub fn procs(&self) -> &[String] {
+        &self.procs
+    }
+
+    /// Run a canned report.
+    pub fn report(&self, report: &Report) -> Result<QueryResult> {
+        match report {
+            Report::Actors => self.query(ACTORS_SQL, &[]),
+            Report::SlowestHandlers { limit } => {
+                self.query(SLOWEST_HANDLERS_SQL, &[&(*limit as i64)])
+            }
+            Report::MessagePath { actor } => self.query(MESSAGE_PATH_SQL, &[actor]),
+            Report::NeverStopped => self.query(
+                &NEVER_STOPPED_SQL.replace(
+                    "{terminal}",
+                    &TERMINAL_STATUSES
+                        .iter()
+                        .map(|status| format!("'{status}'"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+                &[],
+            ),
+            Report::ErrorsByProc => self.query(ERRORS_BY_PROC_SQL, &[]),
+        }
+    }
+
+    /// Run an arbitrary SQL query over the merged tables.
+    pub fn query(&self, sql: &str, params: &[&dyn rusqlite::ToSql]) -> Result<QueryResult> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let rows = stmt
+            .query_map(params, |row| {
+                (0..columns.len())
+                    .map(|i| {
+                        Ok(match row.get_ref(i)? {
+                            ValueRef::Null => Value::Null,
+                            ValueRef::Integer(n) => n.into(),
+                            ValueRef::Real(n) => n.into(),
+                            ValueRef::Text(text) => {
+                                String::from_utf8_lossy(text).into_owned().into()
+                            }
+                            ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()).into(),
+                        })
+                    })
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(QueryResult { columns, rows })
+    }
+}
+
+/// Canned reports over the trace tables.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Report {
+    /// Per-actor counts of sent and handled messages, with handler latencies.
+    Actors,
+    /// The slowest individual message handlers.
+    SlowestHandlers { limit: usize },
+    /// Every message sent by or delivered to an actor, in time order.
+    MessagePath { actor: String },
+    /// Actors whose last recorded status is not terminal.
+    NeverStopped,
+    /// Error-level events, grouped by proc.
+    ErrorsByProc,
+}
+
+// Sends are recorded by the mailbox (with a `sender`); handler completions
+// by the actor instance (with an `elapsed_us`).
+const ACTORS_SQL: &str = "
+select actor,
+       sum(sent) as sent,
+       sum(handled) as handled,
+       round(avg(elapsed_us), 1) as avg_handler_us,
+       max(elapsed_us) as max_handler_us
+from (
+    select sender as actor, 1 as sent, 0 as handled, null as elapsed_us
+    from messages where elapsed_us is null and sender is not null
+    union all
+    select dest, 0, 1, cast(elapsed_us as integer)
+    from messages where elapsed_us is not null
+)
+group by actor
+order by handled desc, sent desc, actor";
+
+const SLOWEST_HANDLERS_SQL: &str = "
+select proc, dest as actor, message_type,
+       cast(elapsed_us as integer) as elapsed_us,
+       cast(time_us as integer) as time_us
+from messages
+where elapsed_us is not null
+order by cast(elapsed_us as integer) desc
+limit ?1";
+
+const MESSAGE_PATH_SQL: &str = "
+select proc,
+       cast(time_us as integer) as time_us,
+       case when elapsed_us is null then 'send' else 'handle' end as kind,
+       sender, dest, message_type,
+       cast(elapsed_us as integer) as elapsed_us
+from messages
+where sender = ?1 or dest = ?1
+order by cast(time_us as integer), proc, seq";
+
+const NEVER_STOPPED_SQL: &str = "
+select proc, actor_id, actor_status as last_status,
+       cast(time_us as integer) as time_us
+from actor_lifecycle as l
+where seq = (
+    select max(seq) from actor_lifecycle
+    where proc = l.proc and actor_id = l.actor_id
+)
+and actor_status not in ({terminal})
+order by proc, actor_id";
+
+const ERRORS_BY_PROC_SQL: &str = "
+select proc,
+       cast(time_us as integer) as time_us,
+       actor_id, module_path, message
+from log_events
+where level = 'ERROR'
+order by proc, cast(time_us as integer), seq";
+
+/// The result of a query: column names and rows of JSON values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl QueryResult {
+    /// The rows as a JSON array of objects keyed by column name.
+    pub fn to_json(&self) -> Value {
+        self.rows
+            .iter()
+            .map(|row| {
+                self.columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().cloned())
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            })
+            .collect::<Vec<Value>>()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+    use tracing::event;
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+    use crate::sqlite::SqliteLayer;
+
+    fn write_db(path: &Path, f: impl FnOnce()) {
+        let layer = SqliteLayer::new_with_file(&path.to_string_lossy()).unwrap();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+    }
+
+    #[test]
+    fn test_reports_across_procs() {
+        let dir = tempfile::tempdir().unwrap();
+        write_db(&dir.path().join("client.db"), || {
+            event!(target: "actor_lifecycle", Level::DEBUG, actor_id = "client[0].a[0]", actor_status = "Idle");
+            event!(target: "messages", Level::TRACE, sender = "client[0].a[0]", dest = "worker[0].b[0]", message_type = "Ping", time_us = 10u64);
+            event!(target: "messages", Level::TRACE, dest = "client[0].a[0]", message_type = "Pong", elapsed_us = 5u64, time_us = 40u64);
+        });
+        write_db(&dir.path().join("worker.db"), || {
+            event!(target: "actor_lifecycle", Level::DEBUG, actor_id = "worker[0].b[0]", actor_status = "Idle");
+            event!(target: "messages", Level::TRACE, dest = "worker[0].b[0]", message_type = "Ping", elapsed_us = 250u64, time_us = 20u64);
+            event!(target: "messages", Level::TRACE, sender = "worker[0].b[0]", dest = "client[0].a[0]", message_type = "Pong", time_us = 30u64);
+            event!(target: "actor_lifecycle", Level::DEBUG, actor_id = "worker[0].b[0]", actor_status = "Stopped");
+            tracing::error!(time_us = 50u64, "worker failed");
+        });
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let db = TraceDb::open(&[dir.path()]).unwrap();
+        assert_eq!(db.procs(), &["client", "worker"]);
+
+        let actors = db.report(&Report::Actors).unwrap();
+        assert_eq!(
+            actors.columns,
+            vec![
+                "actor",
+                "sent",
+                "handled",
+                "avg_handler_us",
+                "max_handler_us"
+            ]
+        );
+        assert_eq!(actors.rows.len(), 2);
+        assert_eq!(actors.rows[0][0], "client[0].a[0]");
+        assert_eq!(actors.to_json()[1]["max_handler_us"], 250);
+
+        let slowest = db
+            .report(&Report::SlowestHandlers { limit: 1 })
+            .unwrap()
+            .to_json();
+        assert_eq!(slowest.as_array().unwrap().len(), 1);
+        assert_eq!(slowest[0]["proc"], "worker");
+        assert_eq!(slowest[0]["message_type"], "Ping");
+
+        let path = db
+            .report(&Report::MessagePath {
+                actor: "worker[0].b[0]".to_string(),
+            })
+            .unwrap()
+            .to_json();
+        let kinds: Vec<_> = path
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds, vec!["send", "handle", "send"]);
+
+        let running = db.report(&Report::NeverStopped).unwrap().to_json();
+        assert_eq!(running.as_array().unwrap().len(), 1);
+        assert_eq!(running[0]["actor_id"], "client[0].a[0]");
+
+        let errors = db.report(&Report::ErrorsByProc).unwrap().to_json();
+        assert_eq!(errors.as_array().unwrap().len(), 1);
+        assert_eq!(errors[0]["proc"], "worker");
+        assert_eq!(errors[0]["message"], "worker failed");
+
+        let adhoc = db
+            .query(
+                "select proc, count(*) as n from messages group by proc",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(
+            adhoc.rows,
+            vec![
+                vec!["client".into(), 2.into()],
+                vec!["worker".into(), 2.into()]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_older_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute(
+            "create table messages (seq INTEGER primary key, span_id TEXT, time_us TEXT, src TEXT, dest TEXT)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "insert into messages (time_us, src, dest) values ('1', 'a', 'b')",
+            [],
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = TraceDb::open(&[&path]).unwrap();
+        let result = db
+            .query("select proc, src, dest, sender from messages", &[])
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![vec!["old".into(), "a".into(), "b".into(), Value::Null]]
+        );
+        assert!(TraceDb::open(&[dir.path().join("missing.db")]).is_err());
+    }
+}
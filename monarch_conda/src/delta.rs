@@ -0,0 +1,295 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! rsync-style block deltas.
+//!
+//! The receiver splits its existing copy of a file (the *basis*) into
+//! fixed-size blocks and sends a [`Signature`] with a weak rolling
+//! checksum and a strong hash per block. The sender slides a window
+//! over its copy, looking the rolling checksum up in the signature,
+//! and describes its file as a sequence of [`DeltaOp`]s: runs of
+//! basis blocks to copy, and literal byte ranges that must be sent.
+//! Applying the ops to the basis reproduces the sender's file
+//! byte-for-byte.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::Result;
+use anyhow::ensure;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+/// Smallest block size used for signatures.
+const MIN_BLOCK_SIZE: usize = 2048;
+
+/// Largest block size used for signatures.
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+
+/// Largest literal range emitted as a single op.
+const MAX_LITERAL: usize = 1024 * 1024;
+
+/// Pick a block size for a basis of `len` bytes: roughly its square
+/// root (as rsync does), within [`MIN_BLOCK_SIZE`, `MAX_BLOCK_SIZE`].
+pub fn block_size_for(len: u64) -> usize {
+    ((len as f64).sqrt() as usize).clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE)
+}
+
+/// Weak and strong checksums of a single basis block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSignature {
+    weak: u32,
+    strong: [u8; 16],
+}
+
+/// The block checksums of a basis file. Only full blocks are included;
+/// any trailing partial block is always sent as a literal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    block_size: usize,
+    blocks: Vec<BlockSignature>,
+}
+
+impl Signature {
+    /// Compute the signature of `basis` using the given block size.
+    pub fn new(basis: &[u8], block_size: usize) -> Self {
+        Self {
+            block_size,
+            blocks: basis
+                .chunks_exact(block_size)
+                .map(|block| BlockSignature {
+                    weak: Rolling::new(block).digest(),
+                    strong: strong_hash(block),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+}
+
+/// One step in reconstructing a file from a basis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaOp {
+    /// Copy `count` consecutive basis blocks, starting at `index`.
+    Copy { index: usize, count: usize },
+    /// Send this byte range of the new file verbatim.
+    Literal(Range<usize>),
+}
+
+/// The rsync rolling checksum over a window of bytes.
+struct Rolling {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl Rolling {
+    fn new(window: &[u8]) -> Self {
+        let len = window.len() as u32;
+        let (mut a, mut b) = (0u32, 0u32);
+        for (i, byte) in window.iter().enumerate() {
+            a = a.wrapping_add(*byte as u32);
+            b = b.wrapping_add((len - i as u32).wrapping_mul(*byte as u32));
+        }
+        Self { a, b, len }
+    }
+
+    /// Slide the window by one byte, dropping `out` and adding `inp`.
+    fn roll(&mut self, out: u8, inp: u8) {
+        self.a = self.a.wrapping_sub(out as u32).wrapping_add(inp as u32);
+        self.b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(out as u32))
+            .wrapping_add(self.a);
+    }
+
+    fn digest(&self) -> u32 {
+        (self.a & 0xffff) | (self.b << 16)
+    }
+}
+
+fn strong_hash(block: &[u8]) -> [u8; 16] {
+    let digest = Sha256::digest(block);
+    let mut strong = [0; 16];
+    strong.copy_from_slice(&digest[..16]);
+    strong
+}
+
+fn push_literal(ops: &mut Vec<DeltaOp>, range: Range<usize>) {
+    let mut start = range.start;
+    while start < range.end {
+        let end = range.end.min(start + MAX_LITERAL);
+        ops.push(DeltaOp::Literal(start..end));
+        start = end;
+    }
+}
+
+fn push_copy(ops: &mut Vec<DeltaOp>, block: usize) {
+    if let Some(DeltaOp::Copy { index, count }) = ops.last_mut() {
+        if *index + *count == block {
+            *count += 1;
+            return;
+        }
+    }
+    ops.push(DeltaOp::Copy {
+        index: block,
+        count: 1,
+    });
+}
+
+/// Describe `data` as a sequence of ops against the basis described by
+/// `signature`.
+pub fn delta(signature: &Signature, data: &[u8]) -> Vec<DeltaOp> {
+    let block_size = signature.block_size;
+    let mut by_weak: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (index, block) in signature.blocks.iter().enumerate() {
+        by_weak.entry(block.weak).or_default().push(index);
+    }
+
+    let mut ops = Vec::new();
+    let mut literal_start = 0;
+    let mut pos = 0;
+    if !by_weak.is_empty() && data.len() >= block_size {
+        let mut rolling = Rolling::new(&data[..block_size]);
+        loop {
+            let end = pos + block_size;
+            let matched = by_weak.get(&rolling.digest()).and_then(|candidates| {
+                let strong = strong_hash(&data[pos..end]);
+                candidates
+                    .iter()
+                    .find(|index| signature.blocks[**index].strong == strong)
+            });
+            if let Some(index) = matched {
+                push_literal(&mut ops, literal_start..pos);
+                push_copy(&mut ops, *index);
+                pos = end;
+                literal_start = pos;
+                if pos + block_size > data.len() {
+                    break;
+                }
+                rolling = Rolling::new(&data[pos..pos + block_size]);
+            } else {
+                if end >= data.len() {
+                    break;
+                }
+                rolling.roll(data[pos], data[end]);
+                pos += 1;
+            }
+        }
+    }
+    push_literal(&mut ops, literal_start..data.len());
+    ops
+}
+
+/// The number of bytes that `ops` copy from the basis rather than
+/// sending over the wire.
+pub fn copied_bytes(ops: &[DeltaOp], block_size: usize) -> u64 {
+    ops.iter()
+        .map(|op| match op {
+            DeltaOp::Copy { count, .. } => (count * block_size) as u64,
+            DeltaOp::Literal(_) => 0,
+        })
+        .sum()
+}
+
+/// The basis bytes referenced by a copy op.
+pub fn basis_range(basis: &[u8], block_size: usize, index: usize, count: usize) -> Result<&[u8]> {
+    let start = index * block_size;
+    let end = start + count * block_size;
+    ensure!(
+        end <= basis.len(),
+        "delta references blocks past end of basis"
+    );
+    Ok(&basis[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::pseudo_random;
+
+    fn apply(basis: &[u8], signature: &Signature, data: &[u8], ops: &[DeltaOp]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for op in ops {
+            match op {
+                DeltaOp::Copy { index, count } => out.extend_from_slice(
+                    basis_range(basis, signature.block_size(), *index, *count).unwrap(),
+                ),
+                DeltaOp::Literal(range) => out.extend_from_slice(&data[range.clone()]),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_rolling_matches_fresh_checksum() {
+        let data = pseudo_random(4096, 1);
+        let mut rolling = Rolling::new(&data[..100]);
+        for pos in 0..1000 {
+            assert_eq!(
+                rolling.digest(),
+                Rolling::new(&data[pos..pos + 100]).digest()
+            );
+            rolling.roll(data[pos], data[pos + 100]);
+        }
+    }
+
+    #[test]
+    fn test_delta_small_edit() {
+        let basis = pseudo_random(256 * 1024, 2);
+        let mut data = basis.clone();
+        // Insert a few bytes in the middle, shifting everything after.
+        data.splice(100_000..100_000, b"hello".iter().copied());
+        // And modify a byte near the end.
+        let len = data.len();
+        data[len - 10] ^= 0xff;
+
+        let signature = Signature::new(&basis, block_size_for(basis.len() as u64));
+        let ops = delta(&signature, &data);
+        assert_eq!(apply(&basis, &signature, &data, &ops), data);
+
+        let literal: usize = ops
+            .iter()
+            .map(|op| match op {
+                DeltaOp::Literal(range) => range.len(),
+                DeltaOp::Copy { .. } => 0,
+            })
+            .sum();
+        assert!(
+            literal < 4 * signature.block_size(),
+            "sent {} literal bytes",
+            literal
+        );
+        assert_eq!(
+            copied_bytes(&ops, signature.block_size()) + literal as u64,
+            data.len() as u64
+        );
+    }
+
+    #[test]
+    fn test_delta_unrelated_data() {
+        let basis = pseudo_random(64 * 1024, 3);
+        let data = pseudo_random(50 * 1024, 4);
+        let signature = Signature::new(&basis, MIN_BLOCK_SIZE);
+        let ops = delta(&signature, &data);
+        assert_eq!(ops, vec![DeltaOp::Literal(0..data.len())]);
+    }
+
+    #[test]
+    fn test_delta_short_basis() {
+        let signature = Signature::new(b"tiny", MIN_BLOCK_SIZE);
+        let ops = delta(&signature, b"tiny file");
+        assert_eq!(ops, vec![DeltaOp::Literal(0..9)]);
+        assert!(delta(&signature, b"").is_empty());
+    }
+}
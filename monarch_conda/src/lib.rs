@@ -8,8 +8,11 @@
 
 #![feature(once_cell_try)]
 
+pub mod delta;
 pub mod diff;
 pub mod hash_utils;
 pub mod pack_meta_history;
 pub mod replace;
 pub mod sync;
+#[cfg(test)]
+mod test_utils;
@@ -35,10 +35,11 @@ async fn main() -> Result<()> {
     let (recv, send) = tokio::io::duplex(5 * 1024 * 1024);
     let (from_receiver, to_receiver) = tokio::io::split(recv);
     let (from_sender, to_sender) = tokio::io::split(send);
-    try_join!(
+    let (actions, stats) = try_join!(
         receiver(&args.dst, from_sender, to_sender, HashMap::new()),
         sender(&args.src, from_receiver, to_receiver),
     )?;
+    println!("{} changes; {}", actions.len(), stats);
 
     Ok(())
 }
@@ -10,7 +10,9 @@ use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::fmt;
 use std::io::ErrorKind;
+use std::io::SeekFrom;
 use std::os::unix::fs::MetadataExt;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
@@ -39,21 +41,34 @@ use ignore::DirEntry;
 use ignore::WalkBuilder;
 use ignore::WalkState;
 use itertools::Itertools;
+use memmap2::Mmap;
 use memmap2::MmapMut;
 use serde::Deserialize;
 use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sha2::Digest;
+use sha2::Sha256;
 use tokio::fs;
 use tokio::io::AsyncRead;
 use tokio::io::AsyncReadExt;
+use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
 use tokio_util::codec::FramedRead;
 use tokio_util::codec::FramedWrite;
 use tokio_util::codec::LengthDelimitedCodec;
 
+use crate::delta;
+use crate::delta::DeltaOp;
+use crate::delta::Signature;
 use crate::diff::CondaFingerprint;
+use crate::replace::Replacer;
 use crate::replace::ReplacerBuilder;
 
+/// Changed files at least this large are sent as block deltas against
+/// the receiver's existing copy, rather than in full.
+const DELTA_MIN_SIZE: u64 = 64 * 1024;
+
 #[derive(Eq, PartialEq)]
 enum Origin {
     Src,
@@ -109,12 +124,113 @@ struct FileSectionHeader {
 struct FileHeader {
     path: PathBuf,
     symlink: bool,
+    /// Block signature of the receiver's existing copy of the file,
+    /// if it is worth sending a delta against.
+    signature: Option<Signature>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 enum FileContents {
     Symlink(PathBuf),
     File(u64),
+    /// The file is sent as a sequence of [`DeltaCommand`]s against the
+    /// receiver's existing copy, which must reconstruct a file of `len`
+    /// bytes with SHA-256 `digest`.
+    Delta {
+        len: u64,
+        block_size: usize,
+        digest: [u8; 32],
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum DeltaCommand {
+    /// Copy `count` blocks of the existing file, starting at `index`.
+    Copy {
+        index: usize,
+        count: usize,
+    },
+    /// The given number of literal bytes follow.
+    Literal(u64),
+    End,
+}
+
+/// What a [`sender`] transferred.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TransferStats {
+    /// Number of files whose contents were sent.
+    pub files: usize,
+    /// Total size of those files.
+    pub bytes: u64,
+    /// File content bytes actually sent; the rest were reconstructed
+    /// by the receiver from its existing copies.
+    pub sent_bytes: u64,
+}
+
+impl TransferStats {
+    /// Bytes that did not need to be sent thanks to block deltas. Files
+    /// whose delta did not apply are sent twice, so this may be zero.
+    pub fn saved_bytes(&self) -> u64 {
+        self.bytes.saturating_sub(self.sent_bytes)
+    }
+}
+
+impl fmt::Display for TransferStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} files, {} bytes, {} bytes sent, {} bytes saved by deltas",
+            self.files,
+            self.bytes,
+            self.sent_bytes,
+            self.saved_bytes()
+        )
+    }
+}
+
+/// Write a length-prefixed, bincode-encoded header to the raw file
+/// contents stream.
+async fn write_header<T: Serialize>(
+    writer: &mut (impl AsyncWrite + Unpin),
+    header: &T,
+) -> Result<()> {
+    let header = bincode::serialize(header)?;
+    writer
+        .write_all(&(header.len() as u64).to_le_bytes())
+        .await?;
+    writer.write_all(&header).await?;
+    Ok(())
+}
+
+/// Read a header written by [`write_header`].
+async fn read_header<T: DeserializeOwned>(reader: &mut (impl AsyncRead + Unpin)) -> Result<T> {
+    let len = reader.read_u64_le().await?;
+    let mut buf = vec![0u8; len as usize];
+    reader.read_exact(&mut buf).await?;
+    Ok(bincode::deserialize(&buf)?)
+}
+
+/// Compute the block signature of the file at `path`, if it is a
+/// regular file large enough to be worth a delta.
+async fn file_signature(path: &Path) -> Result<Option<Signature>> {
+    let metadata = match fs::symlink_metadata(path).await {
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        other => other?,
+    };
+    if !metadata.is_file() || metadata.len() < DELTA_MIN_SIZE {
+        return Ok(None);
+    }
+    let file = std::fs::File::open(path)?;
+    tokio::task::spawn_blocking(move || {
+        // SAFETY: read-only mapping; the file is only replaced (via
+        // rename) after its new contents have been received.
+        let basis = unsafe { Mmap::map(&file)? };
+        Ok(Some(Signature::new(
+            &basis,
+            delta::block_size_for(basis.len() as u64),
+        )))
+    })
+    .await?
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -309,7 +425,7 @@ pub async fn sender(
     src: &Path,
     from_receiver: impl AsyncRead + Unpin,
     to_receiver: impl AsyncWrite + Unpin,
-) -> Result<()> {
+) -> Result<TransferStats> {
     let mut to_receiver = FramedWrite::new(to_receiver, LengthDelimitedCodec::new());
     let mut from_receiver = FramedRead::new(from_receiver, LengthDelimitedCodec::new());
 
@@ -352,41 +468,110 @@ pub async fn sender(
     to_receiver.flush().await?;
     let mut to_receiver = to_receiver.into_inner();
 
-    let hdr: FileSectionHeader =
-        bincode::deserialize(&from_receiver.next().await.context("header")??)?;
-    for _ in 0..hdr.num {
-        let FileHeader { path, symlink } =
-            bincode::deserialize(&from_receiver.next().await.context("signature")??)?;
-        let fpath = src.join(&path);
-        if symlink {
-            let header = FileContentsHeader {
+    // The receiver first asks for every file it needs, then again, in
+    // full, for those whose delta it could not apply.
+    let mut stats = TransferStats::default();
+    for retry in [false, true] {
+        let hdr: FileSectionHeader =
+            bincode::deserialize(&from_receiver.next().await.context("header")??)?;
+        for _ in 0..hdr.num {
+            let FileHeader {
                 path,
-                contents: FileContents::Symlink(fs::read_link(&fpath).await?),
-            };
-            let header = bincode::serialize(&header)?;
-            to_receiver.write_all(&header.len().to_le_bytes()).await?;
-            to_receiver
-                .write_all(&header)
-                .await
-                .context("sending sig header")?;
-        } else {
-            let mut base = fs::File::open(src.join(&path)).await?;
-            let header = FileContentsHeader {
-                path,
-                contents: FileContents::File(base.metadata().await?.len()),
+                symlink,
+                signature,
+            } = bincode::deserialize(&from_receiver.next().await.context("signature")??)?;
+            let fpath = src.join(&path);
+            if symlink {
+                let header = FileContentsHeader {
+                    path,
+                    contents: FileContents::Symlink(fs::read_link(&fpath).await?),
+                };
+                write_header(&mut to_receiver, &header)
+                    .await
+                    .context("sending sig header")?;
+                continue;
+            }
+
+            let base = std::fs::File::open(&fpath)?;
+            let len = base.metadata()?.len();
+            if !retry {
+                stats.files += 1;
+                stats.bytes += len;
+            }
+
+            // Compute a delta against the receiver's copy, and use it if any
+            // blocks matched.
+            let delta = match signature {
+                Some(signature) if len > 0 => {
+                    let block_size = signature.block_size();
+                    // SAFETY: read-only mapping; if the file is modified while
+                    // we read it, the receiver's digest check fails the sync.
+                    let data = unsafe { Mmap::map(&base)? };
+                    let (data, ops, digest) = tokio::task::spawn_blocking(move || {
+                        let ops = delta::delta(&signature, &data);
+                        let digest: [u8; 32] = Sha256::digest(&data[..]).into();
+                        (data, ops, digest)
+                    })
+                    .await?;
+                    ops.iter()
+                        .any(|op| matches!(op, DeltaOp::Copy { .. }))
+                        .then_some((data, ops, digest, block_size))
+                }
+                _ => None,
             };
-            let header = bincode::serialize(&header)?;
-            to_receiver.write_all(&header.len().to_le_bytes()).await?;
-            to_receiver
-                .write_all(&header)
-                .await
-                .context("sending sig header")?;
-            tokio::io::copy(&mut base, &mut to_receiver).await?;
+
+            match delta {
+                Some((data, ops, digest, block_size)) => {
+                    let header = FileContentsHeader {
+                        path,
+                        contents: FileContents::Delta {
+                            len,
+                            block_size,
+                            digest,
+                        },
+                    };
+                    write_header(&mut to_receiver, &header)
+                        .await
+                        .context("sending delta header")?;
+                    for op in ops {
+                        match op {
+                            DeltaOp::Copy { index, count } => {
+                                write_header(
+                                    &mut to_receiver,
+                                    &DeltaCommand::Copy { index, count },
+                                )
+                                .await?;
+                            }
+                            DeltaOp::Literal(range) => {
+                                write_header(
+                                    &mut to_receiver,
+                                    &DeltaCommand::Literal(range.len() as u64),
+                                )
+                                .await?;
+                                to_receiver.write_all(&data[range.clone()]).await?;
+                                stats.sent_bytes += range.len() as u64;
+                            }
+                        }
+                    }
+                    write_header(&mut to_receiver, &DeltaCommand::End).await?;
+                }
+                None => {
+                    let header = FileContentsHeader {
+                        path,
+                        contents: FileContents::File(len),
+                    };
+                    write_header(&mut to_receiver, &header)
+                        .await
+                        .context("sending sig header")?;
+                    let mut base = fs::File::from_std(base).take(len);
+                    stats.sent_bytes += tokio::io::copy(&mut base, &mut to_receiver).await?;
+                }
+            }
         }
+        to_receiver.flush().await?;
     }
-    to_receiver.flush().await?;
 
-    Ok(())
+    Ok(stats)
 }
 
 async fn persist(tmp: TempFile, path: &Path) -> Result<(), std::io::Error> {
@@ -437,6 +622,174 @@ fn is_binary(buf: &[u8]) -> bool {
     non_print * 100 > buf.len() * 30
 }
 
+/// Copy file contents from `reader` into `dst_tmp`, replacing prefixes
+/// if we have a replacer.
+async fn write_contents(
+    mut reader: impl AsyncRead + Unpin,
+    dst_tmp: &mut TempFile,
+    replacer: Option<&Replacer<'_>>,
+) -> Result<()> {
+    if let Some(replacer) = replacer {
+        // We do different copies dependending on whether the file is binary or not.
+        let mut buf = vec![0; 4096];
+        let len = reader.read(&mut buf[..]).await?;
+        buf.truncate(len);
+        if is_binary(&buf) {
+            dst_tmp.write_all(&buf).await?;
+            tokio::io::copy(&mut reader, dst_tmp).await?;
+            dst_tmp.flush().await?;
+
+            // For binary files, replace prefixes.
+            // SAFETY: use mmap for fast in-place prefix replacement
+            let mut mmap = unsafe { MmapMut::map_mut(&**dst_tmp)? };
+            replacer.replace_inplace_padded(&mut mmap)?;
+        } else {
+            reader.read_to_end(&mut buf).await?;
+            replacer.replace_inplace(&mut buf);
+            dst_tmp.write_all(&buf).await?;
+        }
+    } else {
+        tokio::io::copy(&mut reader, dst_tmp).await?;
+    }
+    Ok(())
+}
+
+/// Rebuild a file from the `basis` blocks and the [`DeltaCommand`]s read
+/// from `from_sender`, writing it to `out`. Returns whether the rebuilt
+/// file has the expected length and digest; either way, the delta has
+/// been fully consumed from `from_sender`.
+async fn apply_delta(
+    mut from_sender: impl AsyncRead + Unpin,
+    basis: &[u8],
+    block_size: usize,
+    out: &mut (impl AsyncWrite + Unpin),
+    len: u64,
+    digest: &[u8; 32],
+) -> Result<bool> {
+    let mut hasher = Sha256::new();
+    let mut written = 0u64;
+    let mut buf = Vec::new();
+    loop {
+        match read_header(&mut from_sender).await? {
+            DeltaCommand::Copy { index, count } => {
+                let blocks = delta::basis_range(basis, block_size, index, count)?;
+                hasher.update(blocks);
+                out.write_all(blocks).await?;
+                written += blocks.len() as u64;
+            }
+            DeltaCommand::Literal(n) => {
+                buf.resize(n as usize, 0);
+                from_sender.read_exact(&mut buf).await?;
+                hasher.update(&buf);
+                out.write_all(&buf).await?;
+                written += n;
+            }
+            DeltaCommand::End => break,
+        }
+    }
+    out.flush().await?;
+    Ok(written == len && <[u8; 32]>::from(hasher.finalize()) == *digest)
+}
+
+/// Receive the contents of the next file sent by the [`sender`], and
+/// write it to its place in `dst`. Returns the path of the file if it
+/// was sent as a delta that could not be applied, in which case the
+/// file was left untouched and must be requested again in full.
+async fn receive_contents(
+    from_sender: &mut (impl AsyncRead + Unpin),
+    dst: &Path,
+    files: &HashMap<PathBuf, (SystemTime, &Receive)>,
+    replacer: Option<&Replacer<'_>>,
+) -> Result<Option<PathBuf>> {
+    // Read a file header.
+    let FileContentsHeader { path, contents } = read_header(&mut *from_sender)
+        .await
+        .context("delta header")?;
+    let fpath = dst.join(&path);
+    match (contents, files.get(&path).context("missing file")?) {
+        // Read file contents and write to a tempfile.
+        (FileContents::File(len), (mtime, Receive::File { executable })) => {
+            let mut dst_tmp = TempFile::new_in(fpath.parent().context("parent")?).await?;
+            let reader = (&mut *from_sender).take(len);
+            write_contents(reader, &mut dst_tmp, replacer).await?;
+
+            if *executable {
+                make_executable(dst_tmp.file_path()).await?;
+            }
+            persist(dst_tmp, &fpath).await?;
+            set_mtime(&fpath, *mtime).await?;
+        }
+        // Rebuild the file from our existing copy and the delta.
+        (
+            FileContents::Delta {
+                len,
+                block_size,
+                digest,
+            },
+            (mtime, Receive::File { executable }),
+        ) => {
+            let parent = fpath.parent().context("parent")?;
+            let basis = std::fs::File::open(&fpath)
+                .with_context(|| format!("opening delta basis {}", fpath.display()))?;
+            // SAFETY: read-only mapping of the file we're about to
+            // replace; it's only renamed over once rebuilt.
+            let basis = unsafe { Mmap::map(&basis)? };
+            let mut dst_tmp = TempFile::new_in(parent).await?;
+            let rebuilt = match replacer {
+                // The delta reproduces the sender's bytes, so rebuild
+                // them first and then run them through the replacer.
+                Some(replacer) => {
+                    let mut rebuilt = TempFile::new_in(parent).await?;
+                    let ok = apply_delta(
+                        &mut *from_sender,
+                        &basis,
+                        block_size,
+                        &mut rebuilt,
+                        len,
+                        &digest,
+                    )
+                    .await
+                    .with_context(|| format!("applying delta to {}", fpath.display()))?;
+                    if ok {
+                        rebuilt.seek(SeekFrom::Start(0)).await?;
+                        write_contents(&mut rebuilt, &mut dst_tmp, Some(replacer)).await?;
+                    }
+                    ok
+                }
+                None => apply_delta(
+                    &mut *from_sender,
+                    &basis,
+                    block_size,
+                    &mut dst_tmp,
+                    len,
+                    &digest,
+                )
+                .await
+                .with_context(|| format!("applying delta to {}", fpath.display()))?,
+            };
+            drop(basis);
+            if !rebuilt {
+                return Ok(Some(path));
+            }
+
+            if *executable {
+                make_executable(dst_tmp.file_path()).await?;
+            }
+            persist(dst_tmp, &fpath).await?;
+            set_mtime(&fpath, *mtime).await?;
+        }
+        (FileContents::Symlink(mut target), (mtime, Receive::Symlink)) => {
+            if let Some(replacer) = replacer {
+                target = replacer.replace_path(target);
+            }
+            fs::symlink(target, &fpath).await?;
+            set_mtime(&fpath, *mtime).await?;
+        }
+        _ => bail!("unexpected file contents"),
+    }
+    Ok(None)
+}
+
 pub async fn receiver(
     dst: &Path,
     from_sender: impl AsyncRead + Unpin,
@@ -510,7 +863,7 @@ pub async fn receiver(
         }
     }
 
-    try_join!(
+    let ((mut from_sender, replacer, retries), mut to_sender) = try_join!(
         async {
             // Process deletions first.
             for (path, is_dir) in deletions.into_iter().rev() {
@@ -559,61 +912,13 @@ pub async fn receiver(
 
             // Then pull file data and create files.
             let mut from_sender = from_sender.into_inner();
+            let mut retries = Vec::new();
             for _ in 0..files.len() {
-                // Read a file header.
-                let len = from_sender.read_u64_le().await?;
-                let mut buf = vec![0u8; len as usize];
-                from_sender.read_exact(&mut buf).await?;
-                let FileContentsHeader { path, contents } =
-                    bincode::deserialize(&buf).context("delta header")?;
-                let fpath = dst.join(&path);
-                match (contents, files.get(&path).context("missing file")?) {
-                    // Read file contents and write to a tempfile.
-                    (FileContents::File(len), (mtime, Receive::File { executable })) => {
-                        let mut dst_tmp =
-                            TempFile::new_in(fpath.parent().context("parent")?).await?;
-                        let mut reader = (&mut from_sender).take(len);
-
-                        // Copy the file contents.
-                        if let Some(ref replacer) = replacer {
-                            // We do different copies dependending on whether the file is binary or not.
-                            let mut buf = vec![0; 4096];
-                            let len = reader.read(&mut buf[..]).await?;
-                            buf.truncate(len);
-                            if is_binary(&buf) {
-                                dst_tmp.write_all(&buf).await?;
-                                tokio::io::copy(&mut reader, &mut dst_tmp).await?;
-
-                                // For binary files, replace prefixes.
-                                // SAFETY: use mmap for fast in-place prefix replacement
-                                let mut mmap = unsafe { MmapMut::map_mut(&*dst_tmp)? };
-                                replacer.replace_inplace_padded(&mut mmap)?;
-                            } else {
-                                reader.read_to_end(&mut buf).await?;
-                                replacer.replace_inplace(&mut buf);
-                                dst_tmp.write_all(&buf).await?;
-                            }
-                        } else {
-                            tokio::io::copy(&mut reader, &mut dst_tmp).await?;
-                        }
-
-                        if *executable {
-                            make_executable(dst_tmp.file_path()).await?;
-                        }
-                        persist(dst_tmp, &fpath).await?;
-                        set_mtime(&fpath, *mtime).await?;
-                    }
-                    (FileContents::Symlink(mut target), (mtime, Receive::Symlink)) => {
-                        if let Some(ref replacer) = replacer {
-                            target = replacer.replace_path(target);
-                        }
-                        fs::symlink(target, &fpath).await?;
-                        set_mtime(&fpath, *mtime).await?;
-                    }
-                    _ => bail!("unexpected file contents"),
-                }
+                retries.extend(
+                    receive_contents(&mut from_sender, dst, &files, replacer.as_ref()).await?,
+                );
             }
-            anyhow::Ok(())
+            anyhow::Ok((from_sender, replacer, retries))
         },
         async {
             to_sender
@@ -621,11 +926,18 @@ pub async fn receiver(
                 .await
                 .context("sending sig section header")?;
             for (path, (_, recv)) in files.iter() {
+                let signature = match recv {
+                    Receive::File { .. } => file_signature(&dst.join(path))
+                        .await
+                        .with_context(|| format!("signing {}", path.display()))?,
+                    Receive::Symlink => None,
+                };
                 to_sender
                     .send(
                         bincode::serialize(&FileHeader {
                             path: path.clone(),
                             symlink: matches!(recv, Receive::Symlink),
+                            signature,
                         })?
                         .into(),
                     )
@@ -633,10 +945,35 @@ pub async fn receiver(
                     .context("sending sig header")?;
             }
             to_sender.flush().await?;
-            anyhow::Ok(())
+            anyhow::Ok(to_sender)
         },
     )?;
 
+    // Ask again, without signatures, for files whose delta did not
+    // apply.
+    to_sender
+        .send(bincode::serialize(&FileSectionHeader { num: retries.len() })?.into())
+        .await
+        .context("sending retry section header")?;
+    for path in &retries {
+        to_sender
+            .send(
+                bincode::serialize(&FileHeader {
+                    path: path.clone(),
+                    symlink: false,
+                    signature: None,
+                })?
+                .into(),
+            )
+            .await
+            .context("sending retry header")?;
+    }
+    to_sender.flush().await?;
+    for _ in 0..retries.len() {
+        let retry = receive_contents(&mut from_sender, dst, &files, replacer.as_ref()).await?;
+        ensure!(retry.is_none(), "full transfer sent as a delta");
+    }
+
     Ok(actions)
 }
 
@@ -645,7 +982,7 @@ pub async fn sync(src: &Path, dst: &Path) -> Result<HashMap<PathBuf, Action>> {
     let (recv, send) = tokio::io::duplex(5 * 1024 * 1024);
     let (from_receiver, to_receiver) = tokio::io::split(recv);
     let (from_sender, to_sender) = tokio::io::split(send);
-    let (actions, ()) = try_join!(
+    let (actions, _stats) = try_join!(
         receiver(dst, from_sender, to_sender, HashMap::new()),
         sender(src, from_receiver, to_receiver),
     )?;
@@ -664,19 +1001,29 @@ mod tests {
 
     use anyhow::Result;
     use rattler_conda_types::package::FileMode;
+    use sha2::Digest;
+    use sha2::Sha256;
     use tempfile::TempDir;
     use tokio::fs;
 
     use super::Action;
+    use super::DeltaCommand;
+    use super::TransferStats;
+    use super::apply_delta;
     use super::make_executable;
+    use super::read_header;
+    use super::receiver;
+    use super::sender;
     use super::set_mtime;
     use super::sync;
+    use super::write_header;
     use crate::pack_meta_history::History;
     use crate::pack_meta_history::HistoryRecord;
     use crate::pack_meta_history::Offset;
     use crate::pack_meta_history::OffsetRecord;
     use crate::pack_meta_history::Offsets;
     use crate::sync::Receive;
+    use crate::test_utils::pseudo_random;
 
     /// Helper function to create a basic conda environment structure
     async fn setup_conda_env<P: AsRef<Path>>(
@@ -843,6 +1190,20 @@ mod tests {
         Ok(())
     }
 
+    /// Like `sync`, but also returns the sender's transfer stats.
+    async fn sync_with_stats(
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(HashMap<PathBuf, Action>, TransferStats)> {
+        let (recv, send) = tokio::io::duplex(5 * 1024 * 1024);
+        let (from_receiver, to_receiver) = tokio::io::split(recv);
+        let (from_sender, to_sender) = tokio::io::split(send);
+        futures::try_join!(
+            receiver(dst, from_sender, to_sender, HashMap::new()),
+            sender(src, from_receiver, to_receiver),
+        )
+    }
+
     /// Helper function to verify file content
     async fn verify_file_content(path1: &Path, path2: &Path) -> Result<bool> {
         let content1 = fs::read_to_string(path1).await?;
@@ -1406,4 +1767,119 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_sync_large_file_sends_delta() -> Result<()> {
+        let base_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1672531200);
+        let src_env = setup_conda_env(TempDir::new()?, base_time, None).await?;
+        let dst_env = setup_conda_env(TempDir::new()?, base_time, None).await?;
+
+        // Both sides have a large file, which is then modified slightly in src.
+        let old_content = pseudo_random(512 * 1024, 1);
+        fs::write(dst_env.path().join("lib/large.bin"), &old_content).await?;
+        set_mtime(&dst_env.path().join("lib/large.bin"), base_time).await?;
+
+        let newer_time = base_time + Duration::from_hours(1);
+        let mut new_content = old_content.clone();
+        new_content.splice(200_000..200_000, b"inserted".iter().copied());
+        new_content.truncate(500 * 1024);
+        fs::write(src_env.path().join("lib/large.bin"), &new_content).await?;
+        set_mtime(&src_env.path().join("lib/large.bin"), newer_time).await?;
+
+        let (actions, stats) = sync_with_stats(src_env.path(), dst_env.path()).await?;
+        assert_eq!(
+            actions,
+            HashMap::from([(
+                PathBuf::from("lib/large.bin"),
+                Action::Receive(newer_time, Receive::File { executable: false }),
+            )])
+        );
+        assert_eq!(stats.files, 1);
+        assert_eq!(stats.bytes, new_content.len() as u64);
+        assert!(
+            stats.sent_bytes < stats.bytes / 10,
+            "sent {} of {} bytes",
+            stats.sent_bytes,
+            stats.bytes
+        );
+        assert!(stats.saved_bytes() > 0);
+        assert_eq!(
+            fs::read(dst_env.path().join("lib/large.bin")).await?,
+            new_content
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_delta_with_prefix_replacement() -> Result<()> {
+        let base_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1672531200);
+        let src_prefix = "/opt/conda/src";
+        let dst_prefix = "/opt/conda/dst";
+        let src_env = setup_conda_env(TempDir::new()?, base_time, Some(src_prefix)).await?;
+        let dst_env = setup_conda_env(TempDir::new()?, base_time, Some(dst_prefix)).await?;
+
+        let replace = |content: &[u8]| {
+            let mut content = content.to_vec();
+            for pos in 0..=content.len() - src_prefix.len() {
+                if &content[pos..pos + src_prefix.len()] == src_prefix.as_bytes() {
+                    content[pos..pos + dst_prefix.len()].copy_from_slice(dst_prefix.as_bytes());
+                }
+            }
+            content
+        };
+
+        // A binary file embedding the prefix, previously synced to dst.
+        let mut old_content = pseudo_random(256 * 1024, 2);
+        old_content[..4].copy_from_slice(b"\x7fELF");
+        for offset in [1000, 150_000] {
+            old_content[offset..offset + src_prefix.len()].copy_from_slice(src_prefix.as_bytes());
+        }
+        fs::write(dst_env.path().join("lib/binary"), replace(&old_content)).await?;
+        set_mtime(&dst_env.path().join("lib/binary"), base_time).await?;
+
+        // Modify it in src.
+        let newer_time = base_time + Duration::from_hours(1);
+        let mut new_content = old_content.clone();
+        new_content[100_000..100_010].fill(0xaa);
+        fs::write(src_env.path().join("lib/binary"), &new_content).await?;
+        set_mtime(&src_env.path().join("lib/binary"), newer_time).await?;
+
+        let (_, stats) = sync_with_stats(src_env.path(), dst_env.path()).await?;
+        assert!(stats.saved_bytes() > 0);
+
+        // The rebuilt file still has its prefixes replaced.
+        assert_eq!(
+            fs::read(dst_env.path().join("lib/binary")).await?,
+            replace(&new_content)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_delta_mismatch() -> Result<()> {
+        let basis = pseudo_random(4096, 3);
+        let mut stream = Vec::new();
+        write_header(&mut stream, &DeltaCommand::Copy { index: 0, count: 2 }).await?;
+        write_header(&mut stream, &DeltaCommand::Literal(3)).await?;
+        stream.extend_from_slice(b"abc");
+        write_header(&mut stream, &DeltaCommand::End).await?;
+        write_header(&mut stream, &"next").await?;
+        let mut expected = basis[..2048].to_vec();
+        expected.extend_from_slice(b"abc");
+        let digest: [u8; 32] = Sha256::digest(&expected).into();
+
+        let mut out = Vec::new();
+        let mut reader = &stream[..];
+        assert!(apply_delta(&mut reader, &basis, 1024, &mut out, 2051, &digest).await?);
+        assert_eq!(out, expected);
+
+        // A mismatch is reported, and leaves the stream at the next
+        // header so that the file can be requested again.
+        let mut reader = &stream[..];
+        assert!(!apply_delta(&mut reader, &basis, 1024, &mut Vec::new(), 2051, &[0; 32]).await?);
+        assert_eq!(read_header::<String>(&mut reader).await?, "next");
+        Ok(())
+    }
 }
@@ -20,9 +20,11 @@ use hyperactor_mesh::RootActorMesh;
 use hyperactor_mesh::shared_cell::SharedCell;
 use monarch_hyperactor;
 use monarch_hyperactor::code_sync::WorkspaceLocation;
+use monarch_hyperactor::code_sync::auto_reload::ReloadReport;
 use monarch_hyperactor::code_sync::manager::CodeSyncManager;
 use monarch_hyperactor::code_sync::manager::CodeSyncManagerParams;
 use monarch_hyperactor::code_sync::manager::CodeSyncMethod;
+use monarch_hyperactor::code_sync::manager::RankSyncReport;
 use monarch_hyperactor::code_sync::manager::SetActorMeshMessage;
 use monarch_hyperactor::code_sync::manager::WorkspaceConfig;
 use monarch_hyperactor::code_sync::manager::WorkspaceShape;
@@ -31,6 +33,7 @@ use monarch_hyperactor::context::PyInstance;
 use monarch_hyperactor::proc_mesh::PyProcMesh;
 use monarch_hyperactor::runtime::signal_safe_block_on;
 use monarch_hyperactor::v1::proc_mesh::PyProcMesh as PyProcMeshV1;
+use ndslice::view::ViewExt;
 use pyo3::Bound;
 use pyo3::exceptions::PyException;
 use pyo3::exceptions::PyRuntimeError;
@@ -160,6 +163,7 @@ enum PyCodeSyncMethod {
     CondaSync {
         path_prefix_replacements: HashMap<PathBuf, PyWorkspaceLocation>,
     },
+    NativeSync {},
 }
 
 impl From<PyCodeSyncMethod> for CodeSyncMethod {
@@ -174,6 +178,7 @@ impl From<PyCodeSyncMethod> for CodeSyncMethod {
                     .map(|(l, r)| (l, r.into()))
                     .collect(),
             },
+            PyCodeSyncMethod::NativeSync {} => CodeSyncMethod::NativeSync,
         }
     }
 }
@@ -231,6 +236,90 @@ impl PyWorkspaceConfig {
     }
 }
 
+#[pyclass(
+    name = "ReloadReport",
+    module = "monarch._rust_bindings.monarch_extension.code_sync",
+    frozen,
+    get_all
+)]
+#[derive(Clone, Debug)]
+struct PyReloadReport {
+    reloaded: Vec<String>,
+    failed: Vec<(String, String)>,
+}
+
+impl From<ReloadReport> for PyReloadReport {
+    fn from(report: ReloadReport) -> Self {
+        Self {
+            reloaded: report.reloaded,
+            failed: report.failed,
+        }
+    }
+}
+
+#[pymethods]
+impl PyReloadReport {
+    fn __repr__(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// The outcome of a code sync on a single rank of the mesh.
+#[pyclass(
+    name = "RankSyncReport",
+    module = "monarch._rust_bindings.monarch_extension.code_sync",
+    frozen
+)]
+#[derive(Clone, Debug)]
+struct PyRankSyncReport {
+    #[pyo3(get)]
+    rank: usize,
+    point: String,
+    report: RankSyncReport,
+}
+
+#[pymethods]
+impl PyRankSyncReport {
+    /// Whether the rank was synced and all of its changed modules reloaded.
+    #[getter]
+    fn ok(&self) -> bool {
+        self.report.is_ok()
+    }
+
+    #[getter]
+    fn sync_error(&self) -> Option<String> {
+        self.report.sync_error.clone()
+    }
+
+    #[getter]
+    fn reload(&self) -> Option<PyReloadReport> {
+        self.report.reload.clone()?.ok().map(Into::into)
+    }
+
+    #[getter]
+    fn reload_error(&self) -> Option<String> {
+        self.report.reload.clone()?.err()
+    }
+
+    #[getter]
+    fn rollback(&self) -> Option<PyReloadReport> {
+        self.report.rollback.clone()?.ok().map(Into::into)
+    }
+
+    #[getter]
+    fn rollback_error(&self) -> Option<String> {
+        self.report.rollback.clone()?.err()
+    }
+
+    fn __str__(&self) -> String {
+        format!("{}: {}", self.point, self.report)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RankSyncReport({})", self.__str__())
+    }
+}
+
 #[pyclass(
     frozen,
     name = "CodeSyncMeshClient",
@@ -248,7 +337,8 @@ impl CodeSyncMeshClient {
         remote: RemoteWorkspace,
         method: CodeSyncMethod,
         auto_reload: bool,
-    ) -> Result<()> {
+        atomic: bool,
+    ) -> Result<Vec<PyRankSyncReport>> {
         let actor_mesh = actor_mesh.borrow()?;
         let shape = WorkspaceShape {
             shape: actor_mesh.shape().clone(),
@@ -259,10 +349,26 @@ impl CodeSyncMeshClient {
             location: remote.location.into(),
             shape,
         };
-        code_sync_mesh(instance, &actor_mesh, local, remote, method, auto_reload)
-            .await
-            .map_err(|err| PyRuntimeError::new_err(format!("{:#?}", err)))?;
-        Ok(())
+        let reports = code_sync_mesh(
+            instance,
+            &actor_mesh,
+            local,
+            remote,
+            method,
+            auto_reload,
+            atomic,
+        )
+        .await
+        .map_err(|err| PyRuntimeError::new_err(format!("{:#?}", err)))?;
+
+        Ok(reports
+            .iter()
+            .map(|(point, report)| PyRankSyncReport {
+                rank: point.rank(),
+                point: point.to_string(),
+                report: report.clone(),
+            })
+            .collect())
     }
 }
 
@@ -313,7 +419,7 @@ impl CodeSyncMeshClient {
         }
     }
 
-    #[pyo3(signature = (*, instance, local, remote, method = PyCodeSyncMethod::Rsync {}, auto_reload = false))]
+    #[pyo3(signature = (*, instance, local, remote, method = PyCodeSyncMethod::Rsync {}, auto_reload = false, atomic = false))]
     fn sync_workspace<'py>(
         &self,
         py: Python<'py>,
@@ -322,6 +428,7 @@ impl CodeSyncMeshClient {
         remote: RemoteWorkspace,
         method: PyCodeSyncMethod,
         auto_reload: bool,
+        atomic: bool,
     ) -> PyResult<Bound<'py, PyAny>> {
         let instance = instance.clone();
         let actor_mesh = self.actor_mesh.clone();
@@ -333,37 +440,48 @@ impl CodeSyncMeshClient {
                 remote,
                 method.into(),
                 auto_reload,
+                atomic,
             )
             .err_into()
             .await
         })
     }
 
-    #[pyo3(signature = (*, instance, workspaces, auto_reload = false))]
+    #[pyo3(signature = (*, instance, workspaces, auto_reload = false, atomic = false))]
     fn sync_workspaces<'py>(
         &self,
         py: Python<'py>,
         instance: &PyInstance,
         workspaces: Vec<PyWorkspaceConfig>,
         auto_reload: bool,
+        atomic: bool,
     ) -> PyResult<Bound<'py, PyAny>> {
         let actor_mesh = self.actor_mesh.clone();
         let instance = instance.clone();
         monarch_hyperactor::runtime::future_into_py(
             py,
             async move {
+                // The reports of each workspace synced, stopping at the
+                // first one that failed on any rank.
+                let mut reports = Vec::new();
                 for workspace in workspaces.into_iter() {
-                    CodeSyncMeshClient::sync_workspace_(
+                    let workspace_reports = CodeSyncMeshClient::sync_workspace_(
                         instance.deref(),
                         actor_mesh.clone(),
                         workspace.local,
                         workspace.remote,
                         workspace.method.into(),
                         auto_reload,
+                        atomic,
                     )
                     .await?;
+                    let failed = workspace_reports.iter().any(|report| !report.ok());
+                    reports.push(workspace_reports);
+                    if failed {
+                        break;
+                    }
                 }
-                anyhow::Ok(())
+                anyhow::Ok(reports)
             }
             .err_into(),
         )
@@ -373,6 +491,8 @@ impl CodeSyncMeshClient {
 pub fn register_python_bindings(module: &Bound<'_, PyModule>) -> PyResult<()> {
     module.add_class::<CodeSyncMeshClient>()?;
     module.add_class::<PyCodeSyncMethod>()?;
+    module.add_class::<PyRankSyncReport>()?;
+    module.add_class::<PyReloadReport>()?;
     module.add_class::<PyWorkspaceConfig>()?;
     module.add_class::<PyWorkspaceLocation>()?;
     module.add_class::<PyWorkspaceShape>()?;
@@ -441,7 +441,9 @@ impl RemoteProcessAllocInitializer for PyRemoteProcessAllocInitializer {
                     ChannelAddr::MetaTls(MetaTlsAddr::Host { hostname, .. }) => {
                         (hostname.clone(), hostname.clone())
                     }
-                    ChannelAddr::Unix(_) => (addr.to_string(), addr.to_string()),
+                    ChannelAddr::Unix(_) | ChannelAddr::Shm(_) => {
+                        (addr.to_string(), addr.to_string())
+                    }
                     _ => anyhow::bail!("unsupported transport for channel address: `{addr}`"),
                 };
                 Ok(RemoteProcessAllocHost { id, hostname })
@@ -7,11 +7,16 @@
  */
 
 use std::result::Result;
+use std::str::FromStr;
 
 use clap::Parser;
 use clap::command;
 use hyperactor::channel::ChannelAddr;
 use hyperactor_mesh::alloc::remoteprocess::RemoteProcessAllocator;
+use hyperactor_mesh::alloc::remoteprocess::auth::AllocAuthenticator;
+use hyperactor_mesh::alloc::remoteprocess::auth::AllocPolicy;
+use hyperactor_mesh::alloc::remoteprocess::auth::AllowedCommand;
+use hyperactor_mesh::alloc::remoteprocess::auth::is_plaintext;
 use tokio::process::Command;
 use tokio::time::Duration;
 
@@ -45,12 +50,83 @@ pub struct Args {
         help = "If specified, a timeout for the allocator to wait before exiting. Unspecified means no timeout"
     )]
     pub timeout_sec: Option<u64>,
+
+    #[arg(
+        long,
+        help = "A file of `<principal> <token>` lines. If specified, allocation requests must \
+                present one of the listed tokens. Tokens are refused over plaintext TCP, so \
+                this requires a TLS `--addr` (e.g. `metatls!...`); use `--hmac-secret-file` \
+                on TCP"
+    )]
+    pub token_file: Option<String>,
+
+    #[arg(
+        long,
+        help = "A file holding a shared secret. If specified, allocation requests signed \
+                with HMAC-SHA256 using this secret are accepted"
+    )]
+    pub hmac_secret_file: Option<String>,
+
+    #[arg(
+        long,
+        help = "A program this allocator may launch. May be repeated. If unspecified, \
+                any `--program` is allowed"
+    )]
+    pub allow_program: Vec<String>,
+}
+
+impl Args {
+    /// The address to serve on: `--addr` if specified, or else
+    /// `--port` on all interfaces over TCP.
+    pub fn serve_address(&self) -> Result<ChannelAddr, anyhow::Error> {
+        match &self.addr {
+            Some(addr) => Ok(ChannelAddr::from_str(addr)?),
+            None => Ok(ChannelAddr::from_str(&format!("tcp![::]:{}", self.port))?),
+        }
+    }
+
+    /// The policy applied to allocation requests served on
+    /// `serve_address`, as configured by the authentication and
+    /// allow-list arguments. Fails if `--token-file` is combined with a
+    /// plaintext address, since every token would then be refused.
+    pub fn policy(&self, serve_address: &ChannelAddr) -> Result<AllocPolicy, anyhow::Error> {
+        anyhow::ensure!(
+            self.token_file.is_none() || !is_plaintext(&serve_address.transport()),
+            "--token-file requires a TLS --addr, but {} is plaintext: tokens are refused \
+             over plaintext transports; use --hmac-secret-file instead",
+            serve_address
+        );
+        if self.token_file.is_none() && self.hmac_secret_file.is_none() {
+            tracing::warn!(
+                "neither --token-file nor --hmac-secret-file is set: this allocator launches \
+                 {} for anyone who can reach {}",
+                self.program,
+                serve_address
+            );
+        }
+        let mut policy = AllocPolicy::default();
+        if self.token_file.is_some() || self.hmac_secret_file.is_some() {
+            let mut authenticator = AllocAuthenticator::new();
+            if let Some(path) = &self.token_file {
+                authenticator = authenticator.with_token_file(path)?;
+            }
+            if let Some(path) = &self.hmac_secret_file {
+                authenticator = authenticator.with_secret_file(path)?;
+            }
+            policy = policy.with_authenticator(authenticator);
+        }
+        for program in &self.allow_program {
+            policy = policy.allow(AllowedCommand::program(program));
+        }
+        Ok(policy)
+    }
 }
 
 pub fn main_impl(
     serve_address: ChannelAddr,
     program: Command,
     timeout: Option<Duration>,
+    policy: AllocPolicy,
 ) -> tokio::task::JoinHandle<Result<(), anyhow::Error>> {
     #[cfg(unix)]
     fn ignore_sigpipe() {
@@ -73,7 +149,7 @@ pub fn main_impl(
     tracing::info!("program to spawn on allocation request: [{:?}]", &program);
 
     tokio::spawn(async move {
-        RemoteProcessAllocator::new()
+        RemoteProcessAllocator::with_policy(policy)
             .start(program, serve_address, timeout)
             .await
     })
@@ -82,6 +158,7 @@ pub fn main_impl(
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
+    use std::io::Write;
 
     use clap::Parser;
     use hyperactor::WorldId;
@@ -91,6 +168,9 @@ mod tests {
     use hyperactor_mesh::alloc;
     use hyperactor_mesh::alloc::Alloc;
     use hyperactor_mesh::alloc::remoteprocess;
+    use hyperactor_mesh::alloc::remoteprocess::auth::AllocCredential;
+    use hyperactor_mesh::alloc::remoteprocess::auth::AllocRequest;
+    use hyperactor_mesh::shortuuid::ShortUuid;
     use ndslice::extent;
 
     use super::*;
@@ -122,13 +202,76 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_args_policy() -> Result<(), anyhow::Error> {
+        let mut tokens = tempfile::NamedTempFile::new()?;
+        writeln!(tokens, "alice s3cret")?;
+        let token_file = format!("--token-file={}", tokens.path().display());
+        let args = vec![
+            "process_allocator",
+            "--addr=unix!@allocator",
+            "--program=/bin/echo",
+            &token_file,
+            "--allow-program=/bin/date",
+        ];
+
+        let parsed_args = Args::parse_from(args);
+        assert_eq!(parsed_args.allow_program, vec!["/bin/date".to_string()]);
+
+        let policy = parsed_args.policy(&parsed_args.serve_address()?)?;
+        assert!(policy.check_command(&Command::new("/bin/date")).is_ok());
+        assert!(policy.check_command(&Command::new("/bin/echo")).is_err());
+
+        let alloc_key = ShortUuid::generate();
+        let extent = extent!(x = 1);
+        let addr = ChannelAddr::any(ChannelTransport::Unix);
+        let request = AllocRequest {
+            alloc_key: &alloc_key,
+            extent: &extent,
+            bootstrap_addr: &addr,
+            hosts: &[],
+            client_context: None,
+            forwarder_addr: &addr,
+        };
+        assert!(
+            policy
+                .authenticate(None, request, &ChannelTransport::Unix)
+                .is_err()
+        );
+        assert_eq!(
+            policy.authenticate(
+                Some(&AllocCredential::token("alice", "s3cret")),
+                request,
+                &ChannelTransport::Unix
+            )?,
+            Some("alice".to_string())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_args_token_file_requires_tls() -> Result<(), anyhow::Error> {
+        let mut tokens = tempfile::NamedTempFile::new()?;
+        writeln!(tokens, "alice s3cret")?;
+        let token_file = format!("--token-file={}", tokens.path().display());
+
+        // The default address is plaintext TCP.
+        let parsed_args = Args::parse_from(vec!["process_allocator", &token_file]);
+        let err = parsed_args
+            .policy(&parsed_args.serve_address()?)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("plaintext"), "{}", err);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_main_impl() -> Result<(), anyhow::Error> {
         hyperactor::initialize_with_current_runtime();
 
         let serve_address = ChannelAddr::any(ChannelTransport::Unix);
         let program = Command::new("/bin/date"); // date is usually a unix built-in command
-        let server_handle = main_impl(serve_address.clone(), program, None);
+        let server_handle = main_impl(serve_address.clone(), program, None, AllocPolicy::default());
 
         let spec = alloc::AllocSpec {
             // NOTE: x cannot be more than 1 since we created a single process-allocator server instance!
@@ -181,6 +324,59 @@ mod tests {
         Ok(())
     }
 
+    /// Tests that a client presenting a token from its configured token file
+    /// is allowed to allocate from an allocator that requires one.
+    #[tokio::test]
+    async fn test_main_impl_authenticated() -> Result<(), anyhow::Error> {
+        hyperactor::initialize_with_current_runtime();
+
+        let mut tokens = tempfile::NamedTempFile::new()?;
+        writeln!(tokens, "alice s3cret")?;
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(
+            alloc::REMOTE_ALLOC_TOKEN_FILE,
+            tokens.path().display().to_string(),
+        );
+
+        let serve_address = ChannelAddr::any(ChannelTransport::Unix);
+        let program = Command::new("/bin/date");
+        let policy = AllocPolicy::default()
+            .with_authenticator(AllocAuthenticator::new().with_token_file(tokens.path())?)
+            .allow(AllowedCommand::program("/bin/date"));
+        let server_handle = main_impl(serve_address.clone(), program, None, policy);
+
+        let spec = alloc::AllocSpec {
+            extent: extent! { x=1, y=1 },
+            constraints: Default::default(),
+            proc_name: None,
+            transport: ChannelTransport::Unix,
+            proc_allocation_mode: Default::default(),
+        };
+
+        let mut initializer = remoteprocess::MockRemoteProcessAllocInitializer::new();
+        initializer.expect_initialize_alloc().return_once(move || {
+            Ok(vec![remoteprocess::RemoteProcessAllocHost {
+                hostname: serve_address.to_string(),
+                id: serve_address.to_string(),
+            }])
+        });
+
+        let world_id = WorldId("__unused__".to_string());
+        let mut alloc =
+            remoteprocess::RemoteProcessAlloc::new(spec.clone(), world_id, 0, initializer)
+                .await
+                .unwrap();
+        let proc_state = alloc.next().await.unwrap();
+        assert!(
+            matches!(proc_state, alloc::ProcState::Created { .. }),
+            "unexpected proc state: {:?}",
+            proc_state
+        );
+
+        server_handle.abort();
+        Ok(())
+    }
+
     /// Tests that an allocator with a timeout and no messages will exit and not
     /// finish allocating.
     #[tokio::test]
@@ -191,7 +387,12 @@ mod tests {
         let program = Command::new("/bin/date"); // date is usually a unix built-in command
         // 1 second quick timeout to check that it fails.
         let timeout = Duration::from_millis(500);
-        let server_handle = main_impl(serve_address.clone(), program, Some(timeout));
+        let server_handle = main_impl(
+            serve_address.clone(),
+            program,
+            Some(timeout),
+            AllocPolicy::default(),
+        );
 
         let spec = alloc::AllocSpec {
             // NOTE: x cannot be more than 1 since we created a single process-allocator server instance!
@@ -247,7 +448,12 @@ mod tests {
         let program = Command::new("/bin/date"); // date is usually a unix built-in command
         // Slower timeout so we can send a message in time.
         let timeout = Duration::from_millis(1500);
-        let server_handle = main_impl(serve_address.clone(), program, Some(timeout));
+        let server_handle = main_impl(
+            serve_address.clone(),
+            program,
+            Some(timeout),
+            AllocPolicy::default(),
+        );
 
         let spec = alloc::AllocSpec {
             // NOTE: x cannot be more than 1 since we created a single process-allocator server instance!
@@ -319,7 +525,12 @@ mod tests {
         let mut program = Command::new("/usr/bin/sleep"); // use a command that waits for a while
         program.arg("3");
         let timeout = Duration::from_millis(500);
-        let server_handle = main_impl(serve_address.clone(), program, Some(timeout));
+        let server_handle = main_impl(
+            serve_address.clone(),
+            program,
+            Some(timeout),
+            AllocPolicy::default(),
+        );
 
         let spec = alloc::AllocSpec {
             // NOTE: x cannot be more than 1 since we created a single process-allocator server instance!
@@ -8,12 +8,9 @@
 
 mod common;
 
-use std::str::FromStr;
-
 use clap::Parser;
 use common::Args;
 use common::main_impl;
-use hyperactor::channel::ChannelAddr;
 use tokio::process::Command;
 use tokio::time::Duration;
 
@@ -22,13 +19,12 @@ async fn main() {
     let args = Args::parse();
     hyperactor::initialize_with_current_runtime();
 
-    let bind = args
-        .addr
-        .unwrap_or_else(|| format!("tcp![::]:{}", args.port));
-
-    let serve_address = ChannelAddr::from_str(&bind).unwrap();
+    let serve_address = args.serve_address().unwrap();
+    let policy = args.policy(&serve_address).unwrap();
     let program = Command::new(args.program);
     let timeout = args.timeout_sec.map(Duration::from_secs);
 
-    let _ = main_impl(serve_address, program, timeout).await.unwrap();
+    let _ = main_impl(serve_address, program, timeout, policy)
+        .await
+        .unwrap();
 }
@@ -36,6 +36,7 @@ pub enum PyChannelTransport {
     MetaTlsWithIpV6,
     Local,
     Unix,
+    Shm,
     // Sim(/*transport:*/ ChannelTransport), TODO kiuk@ add support
 }
 
@@ -59,6 +60,7 @@ impl TryFrom<ChannelTransport> for PyChannelTransport {
             ChannelTransport::MetaTls(TlsMode::IpV6) => Ok(PyChannelTransport::MetaTlsWithIpV6),
             ChannelTransport::Local => Ok(PyChannelTransport::Local),
             ChannelTransport::Unix => Ok(PyChannelTransport::Unix),
+            ChannelTransport::Shm => Ok(PyChannelTransport::Shm),
             _ => Err(PyValueError::new_err(format!(
                 "unsupported transport: {}",
                 transport
@@ -178,7 +180,7 @@ impl PyChannelAddr {
             ChannelAddr::Tcp(socket_addr)
             | ChannelAddr::MetaTls(MetaTlsAddr::Socket(socket_addr)) => Ok(socket_addr.port()),
             ChannelAddr::MetaTls(MetaTlsAddr::Host { port, .. }) => Ok(port),
-            ChannelAddr::Unix(_) | ChannelAddr::Local(_) => Ok(0),
+            ChannelAddr::Unix(_) | ChannelAddr::Shm(_) | ChannelAddr::Local(_) => Ok(0),
             _ => Err(PyRuntimeError::new_err(format!(
                 "unsupported transport: `{:?}` for channel address: `{}`",
                 self.inner.transport(),
@@ -201,6 +203,7 @@ impl PyChannelAddr {
             },
             ChannelTransport::Local => Ok(PyChannelTransport::Local),
             ChannelTransport::Unix => Ok(PyChannelTransport::Unix),
+            ChannelTransport::Shm => Ok(PyChannelTransport::Shm),
             _ => Err(PyRuntimeError::new_err(format!(
                 "unsupported transport: `{:?}` for address: `{}`",
                 self.inner.transport(),
@@ -219,6 +222,7 @@ impl From<PyChannelTransport> for ChannelTransport {
             PyChannelTransport::MetaTlsWithIpV6 => ChannelTransport::MetaTls(TlsMode::IpV6),
             PyChannelTransport::Local => ChannelTransport::Local,
             PyChannelTransport::Unix => ChannelTransport::Unix,
+            PyChannelTransport::Shm => ChannelTransport::Shm,
         }
     }
 }
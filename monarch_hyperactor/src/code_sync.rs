@@ -9,7 +9,9 @@
 pub mod auto_reload;
 pub mod conda_sync;
 pub mod manager;
+pub mod native_sync;
 pub mod rsync;
+mod snapshot;
 mod workspace;
 
 pub use workspace::WorkspaceLocation;
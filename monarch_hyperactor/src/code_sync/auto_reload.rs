@@ -24,7 +24,17 @@ use serde::Serialize;
 /// Message to trigger module reloading
 #[derive(Debug, Clone, Named, Serialize, Deserialize)]
 pub struct AutoReloadMessage {
-    pub result: PortRef<Result<(), String>>,
+    pub result: PortRef<Result<ReloadReport, String>>,
+}
+
+/// The modules reloaded by a single reload. A module that fails to
+/// reload does not stop the others from being reloaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Named, Serialize, Deserialize)]
+pub struct ReloadReport {
+    /// The modules that were reloaded.
+    pub reloaded: Vec<String>,
+    /// The modules that failed to reload, with their errors.
+    pub failed: Vec<(String, String)>,
 }
 
 /// Parameters for creating an AutoReloadActor
@@ -76,13 +86,17 @@ impl AutoReloadActor {
         Ok((Arc::new(reloader.into()), hook_guard.into()))
     }
 
-    fn reload(py: Python, py_reloader: &PyObject) -> PyResult<()> {
+    fn reload(py: Python, py_reloader: &PyObject) -> PyResult<ReloadReport> {
         let reloader = py_reloader.bind(py);
-        let changed_modules: Vec<String> = reloader.call_method0("reload_changes")?.extract()?;
-        if !changed_modules.is_empty() {
-            eprintln!("reloaded modules: {:?}", changed_modules);
+        let (reloaded, failed): (Vec<String>, Vec<(String, String)>) =
+            reloader.call_method0("try_reload_changes")?.extract()?;
+        if !reloaded.is_empty() {
+            eprintln!("reloaded modules: {:?}", reloaded);
         }
-        Ok(())
+        for (module, error) in &failed {
+            eprintln!("failed to reload module {}: {}", module, error);
+        }
+        Ok(ReloadReport { reloaded, failed })
     }
 }
 
@@ -96,13 +110,13 @@ impl Handler<AutoReloadMessage> for AutoReloadActor {
         // Call the Python reloader's reload_changes method
         let res = async {
             let py_reloader: Arc<_> = self.state.as_ref().map_err(Clone::clone)?.0.clone();
-            tokio::task::spawn_blocking(move || {
+            let report = tokio::task::spawn_blocking(move || {
                 Python::with_gil(|py| {
                     Self::reload(py, py_reloader.as_ref()).map_err(SerializablePyErr::from_fn(py))
                 })
             })
             .await??;
-            anyhow::Ok(())
+            anyhow::Ok(report)
         }
         .await;
         result.send(cx, res.map_err(|e| format!("{:#?}", e)))?;
@@ -129,7 +129,9 @@ where
                 let (mut read, mut write) = accept(instance, instance.self_id().clone(), connect)
                     .await?
                     .into_split();
-                let res = sender(&local_workspace, &mut read, &mut write).await;
+                let res = sender(&local_workspace, &mut read, &mut write)
+                    .await
+                    .map(|stats| tracing::info!("conda sync sent {}", stats));
 
                 // Shutdown our end, then read from the other end till exhaustion to avoid undeliverable
                 // message spam.
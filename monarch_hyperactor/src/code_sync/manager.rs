@@ -8,10 +8,10 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 
-use anyhow::Context as _;
 use anyhow::Result;
 use anyhow::ensure;
 use async_once_cell::OnceCell;
@@ -44,13 +44,13 @@ use hyperactor_mesh::reference::ActorMeshRef;
 use hyperactor_mesh::reference::ProcMeshId;
 use hyperactor_mesh::sel;
 use hyperactor_mesh::v1;
-use lazy_errors::ErrorStash;
-use lazy_errors::TryCollectOrStash;
+use hyperactor_mesh::v1::ValueMesh;
 use monarch_conda::sync::sender;
 use ndslice::Selection;
 use ndslice::Shape;
 use ndslice::ShapeError;
 use ndslice::View;
+use ndslice::view::CollectMeshExt;
 use ndslice::view::RankedSliceable;
 use ndslice::view::ViewExt;
 use serde::Deserialize;
@@ -63,13 +63,18 @@ use tokio::net::TcpStream;
 use crate::code_sync::WorkspaceLocation;
 use crate::code_sync::auto_reload::AutoReloadActor;
 use crate::code_sync::auto_reload::AutoReloadMessage;
+use crate::code_sync::auto_reload::ReloadReport;
 use crate::code_sync::conda_sync::CondaSyncActor;
 use crate::code_sync::conda_sync::CondaSyncMessage;
 use crate::code_sync::conda_sync::CondaSyncResult;
+use crate::code_sync::native_sync;
+use crate::code_sync::native_sync::NativeSyncActor;
+use crate::code_sync::native_sync::NativeSyncMessage;
 use crate::code_sync::rsync::RsyncActor;
 use crate::code_sync::rsync::RsyncDaemon;
 use crate::code_sync::rsync::RsyncMessage;
 use crate::code_sync::rsync::RsyncResult;
+use crate::code_sync::snapshot::WorkspaceSnapshot;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum Method {
@@ -80,6 +85,9 @@ pub enum Method {
         connect: PortRef<Connect>,
         path_prefix_replacements: HashMap<PathBuf, WorkspaceLocation>,
     },
+    NativeSync {
+        connect: PortRef<Connect>,
+    },
 }
 
 /// Describe the shape of the workspace.
@@ -169,21 +177,95 @@ pub struct WorkspaceConfig {
     pub shape: WorkspaceShape,
 }
 
+/// The outcome of a code sync on a single rank.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Named)]
+pub struct RankSyncReport {
+    /// Why the rank's workspace failed to sync, if it did.
+    pub sync_error: Option<String>,
+    /// If hot-reloading was requested, the modules reloaded on the rank,
+    /// or why the reloader failed.
+    pub reload: Option<Result<ReloadReport, String>>,
+    /// If the sync was rolled back, the modules reloaded on the rank to
+    /// restore the previous code, or why the rollback failed.
+    pub rollback: Option<Result<ReloadReport, String>>,
+}
+
+impl RankSyncReport {
+    /// Whether the rank's workspace was synced, and all of its changed
+    /// modules reloaded (if requested).
+    pub fn is_ok(&self) -> bool {
+        self.sync_error.is_none()
+            && self
+                .reload
+                .as_ref()
+                .is_none_or(|reload| reload.as_ref().is_ok_and(|r| r.failed.is_empty()))
+    }
+}
+
+impl fmt::Display for RankSyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        if let Some(error) = &self.sync_error {
+            write!(f, "sync failed: {}", error)?;
+            sep = "; ";
+        }
+        match &self.reload {
+            Some(Ok(reload)) => {
+                write!(f, "{}reloaded {:?}", sep, reload.reloaded)?;
+                for (module, error) in &reload.failed {
+                    write!(f, "; failed to reload {}: {}", module, error)?;
+                }
+                sep = "; ";
+            }
+            Some(Err(error)) => {
+                write!(f, "{}reload failed: {}", sep, error)?;
+                sep = "; ";
+            }
+            None => {}
+        }
+        match &self.rollback {
+            Some(Ok(reload)) => write!(f, "{}rolled back, reloaded {:?}", sep, reload.reloaded)?,
+            Some(Err(error)) => write!(f, "{}rollback failed: {}", sep, error)?,
+            None if sep.is_empty() => write!(f, "ok")?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
 #[derive(Handler, Clone, Serialize, Deserialize, Debug, Named, Bind, Unbind)]
 pub enum CodeSyncMessage {
     Sync {
         workspace: WorkspaceLocation,
         /// The method to use for syncing.
         method: Method,
-        /// Whether to hot-reload code after syncing.
-        reload: Option<WorkspaceShape>,
-        /// A port to send back the result of the sync operation.
-        result: PortRef<Result<(), String>>,
+        /// The ranks sharing the workspace.
+        shape: WorkspaceShape,
+        /// Whether to hot-reload code on all ranks sharing the workspace after syncing.
+        reload: bool,
+        /// Whether to snapshot the workspace before syncing, so that the sync can be
+        /// undone with `Rollback` (or the snapshot discarded with `Commit`).
+        snapshot: bool,
+        /// A port to send back the outcome for each rank sharing the workspace.
+        result: PortRef<Vec<(usize, RankSyncReport)>>,
     },
     Reload {
         sender_rank: Option<usize>,
-        result: PortRef<Result<(), String>>,
+        /// A port to send back the rank and its reloaded modules.
+        result: PortRef<(usize, Result<ReloadReport, String>)>,
+    },
+    /// Restore the workspace from the snapshot taken by the last `Sync`.
+    Rollback {
+        workspace: WorkspaceLocation,
+        /// The ranks sharing the workspace.
+        shape: WorkspaceShape,
+        /// Whether to hot-reload the restored code on all ranks sharing the workspace.
+        reload: bool,
+        /// A port to send back the modules reloaded on each rank sharing the workspace.
+        result: PortRef<Vec<(usize, Result<ReloadReport, String>)>>,
     },
+    /// Discard the snapshot taken by the last `Sync`.
+    Commit { workspace: WorkspaceLocation },
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, Named, Bind, Unbind)]
@@ -206,8 +288,11 @@ pub struct CodeSyncManager {
     rsync: OnceCell<ActorHandle<RsyncActor>>,
     auto_reload: OnceCell<ActorHandle<AutoReloadActor>>,
     conda_sync: OnceCell<ActorHandle<CondaSyncActor>>,
+    native_sync: OnceCell<ActorHandle<NativeSyncActor>>,
     self_mesh: once_cell::sync::OnceCell<v1::actor_mesh::ActorMeshRef<CodeSyncManager>>,
     rank: once_cell::sync::OnceCell<usize>,
+    /// Snapshots of workspaces taken by atomic syncs, by resolved path.
+    snapshots: HashMap<PathBuf, WorkspaceSnapshot>,
 }
 
 impl Actor for CodeSyncManager {}
@@ -221,8 +306,10 @@ impl RemoteSpawn for CodeSyncManager {
             rsync: OnceCell::new(),
             auto_reload: OnceCell::new(),
             conda_sync: OnceCell::new(),
+            native_sync: OnceCell::new(),
             self_mesh: once_cell::sync::OnceCell::new(),
             rank: once_cell::sync::OnceCell::new(),
+            snapshots: HashMap::new(),
         })
     }
 }
@@ -254,6 +341,98 @@ impl CodeSyncManager {
             .get_or_try_init(async move { CondaSyncActor::default().spawn(cx) })
             .await
     }
+
+    async fn get_native_sync_actor<'a>(
+        &'a mut self,
+        cx: &Context<'a, Self>,
+    ) -> Result<&'a ActorHandle<NativeSyncActor>> {
+        self.native_sync
+            .get_or_try_init(async move { NativeSyncActor::default().spawn(cx) })
+            .await
+    }
+
+    /// This actor's rank in its mesh.
+    fn rank(&self, cx: &Context<Self>) -> usize {
+        self.rank
+            .get()
+            .copied()
+            .unwrap_or_else(|| cx.self_id().rank())
+    }
+
+    /// The ranks sharing this actor's workspace, including itself.
+    fn workspace_ranks(&self, cx: &Context<Self>, workspace_shape: &WorkspaceShape) -> Vec<usize> {
+        let rank = self.rank(cx);
+        workspace_shape
+            .downstream(rank)
+            .map_or_else(|_| vec![rank], |shape| shape.slice().iter().collect())
+    }
+
+    /// Hot-reload changed modules on this rank.
+    async fn reload_local(&mut self, cx: &Context<'_, Self>) -> Result<ReloadReport> {
+        let (tx, mut rx) = cx.open_port();
+        self.get_auto_reload_actor(cx)
+            .await?
+            .send(AutoReloadMessage { result: tx.bind() })?;
+        rx.recv().await?.map_err(anyhow::Error::msg)
+    }
+
+    /// Hot-reload changed modules on all ranks that use/share this actor's
+    /// workspace, returning the outcome for each rank.
+    async fn reload_workspace(
+        &mut self,
+        cx: &Context<'_, Self>,
+        workspace_shape: &WorkspaceShape,
+    ) -> Result<Vec<(usize, Result<ReloadReport, String>)>> {
+        let (tx, rx) = cx.open_port::<(usize, Result<ReloadReport, String>)>();
+        let tx = tx.bind();
+        let len;
+        if let Some(rank) = self.rank.get() {
+            let mesh = self
+                .self_mesh
+                .get()
+                .ok_or_else(|| anyhow::anyhow!("missing self mesh"))?;
+            let mesh = workspace_shape.downstream_mesh(mesh, *rank)?;
+            // This actor ignores its own copy of the message, as it will be
+            // blocked here waiting for results.
+            mesh.cast(
+                cx,
+                CodeSyncMessage::Reload {
+                    sender_rank: Some(*rank),
+                    result: tx,
+                },
+            )?;
+            len = mesh.region().slice().len() - 1;
+        } else {
+            let mesh = workspace_shape.downstream_mesh_v0(cx.self_id())?;
+            mesh.cast(
+                cx,
+                // We make sure to exclude the current rank from the sync, as this actor will
+                // be blocked here waiting for results.
+                sel!(*).without(mesh.shape().slice(), &HashSet::from([cx.self_id().rank()]))?,
+                CodeSyncMessage::Reload {
+                    sender_rank: None,
+                    result: tx,
+                },
+            )?;
+            len = mesh.shape().slice().len() - 1;
+        }
+        let rank = self.rank(cx);
+        let (local, mut reports): (_, Vec<_>) = try_join!(
+            // Run reload for this rank concurrently.
+            self.reload_local(cx).map(anyhow::Ok),
+            rx.take(len).err_into::<anyhow::Error>().try_collect(),
+        )?;
+        reports.push((
+            rank,
+            local.map_err(|e| {
+                format!(
+                    "{:#?}",
+                    e.context(format!("module reload from {}", cx.self_id()))
+                )
+            }),
+        ));
+        Ok(reports)
+    }
 }
 
 #[async_trait]
@@ -264,10 +443,23 @@ impl CodeSyncMessageHandler for CodeSyncManager {
         cx: &Context<Self>,
         workspace: WorkspaceLocation,
         method: Method,
-        reload: Option<WorkspaceShape>,
-        result: PortRef<Result<(), String>>,
+        shape: WorkspaceShape,
+        reload: bool,
+        snapshot: bool,
+        result: PortRef<Vec<(usize, RankSyncReport)>>,
     ) -> Result<()> {
-        let res = async move {
+        let ranks = self.workspace_ranks(cx, &shape);
+        let res = async {
+            if snapshot {
+                let path = workspace.resolve()?;
+                let taken = tokio::task::spawn_blocking({
+                    let path = path.clone();
+                    move || WorkspaceSnapshot::take(&path)
+                })
+                .await??;
+                self.snapshots.insert(path, taken);
+            }
+
             match method {
                 Method::Rsync { connect } => {
                     // Forward rsync connection port to the RsyncActor, which will do the actual
@@ -299,67 +491,83 @@ impl CodeSyncMessageHandler for CodeSyncManager {
                     // Observe any errors.
                     let _ = rx.recv().await?.map_err(anyhow::Error::msg)?;
                 }
-            }
-
-            // Trigger hot reload on all ranks that use/share this workspace.
-            if let Some(workspace_shape) = reload {
-                let (tx, rx) = cx.open_port::<Result<(), String>>();
-                let tx = tx.bind();
-                let len;
-                if let Some(rank) = self.rank.get() {
-                    let mesh = self
-                        .self_mesh
-                        .get()
-                        .ok_or_else(|| anyhow::anyhow!("missing self mesh"))?;
-                    let mesh = workspace_shape.downstream_mesh(mesh, *rank)?;
-                    mesh.cast(
-                        cx,
-                        CodeSyncMessage::Reload {
-                            sender_rank: Some(*rank),
-                            result: tx.clone(),
-                        },
-                    )?;
-                    // Exclude self from the sync.
-                    len = mesh.region().slice().len() - 1;
-                } else {
-                    let mesh = workspace_shape.downstream_mesh_v0(cx.self_id())?;
-                    mesh.cast(
-                        cx,
-                        // We make sure to exclude the current rank from the sync, as this actor will
-                        // be blocked here waiting for results.  We just manually call `reload` to run
-                        // concurrently below.
-                        sel!(*)
-                            .without(mesh.shape().slice(), &HashSet::from([cx.self_id().rank()]))?,
-                        CodeSyncMessage::Reload {
-                            sender_rank: None,
-                            result: tx.clone(),
-                        },
-                    )?;
-                    len = mesh.shape().slice().len();
+                Method::NativeSync { connect } => {
+                    // Forward the connection port to the NativeSyncActor, which will do the
+                    // actual connection and run the receiver.
+                    let (tx, mut rx) = cx.open_port::<Result<RsyncResult, String>>();
+                    self.get_native_sync_actor(cx)
+                        .await?
+                        .send(NativeSyncMessage {
+                            connect,
+                            result: tx.bind(),
+                            workspace,
+                        })?;
+                    // Observe any errors.
+                    let _ = rx.recv().await?.map_err(anyhow::Error::msg)?;
                 }
-                let _: ((), Vec<()>) = try_join!(
-                    // Run reload for this rank.
-                    self.reload(cx, self.rank.get().cloned(), tx),
-                    rx.take(len)
-                        .map(|res| res?.map_err(anyhow::Error::msg))
-                        .try_collect(),
-                )?;
             }
-
             anyhow::Ok(())
         }
         .await;
-        result.send(
-            cx,
-            res.map_err(|e| {
-                format!(
+
+        let reports = match res {
+            Ok(()) => {
+                // Trigger hot reload on all ranks that use/share this workspace.
+                let mut reloads: HashMap<_, _> = if reload {
+                    match self.reload_workspace(cx, &shape).await {
+                        Ok(reloads) => reloads.into_iter().collect(),
+                        Err(e) => {
+                            let error = format!(
+                                "{:#?}",
+                                e.context(format!("module reload from {}", cx.self_id()))
+                            );
+                            ranks
+                                .iter()
+                                .map(|&rank| (rank, Err(error.clone())))
+                                .collect()
+                        }
+                    }
+                } else {
+                    HashMap::new()
+                };
+                ranks
+                    .iter()
+                    .map(|&rank| {
+                        let reload = reload.then(|| {
+                            reloads
+                                .remove(&rank)
+                                .unwrap_or_else(|| Err("missing reload result".to_string()))
+                        });
+                        (
+                            rank,
+                            RankSyncReport {
+                                reload,
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect()
+            }
+            Err(e) => {
+                let error = format!(
                     "{:#?}",
-                    Err::<(), _>(e)
-                        .with_context(|| format!("code sync from {}", cx.self_id()))
-                        .unwrap_err()
-                )
-            }),
-        )?;
+                    e.context(format!("code sync from {}", cx.self_id()))
+                );
+                ranks
+                    .iter()
+                    .map(|&rank| {
+                        (
+                            rank,
+                            RankSyncReport {
+                                sync_error: Some(error.clone()),
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect()
+            }
+        };
+        result.send(cx, reports)?;
         Ok(())
     }
 
@@ -367,7 +575,7 @@ impl CodeSyncMessageHandler for CodeSyncManager {
         &mut self,
         cx: &Context<Self>,
         sender_rank: Option<usize>,
-        result: PortRef<Result<(), String>>,
+        result: PortRef<(usize, Result<ReloadReport, String>)>,
     ) -> Result<()> {
         if self
             .rank
@@ -376,28 +584,88 @@ impl CodeSyncMessageHandler for CodeSyncManager {
         {
             return Ok(());
         }
-        let res = async move {
-            let (tx, mut rx) = cx.open_port();
-            self.get_auto_reload_actor(cx)
-                .await?
-                .send(AutoReloadMessage { result: tx.bind() })?;
-            rx.recv().await?.map_err(anyhow::Error::msg)?;
-            anyhow::Ok(())
-        }
-        .await;
+        let rank = self.rank(cx);
+        let res = self.reload_local(cx).await;
         result.send(
             cx,
-            res.map_err(|e| {
-                format!(
-                    "{:#?}",
-                    Err::<(), _>(e)
-                        .with_context(|| format!("module reload from {}", cx.self_id()))
-                        .unwrap_err()
-                )
-            }),
+            (
+                rank,
+                res.map_err(|e| {
+                    format!(
+                        "{:#?}",
+                        e.context(format!("module reload from {}", cx.self_id()))
+                    )
+                }),
+            ),
         )?;
         Ok(())
     }
+
+    async fn rollback(
+        &mut self,
+        cx: &Context<Self>,
+        workspace: WorkspaceLocation,
+        shape: WorkspaceShape,
+        reload: bool,
+        result: PortRef<Vec<(usize, Result<ReloadReport, String>)>>,
+    ) -> Result<()> {
+        let ranks = self.workspace_ranks(cx, &shape);
+        let res = async {
+            let path = workspace.resolve()?;
+            let snapshot = self
+                .snapshots
+                .remove(&path)
+                .ok_or_else(|| anyhow::anyhow!("no snapshot of workspace {}", path.display()))?;
+            tokio::task::spawn_blocking(move || snapshot.restore(&path)).await??;
+            if reload {
+                self.reload_workspace(cx, &shape).await
+            } else {
+                Ok(Vec::new())
+            }
+        }
+        .await;
+
+        let reports = match res {
+            Ok(reloads) => {
+                let mut reloads: HashMap<_, _> = reloads.into_iter().collect();
+                ranks
+                    .iter()
+                    .map(|&rank| {
+                        (
+                            rank,
+                            reloads
+                                .remove(&rank)
+                                .unwrap_or_else(|| Ok(ReloadReport::default())),
+                        )
+                    })
+                    .collect()
+            }
+            Err(e) => {
+                let error = format!(
+                    "{:#?}",
+                    e.context(format!("code sync rollback from {}", cx.self_id()))
+                );
+                ranks
+                    .iter()
+                    .map(|&rank| (rank, Err(error.clone())))
+                    .collect()
+            }
+        };
+        result.send(cx, reports)?;
+        Ok(())
+    }
+
+    async fn commit(&mut self, _cx: &Context<Self>, workspace: WorkspaceLocation) -> Result<()> {
+        if let Some(snapshot) = workspace
+            .resolve()
+            .ok()
+            .and_then(|path| self.snapshots.remove(&path))
+        {
+            // Removing the snapshot's copy of the workspace may take a while.
+            tokio::task::spawn_blocking(move || drop(snapshot));
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -421,8 +689,17 @@ pub enum CodeSyncMethod {
     CondaSync {
         path_prefix_replacements: HashMap<PathBuf, WorkspaceLocation>,
     },
+    /// Sync via a content-addressed manifest over actor channels,
+    /// without requiring an `rsync` binary on either end.
+    NativeSync,
 }
 
+/// Sync `local_workspace` to the remote workspace of every rank in `actor_mesh`, optionally
+/// hot-reloading changed Python modules afterwards, and return the outcome for each rank.
+///
+/// In `atomic` mode, each remote workspace is snapshotted before it is synced, and if any
+/// rank fails to sync or reload, every rank is rolled back to its snapshot (and its modules
+/// reloaded again), so that the mesh is left running consistent code.
 pub async fn code_sync_mesh(
     cx: &impl context::Actor,
     actor_mesh: &RootActorMesh<'_, CodeSyncManager>,
@@ -430,7 +707,8 @@ pub async fn code_sync_mesh(
     remote_workspace: WorkspaceConfig,
     method: CodeSyncMethod,
     auto_reload: bool,
-) -> Result<()> {
+    atomic: bool,
+) -> Result<ValueMesh<RankSyncReport>> {
     let instance = cx.instance();
 
     // Create a slice of the actor mesh that only includes workspace "owners" (e.g. on multi-GPU hosts,
@@ -494,7 +772,9 @@ pub async fn code_sync_mesh(
                                 accept(instance, instance.self_id().clone(), connect)
                                     .await?
                                     .into_split();
-                            let res = sender(&local_workspace, &mut read, &mut write).await;
+                            let res = sender(&local_workspace, &mut read, &mut write)
+                                .await
+                                .map(|stats| tracing::info!("conda sync sent {}", stats));
 
                             // Shutdown our end, then read from the other end till exhaustion to avoid undeliverable
                             // message spam.
@@ -509,45 +789,119 @@ pub async fn code_sync_mesh(
                 .boxed(),
             )
         }
-    };
-
-    let ((), ()) = try_join!(
-        method_fut,
-        // This async task will cast the code sync message to workspace owners, and process any errors.
-        async move {
-            let (result_tx, result_rx) = instance.open_port::<Result<(), String>>();
-            actor_mesh.cast(
-                instance,
-                sel!(*),
-                CodeSyncMessage::Sync {
-                    method,
-                    workspace: remote_workspace.location.clone(),
-                    reload: if auto_reload {
-                        Some(remote_workspace.shape)
-                    } else {
-                        None
-                    },
-                    result: result_tx.bind(),
+        CodeSyncMethod::NativeSync => {
+            let (conns_tx, conns_rx) = instance.open_port::<Connect>();
+            (
+                Method::NativeSync {
+                    connect: conns_tx.bind(),
                 },
-            )?;
+                async move {
+                    conns_rx
+                        .take(shape.slice().len())
+                        .err_into::<anyhow::Error>()
+                        .try_for_each_concurrent(None, |connect| async {
+                            let (mut read, mut write) =
+                                accept(instance, instance.self_id().clone(), connect)
+                                    .await?
+                                    .into_split();
+                            let res =
+                                native_sync::sender(&local_workspace, &mut read, &mut write).await;
 
-            // Wait for all actors to report result.
-            let results = result_rx
-                .take(actor_mesh.shape().slice().len())
-                .try_collect::<Vec<_>>()
-                .await?;
+                            // Shutdown our end, then read from the other end till exhaustion to avoid undeliverable
+                            // message spam.
+                            write.shutdown().await?;
+                            let mut buf = vec![];
+                            read.read_to_end(&mut buf).await?;
 
-            // Combine all errors into one.
-            let mut errs = ErrorStash::<_, _, anyhow::Error>::new(|| "remote failures");
-            results
-                .into_iter()
-                .map(|res| res.map_err(anyhow::Error::msg))
-                .try_collect_or_stash::<()>(&mut errs);
-            Ok(errs.into_result()?)
+                            res
+                        })
+                        .await
+                }
+                .boxed(),
+            )
+        }
+    };
+
+    // Cast the code sync message to workspace owners, and collect the outcome for every rank.
+    let num_owners = actor_mesh.shape().slice().len();
+    let (result_tx, result_rx) = instance.open_port::<Vec<(usize, RankSyncReport)>>();
+    actor_mesh.cast(
+        instance,
+        sel!(*),
+        CodeSyncMessage::Sync {
+            method,
+            workspace: remote_workspace.location.clone(),
+            shape: remote_workspace.shape.clone(),
+            reload: auto_reload,
+            snapshot: atomic,
+            result: result_tx.bind(),
         },
     )?;
+    let results_fut = result_rx
+        .take(num_owners)
+        .err_into::<anyhow::Error>()
+        .try_collect::<Vec<_>>();
+    tokio::pin!(results_fut);
+    let mut method_fut = method_fut;
+    let (method_res, results) = tokio::select! {
+        method_res = &mut method_fut => (Some(method_res), (&mut results_fut).await?),
+        results = &mut results_fut => (None, results?),
+    };
+    let mut reports: HashMap<usize, RankSyncReport> = results.into_iter().flatten().collect();
+
+    // If an owner failed to sync before connecting back, the method will wait forever
+    // for its connection, so only wait for the method to finish if all owners synced.
+    let method_res = match method_res {
+        Some(res) => res,
+        None if reports.values().all(|report| report.sync_error.is_none()) => method_fut.await,
+        None => Ok(()),
+    };
+    if let Err(err) = &method_res {
+        tracing::warn!("code sync transfer failed: {:#}", err);
+    }
+
+    let failed = method_res.is_err() || reports.values().any(|report| !report.is_ok());
+    if atomic && failed {
+        let (rollback_tx, rollback_rx) =
+            instance.open_port::<Vec<(usize, Result<ReloadReport, String>)>>();
+        actor_mesh.cast(
+            instance,
+            sel!(*),
+            CodeSyncMessage::Rollback {
+                workspace: remote_workspace.location.clone(),
+                shape: remote_workspace.shape.clone(),
+                reload: auto_reload,
+                result: rollback_tx.bind(),
+            },
+        )?;
+        let rollbacks = rollback_rx.take(num_owners).try_collect::<Vec<_>>().await?;
+        for (rank, rollback) in rollbacks.into_iter().flatten() {
+            reports.entry(rank).or_default().rollback = Some(rollback);
+        }
+    } else if atomic {
+        actor_mesh.cast(
+            instance,
+            sel!(*),
+            CodeSyncMessage::Commit {
+                workspace: remote_workspace.location.clone(),
+            },
+        )?;
+    }
 
-    Ok(())
+    // A transfer error that no rank accounts for fails the sync as a whole.
+    if reports.values().all(|report| report.sync_error.is_none()) {
+        method_res?;
+    }
+
+    let region = remote_workspace.shape.shape.region();
+    Ok((0..region.num_ranks())
+        .map(|rank| {
+            reports.remove(&rank).unwrap_or_else(|| RankSyncReport {
+                sync_error: Some(format!("no report from rank {}", rank)),
+                ..Default::default()
+            })
+        })
+        .collect_mesh::<ValueMesh<_>>(region)?)
 }
 
 #[cfg(test)]
@@ -681,15 +1035,22 @@ mod tests {
 
         // Test code_sync_mesh function - this coordinates sync operations across the mesh
         // Test without auto-reload first
-        code_sync_mesh(
+        let reports = code_sync_mesh(
             instance,
             &actor_mesh,
             source_workspace.path().to_path_buf(),
             remote_workspace_config.clone(),
             CodeSyncMethod::Rsync,
             false, // no auto-reload
+            false, // not atomic
         )
         .await?;
+        assert_eq!(reports.values().count(), 2);
+        assert!(
+            reports
+                .values()
+                .all(|report| report == RankSyncReport::default())
+        );
 
         // Verify that files were synchronized correctly
         assert!(
@@ -700,4 +1061,122 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_code_sync_mesh_native() -> Result<()> {
+        let source_workspace = TempDir::new()?;
+        fs::write(source_workspace.path().join("test1.txt"), "content1").await?;
+        fs::create_dir(source_workspace.path().join("subdir")).await?;
+        fs::write(source_workspace.path().join("subdir/test2.txt"), "content2").await?;
+
+        let target_workspace = TempDir::new()?;
+        fs::write(target_workspace.path().join("foo.txt"), "something").await?;
+
+        let alloc = LocalAllocator
+            .allocate(AllocSpec {
+                extent: extent! { replica = 2 },
+                constraints: Default::default(),
+                proc_name: None,
+                transport: ChannelTransport::Local,
+                proc_allocation_mode: Default::default(),
+            })
+            .await?;
+        let proc_mesh = ProcMesh::allocate(alloc).await?;
+        let instance = global_root_client();
+        let actor_mesh: RootActorMesh<CodeSyncManager> = proc_mesh
+            .spawn(
+                &instance,
+                "code_sync_native_test",
+                &CodeSyncManagerParams {},
+            )
+            .await?;
+
+        code_sync_mesh(
+            instance,
+            &actor_mesh,
+            source_workspace.path().to_path_buf(),
+            WorkspaceConfig {
+                location: WorkspaceLocation::Constant(target_workspace.path().to_path_buf()),
+                shape: WorkspaceShape {
+                    shape: shape! { replica = 2 },
+                    dimension: Some("replica".to_string()),
+                },
+            },
+            CodeSyncMethod::NativeSync,
+            false, // no auto-reload
+            true,  // atomic
+        )
+        .await?;
+
+        assert!(
+            !dir_diff::is_different(&source_workspace, &target_workspace)
+                .map_err(|e| anyhow!("{:?}", e))?,
+            "Source and target workspaces should be identical after sync"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_code_sync_mesh_reports_failures() -> Result<()> {
+        let source_workspace = TempDir::new()?;
+        fs::write(source_workspace.path().join("test1.txt"), "content1").await?;
+
+        let alloc = LocalAllocator
+            .allocate(AllocSpec {
+                extent: extent! { replica = 2 },
+                constraints: Default::default(),
+                proc_name: None,
+                transport: ChannelTransport::Local,
+                proc_allocation_mode: Default::default(),
+            })
+            .await?;
+        let proc_mesh = ProcMesh::allocate(alloc).await?;
+        let instance = global_root_client();
+        let actor_mesh: RootActorMesh<CodeSyncManager> = proc_mesh
+            .spawn(
+                &instance,
+                "code_sync_failure_test",
+                &CodeSyncManagerParams {},
+            )
+            .await?;
+
+        // The remote workspace cannot be resolved, so every rank fails to sync (and, having
+        // no snapshot, to roll back).
+        let reports = code_sync_mesh(
+            instance,
+            &actor_mesh,
+            source_workspace.path().to_path_buf(),
+            WorkspaceConfig {
+                location: WorkspaceLocation::FromEnvVar {
+                    env: "__CODE_SYNC_TEST_NON_EXISTENT__".to_string(),
+                    relpath: PathBuf::new(),
+                },
+                shape: WorkspaceShape {
+                    shape: shape! { replica = 2 },
+                    dimension: None,
+                },
+            },
+            CodeSyncMethod::NativeSync,
+            false, // no auto-reload
+            true,  // atomic
+        )
+        .await?;
+
+        assert_eq!(reports.values().count(), 2);
+        for report in reports.values() {
+            assert!(!report.is_ok());
+            assert!(
+                report
+                    .sync_error
+                    .as_ref()
+                    .is_some_and(|error| error.contains("__CODE_SYNC_TEST_NON_EXISTENT__")),
+                "unexpected report: {}",
+                report
+            );
+            assert!(matches!(report.rollback, Some(Err(_))));
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,605 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! A pure-Rust, rsync-free workspace sync.
+//!
+//! The sender walks its workspace and ships a content-addressed
+//! manifest (every path with the SHA-256 digest of its contents) to
+//! the receiver. The receiver diffs the manifest against its own
+//! workspace, asks only for blobs it has under no path at all (so
+//! renames and copies cost nothing on the wire), stages the blobs,
+//! and then applies the diff. Changes are reported in the same
+//! [`RsyncResult`] format as the rsync method, so that auto-reload
+//! works the same way regardless of the method.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::ensure;
+use async_trait::async_trait;
+use futures::SinkExt;
+use futures::StreamExt;
+use futures::try_join;
+use hyperactor::Actor;
+use hyperactor::Bind;
+use hyperactor::Handler;
+use hyperactor::Named;
+use hyperactor::PortRef;
+use hyperactor::Unbind;
+use hyperactor_mesh::connect::Connect;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest as _;
+use sha2::Sha256;
+use tokio::fs;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio_util::codec::FramedRead;
+use tokio_util::codec::FramedWrite;
+use tokio_util::codec::LengthDelimitedCodec;
+use walkdir::WalkDir;
+
+use crate::code_sync::WorkspaceLocation;
+use crate::code_sync::rsync::Change;
+use crate::code_sync::rsync::ChangeAction;
+use crate::code_sync::rsync::ChangeMessage;
+use crate::code_sync::rsync::ChangeType;
+use crate::code_sync::rsync::FileType;
+use crate::code_sync::rsync::RsyncResult;
+
+/// SHA-256 digest of a file's contents.
+type Digest = [u8; 32];
+
+/// Prefix of the receiver's staging directory, which is created inside
+/// the workspace (so that staged files can be renamed into place) and
+/// is never synced.
+const STAGING_PREFIX: &str = ".monarch-sync-tmp.";
+
+/// Maximum size of the data frames used to ship blobs.
+const CHUNK_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Entry {
+    Directory,
+    File { digest: Digest, len: u64, mode: u32 },
+    Symlink(PathBuf),
+}
+
+impl Entry {
+    fn file_type(&self) -> FileType {
+        match self {
+            Entry::Directory => FileType::Directory,
+            Entry::File { .. } => FileType::File,
+            Entry::Symlink(_) => FileType::Symlink,
+        }
+    }
+}
+
+/// Workspace-relative paths mapped to their entries. Being ordered,
+/// parents always precede their children.
+type Manifest = BTreeMap<PathBuf, Entry>;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum ManifestFrame {
+    Entry(PathBuf, Entry),
+    Done,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BlobHeader {
+    digest: Digest,
+    len: u64,
+}
+
+fn hex(digest: &Digest) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn file_digest(path: &Path) -> Result<Digest> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// Build the manifest of the workspace rooted at `root`. Special files
+/// (sockets, fifos, ...) are skipped.
+fn build_manifest(root: &Path) -> Result<Manifest> {
+    let mut manifest = Manifest::new();
+    let walker = WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|ent| {
+            !(ent.depth() == 1
+                && ent
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(STAGING_PREFIX))
+        });
+    for ent in walker {
+        let ent = ent?;
+        let path = ent.path().strip_prefix(root)?.to_path_buf();
+        let file_type = ent.file_type();
+        let entry = if file_type.is_dir() {
+            Entry::Directory
+        } else if file_type.is_symlink() {
+            Entry::Symlink(std::fs::read_link(ent.path())?)
+        } else if file_type.is_file() {
+            let metadata = ent.metadata()?;
+            Entry::File {
+                digest: file_digest(ent.path())
+                    .with_context(|| format!("hashing {}", ent.path().display()))?,
+                len: metadata.len(),
+                mode: metadata.permissions().mode() & 0o7777,
+            }
+        } else {
+            continue;
+        };
+        manifest.insert(path, entry);
+    }
+    Ok(manifest)
+}
+
+/// The blobs referenced by `src` that are not present anywhere in
+/// `local`, in a deterministic order.
+fn missing_blobs(src: &Manifest, local: &Manifest) -> Vec<Digest> {
+    let have: BTreeSet<&Digest> = local
+        .values()
+        .filter_map(|entry| match entry {
+            Entry::File { digest, .. } => Some(digest),
+            _ => None,
+        })
+        .collect();
+    src.values()
+        .filter_map(|entry| match entry {
+            Entry::File { digest, .. } if !have.contains(digest) => Some(*digest),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Serve the workspace at `src` to a [`receiver`] on the other end of
+/// the given stream.
+pub async fn sender(
+    src: &Path,
+    from_receiver: impl AsyncRead + Unpin,
+    to_receiver: impl AsyncWrite + Unpin,
+) -> Result<()> {
+    let mut to_receiver = FramedWrite::new(to_receiver, LengthDelimitedCodec::new());
+    let mut from_receiver = FramedRead::new(from_receiver, LengthDelimitedCodec::new());
+
+    let root = src.to_path_buf();
+    let manifest = tokio::task::spawn_blocking(move || build_manifest(&root)).await??;
+    for (path, entry) in &manifest {
+        to_receiver
+            .send(bincode::serialize(&ManifestFrame::Entry(path.clone(), entry.clone()))?.into())
+            .await
+            .context("sending manifest entry")?;
+    }
+    to_receiver
+        .send(bincode::serialize(&ManifestFrame::Done)?.into())
+        .await
+        .context("sending manifest end")?;
+    to_receiver.flush().await?;
+
+    let paths: HashMap<&Digest, &Path> = manifest
+        .iter()
+        .filter_map(|(path, entry)| match entry {
+            Entry::File { digest, .. } => Some((digest, path.as_path())),
+            _ => None,
+        })
+        .collect();
+    let wanted: Vec<Digest> =
+        bincode::deserialize(&from_receiver.next().await.context("wanted blobs")??)?;
+
+    let mut buf = vec![0; CHUNK_SIZE];
+    for digest in wanted {
+        let path = paths
+            .get(&digest)
+            .with_context(|| format!("receiver asked for unknown blob {}", hex(&digest)))?;
+        let mut file = fs::File::open(src.join(path)).await?;
+        let len = file.metadata().await?.len();
+        to_receiver
+            .send(bincode::serialize(&BlobHeader { digest, len })?.into())
+            .await
+            .context("sending blob header")?;
+        let mut remaining = len;
+        while remaining > 0 {
+            let want = CHUNK_SIZE.min(remaining.try_into().unwrap_or(CHUNK_SIZE));
+            let n = file.read(&mut buf[..want]).await?;
+            ensure!(n > 0, "{} shrank during sync", path.display());
+            to_receiver
+                .send(bytes::Bytes::copy_from_slice(&buf[..n]))
+                .await
+                .context("sending blob data")?;
+            remaining -= n as u64;
+        }
+    }
+    to_receiver.flush().await?;
+
+    Ok(())
+}
+
+/// Check that a manifest `path` received from the sender stays inside
+/// the workspace: it must be a non-empty relative path made only of
+/// normal components, and its parent (if any) must be a directory
+/// already in `manifest`, so that it cannot be reached through a
+/// symlink.
+fn check_path(path: &Path, manifest: &Manifest) -> Result<()> {
+    ensure!(
+        path.components().next().is_some()
+            && path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_))),
+        "invalid path in manifest: {}",
+        path.display()
+    );
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        ensure!(
+            matches!(manifest.get(parent), Some(Entry::Directory)),
+            "path {} in manifest is not under a directory",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn change(change_type: ChangeType, path: &Path) -> Change {
+    Change {
+        change_type,
+        path: path.to_path_buf(),
+    }
+}
+
+/// Sync the workspace at `dst` with the one served by a [`sender`] on
+/// the other end of the given stream, returning the applied changes.
+pub async fn receiver(
+    dst: &Path,
+    from_sender: impl AsyncRead + Unpin,
+    to_sender: impl AsyncWrite + Unpin,
+) -> Result<RsyncResult> {
+    let mut to_sender = FramedWrite::new(to_sender, LengthDelimitedCodec::new());
+    let mut from_sender = FramedRead::new(from_sender, LengthDelimitedCodec::new());
+
+    fs::create_dir_all(dst).await?;
+    let root = dst.to_path_buf();
+    let (local, src) = try_join!(
+        async { tokio::task::spawn_blocking(move || build_manifest(&root)).await? },
+        async {
+            let mut src = Manifest::new();
+            loop {
+                match bincode::deserialize(&from_sender.next().await.context("manifest")??)? {
+                    ManifestFrame::Entry(path, entry) => {
+                        check_path(&path, &src)?;
+                        src.insert(path, entry);
+                    }
+                    ManifestFrame::Done => break,
+                }
+            }
+            anyhow::Ok(src)
+        },
+    )?;
+
+    let wanted = missing_blobs(&src, &local);
+    to_sender
+        .send(bincode::serialize(&wanted)?.into())
+        .await
+        .context("sending wanted blobs")?;
+    to_sender.flush().await?;
+
+    // Stage every blob we need before touching the workspace: first
+    // those shipped by the sender, then copies of local files, which
+    // may be moved or deleted below.
+    let staging = tempfile::Builder::new()
+        .prefix(STAGING_PREFIX)
+        .tempdir_in(dst)?;
+    let mut blobs: HashMap<Digest, PathBuf> = HashMap::new();
+    for _ in 0..wanted.len() {
+        let BlobHeader { digest, len } =
+            bincode::deserialize(&from_sender.next().await.context("blob header")??)?;
+        let path = staging.path().join(hex(&digest));
+        let mut file = fs::File::create(&path).await?;
+        let mut hasher = Sha256::new();
+        let mut remaining = len;
+        while remaining > 0 {
+            let chunk = from_sender.next().await.context("blob data")??;
+            remaining = remaining
+                .checked_sub(chunk.len() as u64)
+                .context("blob data overrun")?;
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        ensure!(
+            <Digest>::from(hasher.finalize()) == digest,
+            "blob {} changed during sync",
+            hex(&digest)
+        );
+        blobs.insert(digest, path);
+    }
+    let written: BTreeSet<&Digest> = src
+        .iter()
+        .filter_map(|(path, entry)| match (entry, local.get(path)) {
+            (
+                Entry::File { digest, .. },
+                Some(Entry::File {
+                    digest: existing, ..
+                }),
+            ) if digest == existing => None,
+            (Entry::File { digest, .. }, _) => Some(digest),
+            _ => None,
+        })
+        .collect();
+    for (path, entry) in &local {
+        if let Entry::File { digest, .. } = entry {
+            if written.contains(digest) && !blobs.contains_key(digest) {
+                let staged = staging.path().join(hex(digest));
+                fs::copy(dst.join(path), &staged).await?;
+                blobs.insert(*digest, staged);
+            }
+        }
+    }
+
+    let mut changes = Vec::new();
+
+    // Delete paths that are gone, or whose kind changed, deepest first.
+    for (path, entry) in local.iter().rev() {
+        let keep = match (entry, src.get(path)) {
+            (Entry::Directory, Some(Entry::Directory)) => true,
+            (Entry::Directory, _) | (_, None) | (_, Some(Entry::Directory)) => false,
+            _ => true,
+        };
+        if !keep {
+            let target = dst.join(path);
+            if matches!(entry, Entry::Directory) {
+                fs::remove_dir(&target).await?;
+            } else {
+                fs::remove_file(&target).await?;
+            }
+            changes.push(change(ChangeType::Message(ChangeMessage::Deleting), path));
+        }
+    }
+
+    // Create and update everything else, parents first.
+    for (index, (path, entry)) in src.iter().enumerate() {
+        let existing = local.get(path);
+        if existing == Some(entry) {
+            continue;
+        }
+        let target = dst.join(path);
+        let action = match (entry, existing) {
+            (Entry::Directory, _) => {
+                fs::create_dir_all(&target).await?;
+                ChangeAction::Received
+            }
+            (
+                Entry::File { digest, mode, .. },
+                Some(Entry::File {
+                    digest: existing, ..
+                }),
+            ) if digest == existing => {
+                // Only the permissions changed.
+                fs::set_permissions(&target, std::fs::Permissions::from_mode(*mode)).await?;
+                ChangeAction::NotTransferred
+            }
+            (Entry::File { digest, mode, .. }, _) => {
+                let blob = blobs
+                    .get(digest)
+                    .with_context(|| format!("missing blob for {}", path.display()))?;
+                // The same blob may be needed at several paths, so copy
+                // it rather than moving it, then atomically rename.
+                let tmp = staging.path().join(format!("file.{}", index));
+                fs::copy(blob, &tmp).await?;
+                fs::set_permissions(&tmp, std::fs::Permissions::from_mode(*mode)).await?;
+                fs::rename(&tmp, &target).await?;
+                ChangeAction::Received
+            }
+            (Entry::Symlink(link), _) => {
+                let tmp = staging.path().join(format!("link.{}", index));
+                fs::symlink(link, &tmp).await?;
+                fs::rename(&tmp, &target).await?;
+                ChangeAction::Received
+            }
+        };
+        changes.push(change(ChangeType::Action(action, entry.file_type()), path));
+    }
+
+    Ok(RsyncResult { changes })
+}
+
+#[derive(Debug, Clone, Named, Serialize, Deserialize, Bind, Unbind)]
+pub struct NativeSyncMessage {
+    /// The connect message to create a duplex bytestream with the client.
+    pub connect: PortRef<Connect>,
+    /// A port to send back the result or any errors.
+    pub result: PortRef<Result<RsyncResult, String>>,
+    /// The location of the workspace to sync.
+    pub workspace: WorkspaceLocation,
+}
+
+#[derive(Debug, Default)]
+#[hyperactor::export(spawn = true, handlers = [NativeSyncMessage { cast = true }])]
+pub struct NativeSyncActor {}
+
+impl Actor for NativeSyncActor {}
+
+#[async_trait]
+impl Handler<NativeSyncMessage> for NativeSyncActor {
+    async fn handle(
+        &mut self,
+        cx: &hyperactor::Context<Self>,
+        NativeSyncMessage {
+            workspace,
+            connect,
+            result,
+        }: NativeSyncMessage,
+    ) -> Result<(), anyhow::Error> {
+        let res = async {
+            let workspace = workspace
+                .resolve()
+                .context("resolving workspace location")?;
+            let (connect_msg, completer) = Connect::allocate(cx.self_id().clone(), cx);
+            connect.send(cx, connect_msg)?;
+            let (mut read, mut write) = completer.complete().await?.into_split();
+            let res = receiver(&workspace, &mut read, &mut write).await;
+
+            // Shutdown our end, then read from the other end till exhaustion to avoid undeliverable
+            // message spam.
+            write.shutdown().await?;
+            let mut buf = vec![];
+            read.read_to_end(&mut buf).await?;
+
+            res
+        }
+        .await;
+        result.send(cx, res.map_err(|e| format!("{:#?}", e)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    async fn sync(src: &Path, dst: &Path) -> Result<RsyncResult> {
+        let (local, remote) = tokio::io::duplex(1 << 16);
+        let (local_read, local_write) = tokio::io::split(local);
+        let (remote_read, remote_write) = tokio::io::split(remote);
+        let ((), result) = try_join!(
+            sender(src, local_read, local_write),
+            receiver(dst, remote_read, remote_write),
+        )?;
+        Ok(result)
+    }
+
+    fn has_change(result: &RsyncResult, change_type: ChangeType, path: &str) -> bool {
+        result
+            .changes
+            .iter()
+            .any(|c| c.change_type == change_type && c.path == Path::new(path))
+    }
+
+    #[tokio::test]
+    async fn test_native_sync() -> Result<()> {
+        let src = TempDir::new()?;
+        let dst = TempDir::new()?;
+        fs::write(src.path().join("a.txt"), "aaa").await?;
+        fs::create_dir(src.path().join("pkg")).await?;
+        fs::write(src.path().join("pkg/mod.py"), "print('hi')").await?;
+        fs::write(src.path().join("run.sh"), "#!/bin/sh").await?;
+        fs::set_permissions(
+            src.path().join("run.sh"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .await?;
+        fs::symlink("a.txt", src.path().join("link")).await?;
+        fs::create_dir(dst.path().join("stale")).await?;
+        fs::write(dst.path().join("stale/old.txt"), "old").await?;
+
+        let result = sync(src.path(), dst.path()).await?;
+        assert!(!dir_diff::is_different(src.path(), dst.path()).unwrap());
+        assert!(has_change(
+            &result,
+            ChangeType::Action(ChangeAction::Received, FileType::File),
+            "pkg/mod.py"
+        ));
+        assert!(has_change(
+            &result,
+            ChangeType::Action(ChangeAction::Received, FileType::Symlink),
+            "link"
+        ));
+        assert!(has_change(
+            &result,
+            ChangeType::Message(ChangeMessage::Deleting),
+            "stale/old.txt"
+        ));
+        assert_eq!(
+            std::fs::metadata(dst.path().join("run.sh"))?
+                .permissions()
+                .mode()
+                & 0o777,
+            0o755
+        );
+
+        // An incremental sync only reports what changed.
+        fs::write(src.path().join("pkg/mod.py"), "print('bye')").await?;
+        fs::rename(src.path().join("a.txt"), src.path().join("b.txt")).await?;
+        fs::remove_file(src.path().join("link")).await?;
+        let result = sync(src.path(), dst.path()).await?;
+        assert!(!dir_diff::is_different(src.path(), dst.path()).unwrap());
+        let mut paths: Vec<_> = result.changes.iter().map(|c| c.path.clone()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("a.txt"),
+                PathBuf::from("b.txt"),
+                PathBuf::from("link"),
+                PathBuf::from("pkg/mod.py"),
+            ]
+        );
+
+        // Nothing changed, nothing to do.
+        let result = sync(src.path(), dst.path()).await?;
+        assert!(result.changes.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_blobs_skips_local_content() -> Result<()> {
+        let src = TempDir::new()?;
+        let dst = TempDir::new()?;
+        std::fs::write(src.path().join("renamed.txt"), "same")?;
+        std::fs::write(src.path().join("new.txt"), "new")?;
+        std::fs::write(dst.path().join("original.txt"), "same")?;
+
+        let wanted = missing_blobs(&build_manifest(src.path())?, &build_manifest(dst.path())?);
+        assert_eq!(wanted, vec![file_digest(&src.path().join("new.txt"))?]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_path() {
+        let mut manifest = Manifest::new();
+        manifest.insert(PathBuf::from("pkg"), Entry::Directory);
+        manifest.insert(PathBuf::from("link"), Entry::Symlink(PathBuf::from("/etc")));
+
+        assert!(check_path(Path::new("a.txt"), &manifest).is_ok());
+        assert!(check_path(Path::new("pkg/mod.py"), &manifest).is_ok());
+        for path in [
+            "",
+            "/etc/passwd",
+            "../escape",
+            "pkg/../../escape",
+            "./a.txt",
+        ] {
+            assert!(
+                check_path(Path::new(path), &manifest).is_err(),
+                "{path:?} should be rejected"
+            );
+        }
+        // Children must come after, and live in, a directory.
+        assert!(check_path(Path::new("missing/a.txt"), &manifest).is_err());
+        assert!(check_path(Path::new("link/passwd"), &manifest).is_err());
+    }
+}
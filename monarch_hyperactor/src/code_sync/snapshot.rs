@@ -0,0 +1,176 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Snapshots of remote workspaces, taken before an atomic code sync so
+//! that the workspace can be rolled back if the sync fails anywhere.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+/// A copy of a workspace as it was before a sync. The copy is removed
+/// when the snapshot is dropped.
+#[derive(Debug)]
+pub struct WorkspaceSnapshot {
+    /// The copy of the workspace, or `None` if the workspace did not
+    /// exist when the snapshot was taken.
+    copy: Option<TempDir>,
+}
+
+impl WorkspaceSnapshot {
+    /// Snapshot the workspace at `workspace`. This copies every file in
+    /// the workspace, so may block for a while.
+    pub fn take(workspace: &Path) -> Result<Self> {
+        if !workspace.exists() {
+            return Ok(Self { copy: None });
+        }
+        let copy = tempfile::Builder::new()
+            .prefix("code_sync_snapshot.")
+            .tempdir()?;
+        copy_tree(workspace, copy.path())
+            .with_context(|| format!("snapshotting workspace {}", workspace.display()))?;
+        fs::set_permissions(copy.path(), fs::metadata(workspace)?.permissions())?;
+        Ok(Self { copy: Some(copy) })
+    }
+
+    /// Restore `workspace` to its contents when the snapshot was taken.
+    /// Modification times are restored too, so that the Python modules
+    /// of files left untouched by the sync are not reloaded.
+    ///
+    /// The snapshot is copied into a sibling of `workspace`, which is then
+    /// renamed into place, so a failure part way through the copy leaves
+    /// the workspace as the sync left it rather than half restored.
+    pub fn restore(&self, workspace: &Path) -> Result<()> {
+        let parent = workspace
+            .parent()
+            .with_context(|| format!("workspace {} has no parent", workspace.display()))?;
+        let name = workspace
+            .file_name()
+            .with_context(|| format!("workspace {} has no name", workspace.display()))?
+            .to_string_lossy();
+        fs::create_dir_all(parent)?;
+
+        let restored = tempfile::Builder::new()
+            .prefix(&format!(".{}.restore.", name))
+            .tempdir_in(parent)?;
+        let permissions = match &self.copy {
+            Some(copy) => {
+                copy_tree(copy.path(), restored.path())
+                    .with_context(|| format!("restoring workspace {}", workspace.display()))?;
+                fs::metadata(copy.path())?.permissions()
+            }
+            None => fs::Permissions::from_mode(0o755),
+        };
+        fs::set_permissions(restored.path(), permissions)?;
+
+        // A directory can't be renamed over a non-empty one, so move the
+        // synced workspace aside first, and only remove it once the
+        // restored copy is in place.
+        let replaced = tempfile::Builder::new()
+            .prefix(&format!(".{}.replaced.", name))
+            .tempdir_in(parent)?;
+        let displaced = replaced.path().join(&*name);
+        if workspace.exists() {
+            fs::rename(workspace, &displaced)?;
+        }
+        if let Err(err) = fs::rename(restored.path(), workspace) {
+            if displaced.exists() {
+                fs::rename(&displaced, workspace)?;
+            }
+            return Err(err)
+                .with_context(|| format!("restoring workspace {}", workspace.display()));
+        }
+        let _ = restored.keep();
+        Ok(())
+    }
+}
+
+/// Copy the contents of `src` into the existing directory `dst`,
+/// preserving symlinks, permissions and file modification times.
+/// Special files (sockets, fifos, ...) are skipped.
+fn copy_tree(src: &Path, dst: &Path) -> Result<()> {
+    for entry in WalkDir::new(src).min_depth(1) {
+        let entry = entry?;
+        let target = dst.join(entry.path().strip_prefix(src)?);
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            fs::set_permissions(&target, entry.metadata()?.permissions())?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &target)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &target)?;
+            fs::File::open(&target)?.set_modified(entry.metadata()?.modified()?)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_restore() -> Result<()> {
+        let parent = TempDir::new()?;
+        let path = &parent.path().join("workspace");
+        fs::create_dir(path)?;
+        fs::write(path.join("a.py"), "a = 1")?;
+        fs::create_dir(path.join("pkg"))?;
+        fs::write(path.join("pkg/b.py"), "b = 1")?;
+        std::os::unix::fs::symlink("a.py", path.join("link.py"))?;
+        let mtime = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::open(path.join("a.py"))?.set_modified(mtime)?;
+
+        let expected = TempDir::new()?;
+        copy_tree(path, expected.path())?;
+
+        let snapshot = WorkspaceSnapshot::take(path)?;
+        fs::write(path.join("a.py"), "a = 2")?;
+        fs::remove_dir_all(path.join("pkg"))?;
+        fs::write(path.join("c.py"), "c = 1")?;
+
+        snapshot.restore(path)?;
+        assert!(
+            !dir_diff::is_different(expected.path(), path).map_err(|e| anyhow!("{:?}", e))?,
+            "the workspace should be restored"
+        );
+        assert_eq!(fs::metadata(path.join("a.py"))?.modified()?, mtime);
+        assert_eq!(fs::read_link(path.join("link.py"))?, Path::new("a.py"));
+        assert_eq!(
+            fs::read_dir(parent.path())?.count(),
+            1,
+            "restore should not leave anything beside the workspace"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_missing_workspace() -> Result<()> {
+        let parent = TempDir::new()?;
+        let path = parent.path().join("workspace");
+
+        let snapshot = WorkspaceSnapshot::take(&path)?;
+        fs::create_dir(&path)?;
+        fs::write(path.join("a.py"), "a = 1")?;
+
+        snapshot.restore(&path)?;
+        assert_eq!(fs::read_dir(&path)?.count(), 0);
+        Ok(())
+    }
+}
@@ -90,9 +90,9 @@ declare_attrs! {
 /// Reload configuration from environment variables
 #[pyfunction()]
 pub fn reload_config_from_env() -> PyResult<()> {
-    // Reload the hyperactor global configuration from environment variables
-    hyperactor_config::global::init_from_env();
-    Ok(())
+    // Reload the hyperactor global configuration from environment
+    // variables, rejecting unknown variables in strict mode.
+    hyperactor_config::global::init_from_env().map_err(|e| PyValueError::new_err(e.to_string()))
 }
 
 #[pyfunction()]
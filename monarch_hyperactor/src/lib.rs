@@ -27,6 +27,7 @@ pub mod metrics;
 pub mod ndslice;
 pub mod proc;
 pub mod proc_mesh;
+mod profiler;
 pub mod pytokio;
 pub mod runtime;
 pub mod selection;
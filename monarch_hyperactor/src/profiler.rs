@@ -0,0 +1,82 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Contributes the stacks of Python threads to actor profiles (see
+//! [`hyperactor::profiler`]).
+
+use std::collections::HashMap;
+
+use hyperactor::profiler::StackSampler;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+hyperactor::submit! {
+    StackSampler {
+        name: "python",
+        sample: python_stacks,
+    }
+}
+
+/// Sample the stack of every Python thread, rooted at the thread's name.
+fn python_stacks() -> Vec<Vec<String>> {
+    // SAFETY: this only reads interpreter state, and may be called at any time.
+    if unsafe { pyo3::ffi::Py_IsInitialized() } == 0 {
+        return Vec::new();
+    }
+    Python::with_gil(|py| {
+        thread_stacks(py).unwrap_or_else(|err| {
+            tracing::warn!("failed to sample python stacks: {}", err);
+            Vec::new()
+        })
+    })
+}
+
+fn thread_stacks(py: Python<'_>) -> PyResult<Vec<Vec<String>>> {
+    let traceback = py.import("traceback")?;
+
+    let mut thread_names = HashMap::new();
+    for thread in py
+        .import("threading")?
+        .call_method0("enumerate")?
+        .try_iter()?
+    {
+        let thread = thread?;
+        if let Some(ident) = thread.getattr("ident")?.extract::<Option<u64>>()? {
+            thread_names.insert(ident, thread.getattr("name")?.extract::<String>()?);
+        }
+    }
+
+    let frames = py.import("sys")?.call_method0("_current_frames")?;
+    let mut stacks = Vec::new();
+    for (ident, frame) in frames.downcast::<PyDict>()?.iter() {
+        let ident = ident.extract::<u64>()?;
+        let mut stack = vec![
+            thread_names
+                .remove(&ident)
+                .unwrap_or_else(|| format!("thread {}", ident)),
+        ];
+        // Frames are listed outermost first.
+        for summary in traceback
+            .call_method1("extract_stack", (frame,))?
+            .try_iter()?
+        {
+            let summary = summary?;
+            stack.push(format!(
+                "{} ({}:{})",
+                summary.getattr("name")?.extract::<String>()?,
+                summary.getattr("filename")?.extract::<String>()?,
+                summary
+                    .getattr("lineno")?
+                    .extract::<Option<u32>>()?
+                    .unwrap_or_default(),
+            ));
+        }
+        stacks.push(stack);
+    }
+    Ok(stacks)
+}
@@ -0,0 +1,68 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Merge the per-process Perfetto traces of an execution into a single
+//! trace with message flow arrows.
+//!
+//! ```text
+//! monarch_perfetto_merge /tmp/$USER/monarch_traces/executions/latest
+//! ```
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use monarch_perfetto_trace::merge;
+use monarch_perfetto_trace::merge::MergeOptions;
+
+#[derive(Parser, Debug)]
+#[command(about = "Merge the per-process .pftrace files of an execution into one trace")]
+struct Args {
+    /// The execution directory, e.g. `{trace_dir}/executions/{execution_id}`.
+    dir: PathBuf,
+
+    /// Where to write the merged trace. Defaults to `merged.pftrace` in the
+    /// execution directory.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Do not adjust process clocks.
+    #[arg(long)]
+    no_align: bool,
+
+    /// Debug annotation identifying a message on its send event.
+    #[arg(long, default_value = merge::DEFAULT_SEND_KEY)]
+    send_key: String,
+
+    /// Debug annotation identifying a message on its handler slice.
+    #[arg(long, default_value = merge::DEFAULT_HANDLE_KEY)]
+    handle_key: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let output = args
+        .output
+        .unwrap_or_else(|| merge::default_output(&args.dir));
+    let options = MergeOptions {
+        send_key: args.send_key,
+        handle_key: args.handle_key,
+        align_clocks: !args.no_align,
+    };
+
+    let report = merge::merge_execution_dir(&args.dir, &output, &options)?;
+
+    for (process, offset_ns) in &report.offsets_ns {
+        println!("{process}: clock offset {offset_ns}ns");
+    }
+    println!(
+        "linked {} messages ({} sends and {} handlers unmatched)",
+        report.flows, report.unmatched_sends, report.unmatched_handles
+    );
+    println!("wrote {}", output.display());
+    Ok(())
+}
@@ -6,6 +6,8 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+pub mod merge;
+
 use std::collections::HashMap;
 use std::sync::atomic::AtomicU64;
 use std::time::SystemTime;
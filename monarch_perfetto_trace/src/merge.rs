@@ -0,0 +1,657 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Merge the per-process `.pftrace` files of an execution into a single trace.
+//!
+//! `hyperactor_telemetry`'s Perfetto sink writes one file per process under
+//! `executions/{execution_id}/`. Each file is its own packet sequence with
+//! its own track uuids and pids, and timestamps come from the local clock of
+//! its host. Merging:
+//!
+//! * gives every input file a distinct packet sequence, and remaps track
+//!   uuids and (colliding) pids so that tracks from different processes
+//!   never alias;
+//! * links each message send to its handler with a Perfetto flow. A send is
+//!   any slice or instant annotated with [`MergeOptions::send_key`]; its
+//!   handler is the slice or instant annotated with
+//!   [`MergeOptions::handle_key`] carrying the same id;
+//! * aligns clocks using those message pairs: a message cannot be handled
+//!   before it was sent, so each pair bounds the offset between the two
+//!   processes' clocks. Offsets are propagated from a reference process
+//!   (the client, if present) along the processes that exchanged messages.
+//!
+//! hyperactor records [`DEFAULT_SEND_KEY`] on its `message_send` trace events
+//! and [`DEFAULT_HANDLE_KEY`] on its `handle_message` spans. Both are
+//! captured by the Perfetto sink in `dev` trace mode.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use prost::Message;
+use tracing_perfetto_sdk_schema::DebugAnnotation;
+use tracing_perfetto_sdk_schema::Trace;
+use tracing_perfetto_sdk_schema::TracePacket;
+use tracing_perfetto_sdk_schema::debug_annotation::NameField;
+use tracing_perfetto_sdk_schema::debug_annotation::Value as DBGValue;
+use tracing_perfetto_sdk_schema::trace_packet::Data;
+use tracing_perfetto_sdk_schema::trace_packet::OptionalTrustedPacketSequenceId;
+use tracing_perfetto_sdk_schema::track_event::Type as TrackEventType;
+
+/// Annotation carrying the id of a message on its send event.
+pub const DEFAULT_SEND_KEY: &str = "sent_message_id";
+
+/// Annotation carrying the id of a message on its handler span.
+pub const DEFAULT_HANDLE_KEY: &str = "message_id";
+
+/// Name of the merged trace written into an execution directory by default.
+pub const MERGED_TRACE_NAME: &str = "merged.pftrace";
+
+/// Options controlling [`merge`].
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// Debug annotation identifying a message on its send event.
+    pub send_key: String,
+    /// Debug annotation identifying a message on its handler slice.
+    pub handle_key: String,
+    /// Whether to shift process clocks so that no message is handled
+    /// before it was sent.
+    pub align_clocks: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            send_key: DEFAULT_SEND_KEY.to_string(),
+            handle_key: DEFAULT_HANDLE_KEY.to_string(),
+            align_clocks: true,
+        }
+    }
+}
+
+/// The trace of a single process.
+#[derive(Debug, Clone)]
+pub struct ProcessTrace {
+    /// The process name; the file stem of its `.pftrace` file.
+    pub name: String,
+    /// The decoded trace.
+    pub trace: Trace,
+}
+
+/// Summary of a merge.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Clock offset applied to each process, in nanoseconds.
+    pub offsets_ns: Vec<(String, i64)>,
+    /// Number of send/handle pairs linked with a flow.
+    pub flows: usize,
+    /// Sends without a handler in any of the traces.
+    pub unmatched_sends: usize,
+    /// Handlers without a send in any of the traces.
+    pub unmatched_handles: usize,
+}
+
+/// Decode a `.pftrace` file. Files may be a concatenation of encoded
+/// `Trace` messages, which decodes as a single trace.
+pub fn read_trace(path: impl AsRef<Path>) -> anyhow::Result<Trace> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    Trace::decode(bytes.as_slice()).with_context(|| format!("decoding {}", path.display()))
+}
+
+/// Read all `.pftrace` files in `dir`, except `skip`, ordered by name.
+pub fn read_execution_dir(
+    dir: impl AsRef<Path>,
+    skip: Option<&Path>,
+) -> anyhow::Result<Vec<ProcessTrace>> {
+    let dir = dir.as_ref();
+    let skip = skip.and_then(|path| path.canonicalize().ok());
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("reading {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| {
+        path.extension().is_some_and(|ext| ext == "pftrace")
+            && (skip.is_none() || path.canonicalize().ok() != skip)
+    });
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            Ok(ProcessTrace {
+                name: path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                trace: read_trace(&path)?,
+            })
+        })
+        .collect()
+}
+
+/// Merge the traces in execution directory `dir` into `output`.
+pub fn merge_execution_dir(
+    dir: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: &MergeOptions,
+) -> anyhow::Result<MergeReport> {
+    let output = output.as_ref();
+    let traces = read_execution_dir(dir, Some(output))?;
+    anyhow::ensure!(!traces.is_empty(), "no .pftrace files to merge");
+    let (merged, report) = merge(traces, options);
+    fs::write(output, merged.encode_to_vec())
+        .with_context(|| format!("writing {}", output.display()))?;
+    Ok(report)
+}
+
+/// Location of an annotated track event.
+#[derive(Debug, Clone, Copy)]
+struct Endpoint {
+    process: usize,
+    packet: usize,
+    timestamp: u64,
+}
+
+/// Message ids found in one process's trace.
+#[derive(Default)]
+struct Scan {
+    sends: Vec<(u64, Endpoint)>,
+    handles: Vec<(u64, Endpoint)>,
+}
+
+fn annotation_id(annotation: &DebugAnnotation) -> Option<u64> {
+    match annotation.value.as_ref()? {
+        DBGValue::IntValue(i) => Some(*i as u64),
+        DBGValue::UintValue(u) => Some(*u),
+        DBGValue::StringValue(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn scan(process: usize, trace: &Trace, options: &MergeOptions) -> Scan {
+    let mut result = Scan::default();
+    // Interned annotation names, per packet sequence.
+    let mut names: HashMap<u32, HashMap<u64, String>> = HashMap::new();
+
+    for (index, packet) in trace.packet.iter().enumerate() {
+        let seq = match packet.optional_trusted_packet_sequence_id {
+            Some(OptionalTrustedPacketSequenceId::TrustedPacketSequenceId(seq)) => seq,
+            None => 0,
+        };
+        if packet.incremental_state_cleared == Some(true) {
+            names.remove(&seq);
+        }
+        if let Some(interned) = &packet.interned_data {
+            let names = names.entry(seq).or_default();
+            for name in &interned.debug_annotation_names {
+                if let (Some(iid), Some(name)) = (name.iid, &name.name) {
+                    names.insert(iid, name.clone());
+                }
+            }
+        }
+
+        let Some(Data::TrackEvent(event)) = &packet.data else {
+            continue;
+        };
+        if !matches!(
+            event.r#type.and_then(|t| TrackEventType::try_from(t).ok()),
+            Some(TrackEventType::SliceBegin | TrackEventType::Instant)
+        ) {
+            continue;
+        }
+        let endpoint = Endpoint {
+            process,
+            packet: index,
+            timestamp: packet.timestamp.unwrap_or_default(),
+        };
+        for annotation in &event.debug_annotations {
+            let name = match &annotation.name_field {
+                Some(NameField::Name(name)) => Some(name.as_str()),
+                Some(NameField::NameIid(iid)) => names
+                    .get(&seq)
+                    .and_then(|names| names.get(iid))
+                    .map(String::as_str),
+                None => None,
+            };
+            let Some(id) = annotation_id(annotation) else {
+                continue;
+            };
+            if name == Some(options.send_key.as_str()) {
+                result.sends.push((id, endpoint));
+            } else if name == Some(options.handle_key.as_str()) {
+                result.handles.push((id, endpoint));
+            }
+        }
+    }
+    result
+}
+
+/// Estimate a clock offset for each process such that, after adding it to
+/// the process's timestamps, no message is handled before it was sent.
+fn align(num_processes: usize, reference: usize, pairs: &[(Endpoint, Endpoint)]) -> Vec<i64> {
+    // bounds[(a, b)]: the largest `send - handle` over messages a -> b. The
+    // offset difference `off[b] - off[a]` must be at least this.
+    let mut bounds: HashMap<(usize, usize), i64> = HashMap::new();
+    let mut neighbors: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for (send, handle) in pairs {
+        if send.process == handle.process {
+            continue;
+        }
+        let delta = send.timestamp as i64 - handle.timestamp as i64;
+        bounds
+            .entry((send.process, handle.process))
+            .and_modify(|bound| *bound = (*bound).max(delta))
+            .or_insert(delta);
+        neighbors
+            .entry(send.process)
+            .or_default()
+            .insert(handle.process);
+        neighbors
+            .entry(handle.process)
+            .or_default()
+            .insert(send.process);
+    }
+
+    let mut offsets = vec![None; num_processes];
+    offsets[reference] = Some(0i64);
+    let mut queue = VecDeque::from([reference]);
+    while let Some(a) = queue.pop_front() {
+        let base = offsets[a].unwrap();
+        let mut next: Vec<usize> = neighbors.get(&a).into_iter().flatten().copied().collect();
+        next.sort();
+        for b in next {
+            if offsets[b].is_some() {
+                continue;
+            }
+            let lower = bounds.get(&(a, b)).copied();
+            let upper = bounds.get(&(b, a)).map(|bound| -bound);
+            let delta = match (lower, upper) {
+                // Messages in both directions: split the difference, as NTP
+                // does with a request and its response.
+                (Some(lower), Some(upper)) => lower + (upper - lower) / 2,
+                // Otherwise shift only as much as causality requires.
+                (Some(lower), None) => lower.max(0),
+                (None, Some(upper)) => upper.min(0),
+                (None, None) => 0,
+            };
+            offsets[b] = Some(base + delta);
+            queue.push_back(b);
+        }
+    }
+    offsets
+        .into_iter()
+        .map(|offset| offset.unwrap_or(0))
+        .collect()
+}
+
+/// Merge `traces` into a single trace.
+pub fn merge(traces: Vec<ProcessTrace>, options: &MergeOptions) -> (Trace, MergeReport) {
+    let mut report = MergeReport::default();
+
+    // Pair up sends and handlers by message id.
+    let mut sends: HashMap<u64, Endpoint> = HashMap::new();
+    let mut handles: HashMap<u64, Endpoint> = HashMap::new();
+    for (process, trace) in traces.iter().enumerate() {
+        let scan = scan(process, &trace.trace, options);
+        for (id, endpoint) in scan.sends {
+            sends.entry(id).or_insert(endpoint);
+        }
+        for (id, endpoint) in scan.handles {
+            handles.entry(id).or_insert(endpoint);
+        }
+    }
+    let mut pairs = Vec::new();
+    // Flow ids to start and terminate, per (process, packet).
+    let mut flow_starts: HashMap<(usize, usize), Vec<u64>> = HashMap::new();
+    let mut flow_ends: HashMap<(usize, usize), Vec<u64>> = HashMap::new();
+    for (id, send) in &sends {
+        let Some(handle) = handles.get(id) else {
+            report.unmatched_sends += 1;
+            continue;
+        };
+        // Flow ids must be nonzero.
+        let flow_id = (*id).max(1);
+        flow_starts
+            .entry((send.process, send.packet))
+            .or_default()
+            .push(flow_id);
+        flow_ends
+            .entry((handle.process, handle.packet))
+            .or_default()
+            .push(flow_id);
+        pairs.push((*send, *handle));
+    }
+    report.flows = pairs.len();
+    report.unmatched_handles = handles.keys().filter(|id| !sends.contains_key(id)).count();
+
+    let reference = traces
+        .iter()
+        .position(|trace| trace.name.starts_with("client"))
+        .unwrap_or(0);
+    let offsets = if options.align_clocks && !traces.is_empty() {
+        align(traces.len(), reference, &pairs)
+    } else {
+        vec![0; traces.len()]
+    };
+
+    let mut merged = Trace::default();
+    let mut next_uuid = 1u64;
+    let mut next_seq = 1u32;
+    let mut used_pids: HashSet<i32> = HashSet::new();
+    let mut next_synthetic_pid = i32::MAX;
+
+    for (process, trace) in traces.into_iter().enumerate() {
+        let offset = offsets[process];
+        report.offsets_ns.push((trace.name, offset));
+
+        let mut uuids: HashMap<u64, u64> = HashMap::new();
+        let mut remap_uuid = |uuid: u64| {
+            *uuids.entry(uuid).or_insert_with(|| {
+                let remapped = next_uuid;
+                next_uuid += 1;
+                remapped
+            })
+        };
+        let mut seqs: HashMap<u32, u32> = HashMap::new();
+        let mut pids: HashMap<i32, i32> = HashMap::new();
+        let mut remap_pid = |pid: i32| {
+            *pids.entry(pid).or_insert_with(|| {
+                if used_pids.insert(pid) {
+                    pid
+                } else {
+                    // Another process (on another host) had the same pid.
+                    while !used_pids.insert(next_synthetic_pid) {
+                        next_synthetic_pid -= 1;
+                    }
+                    next_synthetic_pid
+                }
+            })
+        };
+
+        for (index, mut packet) in trace.trace.packet.into_iter().enumerate() {
+            let seq = match packet.optional_trusted_packet_sequence_id {
+                Some(OptionalTrustedPacketSequenceId::TrustedPacketSequenceId(seq)) => seq,
+                None => 0,
+            };
+            let seq = *seqs.entry(seq).or_insert_with(|| {
+                let remapped = next_seq;
+                next_seq += 1;
+                remapped
+            });
+            packet.optional_trusted_packet_sequence_id = Some(
+                OptionalTrustedPacketSequenceId::TrustedPacketSequenceId(seq),
+            );
+
+            if let Some(timestamp) = packet.timestamp.as_mut() {
+                *timestamp = timestamp.saturating_add_signed(offset);
+            }
+
+            match packet.data.as_mut() {
+                Some(Data::TrackDescriptor(desc)) => {
+                    desc.uuid = desc.uuid.map(&mut remap_uuid);
+                    desc.parent_uuid = desc.parent_uuid.map(&mut remap_uuid);
+                    if let Some(proc) = desc.process.as_mut() {
+                        proc.pid = proc.pid.map(&mut remap_pid);
+                    }
+                    if let Some(thread) = desc.thread.as_mut() {
+                        thread.pid = thread.pid.map(&mut remap_pid);
+                    }
+                }
+                Some(Data::TrackEvent(event)) => {
+                    event.track_uuid = event.track_uuid.map(&mut remap_uuid);
+                    if let Some(ids) = flow_starts.remove(&(process, index)) {
+                        event.flow_ids.extend(ids);
+                    }
+                    if let Some(ids) = flow_ends.remove(&(process, index)) {
+                        event.terminating_flow_ids.extend(ids);
+                    }
+                }
+                _ => {}
+            }
+
+            merged.packet.push(packet);
+        }
+    }
+
+    (merged, report)
+}
+
+/// Convenience for writing a merged trace next to its inputs.
+pub fn default_output(dir: impl AsRef<Path>) -> PathBuf {
+    dir.as_ref().join(MERGED_TRACE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_perfetto_sdk_schema::DebugAnnotationName;
+    use tracing_perfetto_sdk_schema::InternedData;
+    use tracing_perfetto_sdk_schema::ProcessDescriptor;
+    use tracing_perfetto_sdk_schema::TrackDescriptor;
+    use tracing_perfetto_sdk_schema::TrackEvent;
+
+    use super::*;
+
+    fn packet(seq: u32, timestamp: Option<u64>, data: Option<Data>) -> TracePacket {
+        TracePacket {
+            timestamp,
+            data,
+            optional_trusted_packet_sequence_id: Some(
+                OptionalTrustedPacketSequenceId::TrustedPacketSequenceId(seq),
+            ),
+            ..Default::default()
+        }
+    }
+
+    /// A trace shaped like the Perfetto sink's output: a process track,
+    /// interned annotation names, and one annotated event per message.
+    fn process_trace(
+        name: &str,
+        pid: i32,
+        events: &[(TrackEventType, u64, &str, u64)],
+    ) -> ProcessTrace {
+        // All processes use the same sequence id and track uuids, as
+        // processes started within the same second do.
+        let seq = 7;
+        let mut packets = vec![
+            packet(
+                seq,
+                None,
+                Some(Data::TrackDescriptor(TrackDescriptor {
+                    uuid: Some(1),
+                    process: Some(ProcessDescriptor {
+                        pid: Some(pid),
+                        process_name: Some(name.to_string()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })),
+            ),
+            TracePacket {
+                interned_data: Some(InternedData {
+                    debug_annotation_names: vec![
+                        DebugAnnotationName {
+                            iid: Some(1),
+                            name: Some(DEFAULT_SEND_KEY.to_string()),
+                            ..Default::default()
+                        },
+                        DebugAnnotationName {
+                            iid: Some(2),
+                            name: Some(DEFAULT_HANDLE_KEY.to_string()),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                }),
+                ..packet(seq, None, None)
+            },
+        ];
+        for (kind, timestamp, key, id) in events {
+            packets.push(packet(
+                seq,
+                Some(*timestamp),
+                Some(Data::TrackEvent(TrackEvent {
+                    track_uuid: Some(1),
+                    r#type: Some(*kind as i32),
+                    debug_annotations: vec![DebugAnnotation {
+                        name_field: Some(NameField::NameIid(if *key == DEFAULT_SEND_KEY {
+                            1
+                        } else {
+                            2
+                        })),
+                        value: Some(DBGValue::IntValue(*id as i64)),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                })),
+            ));
+        }
+        ProcessTrace {
+            name: name.to_string(),
+            trace: Trace { packet: packets },
+        }
+    }
+
+    fn events(trace: &Trace) -> Vec<(&TracePacket, &TrackEvent)> {
+        trace
+            .packet
+            .iter()
+            .filter_map(|packet| match &packet.data {
+                Some(Data::TrackEvent(event)) => Some((packet, event)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_links_and_aligns() {
+        let client = process_trace(
+            "client",
+            100,
+            &[(TrackEventType::Instant, 1_000, DEFAULT_SEND_KEY, 42)],
+        );
+        // The worker's clock is 600ns behind: it appears to handle the
+        // message before it was sent.
+        let worker = process_trace(
+            "worker",
+            100,
+            &[
+                (TrackEventType::SliceBegin, 500, DEFAULT_HANDLE_KEY, 42),
+                (TrackEventType::SliceBegin, 700, DEFAULT_HANDLE_KEY, 43),
+            ],
+        );
+
+        let (merged, report) = merge(vec![client, worker], &MergeOptions::default());
+        assert_eq!(
+            report,
+            MergeReport {
+                offsets_ns: vec![("client".to_string(), 0), ("worker".to_string(), 500)],
+                flows: 1,
+                unmatched_sends: 0,
+                unmatched_handles: 1,
+            }
+        );
+
+        let events = events(&merged);
+        let (send_packet, send) = events[0];
+        let (handle_packet, handle) = events[1];
+        assert_eq!(send.flow_ids, vec![42]);
+        assert_eq!(handle.terminating_flow_ids, vec![42]);
+        assert!(handle_packet.timestamp >= send_packet.timestamp);
+        assert_eq!(events[2].0.timestamp, Some(1_200));
+
+        // Tracks, sequences and pids no longer collide.
+        assert_ne!(send.track_uuid, handle.track_uuid);
+        assert_ne!(
+            send_packet.optional_trusted_packet_sequence_id,
+            handle_packet.optional_trusted_packet_sequence_id
+        );
+        let pids: HashSet<_> = merged
+            .packet
+            .iter()
+            .filter_map(|packet| match &packet.data {
+                Some(Data::TrackDescriptor(desc)) => desc.process.as_ref()?.pid,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pids.len(), 2);
+    }
+
+    #[test]
+    fn test_align_round_trip() {
+        // Worker clock is 1000ns ahead. A request takes 100ns to arrive,
+        // and the response 100ns to return.
+        let client = process_trace(
+            "client",
+            1,
+            &[
+                (TrackEventType::Instant, 0, DEFAULT_SEND_KEY, 1),
+                (TrackEventType::SliceBegin, 300, DEFAULT_HANDLE_KEY, 2),
+            ],
+        );
+        let worker = process_trace(
+            "worker",
+            2,
+            &[
+                (TrackEventType::SliceBegin, 1_100, DEFAULT_HANDLE_KEY, 1),
+                (TrackEventType::Instant, 1_200, DEFAULT_SEND_KEY, 2),
+            ],
+        );
+        let (_, report) = merge(vec![client, worker], &MergeOptions::default());
+        assert_eq!(report.flows, 2);
+        assert_eq!(report.offsets_ns[1], ("worker".to_string(), -1_000));
+
+        let (_, report) = merge(
+            vec![process_trace("a", 1, &[]), process_trace("b", 2, &[])],
+            &MergeOptions {
+                align_clocks: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            report.offsets_ns,
+            vec![("a".to_string(), 0), ("b".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn test_merge_execution_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        for trace in [
+            process_trace(
+                "client",
+                1,
+                &[(TrackEventType::Instant, 10, DEFAULT_SEND_KEY, 5)],
+            ),
+            process_trace(
+                "worker",
+                2,
+                &[(TrackEventType::SliceBegin, 20, DEFAULT_HANDLE_KEY, 5)],
+            ),
+        ] {
+            fs::write(
+                dir.path().join(format!("{}.pftrace", trace.name)),
+                trace.trace.encode_to_vec(),
+            )
+            .unwrap();
+        }
+
+        let output = default_output(dir.path());
+        let report = merge_execution_dir(dir.path(), &output, &MergeOptions::default()).unwrap();
+        assert_eq!(report.flows, 1);
+
+        // Merging again ignores the previous output.
+        let report = merge_execution_dir(dir.path(), &output, &MergeOptions::default()).unwrap();
+        assert_eq!(report.offsets_ns.len(), 2);
+        assert_eq!(events(&read_trace(&output).unwrap()).len(), 2);
+    }
+}
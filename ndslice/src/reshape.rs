@@ -26,6 +26,8 @@ use std::fmt;
 use crate::Range;
 use crate::Selection;
 use crate::dsl::union;
+use crate::selection::Operand;
+use crate::selection::Predicate;
 use crate::shape::Shape;
 use crate::slice::Slice;
 
@@ -377,6 +379,38 @@ pub fn expand_labels(factors: &[(String, Vec<usize>)]) -> Vec<String> {
     labels
 }
 
+/// Express `operand`, over the dimensions of `original_slice`, in
+/// those of `reshaped_slice`: sizes become constants, and coordinates
+/// are renumbered. Returns `None` for a coordinate along a dimension
+/// that was split, which has no counterpart.
+fn reshape_operand(
+    operand: Operand,
+    original_slice: &Slice,
+    reshaped_slice: &Slice,
+) -> Option<Operand> {
+    match operand {
+        Operand::Value(_) => Some(operand),
+        Operand::Size(..) => operand
+            .resolve(original_slice.sizes(), &[])
+            .map(Operand::Value),
+        Operand::Coord(dim) => {
+            // Find the reshaped dimensions that `dim` was factored
+            // into.
+            let mut start = 0;
+            for &size in original_slice.sizes().get(..dim)? {
+                let mut accum = reshaped_slice.sizes()[start];
+                start += 1;
+                while accum < size {
+                    accum *= reshaped_slice.sizes()[start];
+                    start += 1;
+                }
+            }
+            let size = *original_slice.sizes().get(dim)?;
+            (reshaped_slice.sizes().get(start) == Some(&size)).then_some(Operand::Coord(start))
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ReshapeError {
     #[error("unsupported selection kind {selection}")]
@@ -492,6 +526,97 @@ pub fn reshape_selection(
                     _ => Selection::Intersection(Box::new(left), Box::new(right)),
                 })
             }
+            Selection::Difference(left, right) => {
+                let left = recursive_fold(
+                    *left,
+                    original_slice,
+                    original_size_index,
+                    reshaped_slice,
+                    reshaped_size_index,
+                )?;
+                if matches!(left, Selection::False) {
+                    return Ok(Selection::False);
+                }
+
+                let right = recursive_fold(
+                    *right,
+                    original_slice,
+                    original_size_index,
+                    reshaped_slice,
+                    reshaped_size_index,
+                )?;
+                Ok(match right {
+                    Selection::False => left,
+                    Selection::True => Selection::False,
+                    _ => Selection::Difference(Box::new(left), Box::new(right)),
+                })
+            }
+            Selection::Complement(inner) => {
+                let inner = recursive_fold(
+                    *inner,
+                    original_slice,
+                    original_size_index,
+                    reshaped_slice,
+                    reshaped_size_index,
+                )?;
+                Ok(match inner {
+                    Selection::True => Selection::False,
+                    Selection::False => Selection::True,
+                    _ => Selection::Complement(Box::new(inner)),
+                })
+            }
+            // A predicate on a dimension that is not split keeps its
+            // meaning, once its operand is expressed in the reshaped
+            // dimensions. Otherwise it is expanded into the (unit)
+            // ranges it matches, which fold like any other range.
+            Selection::Where(predicate, inner)
+                if next_reshaped_dimension_start == reshaped_size_index + 1 =>
+            {
+                let predicate = Predicate {
+                    value: reshape_operand(predicate.value, original_slice, reshaped_slice)
+                        .ok_or_else(|| ReshapeError::UnsupportedSelection {
+                            selection: Selection::Where(predicate, inner.clone()),
+                        })?,
+                    ..predicate
+                };
+                let inner = recursive_fold(
+                    *inner,
+                    original_slice,
+                    original_size_index + 1,
+                    reshaped_slice,
+                    next_reshaped_dimension_start,
+                )?;
+                if matches!(inner, Selection::False) {
+                    return Ok(inner);
+                }
+                Ok(Selection::Where(predicate, Box::new(inner)))
+            }
+            Selection::Where(predicate, inner) => {
+                // The expansion is shared by all points, so it cannot
+                // depend on their coordinates.
+                if matches!(predicate.value, Operand::Coord(_)) {
+                    return Err(ReshapeError::UnsupportedSelection {
+                        selection: Selection::Where(predicate, inner),
+                    });
+                }
+                let point = |i| {
+                    let mut point = vec![0; original_size_index];
+                    point.push(i);
+                    point
+                };
+                let expanded = (0..original_dim_size)
+                    .filter(|&i| predicate.matches(original_slice.sizes(), &point(i)))
+                    .map(|i| Selection::Range(Range(i, Some(i + 1), 1), inner.clone()))
+                    .reduce(union)
+                    .unwrap_or(Selection::False);
+                recursive_fold(
+                    expanded,
+                    original_slice,
+                    original_size_index,
+                    reshaped_slice,
+                    reshaped_size_index,
+                )
+            }
             Selection::All(inner) => {
                 let inner = recursive_fold(
                     *inner,
@@ -1259,6 +1384,53 @@ mod tests {
         assert_eq!(reshaped.shape.slice(), &expected);
     }
 
+    #[test]
+    fn test_reshape_selection_predicates() {
+        use crate::selection::CmpOp;
+        use crate::selection::dsl::*;
+
+        // [zone, host, gpu] = [4, 4, 16] -> [4, 4, 4, 4]
+        let slice = Slice::new_row_major(vec![4, 4, 16]);
+        let reshaped = reshape_with_limit(&slice, Limit::from(4));
+        assert_eq!(reshaped.sizes(), &[4, 4, 4, 4]);
+
+        for selection in [
+            // Half the zones, by the size of an unsplit dimension.
+            where_(
+                Predicate::new(CmpOp::Lt, Operand::Size(0, 2)),
+                all(all(true_())),
+            ),
+            // Half the GPUs, on a split dimension.
+            all(all(where_(
+                Predicate::new(CmpOp::Lt, Operand::Size(2, 2)),
+                true_(),
+            ))),
+            // Hosts matching their zone.
+            all(where_(
+                Predicate::new(CmpOp::Eq, Operand::Coord(0)),
+                all(true_()),
+            )),
+        ] {
+            let expected = selection
+                .eval(&EvalOpts::strict(), &slice)
+                .unwrap()
+                .collect::<BTreeSet<_>>();
+            let reshaped_selection = reshape_selection(selection, &slice, &reshaped).unwrap();
+            let actual = reshaped_selection
+                .eval(&EvalOpts::strict(), &reshaped)
+                .unwrap()
+                .collect::<BTreeSet<_>>();
+            assert_eq!(actual, expected);
+        }
+
+        // Predicates on a split dimension cannot depend on coordinates.
+        let selection = all(all(where_(
+            Predicate::new(CmpOp::Eq, Operand::Coord(1)),
+            true_(),
+        )));
+        assert!(reshape_selection(selection, &slice, &reshaped).is_err());
+    }
+
     use std::collections::BTreeSet;
 
     use proptest::prelude::*;
@@ -10,9 +10,10 @@
 //! in a multidimensional space.
 //!
 //! A `Selection` describes constraints across dimensions of an
-//! `ndslice::Slice`. Variants like [`All`], [`First`], and [`Range`]
-//! operate dimensionally, while [`Intersection`] and [`Union`] allow
-//! for logical composition of selections.
+//! `ndslice::Slice`. Variants like [`All`], [`First`], [`Range`] and
+//! [`Where`] operate dimensionally, while [`Intersection`],
+//! [`Union`], [`Difference`] and [`Complement`] allow for logical
+//! composition of selections.
 //!
 //! ## Example
 //!
@@ -98,6 +99,7 @@ pub mod normal;
 
 pub mod test_utils;
 
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -150,6 +152,19 @@ pub trait SelectionSYM {
 
     /// The union (logical OR) of two selection expressions.
     fn union(lhs: Self, selection: Self) -> Self;
+
+    /// Selects values along the current dimension whose index
+    /// satisfies the given predicate, then applies the inner
+    /// selection.
+    fn where_(predicate: Predicate, selection: Self) -> Self;
+
+    /// The difference (logical AND NOT) of two selection
+    /// expressions.
+    fn difference(lhs: Self, rhs: Self) -> Self;
+
+    /// The complement (logical NOT) of a selection expression,
+    /// relative to the remaining dimensions.
+    fn complement(selection: Self) -> Self;
 }
 
 /// `SelectionSYM`-based constructors specialized to the [`Selection`]
@@ -157,6 +172,7 @@ pub trait SelectionSYM {
 pub mod dsl {
 
     use super::LabelKey;
+    use super::Predicate;
     use super::Selection;
     use super::SelectionSYM;
     use crate::shape;
@@ -188,6 +204,15 @@ pub mod dsl {
     pub fn union(lhs: Selection, rhs: Selection) -> Selection {
         SelectionSYM::union(lhs, rhs)
     }
+    pub fn where_(predicate: Predicate, inner: Selection) -> Selection {
+        SelectionSYM::where_(predicate, inner)
+    }
+    pub fn difference(lhs: Selection, rhs: Selection) -> Selection {
+        SelectionSYM::difference(lhs, rhs)
+    }
+    pub fn complement(inner: Selection) -> Selection {
+        SelectionSYM::complement(inner)
+    }
 }
 
 impl SelectionSYM for Selection {
@@ -219,6 +244,15 @@ impl SelectionSYM for Selection {
     fn union(lhs: Self, rhs: Self) -> Self {
         ast::union(lhs, rhs)
     }
+    fn where_(predicate: Predicate, selection: Self) -> Self {
+        ast::where_(predicate, selection)
+    }
+    fn difference(lhs: Self, rhs: Self) -> Self {
+        ast::difference(lhs, rhs)
+    }
+    fn complement(selection: Self) -> Self {
+        ast::complement(selection)
+    }
 }
 
 impl fmt::Display for Selection {
@@ -280,6 +314,278 @@ impl std::fmt::Display for LabelKey {
     }
 }
 
+/// Supplies the label values used to evaluate [`Selection::Label`].
+///
+/// A provider answers whether the element at a coordinate prefix
+/// carries any of a set of labels. `prefix` holds the coordinates of
+/// dimensions `0..=dim`; the element being tested is `prefix[dim]`
+/// along dimension `dim`.
+pub trait LabelProvider {
+    /// Returns `true` if the element at `prefix[dim]` along dimension
+    /// `dim` carries any of `labels`.
+    fn matches(&self, dim: usize, prefix: &[usize], labels: &[LabelKey]) -> bool;
+}
+
+/// The identity label provider: every coordinate matches every
+/// label, so that `Label(_, s)` evaluates exactly like `s`. This is
+/// what [`Selection::eval`] and [`Selection::contains`] use.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityLabels;
+
+impl LabelProvider for IdentityLabels {
+    fn matches(&self, _dim: usize, _prefix: &[usize], _labels: &[LabelKey]) -> bool {
+        true
+    }
+}
+
+/// A table of label values attached to the indices of a slice's
+/// dimensions, e.g. the GPU type or rack of each host in a host
+/// mesh.
+///
+/// Labels are keyed by `(dim, index)`. An index matches
+/// `Label(labels, _)` only if it carries at least one of `labels`; in
+/// particular, nothing along an unlabeled dimension matches.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Labels {
+    dims: BTreeMap<usize, BTreeMap<usize, BTreeSet<LabelKey>>>,
+}
+
+impl Labels {
+    /// An empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach `label` to `index` along dimension `dim`.
+    pub fn insert(&mut self, dim: usize, index: usize, label: impl Into<LabelKey>) {
+        self.dims
+            .entry(dim)
+            .or_default()
+            .entry(index)
+            .or_default()
+            .insert(label.into());
+    }
+
+    /// Builder-style [`Labels::insert`].
+    pub fn with(mut self, dim: usize, index: usize, label: impl Into<LabelKey>) -> Self {
+        self.insert(dim, index, label);
+        self
+    }
+
+    /// The labels attached to `index` along dimension `dim`.
+    pub fn get(&self, dim: usize, index: usize) -> impl Iterator<Item = &LabelKey> {
+        self.dims
+            .get(&dim)
+            .and_then(|indices| indices.get(&index))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Returns `true` if no labels have been attached.
+    pub fn is_empty(&self) -> bool {
+        self.dims.is_empty()
+    }
+}
+
+impl LabelProvider for Labels {
+    fn matches(&self, dim: usize, prefix: &[usize], labels: &[LabelKey]) -> bool {
+        self.get(dim, prefix[dim])
+            .any(|attached| labels.contains(attached))
+    }
+}
+
+/// A comparison operator, used in [`Predicate`]s.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    PartialOrd,
+    Ord
+)]
+pub enum CmpOp {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl CmpOp {
+    /// Applies the comparison to `lhs` and `rhs`.
+    pub fn apply(self, lhs: usize, rhs: usize) -> bool {
+        match self {
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        })
+    }
+}
+
+/// The right-hand side of a [`Predicate`], resolved against the
+/// extent and the point being tested.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    PartialOrd,
+    Ord
+)]
+pub enum Operand {
+    /// A constant.
+    Value(usize),
+    /// The size of dimension `dim` divided (rounding down) by
+    /// `divisor`: `Size(1, 2)` is half the size of dimension 1.
+    Size(usize, usize),
+    /// The point's coordinate along dimension `dim`. Only the
+    /// dimensions up to the predicate's own are known; a later one
+    /// matches nothing.
+    Coord(usize),
+}
+
+impl Operand {
+    /// The operand's value, or `None` if it refers to a dimension
+    /// outside `extent` or `point`.
+    pub fn resolve(&self, extent: &[usize], point: &[usize]) -> Option<usize> {
+        match *self {
+            Operand::Value(value) => Some(value),
+            Operand::Size(dim, divisor) => extent.get(dim)?.checked_div(divisor),
+            Operand::Coord(dim) => point.get(dim).copied(),
+        }
+    }
+}
+
+impl From<usize> for Operand {
+    fn from(value: usize) -> Self {
+        Operand::Value(value)
+    }
+}
+
+/// Renders the operand as in [`parse`](crate::selection::parse::parse):
+/// `4`, `#1/2` (half the size of dimension 1) or `@0` (the coordinate
+/// along dimension 0).
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Value(value) => write!(f, "{}", value),
+            Operand::Size(dim, 1) => write!(f, "#{}", dim),
+            Operand::Size(dim, divisor) => write!(f, "#{}/{}", dim, divisor),
+            Operand::Coord(dim) => write!(f, "@{}", dim),
+        }
+    }
+}
+
+/// A predicate over the index `i` along a dimension, of the form `i
+/// op value`, or `i % modulus op value` when a modulus is given. The
+/// value may depend on the extent and on the point's coordinates
+/// along earlier dimensions (see [`Operand`]).
+///
+/// For example, `Predicate::modulo(2, CmpOp::Eq, 0)` matches even
+/// indices, `Predicate::new(CmpOp::Lt, 4)` matches the first four,
+/// and `Predicate::new(CmpOp::Lt, Operand::Size(1, 2))` the first
+/// half of dimension 1. Predicates are used by `Selection::Where`;
+/// conjunctions are expressed by intersecting selections.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    PartialOrd,
+    Ord
+)]
+pub struct Predicate {
+    /// If set, the index is reduced modulo `modulus` before the
+    /// comparison. A modulus of 0 matches nothing.
+    pub modulus: Option<usize>,
+    /// The comparison operator.
+    pub op: CmpOp,
+    /// The right-hand side of the comparison.
+    pub value: Operand,
+}
+
+impl Predicate {
+    /// The predicate `i op value`.
+    pub fn new(op: CmpOp, value: impl Into<Operand>) -> Self {
+        Self {
+            modulus: None,
+            op,
+            value: value.into(),
+        }
+    }
+
+    /// The predicate `i % modulus op value`.
+    pub fn modulo(modulus: usize, op: CmpOp, value: impl Into<Operand>) -> Self {
+        Self {
+            modulus: Some(modulus),
+            op,
+            value: value.into(),
+        }
+    }
+
+    /// Returns `true` if the last coordinate of `point` satisfies the
+    /// predicate. `point` holds the coordinates up to and including
+    /// the predicate's dimension, and `extent` the sizes of all
+    /// dimensions.
+    pub fn matches(&self, extent: &[usize], point: &[usize]) -> bool {
+        let (Some(&i), Some(value)) = (point.last(), self.value.resolve(extent, point)) else {
+            return false;
+        };
+        match self.modulus {
+            None => self.op.apply(i, value),
+            Some(modulus) => i
+                .checked_rem(modulus)
+                .is_some_and(|i| self.op.apply(i, value)),
+        }
+    }
+}
+
+/// Renders the predicate in the surface syntax accepted by
+/// [`parse`](crate::selection::parse::parse), e.g. `%2==0` or `<4`.
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(modulus) = self.modulus {
+            write!(f, "%{}", modulus)?;
+        }
+        write!(f, "{}{}", self.op, self.value)
+    }
+}
+
 /// An algebra for expressing node selection.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
@@ -315,6 +621,19 @@ pub enum Selection {
 
     /// The union (logical OR) of two selections.
     Union(Box<Selection>, Box<Selection>),
+
+    /// Selects values along the current dimension whose index
+    /// satisfies the predicate, continuing with the given selection.
+    Where(Predicate, Box<Selection>),
+
+    /// The difference of two selections: everything selected by the
+    /// first and not by the second.
+    Difference(Box<Selection>, Box<Selection>),
+
+    /// The complement of a selection: everything in the remaining
+    /// dimensions that it does not select. Equivalent to
+    /// `Difference(True, s)`.
+    Complement(Box<Selection>),
 }
 
 // Compile-time check: ensure Selection is thread-safe and fully
@@ -339,10 +658,16 @@ pub fn structurally_equal(a: &Selection, b: &Selection) -> bool {
         (Any(x), Any(y)) => structurally_equal(x, y),
         (First(x), First(y)) => structurally_equal(x, y),
         (Range(r1, x), Range(r2, y)) => r1 == r2 && structurally_equal(x, y),
+        (Label(l1, x), Label(l2, y)) => l1 == l2 && structurally_equal(x, y),
         (Intersection(x1, y1), Intersection(x2, y2)) => {
             structurally_equal(x1, x2) && structurally_equal(y1, y2)
         }
         (Union(x1, y1), Union(x2, y2)) => structurally_equal(x1, x2) && structurally_equal(y1, y2),
+        (Where(p1, x), Where(p2, y)) => p1 == p2 && structurally_equal(x, y),
+        (Difference(x1, y1), Difference(x2, y2)) => {
+            structurally_equal(x1, x2) && structurally_equal(y1, y2)
+        }
+        (Complement(x), Complement(y)) => structurally_equal(x, y),
         _ => false,
     }
 }
@@ -394,6 +719,7 @@ impl NormalizedSelectionKey {
 
 mod ast {
     use super::LabelKey;
+    use super::Predicate;
     use super::Selection;
     use crate::shape;
 
@@ -426,6 +752,15 @@ mod ast {
     pub(crate) fn union(lhs: Selection, rhs: Selection) -> Selection {
         Selection::Union(Box::new(lhs), Box::new(rhs))
     }
+    pub(crate) fn where_(predicate: Predicate, selection: Selection) -> Selection {
+        Selection::Where(predicate, Box::new(selection))
+    }
+    pub(crate) fn difference(lhs: Selection, rhs: Selection) -> Selection {
+        Selection::Difference(Box::new(lhs), Box::new(rhs))
+    }
+    pub(crate) fn complement(selection: Selection) -> Selection {
+        Selection::Complement(Box::new(selection))
+    }
 }
 
 /// `EvalOpts` controls runtime behavior of [`Selection::eval`] by
@@ -523,15 +858,27 @@ impl Selection {
                 s.validate_rec(opts, slice, top, dim + 1)?;
                 Ok(())
             }
-            Selection::All(s) | Selection::Label(_, s) | Selection::First(s) => {
+            Selection::All(s) | Selection::First(s) | Selection::Where(_, s) => {
                 s.validate_rec(opts, slice, top, dim + 1)?;
                 Ok(())
             }
-            Selection::Intersection(a, b) | Selection::Union(a, b) => {
+            // `Label` filters the current dimension without consuming
+            // it.
+            Selection::Label(_, s) => {
+                s.validate_rec(opts, slice, top, dim)?;
+                Ok(())
+            }
+            Selection::Intersection(a, b)
+            | Selection::Union(a, b)
+            | Selection::Difference(a, b) => {
                 a.validate_rec(opts, slice, top, dim)?;
                 b.validate_rec(opts, slice, top, dim)?;
                 Ok(())
             }
+            Selection::Complement(s) => {
+                s.validate_rec(opts, slice, top, dim)?;
+                Ok(())
+            }
         }
     }
 
@@ -574,17 +921,34 @@ impl Selection {
     /// The result is that selection expressions are always evaluated
     /// over a slice with at least one dimension, and uniform logic
     /// applies.
+    ///
+    /// `Label` selections are evaluated under the identity label
+    /// provider, i.e. they don't filter anything. Use
+    /// [`Selection::eval_with_labels`] to evaluate them against
+    /// actual labels.
     pub fn eval<'a>(
         &self,
         opts: &EvalOpts,
         slice: &'a Slice,
+    ) -> Result<Box<dyn Iterator<Item = usize> + 'a>, ShapeError> {
+        self.eval_with_labels(opts, slice, &IdentityLabels)
+    }
+
+    /// Like [`Selection::eval`], but `Label` selections only match
+    /// coordinates that carry one of their labels according to
+    /// `labels`.
+    pub fn eval_with_labels<'a>(
+        &self,
+        opts: &EvalOpts,
+        slice: &'a Slice,
+        labels: &'a dyn LabelProvider,
     ) -> Result<Box<dyn Iterator<Item = usize> + 'a>, ShapeError> {
         // Canonically embed 0D as 1D (extent 1).
         if slice.num_dim() == 0 {
             let slice = Slice::new(slice.offset(), vec![1], vec![1]).unwrap();
             return Ok(Box::new(
                 self.validate(opts, &slice)?
-                    .eval_rec(&slice, vec![0; 1], 0)
+                    .eval_rec(&slice, vec![0; 1], 0, labels)
                     .collect::<Vec<_>>()
                     .into_iter(),
             ));
@@ -592,7 +956,7 @@ impl Selection {
 
         Ok(self
             .validate(opts, slice)?
-            .eval_rec(slice, vec![0; slice.num_dim()], 0))
+            .eval_rec(slice, vec![0; slice.num_dim()], 0, labels))
     }
 
     fn eval_rec<'a>(
@@ -600,6 +964,7 @@ impl Selection {
         slice: &'a Slice,
         env: Vec<usize>,
         dim: usize,
+        labels: &'a dyn LabelProvider,
     ) -> Box<dyn Iterator<Item = usize> + 'a> {
         if dim == slice.num_dim() {
             match self {
@@ -619,14 +984,14 @@ impl Selection {
             Selection::True => Box::new((0..slice.sizes()[dim]).flat_map(move |i| {
                 let mut env = env.clone();
                 env[dim] = i;
-                Selection::True.eval_rec(slice, env, dim + 1)
+                Selection::True.eval_rec(slice, env, dim + 1, labels)
             })),
             Selection::All(select) => {
                 let select = Box::clone(select);
                 Box::new((0..slice.sizes()[dim]).flat_map(move |i| {
                     let mut env = env.clone();
                     env[dim] = i;
-                    select.eval_rec(slice, env, dim + 1)
+                    select.eval_rec(slice, env, dim + 1, labels)
                 }))
             }
             Selection::First(select) => {
@@ -634,7 +999,7 @@ impl Selection {
                 Box::new(iterutils::first(slice.sizes()[dim], move |i| {
                     let mut env = env.clone();
                     env[dim] = i;
-                    select.eval_rec(slice, env, dim + 1)
+                    select.eval_rec(slice, env, dim + 1, labels)
                 }))
             }
             Selection::Range(range, select) => {
@@ -643,7 +1008,7 @@ impl Selection {
                 Box::new((min..max).step_by(step).flat_map(move |i| {
                     let mut env = env.clone();
                     env[dim] = i;
-                    select.eval_rec(slice, env, dim + 1)
+                    select.eval_rec(slice, env, dim + 1, labels)
                 }))
             }
 
@@ -669,12 +1034,7 @@ impl Selection {
             //
             //   sel!(*, ["foo"]*, *)  // select all hosts with label "foo", then all GPUs
             //   = all(label(["foo"], all(all(true_()))))
-            //
-            // **Note:** Label filtering is not yet implemented — all coordinates
-            // are currently accepted.
-            Selection::Label(labels, inner) => {
-                Self::eval_label(labels, inner, slice, env, dim /*, provider */)
-            }
+            Selection::Label(keys, inner) => Self::eval_label(keys, inner, slice, env, dim, labels),
             Selection::Any(select) => {
                 let select = Box::clone(select);
                 let r = {
@@ -685,13 +1045,48 @@ impl Selection {
                 Box::new((r..r + 1).flat_map(move |i| {
                     let mut env = env.clone();
                     env[dim] = i;
-                    select.eval_rec(slice, env, dim + 1)
+                    select.eval_rec(slice, env, dim + 1, labels)
                 }))
             }
+            // As in routing, intersecting with `Any` picks one of the
+            // indices selected by the other branch.
+            Selection::Intersection(a, b)
+                if matches!(**a, Selection::Any(_)) || matches!(**b, Selection::Any(_)) =>
+            {
+                let (other, inner) = match (&**a, &**b) {
+                    (other, Selection::Any(inner)) | (Selection::Any(inner), other) => {
+                        (other, inner)
+                    }
+                    _ => unreachable!(),
+                };
+                let candidates = other
+                    .eval_rec(slice, env.clone(), dim, labels)
+                    .map(|rank| slice.coordinates(rank).unwrap()[dim])
+                    .collect::<BTreeSet<_>>();
+                if candidates.is_empty() {
+                    return Box::new(std::iter::empty());
+                }
+                let r = *candidates
+                    .iter()
+                    .nth(rand::thread_rng().gen_range(0..candidates.len()))
+                    .unwrap();
+                let restricted = Selection::Range(shape::Range(r, Some(r + 1), 1), inner.clone());
+                Box::new(
+                    itertools::merge_join_by(
+                        other.eval_rec(slice, env.clone(), dim, labels),
+                        restricted.eval_rec(slice, env, dim, labels),
+                        |x, y| x.cmp(y),
+                    )
+                    .filter_map(|either| match either {
+                        EitherOrBoth::Both(x, _) => Some(x),
+                        _ => None,
+                    }),
+                )
+            }
             Selection::Intersection(a, b) => Box::new(
                 itertools::merge_join_by(
-                    a.eval_rec(slice, env.clone(), dim),
-                    b.eval_rec(slice, env.clone(), dim),
+                    a.eval_rec(slice, env.clone(), dim, labels),
+                    b.eval_rec(slice, env.clone(), dim, labels),
                     |x, y| x.cmp(y),
                 )
                 .filter_map(|either| match either {
@@ -701,8 +1096,8 @@ impl Selection {
             ),
             Selection::Union(a, b) => Box::new(
                 itertools::merge_join_by(
-                    a.eval_rec(slice, env.clone(), dim),
-                    b.eval_rec(slice, env.clone(), dim),
+                    a.eval_rec(slice, env.clone(), dim, labels),
+                    b.eval_rec(slice, env.clone(), dim, labels),
                     |x, y| x.cmp(y),
                 )
                 .map(|either| match either {
@@ -711,9 +1106,53 @@ impl Selection {
                     EitherOrBoth::Both(x, _) => x,
                 }),
             ),
+            Selection::Where(predicate, select) => {
+                let select = Box::clone(select);
+                let predicate = *predicate;
+                let mut point = env[..dim].to_vec();
+                point.push(0);
+                Box::new(
+                    (0..slice.sizes()[dim])
+                        .filter(move |&i| {
+                            point[dim] = i;
+                            predicate.matches(slice.sizes(), &point)
+                        })
+                        .flat_map(move |i| {
+                            let mut env = env.clone();
+                            env[dim] = i;
+                            select.eval_rec(slice, env, dim + 1, labels)
+                        }),
+                )
+            }
+            Selection::Difference(a, b) => Self::eval_difference(
+                a.eval_rec(slice, env.clone(), dim, labels),
+                b.eval_rec(slice, env, dim, labels),
+            ),
+            // The complement is taken relative to the subspace at
+            // `env`: everything `True` selects from here on, less
+            // `a`.
+            Selection::Complement(a) => Self::eval_difference(
+                Selection::True.eval_rec(slice, env.clone(), dim, labels),
+                a.eval_rec(slice, env, dim, labels),
+            ),
         }
     }
 
+    /// The (sorted) indices yielded by `a` and not by `b`.
+    fn eval_difference<'a>(
+        a: Box<dyn Iterator<Item = usize> + 'a>,
+        b: Box<dyn Iterator<Item = usize> + 'a>,
+    ) -> Box<dyn Iterator<Item = usize> + 'a> {
+        use itertools::EitherOrBoth;
+
+        Box::new(itertools::merge_join_by(a, b, |x, y| x.cmp(y)).filter_map(
+            |either| match either {
+                EitherOrBoth::Left(x) => Some(x),
+                _ => None,
+            },
+        ))
+    }
+
     /// Evaluates a `Label(labels, inner)` selection.
     ///
     /// This operator filters coordinates along the current dimension
@@ -729,22 +1168,19 @@ impl Selection {
     ///   space
     ///
     /// At runtime, we simulate `p⁻¹(S)` by traversing `B` and querying a
-    /// `LabelProvider` at each coordinate. Under the identity provider,
+    /// [`LabelProvider`] at each coordinate. Under the identity provider,
     /// label filtering has no effect and `eval_label` reduces to the
     /// geometric case.
     ///
     /// - If `inner` is `Any`, we select one matching index at random
     /// - Otherwise, we recurse and filter lazily
-    ///
-    /// **Note:** Label filtering is not yet implemented — all coordinates
-    /// are currently accepted.
     fn eval_label<'a>(
-        _labels: &[LabelKey],
+        keys: &[LabelKey],
         inner: &Selection,
         slice: &'a Slice,
         env: Vec<usize>,
         dim: usize,
-        // provider: &dyn LabelProvider  // TODO: add when ready
+        labels: &'a dyn LabelProvider,
     ) -> Box<dyn Iterator<Item = usize> + 'a> {
         match inner {
             // Case 1: label(..., any(...))
@@ -758,7 +1194,7 @@ impl Selection {
                     .filter(|&i| {
                         let mut prefix = env.clone();
                         prefix[dim] = i;
-                        true // TODO: provider.matches(dim, &prefix[0..=dim], labels)
+                        labels.matches(dim, &prefix[0..=dim], keys)
                     })
                     .collect();
 
@@ -771,7 +1207,7 @@ impl Selection {
 
                 let mut coord = env;
                 coord[dim] = chosen;
-                sub_inner.eval_rec(slice, coord, dim + 1 /*, provider */)
+                sub_inner.eval_rec(slice, coord, dim + 1, labels)
             }
             // Case 2: label(..., inner)
             //
@@ -784,11 +1220,14 @@ impl Selection {
             // which requires eager collection and is handled
             // separately.
             _ => {
+                let keys = keys.to_vec();
                 // evaluate the inner selection — recurse as usual
-                let iter = inner.eval_rec(slice, env.clone(), dim /* , provider */);
+                let iter = inner.eval_rec(slice, env.clone(), dim, labels);
                 Box::new(iter.filter(move |&flat| {
-                    let _coord = slice.coordinates(flat);
-                    true // TODO: provider.matches(dim, &coord[0..=dim], labels)
+                    let coord = slice
+                        .coordinates(flat)
+                        .expect("evaluated index is in the slice");
+                    labels.matches(dim, &coord[0..=dim], &keys)
                 }))
             }
         }
@@ -829,22 +1268,44 @@ impl Selection {
         matches!(sel, Selection::True)
     }
 
-    /// Evaluates whether the specified coordinates are part of the selection.
+    /// Evaluates whether the specified coordinates are part of the
+    /// selection, in a mesh whose dimensions have sizes `extent`.
     /// Returns true if they are, false otherwise.
     ///
+    /// `Label` selections are evaluated under the identity label
+    /// provider; see [`Selection::contains_with_labels`].
+    ///
     /// Example:
     /// let selection = union(
     ///     range(0..2, range(0..1, range(0..2, true_()))),
     ///     range(0..2, range(1..2, range(0..2, true_()))),
     /// );
     ///
-    /// assert!(selection.contains(&[0, 0, 1]));
-    /// assert!(!selection.contains(&[2, 0, 1]));
-    pub fn contains(&self, coords: &[usize]) -> bool {
-        self.contains_rec(coords, 0)
+    /// assert!(selection.contains(&[0, 0, 1], &[4, 4, 4]));
+    /// assert!(!selection.contains(&[2, 0, 1], &[4, 4, 4]));
+    pub fn contains(&self, coords: &[usize], extent: &[usize]) -> bool {
+        self.contains_with_labels(coords, extent, &IdentityLabels)
     }
 
-    fn contains_rec(&self, coords: &[usize], dim: usize) -> bool {
+    /// Like [`Selection::contains`], but `Label` selections only
+    /// match coordinates that carry one of their labels according to
+    /// `labels`.
+    pub fn contains_with_labels(
+        &self,
+        coords: &[usize],
+        extent: &[usize],
+        labels: &dyn LabelProvider,
+    ) -> bool {
+        self.contains_rec(coords, extent, 0, labels)
+    }
+
+    fn contains_rec(
+        &self,
+        coords: &[usize],
+        extent: &[usize],
+        dim: usize,
+        labels: &dyn LabelProvider,
+    ) -> bool {
         if dim >= coords.len() {
             return matches!(self, Selection::True);
         }
@@ -852,20 +1313,39 @@ impl Selection {
         match self {
             Selection::False => false,
             Selection::True => true,
-            Selection::All(inner) => inner.contains_rec(coords, dim + 1),
+            Selection::All(inner) => inner.contains_rec(coords, extent, dim + 1, labels),
             Selection::Range(range, inner) => {
-                let (min, max, step) = range.resolve(coords.len());
+                let (min, max, step) = range.resolve(extent[dim]);
                 let index = coords[dim];
                 index >= min
                     && index < max
                     && (index - min).is_multiple_of(step)
-                    && inner.contains_rec(coords, dim + 1)
+                    && inner.contains_rec(coords, extent, dim + 1, labels)
             }
             Selection::Intersection(a, b) => {
-                a.contains_rec(coords, dim) && b.contains_rec(coords, dim)
+                a.contains_rec(coords, extent, dim, labels)
+                    && b.contains_rec(coords, extent, dim, labels)
+            }
+            Selection::Union(a, b) => {
+                a.contains_rec(coords, extent, dim, labels)
+                    || b.contains_rec(coords, extent, dim, labels)
+            }
+            // `Label` filters the current dimension without consuming
+            // it; `inner` decides how the dimension is traversed.
+            Selection::Label(keys, inner) => {
+                labels.matches(dim, &coords[0..=dim], keys)
+                    && inner.contains_rec(coords, extent, dim, labels)
+            }
+            Selection::Where(predicate, inner) => {
+                predicate.matches(extent, &coords[..=dim])
+                    && inner.contains_rec(coords, extent, dim + 1, labels)
             }
-            Selection::Union(a, b) => a.contains_rec(coords, dim) || b.contains_rec(coords, dim),
-            Selection::Label(_, _) | Selection::First(_) | Selection::Any(_) => {
+            Selection::Difference(a, b) => {
+                a.contains_rec(coords, extent, dim, labels)
+                    && !b.contains_rec(coords, extent, dim, labels)
+            }
+            Selection::Complement(a) => !a.contains_rec(coords, extent, dim, labels),
+            Selection::First(_) | Selection::Any(_) => {
                 unimplemented!()
             }
         }
@@ -892,6 +1372,24 @@ impl Selection {
         }
     }
 
+    /// Simplifies the difference of two `Selection` expressions.
+    ///
+    /// The counterpart of [`Selection::reduce_intersection`]:
+    ///
+    /// - If `self` is `False`, or `b` is equivalent to `True`, the
+    ///   result is `False`.
+    /// - If `b` is `False`, the result is `self`.
+    /// - Otherwise, constructs an explicit `Difference`.
+    pub fn reduce_difference(self: Selection, b: Selection) -> Selection {
+        if matches!(self, Selection::False) || Selection::is_equivalent_to_true(&b) {
+            return Selection::False;
+        }
+        if matches!(b, Selection::False) {
+            return self;
+        }
+        Selection::Difference(Box::new(self), Box::new(b))
+    }
+
     /// Canonicalizes this selection to the specified number of
     /// dimensions.
     ///
@@ -952,6 +1450,9 @@ impl Selection {
             Selection::Range(r, inner) => {
                 range(r, inner.canonicalize_to_dimensions_rec(dim + 1, num_dims))
             }
+            Selection::Label(labels, inner) => {
+                label(labels, inner.canonicalize_to_dimensions_rec(dim, num_dims))
+            }
             Selection::Intersection(a, b) => intersection(
                 a.canonicalize_to_dimensions_rec(dim, num_dims),
                 b.canonicalize_to_dimensions_rec(dim, num_dims),
@@ -960,6 +1461,15 @@ impl Selection {
                 a.canonicalize_to_dimensions_rec(dim, num_dims),
                 b.canonicalize_to_dimensions_rec(dim, num_dims),
             ),
+            Selection::Where(predicate, inner) => where_(
+                predicate,
+                inner.canonicalize_to_dimensions_rec(dim + 1, num_dims),
+            ),
+            Selection::Difference(a, b) => difference(
+                a.canonicalize_to_dimensions_rec(dim, num_dims),
+                b.canonicalize_to_dimensions_rec(dim, num_dims),
+            ),
+            Selection::Complement(a) => complement(a.canonicalize_to_dimensions_rec(dim, num_dims)),
 
             other => other,
         }
@@ -988,6 +1498,9 @@ impl Selection {
             Selection::Any(inner) => S::any(inner.fold::<S>()),
             Selection::Intersection(a, b) => S::intersection(a.fold::<S>(), b.fold::<S>()),
             Selection::Union(a, b) => S::union(a.fold::<S>(), b.fold::<S>()),
+            Selection::Where(p, inner) => S::where_(*p, inner.fold::<S>()),
+            Selection::Difference(a, b) => S::difference(a.fold::<S>(), b.fold::<S>()),
+            Selection::Complement(inner) => S::complement(inner.fold::<S>()),
         }
     }
 
@@ -1444,7 +1957,11 @@ mod tests {
     use std::assert_matches::assert_matches;
     use std::collections::BTreeSet;
 
+    use super::CmpOp;
     use super::EvalOpts;
+    use super::Labels;
+    use super::Operand;
+    use super::Predicate;
     use super::ReifySlice;
     use super::Selection;
     use super::dsl::*;
@@ -2012,67 +2529,302 @@ mod tests {
         assert!(set.contains(&key_b));
     }
 
+    // The extent `contains` tests evaluate their coordinates in.
+    const EXTENT: &[usize] = &[4, 4, 4];
+
     #[test]
     fn test_contains_true() {
         let selection = true_();
-        assert!(selection.contains(&[0, 0, 0]));
-        assert!(selection.contains(&[1, 2, 3]));
+        assert!(selection.contains(&[0, 0, 0], EXTENT));
+        assert!(selection.contains(&[1, 2, 3], EXTENT));
     }
 
     #[test]
     fn test_contains_false() {
         let selection = false_();
-        assert!(!selection.contains(&[0, 0, 0]));
-        assert!(!selection.contains(&[1, 2, 3]));
+        assert!(!selection.contains(&[0, 0, 0], EXTENT));
+        assert!(!selection.contains(&[1, 2, 3], EXTENT));
     }
 
     #[test]
     fn test_contains_all() {
         let selection = all(true_());
-        assert!(selection.contains(&[0, 0, 0]));
-        assert!(selection.contains(&[1, 2, 3]));
+        assert!(selection.contains(&[0, 0, 0], EXTENT));
+        assert!(selection.contains(&[1, 2, 3], EXTENT));
     }
 
     #[test]
     fn test_contains_range() {
         let selection = range(1..3, true_());
-        assert!(selection.contains(&[1, 0, 0]));
-        assert!(!selection.contains(&[3, 0, 0]));
+        assert!(selection.contains(&[1, 0, 0], EXTENT));
+        assert!(!selection.contains(&[3, 0, 0], EXTENT));
     }
 
     #[test]
     fn test_contains_intersection() {
         let selection = intersection(range(1..3, true_()), range(2..4, true_()));
-        assert!(selection.contains(&[2, 0, 0]));
-        assert!(!selection.contains(&[1, 0, 0]));
+        assert!(selection.contains(&[2, 0, 0], EXTENT));
+        assert!(!selection.contains(&[1, 0, 0], EXTENT));
     }
 
     #[test]
     fn test_contains_union() {
         let selection = union(range(1..2, true_()), range(3..4, true_()));
-        assert!(selection.contains(&[1, 0, 0]));
-        assert!(!selection.contains(&[2, 0, 0]));
+        assert!(selection.contains(&[1, 0, 0], EXTENT));
+        assert!(!selection.contains(&[2, 0, 0], EXTENT));
+    }
+
+    #[test]
+    fn test_contains_where() {
+        let selection = all(where_(Predicate::modulo(2, CmpOp::Eq, 0), true_()));
+        assert!(selection.contains(&[1, 2, 3], EXTENT));
+        assert!(!selection.contains(&[1, 3, 3], EXTENT));
+    }
+
+    #[test]
+    fn test_contains_where_size() {
+        // The lower half of the last dimension.
+        let selection = all(all(where_(
+            Predicate::new(CmpOp::Lt, Operand::Size(2, 2)),
+            true_(),
+        )));
+        assert!(selection.contains(&[1, 2, 1], EXTENT));
+        assert!(!selection.contains(&[1, 2, 2], EXTENT));
+        assert!(selection.contains(&[1, 2, 2], &[4, 4, 8]));
+    }
+
+    #[test]
+    fn test_contains_difference() {
+        let selection = difference(all(all(true_())), all(range(3, true_())));
+        assert!(selection.contains(&[1, 2, 3], EXTENT));
+        assert!(!selection.contains(&[1, 3, 3], EXTENT));
+    }
+
+    #[test]
+    fn test_contains_complement() {
+        let selection = complement(range(1, all(true_())));
+        assert!(selection.contains(&[0, 2, 3], EXTENT));
+        assert!(!selection.contains(&[1, 2, 3], EXTENT));
     }
 
     #[test]
     #[should_panic(expected = "not implemented")]
     fn test_contains_any() {
         let selection = any(true_());
-        selection.contains(&[0, 0, 0]);
+        selection.contains(&[0, 0, 0], EXTENT);
     }
 
     #[test]
-    #[should_panic(expected = "not implemented")]
     fn test_contains_label() {
+        // Under the identity provider, labels don't filter anything.
         let selection = label(vec!["zone".to_string()], true_());
-        selection.contains(&[1, 2, 3]);
+        assert!(selection.contains(&[1, 2, 3], EXTENT));
+
+        let labels = Labels::new().with(0, 1, "h100").with(0, 2, "a100");
+        let selection = label(vec!["h100"], all(all(true_())));
+        assert!(selection.contains_with_labels(&[1, 2, 3], EXTENT, &labels));
+        assert!(!selection.contains_with_labels(&[2, 2, 3], EXTENT, &labels));
+        assert!(!selection.contains_with_labels(&[0, 2, 3], EXTENT, &labels));
     }
 
     #[test]
     #[should_panic(expected = "not implemented")]
     fn test_contains_first() {
         let selection = first(true_());
-        selection.contains(&[0, 0, 0]);
+        selection.contains(&[0, 0, 0], EXTENT);
+    }
+
+    // Labels hosts 1 and 3 of `test_slice()` "h100", and the others
+    // "a100".
+    fn test_host_labels() -> Labels {
+        (0..4).fold(Labels::new(), |labels, host| {
+            labels.with(1, host, if host % 2 == 1 { "h100" } else { "a100" })
+        })
+    }
+
+    #[test]
+    fn test_eval_label() {
+        let slice = &test_slice();
+        let labels = test_host_labels();
+
+        // sel!(*, ["h100"]*, *)
+        let selection = all(label(vec!["h100"], all(all(true_()))));
+        let selected: Vec<_> = selection
+            .eval_with_labels(&EvalOpts::strict(), slice, &labels)
+            .unwrap()
+            .collect();
+        assert_eq!(
+            selected,
+            eval(
+                all(union(range(1..2, all(true_())), range(3..4, all(true_())))),
+                slice
+            )
+        );
+
+        // Multiple labels match any of them.
+        let selection = all(label(vec!["h100", "a100"], all(all(true_()))));
+        assert_eq!(
+            selection
+                .eval_with_labels(&EvalOpts::strict(), slice, &labels)
+                .unwrap()
+                .count(),
+            64
+        );
+
+        // Without labels, `Label` doesn't filter.
+        let selection = all(label(vec!["h100"], all(all(true_()))));
+        assert_eq!(eval(selection, slice).len(), 64);
+
+        // No host is labeled "v100".
+        let selection = all(label(vec!["v100"], all(all(true_()))));
+        assert_eq!(
+            selection
+                .eval_with_labels(&EvalOpts::strict(), slice, &labels)
+                .unwrap()
+                .count(),
+            0
+        );
+
+        // Zones are not labeled, so no zone matches.
+        let selection = label(vec!["h100"], all(all(true_())));
+        assert_eq!(
+            selection
+                .eval_with_labels(&EvalOpts::strict(), slice, &labels)
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_eval_label_any() {
+        let slice = &test_slice();
+        let labels = test_host_labels();
+
+        // sel!(*, ["h100"]?, *): one h100 host per zone, all its gpus.
+        let selection = all(label(vec!["h100"], any(all(true_()))));
+        for _ in 0..16 {
+            let selected: Vec<_> = selection
+                .eval_with_labels(&EvalOpts::strict(), slice, &labels)
+                .unwrap()
+                .collect();
+            assert_eq!(selected.len(), 16);
+            for rank in selected {
+                let coords = slice.coordinates(rank).unwrap();
+                assert!(coords[1] == 1 || coords[1] == 3, "{:?}", coords);
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_label_unlabeled_dim() {
+        let slice = &test_slice();
+        let labels = test_host_labels();
+
+        // Zones aren't labeled, so no zone carries "h100" and nothing
+        // is selected.
+        let selection = label(vec!["h100"], all(all(all(true_()))));
+        assert_eq!(
+            selection
+                .eval_with_labels(&EvalOpts::strict(), slice, &labels)
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_eval_where() {
+        let slice = &test_slice();
+
+        // GPUs with an even index, on hosts 0 and 1, in every zone:
+        // sel!(*, <2, %2==0).
+        let selection = all(where_(
+            Predicate::new(CmpOp::Lt, 2),
+            where_(Predicate::modulo(2, CmpOp::Eq, 0), true_()),
+        ));
+        assert_eq!(
+            eval(selection, slice),
+            vec![0, 2, 4, 6, 8, 10, 12, 14, 32, 34, 36, 38, 40, 42, 44, 46]
+        );
+
+        // The same, as an intersection of two predicates.
+        let selection = intersection(
+            all(where_(Predicate::new(CmpOp::Lt, 2), all(true_()))),
+            all(all(where_(Predicate::modulo(2, CmpOp::Eq, 0), true_()))),
+        );
+        assert_eq!(
+            eval(selection, slice),
+            vec![0, 2, 4, 6, 8, 10, 12, 14, 32, 34, 36, 38, 40, 42, 44, 46]
+        );
+
+        // A zero modulus matches nothing.
+        let selection = where_(Predicate::modulo(0, CmpOp::Eq, 0), true_());
+        assert_eq!(eval(selection, slice), Vec::<usize>::new());
+
+        // The first half of the hosts in every zone: sel!(*, <#1/2, *).
+        let selection = all(where_(
+            Predicate::new(CmpOp::Lt, Operand::Size(1, 2)),
+            all(true_()),
+        ));
+        let expected: Vec<_> = (0..64).filter(|rank| rank % 32 / 8 < 2).collect();
+        assert_eq!(eval(selection, slice), expected);
+
+        // The GPU whose index is that of its host: sel!(*, *, ==@1).
+        let selection = all(all(where_(
+            Predicate::new(CmpOp::Eq, Operand::Coord(1)),
+            true_(),
+        )));
+        assert_eq!(eval(selection, slice), vec![0, 9, 18, 27, 32, 41, 50, 59]);
+
+        // Later coordinates are not known yet.
+        let selection = where_(
+            Predicate::new(CmpOp::Eq, Operand::Coord(1)),
+            all(all(true_())),
+        );
+        assert_eq!(eval(selection, slice), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_eval_difference() {
+        let slice = &test_slice();
+
+        // All ranks except those on host 3.
+        let selection = difference(all(all(all(true_()))), all(range(3, all(true_()))));
+        let expected: Vec<_> = (0..64).filter(|rank| rank % 32 / 8 != 3).collect();
+        assert_eq!(eval(selection, slice), expected);
+
+        // Dimension-local difference: hosts other than 3.
+        let selection = all(difference(all(all(true_())), range(3, all(true_()))));
+        assert_eq!(eval(selection, slice), expected);
+
+        assert_eq!(
+            eval(difference(all(true_()), all(true_())), slice),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            eval(difference(range(1, true_()), false_()), slice),
+            (32..64).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_eval_complement() {
+        let slice = &test_slice();
+
+        let selection = all(complement(range(3, all(true_()))));
+        let expected: Vec<_> = (0..64).filter(|rank| rank % 32 / 8 != 3).collect();
+        assert_eq!(eval(selection, slice), expected);
+
+        assert_eq!(
+            eval(complement(false_()), slice),
+            (0..64).collect::<Vec<_>>()
+        );
+        assert_eq!(eval(complement(true_()), slice), Vec::<usize>::new());
+        assert_eq!(
+            eval(complement(complement(range(1, true_()))), slice),
+            (32..64).collect::<Vec<_>>()
+        );
     }
 
     #[test]
@@ -10,6 +10,7 @@ use std::collections::BTreeSet;
 
 use crate::Selection;
 use crate::selection::LabelKey;
+use crate::selection::Predicate;
 use crate::selection::SelectionSYM;
 use crate::selection::dsl;
 use crate::shape;
@@ -29,6 +30,9 @@ pub enum NormalizedSelection {
     Any(Box<NormalizedSelection>),
     Union(BTreeSet<NormalizedSelection>),
     Intersection(BTreeSet<NormalizedSelection>),
+    Where(Predicate, Box<NormalizedSelection>),
+    Difference(Box<NormalizedSelection>, Box<NormalizedSelection>),
+    Complement(Box<NormalizedSelection>),
 }
 
 impl SelectionSYM for NormalizedSelection {
@@ -76,6 +80,18 @@ impl SelectionSYM for NormalizedSelection {
         set.insert(rhs);
         Self::Union(set)
     }
+
+    fn where_(predicate: Predicate, inner: Self) -> Self {
+        Self::Where(predicate, Box::new(inner))
+    }
+
+    fn difference(lhs: Self, rhs: Self) -> Self {
+        Self::Difference(Box::new(lhs), Box::new(rhs))
+    }
+
+    fn complement(inner: Self) -> Self {
+        Self::Complement(Box::new(inner))
+    }
 }
 
 impl NormalizedSelection {
@@ -98,6 +114,12 @@ impl NormalizedSelection {
             Label(labels, inner) => Label(labels, Box::new(f(*inner))),
             Union(set) => Union(set.into_iter().map(f).collect()),
             Intersection(set) => Intersection(set.into_iter().map(f).collect()),
+            Where(predicate, inner) => Where(predicate, Box::new(f(*inner))),
+            Difference(a, b) => {
+                let a = f(*a);
+                Difference(Box::new(a), Box::new(f(*b)))
+            }
+            Complement(inner) => Complement(Box::new(f(*inner))),
             leaf @ (True | False) => leaf,
         }
     }
@@ -168,6 +190,9 @@ impl From<NormalizedSelection> for Selection {
                 .unwrap_or_else(true_),
             Range(r, inner) => Selection::range(r, (*inner).into()),
             Label(labels, inner) => Selection::label(labels, (*inner).into()),
+            Where(predicate, inner) => where_(predicate, (*inner).into()),
+            Difference(a, b) => difference((*a).into(), (*b).into()),
+            Complement(inner) => complement((*inner).into()),
         }
     }
 }
@@ -188,6 +213,10 @@ impl RewriteRule for IdentityRules {
     // - Union(False, x)       → x         // identity
     // - Union({x})            → x         // trivial
     // - Union({})             → False     // trivial
+    // - Difference(x, False)  → x         // identity
+    // - Difference(False, x)  → False     // passthrough
+    // - Complement(Complement(x)) → x     // involution
+    // - Complement(False)     → True      // identity
     //
     // Absorbtion rules like `Union(True, x) → x` are handled in a
     // different rewrite.
@@ -220,6 +249,18 @@ impl RewriteRule for IdentityRules {
                 }
             }
 
+            Difference(a, b) => match (*a, *b) {
+                (a, False) => a,     // Difference(x, False) → x
+                (False, _) => False, // Difference(False, x) → False
+                (a, b) => Difference(Box::new(a), Box::new(b)),
+            },
+
+            Complement(inner) => match *inner {
+                Complement(x) => *x, // Complement(Complement(x)) → x
+                False => True,       // Complement(False) → True
+                inner => Complement(Box::new(inner)),
+            },
+
             _ => node,
         }
     }
@@ -285,6 +326,9 @@ impl RewriteRule for AbsorbtionRules {
     //
     // - Union(..., True, ...) → True
     // - Intersection(..., False, ...) → False
+    // - Difference(x, True) → False
+    // - Difference(x, x) → False
+    // - Complement(True) → False
     fn rewrite(&self, node: NormalizedSelection) -> NormalizedSelection {
         use NormalizedSelection::*;
 
@@ -303,6 +347,17 @@ impl RewriteRule for AbsorbtionRules {
                     Intersection(set)
                 }
             }
+            Difference(a, b) => {
+                if *b == True || a == b {
+                    False // Difference(x, True) → False, Difference(x, x) → False
+                } else {
+                    Difference(a, b)
+                }
+            }
+            Complement(inner) => match *inner {
+                True => False, // Complement(True) → False
+                inner => Complement(Box::new(inner)),
+            },
             other => other,
         }
     }
@@ -366,6 +421,30 @@ mod tests {
         assert_structurally_eq!(&normed.into(), &expected);
     }
 
+    #[test]
+    fn normalize_difference_and_complement() {
+        use crate::assert_structurally_eq;
+        use crate::selection::dsl::*;
+        use crate::selection::normalize;
+
+        // (*,*) - (*,*) normalizes to False.
+        let sel = difference(all(all(true_())), all(all(true_())));
+        assert_structurally_eq!(&normalize(&sel).into(), &false_());
+
+        // Removing nothing is the identity.
+        let sel = difference(all(range(1, true_())), union(false_(), false_()));
+        assert_structurally_eq!(&normalize(&sel).into(), &all(range(1, true_())));
+
+        // Double complements cancel.
+        let sel = all(complement(complement(range(3, true_()))));
+        assert_structurally_eq!(&normalize(&sel).into(), &all(range(3, true_())));
+
+        // !* selects nothing; !(* - *) everything.
+        assert_structurally_eq!(&normalize(&complement(all(true_()))).into(), &false_());
+        let sel = complement(difference(all(true_()), all(true_())));
+        assert_structurally_eq!(&normalize(&sel).into(), &true_());
+    }
+
     #[test]
     fn test_union_flattening() {
         use NormalizedSelection::*;
@@ -10,14 +10,24 @@
 //! describe hierarchical selections over multidimensional meshes.
 //! ```text
 //! expression       ::= union
-//! union            ::= intersection ( "|" intersection )*
+//! union            ::= difference ( "|" difference )*
+//! difference       ::= intersection ( "-" intersection )*
 //! intersection     ::= chain ( "&" chain )*
 //! chain            ::= group ( "," group )*
 //! group            ::= range
 //!                    | index
 //!                    | wildcard
 //!                    | any
+//!                    | predicate
+//!                    | label
+//!                    | complement
 //!                    | "(" expression ")"
+//! label            ::= "[" string ( "," string )* "]" group
+//! complement       ::= "!" group
+//! predicate        ::= ( "%" number )? comparison operand
+//! operand          ::= number | "#" number ( "/" number )? | "@" number
+//! comparison       ::= "==" | "!=" | "<=" | ">=" | "<" | ">"
+//! string           ::= '"' [^"]* '"'
 //! range            ::= number? ":" number? ( ":" number )?
 //! index            ::= number
 //! wildcard         ::= "*"
@@ -28,7 +38,9 @@
 //! Notes:
 //! - `,` separates **nested dimensions** (i.e., descent into next
 //!   dimension).
-//! - `|` is union, `&` is intersection. `&` binds tighter than `|`.
+//! - `|` is union, `-` is difference, `&` is intersection. `&` binds
+//!   tighter than `-`, which binds tighter than `|`; `-` associates
+//!   to the left.
 //! - `*` selects all values at the current dimension and descends.
 //! - `?` selects a random value at the current dimension and descends.
 //! - A range like `2:5:1` has the form `start:end:step`. Missing
@@ -37,6 +49,18 @@
 //!     - `end = full extent`
 //!     - `step = 1`
 //! - An index like `3` is shorthand for the range `3:4`.
+//! - A label list like `["A100","H100"]*` restricts the group that
+//!   follows it to indices carrying any of the labels, without
+//!   consuming a dimension of its own.
+//! - A predicate like `%2==0` or `<4` selects the indices at the
+//!   current dimension that satisfy it (here, even indices and the
+//!   first four) and descends. The right-hand side can also be
+//!   `#d` or `#d/n`, the size of dimension `d` (divided by `n`), or
+//!   `@d`, the coordinate along an earlier dimension `d`: `<#1/2`
+//!   selects the first half of dimension 1, and `*,==@0` the
+//!   diagonal.
+//! - `!` complements the group that follows it, together with the
+//!   rest of the chain: `*,!3,*` selects every host but host 3.
 //! - Parentheses `()` allow grouping for precedence control and
 //!   nesting of chains.
 //! - Whitespace is not allowed (although the `parse` function will
@@ -53,6 +77,7 @@ use nom::IResult;
 use nom::Parser as _;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
+use nom::bytes::complete::take_while;
 use nom::character::complete::char;
 use nom::character::complete::digit1;
 use nom::combinator::map;
@@ -62,6 +87,9 @@ use nom::multi::separated_list1;
 use nom::sequence::delimited;
 use nom::sequence::preceded;
 
+use crate::selection::CmpOp;
+use crate::selection::Operand;
+use crate::selection::Predicate;
 use crate::selection::Selection;
 use crate::selection::dsl;
 use crate::shape;
@@ -99,6 +127,60 @@ fn any(input: &str) -> IResult<&str, Selection> {
     map(tag("?"), |_| dsl::any(dsl::true_())).parse(input)
 }
 
+fn quoted(input: &str) -> IResult<&str, &str> {
+    delimited(char('"'), take_while(|c| c != '"'), char('"')).parse(input)
+}
+
+fn label(input: &str) -> IResult<&str, Selection> {
+    map(
+        (
+            delimited(char('['), separated_list1(char(','), quoted), char(']')),
+            group,
+        ),
+        |(labels, inner)| dsl::label(labels, inner),
+    )
+    .parse(input)
+}
+
+fn comparison(input: &str) -> IResult<&str, CmpOp> {
+    alt((
+        map(tag("=="), |_| CmpOp::Eq),
+        map(tag("!="), |_| CmpOp::Ne),
+        map(tag("<="), |_| CmpOp::Le),
+        map(tag(">="), |_| CmpOp::Ge),
+        map(tag("<"), |_| CmpOp::Lt),
+        map(tag(">"), |_| CmpOp::Gt),
+    ))
+    .parse(input)
+}
+
+fn operand(input: &str) -> IResult<&str, Operand> {
+    alt((
+        map(number, Operand::Value),
+        map(
+            (
+                preceded(char('#'), number),
+                opt(preceded(char('/'), number)),
+            ),
+            |(dim, divisor)| Operand::Size(dim, divisor.unwrap_or(1)),
+        ),
+        map(preceded(char('@'), number), Operand::Coord),
+    ))
+    .parse(input)
+}
+
+fn predicate(input: &str) -> IResult<&str, Selection> {
+    map(
+        (opt(preceded(char('%'), number)), comparison, operand),
+        |(modulus, op, value)| dsl::where_(Predicate { modulus, op, value }, dsl::true_()),
+    )
+    .parse(input)
+}
+
+fn complement(input: &str) -> IResult<&str, Selection> {
+    map(preceded(char('!'), group), dsl::complement).parse(input)
+}
+
 fn group(input: &str) -> IResult<&str, Selection> {
     alt((
         delimited(char('('), expression, char(')')),
@@ -106,6 +188,9 @@ fn group(input: &str) -> IResult<&str, Selection> {
         index,
         wildcard,
         any,
+        predicate,
+        label,
+        complement,
     ))
     .parse(input)
 }
@@ -128,14 +213,19 @@ fn chain(input: &str) -> IResult<&str, Selection> {
 // Nesting proceeds right to left:
 //   Any(True) → Range(1..4, Any(True)) → All(Range(1..4, Any(True)))
 //
-// Unions and intersections nest both branches.
+// Unions, intersections and differences nest both branches;
+// complements (like labels) nest their operand.
 fn nest(dim: Selection, tail: Selection) -> Selection {
     match dim {
         Selection::All(inner) => dsl::all(nest(*inner, tail)),
         Selection::Any(inner) => dsl::any(nest(*inner, tail)),
         Selection::Range(r, inner) => dsl::range(r, nest(*inner, tail)),
+        Selection::Label(labels, inner) => dsl::label(labels, nest(*inner, tail)),
         Selection::Union(a, b) => dsl::union(nest(*a, tail.clone()), nest(*b, tail)),
         Selection::Intersection(a, b) => dsl::intersection(nest(*a, tail.clone()), nest(*b, tail)),
+        Selection::Where(p, inner) => dsl::where_(p, nest(*inner, tail)),
+        Selection::Difference(a, b) => dsl::difference(nest(*a, tail.clone()), nest(*b, tail)),
+        Selection::Complement(inner) => dsl::complement(nest(*inner, tail)),
         Selection::True => tail,
         Selection::False => dsl::false_(),
         other => panic!("unexpected selection variant in chain: {:?}", other),
@@ -149,8 +239,15 @@ fn intersection(input: &str) -> IResult<&str, Selection> {
     .parse(input)
 }
 
+fn difference(input: &str) -> IResult<&str, Selection> {
+    map(separated_list1(char('-'), intersection), |items| {
+        items.into_iter().reduce(dsl::difference).unwrap()
+    })
+    .parse(input)
+}
+
 pub fn expression(input: &str) -> IResult<&str, Selection> {
-    map(separated_list1(char('|'), intersection), |items| {
+    map(separated_list1(char('|'), difference), |items| {
         items.into_iter().reduce(dsl::union).unwrap()
     })
     .parse(input)
@@ -312,6 +409,80 @@ mod tests {
         assert_parses_to!("((1:4),2)", range(1..4, range(2, true_())));
     }
 
+    #[test]
+    fn test_parse_difference_complement_predicate() {
+        use crate::selection::CmpOp;
+        use crate::selection::Operand;
+        use crate::selection::Predicate;
+        use crate::selection::dsl::*;
+
+        assert_parses_to!(
+            "*,*-*,3",
+            difference(all(all(true_())), all(range(3, true_())))
+        );
+        assert_parses_to!(
+            "*-1-2",
+            difference(
+                difference(all(true_()), range(1, true_())),
+                range(2, true_())
+            )
+        );
+        assert_parses_to!(
+            "*-1&2|3",
+            union(
+                difference(
+                    all(true_()),
+                    intersection(range(1, true_()), range(2, true_()))
+                ),
+                range(3, true_())
+            )
+        );
+        assert_parses_to!(
+            "*,(*-3),*",
+            all(difference(all(all(true_())), range(3, all(true_()))))
+        );
+        assert_parses_to!("*,!3,*", all(complement(range(3, all(true_())))));
+        assert_parses_to!(
+            "!(0|1)",
+            complement(union(range(0, true_()), range(1, true_())))
+        );
+        assert_parses_to!(
+            "*,<2,%2==0",
+            all(where_(
+                Predicate::new(CmpOp::Lt, 2),
+                where_(Predicate::modulo(2, CmpOp::Eq, 0), true_())
+            ))
+        );
+        assert_parses_to!("!=3", where_(Predicate::new(CmpOp::Ne, 3), true_()));
+        assert_parses_to!(
+            "%4>=1,*",
+            where_(Predicate::modulo(4, CmpOp::Ge, 1), all(true_()))
+        );
+        assert_parses_to!("<=2", where_(Predicate::new(CmpOp::Le, 2), true_()));
+        assert_parses_to!(">2", where_(Predicate::new(CmpOp::Gt, 2), true_()));
+        assert_parses_to!(
+            "*,<#1/2",
+            all(where_(
+                Predicate::new(CmpOp::Lt, Operand::Size(1, 2)),
+                true_()
+            ))
+        );
+        assert_parses_to!(
+            "%2==#0",
+            where_(
+                Predicate::modulo(2, CmpOp::Eq, Operand::Size(0, 1)),
+                true_()
+            )
+        );
+        assert_parses_to!(
+            "*,==@0",
+            all(where_(
+                Predicate::new(CmpOp::Eq, Operand::Coord(0)),
+                true_()
+            ))
+        );
+    }
+
     #[test]
     fn test_12() {
         use crate::dsl::all;
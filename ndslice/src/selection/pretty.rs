@@ -15,6 +15,7 @@
 //! module and uses the `SelectionPretty` representation.
 use crate::Selection;
 use crate::selection::LabelKey;
+use crate::selection::Predicate;
 use crate::selection::SelectionSYM;
 use crate::shape;
 
@@ -80,6 +81,15 @@ impl SelectionSYM for SelectionPretty {
     fn union(a: Self, b: Self) -> Self {
         SelectionPretty(format!("union({}, {})", a.0, b.0))
     }
+    fn where_(predicate: Predicate, s: Self) -> Self {
+        SelectionPretty(format!("where_({}, {})", predicate, s.0))
+    }
+    fn difference(a: Self, b: Self) -> Self {
+        SelectionPretty(format!("difference({}, {})", a.0, b.0))
+    }
+    fn complement(s: Self) -> Self {
+        SelectionPretty(format!("complement({})", s.0))
+    }
 }
 
 /// Renders a [`Selection`] as a structured DSL expression.
@@ -118,6 +128,7 @@ pub fn pretty(selection: &Selection) -> SelectionPretty {
 /// - `*`
 /// - `0, 1..4, *`
 /// - `["A100"]?`
+/// - `*, !3, %2==0`
 /// - `(0, (0 | 2), *) & (0, *, *)`
 ///   — intersection of two 3D expressions; simplifies to just `0, (0
 ///     | 2), *` since the second operand is a superset
@@ -204,6 +215,27 @@ impl SelectionSYM for SelectionCompact {
     fn union(a: Self, b: Self) -> Self {
         SelectionCompact(format!("({}|{})", a.0, b.0))
     }
+
+    fn where_(predicate: Predicate, s: Self) -> Self {
+        if s.0.is_empty() {
+            SelectionCompact(predicate.to_string())
+        } else {
+            SelectionCompact(format!("{},{}", predicate, s.0))
+        }
+    }
+
+    fn difference(a: Self, b: Self) -> Self {
+        SelectionCompact(format!("({}-{})", a.0, b.0))
+    }
+
+    fn complement(s: Self) -> Self {
+        // `true` has no surface syntax of its own; `*` selects the
+        // same (everything from here on).
+        if s.0.is_empty() {
+            return SelectionCompact("!*".into());
+        }
+        SelectionCompact(format!("!{}", s.0))
+    }
 }
 
 /// Returns a [`SelectionCompact`] rendering of the given
@@ -228,7 +260,11 @@ pub fn compact(selection: &Selection) -> SelectionCompact {
 #[cfg(test)]
 mod tests {
     use crate::assert_round_trip;
+    use crate::selection::CmpOp;
+    use crate::selection::Operand;
+    use crate::selection::Predicate;
     use crate::selection::Selection;
+    use crate::selection::pretty::compact;
     use crate::shape;
 
     #[test]
@@ -302,9 +338,38 @@ mod tests {
             all(all(range(shape::Range(4, None, 1), true_()))),
         ));
         assert_round_trip!(range(1..4, range(2, true_())));
+        assert_round_trip!(label(vec!["A100"], all(true_())));
+        assert_round_trip!(all(label(vec!["A100", "H100"], any(all(true_())))));
+
+        assert_round_trip!(difference(all(all(true_())), all(range(3, true_()))));
+        assert_round_trip!(difference(
+            difference(all(true_()), range(1, true_())),
+            range(2, true_())
+        ));
+        assert_round_trip!(all(difference(all(all(true_())), range(3, all(true_())))));
+        assert_round_trip!(all(complement(range(3, all(true_())))));
+        assert_round_trip!(complement(union(range(0, true_()), range(1, true_()))));
+        assert_round_trip!(complement(difference(all(true_()), range(1, true_()))));
+        assert_round_trip!(all(where_(
+            Predicate::new(CmpOp::Lt, 2),
+            where_(Predicate::modulo(2, CmpOp::Eq, 0), true_())
+        )));
+        assert_round_trip!(where_(Predicate::new(CmpOp::Ne, 3), all(true_())));
+        assert_round_trip!(intersection(
+            all(where_(Predicate::modulo(3, CmpOp::Ge, 1), true_())),
+            all(range(1..3, true_()))
+        ));
+        assert_round_trip!(all(where_(
+            Predicate::new(CmpOp::Lt, Operand::Size(1, 2)),
+            where_(Predicate::new(CmpOp::Ge, Operand::Coord(1)), true_())
+        )));
+        assert_round_trip!(where_(
+            Predicate::modulo(2, CmpOp::Eq, Operand::Size(0, 1)),
+            true_()
+        ));
 
-        // TODO(SF, 2025-05-05): There isn't parse support for `label`
-        // yet.
-        // assert_round_trip!(label(vec!["A100"], all(true_())));
+        // `true` renders as `*`, which selects the same.
+        assert_eq!(compact(&complement(true_())).to_string(), "!*");
+        assert_eq!(compact(&all(complement(true_()))).to_string(), "*,!*");
     }
 }
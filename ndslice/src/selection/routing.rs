@@ -58,6 +58,7 @@
 //!
 //! This module provides the foundation for building structured,
 //! recursive routing logic over multidimensional coordinate spaces.
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Write;
@@ -207,6 +208,16 @@ impl Choice {
     }
 }
 
+/// A chooser that resolves a [`Choice`] uniformly at random among
+/// its candidates. This is how `Selection::eval` resolves `Any`.
+pub fn random_choice(choice: &Choice) -> usize {
+    use rand::seq::SliceRandom;
+    *choice
+        .candidates
+        .choose(&mut rand::thread_rng())
+        .expect("choices have at least one candidate")
+}
+
 /// Key used to deduplicate routing frames.
 #[derive(Debug, Hash, PartialEq, Eq)]
 pub struct RoutingFrameKey {
@@ -330,14 +341,24 @@ impl RoutingFrame {
     /// The traversal proceeds **dimension-by-dimension**,
     /// structurally mirroring the shape of the selection expression:
     ///
-    /// - [`Selection::All`] and [`Selection::Range`] iterate over a
-    ///   range of coordinates, emitting one [`RoutingStep::Forward`]
-    ///   per valid index.
-    /// - [`Selection::Union`] and [`Selection::Intersection`] recurse
-    ///   into both branches. Intersection steps are joined at matching
+    /// - [`Selection::All`], [`Selection::Range`] and
+    ///   [`Selection::Where`] iterate over a range of coordinates,
+    ///   emitting one [`RoutingStep::Forward`] per valid index.
+    /// - [`Selection::Union`], [`Selection::Intersection`] and
+    ///   [`Selection::Difference`] recurse into both branches.
+    ///   Intersection and difference steps are joined at matching
     ///   coordinates and residual selections are reduced.
-    /// - [`Selection::Any`] randomly selects one index along the
-    ///   current dimension and emits a single step.
+    ///   [`Selection::Complement`] is routed as a difference from
+    ///   everything.
+    /// - [`Selection::Any`] selects one index along the current
+    ///   dimension, as picked by the `chooser`, and emits a single
+    ///   step.
+    /// - [`Selection::First`] selects the first index along the
+    ///   current dimension under which the inner selection is
+    ///   non-empty, and emits a single step.
+    /// - [`Selection::Label`] routes as its inner selection: labels
+    ///   must be resolved before routing (see
+    ///   [`Selection::eval_with_labels`]).
     /// - [`Selection::True`] and [`Selection::False`] emit no steps.
     ///
     /// At each step, only the current dimension (tracked via `self.dim`)
@@ -354,7 +375,7 @@ impl RoutingFrame {
     /// - **Selection::False**
     ///   No match — routing halts.
     ///
-    /// - **Selection::All / Selection::Range**
+    /// - **Selection::All / Selection::Range / Selection::Where**
     ///   Emits one [`RoutingStep::Forward`] per matching index, each
     ///   advancing to the next dimension with the inner selection.
     ///
@@ -365,10 +386,30 @@ impl RoutingFrame {
     /// - **Selection::Intersection**
     ///   Emits only those steps where both branches produce the same
     ///   coordinate, combining the residual selections at that point.
+    ///   If one branch is `Any`, the `chooser` instead picks among
+    ///   the indices stepped to by the other branch.
+    ///
+    /// - **Selection::Difference**
+    ///   Emits the steps of the first branch. Where the second branch
+    ///   produces the same coordinate, its residual is subtracted from
+    ///   the first's, and the step is dropped if nothing remains.
+    ///
+    /// - **Selection::Complement**
+    ///   Routes as the difference between the (canonicalized) `True`
+    ///   selection and the inner selection.
     ///
     /// - **Selection::Any**
-    ///   Randomly selects one index and emits a single
-    ///   [`RoutingStep::Forward`].
+    ///   Offers every index as a [`Choice`] to the `chooser`, and
+    ///   emits a single [`RoutingStep::Forward`] to the chosen one.
+    ///   Use [`random_choice`] for uniformly random selection.
+    ///
+    /// - **Selection::First**
+    ///   Emits a single [`RoutingStep::Forward`] to the first index
+    ///   under which the inner selection selects anything.
+    ///
+    /// - **Selection::Label**
+    ///   Emits the steps of the inner selection, as [`Selection::eval`]
+    ///   does.
     ///
     /// - **Selection::Choice**
     ///   Defers decision to the caller by invoking the `chooser`
@@ -404,13 +445,14 @@ impl RoutingFrame {
     /// - **Interruptible**: Early termination is supported via
     ///   [`ControlFlow`].
     /// - **Minimally allocating**: Avoids intermediate buffers in
-    ///   most cases; only [`Selection::Intersection`] allocates
-    ///   temporary state for pairwise matching.
+    ///   most cases; only [`Selection::Intersection`] and
+    ///   [`Selection::Difference`] allocate temporary state for
+    ///   pairwise matching.
     /// - **Policy-ready**: Integrates with runtime routing policies
     ///   via the `chooser`.
     pub fn next_steps(
         &self,
-        _chooser: &mut dyn FnMut(&Choice) -> usize,
+        chooser: &mut dyn FnMut(&Choice) -> usize,
         f: &mut dyn FnMut(RoutingStep) -> ControlFlow<()>,
     ) -> ControlFlow<()> {
         assert!(self.slice.num_dim() > 0, "next_steps requires num_dims > 0");
@@ -447,28 +489,96 @@ impl RoutingFrame {
                 ControlFlow::Continue(())
             }
 
+            Selection::Where(predicate, inner) => {
+                let size = self.slice.sizes()[self.dim];
+
+                let mut point = self.here[..self.dim].to_vec();
+                point.push(0);
+                for i in (0..size).filter(|&i| {
+                    point[self.dim] = i;
+                    predicate.matches(self.slice.sizes(), &point)
+                }) {
+                    let mut coord = self.here.clone();
+                    coord[self.dim] = i;
+                    let frame = self.advance(coord, (**inner).clone());
+                    if let ControlFlow::Break(_) = f(RoutingStep::Forward(frame)) {
+                        return ControlFlow::Break(());
+                    }
+                }
+
+                ControlFlow::Continue(())
+            }
+
             Selection::Any(inner) => {
                 let size = self.slice.sizes()[self.dim];
                 if size == 0 {
                     return ControlFlow::Continue(());
                 }
+                self.forward_choice((0..size).collect(), inner, chooser, f)
+            }
 
-                use rand::Rng;
-                let mut rng: rand::prelude::ThreadRng = rand::thread_rng();
-                let i = rng.gen_range(0..size);
-                let mut coord = self.here.clone();
-                coord[self.dim] = i;
-                let frame = self.advance(coord, (**inner).clone());
-                f(RoutingStep::Forward(frame))
+            // `First` is deterministic: we route to the first index
+            // along this dimension under which `inner` selects
+            // anything.
+            Selection::First(inner) => {
+                match (0..self.slice.sizes()[self.dim]).find(|&i| self.selects_any_at(i, inner)) {
+                    Some(i) => {
+                        let mut coord = self.here.clone();
+                        coord[self.dim] = i;
+                        f(RoutingStep::Forward(self.advance(coord, (**inner).clone())))
+                    }
+                    None => ControlFlow::Continue(()),
+                }
             }
 
             Selection::Union(a, b) => {
                 if let ControlFlow::Break(_) =
-                    self.with_selection((**a).clone()).next_steps(_chooser, f)
+                    self.with_selection((**a).clone()).next_steps(chooser, f)
                 {
                     return ControlFlow::Break(());
                 }
-                self.with_selection((**b).clone()).next_steps(_chooser, f)
+                self.with_selection((**b).clone()).next_steps(chooser, f)
+            }
+
+            // Intersecting with `Any` restricts the choice to the
+            // indices stepped to by the other branch.
+            Selection::Intersection(a, b)
+                if matches!(**a, Selection::Any(_)) || matches!(**b, Selection::Any(_)) =>
+            {
+                let (other, inner) = match (&**a, &**b) {
+                    (other, Selection::Any(inner)) | (Selection::Any(inner), other) => {
+                        (other, inner)
+                    }
+                    _ => unreachable!(),
+                };
+
+                let mut residuals: BTreeMap<usize, Selection> = BTreeMap::new();
+                self.with_selection(other.clone())
+                    .next_steps(chooser, &mut |step| {
+                        if let RoutingStep::Forward(frame) = step {
+                            let index = frame.here[self.dim];
+                            let residual = match residuals.remove(&index) {
+                                Some(residual) => {
+                                    Selection::Union(Box::new(residual), Box::new(frame.selection))
+                                }
+                                None => frame.selection,
+                            };
+                            residuals.insert(index, residual);
+                        }
+                        ControlFlow::Continue(())
+                    })?;
+                if residuals.is_empty() {
+                    return ControlFlow::Continue(());
+                }
+
+                let i = self.choose(residuals.keys().copied().collect(), inner, chooser);
+                let residual = residuals
+                    .remove(&i)
+                    .unwrap()
+                    .reduce_intersection((**inner).clone());
+                let mut coord = self.here.clone();
+                coord[self.dim] = i;
+                f(RoutingStep::Forward(self.advance(coord, residual)))
             }
 
             Selection::Intersection(a, b) => {
@@ -489,9 +599,9 @@ impl RoutingFrame {
                 };
 
                 self.with_selection((**a).clone())
-                    .next_steps(_chooser, &mut collect_left)?;
+                    .next_steps(chooser, &mut collect_left)?;
                 self.with_selection((**b).clone())
-                    .next_steps(_chooser, &mut collect_right)?;
+                    .next_steps(chooser, &mut collect_right)?;
 
                 for fa in &left {
                     for fb in &right {
@@ -511,28 +621,122 @@ impl RoutingFrame {
                 ControlFlow::Continue(())
             }
 
-            // TODO(SF, 2025-04-30): This term is not in the algebra
-            // yet.
-            // Selection::LoadBalanced(inner) => {
-            //     let size = self.slice.sizes()[self.dim];
-            //     if size == 0 {
-            //         ControlFlow::Continue(())
-            //     } else {
-            //         let candidates = (0..size).collect();
-            //         let choice = Choice {
-            //             candidates,
-            //             frame: self.with_selection((*inner).clone()),
-            //         };
-            //         let index = chooser(&choice);
-            //         f(choice.choose(index))
-            //     }
-            // }
-
-            // Catch-all for future combinators (e.g., Label).
-            _ => unimplemented!(),
+            // Steps of `a` are forwarded as is, unless `b` steps to
+            // the same coordinate; then we forward what remains of
+            // `a`'s residual once `b`'s residuals are taken out (if
+            // anything). Since steps only ever follow `a`, path
+            // determinism is preserved.
+            Selection::Difference(a, b) => {
+                let mut left = vec![];
+                let mut right = vec![];
+
+                let mut collect_left = |step: RoutingStep| {
+                    if let RoutingStep::Forward(frame) = step {
+                        left.push(frame);
+                    }
+                    ControlFlow::Continue(())
+                };
+                let mut collect_right = |step: RoutingStep| {
+                    if let RoutingStep::Forward(frame) = step {
+                        right.push(frame);
+                    }
+                    ControlFlow::Continue(())
+                };
+
+                self.with_selection((**a).clone())
+                    .next_steps(chooser, &mut collect_left)?;
+                self.with_selection((**b).clone())
+                    .next_steps(chooser, &mut collect_right)?;
+
+                for fa in left {
+                    let residual = right
+                        .iter()
+                        .filter(|fb| fb.here == fa.here)
+                        .fold(fa.selection.clone(), |residual, fb| {
+                            residual.reduce_difference(fb.selection.clone())
+                        });
+                    if matches!(residual, Selection::False) {
+                        continue;
+                    }
+                    let frame = self.advance(fa.here, residual);
+                    if let ControlFlow::Break(_) = f(RoutingStep::Forward(frame)) {
+                        return ControlFlow::Break(());
+                    }
+                }
+
+                ControlFlow::Continue(())
+            }
+
+            Selection::Complement(inner) => {
+                let everything =
+                    Selection::True.canonicalize_to_dimensions_rec(self.dim, self.slice.num_dim());
+                self.with_selection(everything.reduce_difference((**inner).clone()))
+                    .next_steps(chooser, f)
+            }
+
+            // Routing has no labels to filter on, so `Label` routes
+            // like its inner selection, as in `Selection::eval`.
+            // Callers resolve labels to ranks before routing.
+            Selection::Label(_, inner) => self
+                .with_selection((**inner).clone())
+                .next_steps(chooser, f),
         }
     }
 
+    /// Resolves a choice among `candidates` (indices along the
+    /// current dimension) using `chooser`, and forwards to the chosen
+    /// index, continuing with `inner`.
+    fn forward_choice(
+        &self,
+        candidates: Vec<usize>,
+        inner: &Selection,
+        chooser: &mut dyn FnMut(&Choice) -> usize,
+        f: &mut dyn FnMut(RoutingStep) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let i = self.choose(candidates, inner, chooser);
+        let mut coord = self.here.clone();
+        coord[self.dim] = i;
+        f(RoutingStep::Forward(self.advance(coord, inner.clone())))
+    }
+
+    /// Offer `candidates` (indices of the current dimension) to the
+    /// `chooser`, returning the chosen one.
+    fn choose(
+        &self,
+        candidates: Vec<usize>,
+        inner: &Selection,
+        chooser: &mut dyn FnMut(&Choice) -> usize,
+    ) -> usize {
+        let choice = Choice {
+            candidates,
+            frame: self.with_selection(inner.clone()),
+        };
+        let i = chooser(&choice);
+        assert!(
+            choice.candidates.contains(&i),
+            "chooser picked {} which is not among the candidates {:?}",
+            i,
+            choice.candidates
+        );
+        i
+    }
+
+    /// Returns true if `inner`, applied at index `i` of the current
+    /// dimension (and the coordinates of `here` before it), selects
+    /// any element.
+    fn selects_any_at(&self, i: usize, inner: &Selection) -> bool {
+        use crate::selection::EvalOpts;
+        use crate::selection::dsl::range;
+
+        let selection = self.here[..self.dim]
+            .iter()
+            .rev()
+            .fold(range(i..=i, inner.clone()), |acc, &j| range(j..=j, acc));
+        selection
+            .eval(&EvalOpts::lenient(), &self.slice)
+            .is_ok_and(|mut selected| selected.next().is_some())
+    }
+
     /// Returns true if this frame represents a terminal delivery
     /// point — i.e., the selection is `True` and all dimensions have
     /// been traversed.
@@ -577,18 +781,15 @@ impl RoutingFrame {
             }
 
             let mut found = None;
-            let _ = frame.next_steps(
-                &mut |_| panic!("Choice encountered in trace_route"),
-                &mut |step: RoutingStep| {
-                    let next = step.into_forward().unwrap();
-                    if let Some(result) = go(next, dest, path.clone(), seen) {
-                        found = Some(result);
-                        ControlFlow::Break(())
-                    } else {
-                        ControlFlow::Continue(())
-                    }
-                },
-            );
+            let _ = frame.next_steps(&mut random_choice, &mut |step: RoutingStep| {
+                let next = step.into_forward().unwrap();
+                if let Some(result) = go(next, dest, path.clone(), seen) {
+                    found = Some(result);
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            });
 
             found
         }
@@ -681,14 +882,11 @@ fn format_routing_tree_rec(
         }
         RoutingAction::Forward => {
             writeln!(out, "{}{}", indent_str, coord_str)?;
-            let _ = frame.next_steps(
-                &mut |_| panic!("Choice encountered in format_routing_tree_rec"),
-                &mut |step| {
-                    let next = step.into_forward().unwrap();
-                    format_routing_tree_rec(&next, indent + 1, out, seen).unwrap();
-                    ControlFlow::Continue(())
-                },
-            );
+            let _ = frame.next_steps(&mut random_choice, &mut |step| {
+                let next = step.into_forward().unwrap();
+                format_routing_tree_rec(&next, indent + 1, out, seen).unwrap();
+                ControlFlow::Continue(())
+            });
         }
     }
 
@@ -867,6 +1065,7 @@ mod tests {
     use crate::selection::dsl::*;
     use crate::selection::test_utils::RoutedMessage;
     use crate::selection::test_utils::collect_commactor_routing_tree;
+    use crate::selection::test_utils::collect_commactor_routing_tree_with;
     use crate::selection::test_utils::collect_routed_nodes;
     use crate::selection::test_utils::collect_routed_paths;
     use crate::shape;
@@ -940,6 +1139,163 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_routing_label() {
+        let slice = test_slice(); // [2, 4, 8], strides [32, 8, 1]
+
+        // Labels are resolved before routing, so `Label` routes like
+        // its inner selection.
+        for (selection, inner) in [
+            (
+                all(label(vec!["h100"], all(all(true_())))),
+                all(all(all(true_()))),
+            ),
+            (
+                all(label(vec!["a100"], range(0..3, all(true_())))),
+                all(range(0..3, all(true_()))),
+            ),
+        ] {
+            let mut expected: Vec<_> = collect_routed_paths(&inner, &slice)
+                .delivered
+                .into_keys()
+                .collect();
+            expected.sort();
+            let mut actual: Vec<_> = collect_routed_paths(&selection, &slice)
+                .delivered
+                .into_keys()
+                .collect();
+            actual.sort();
+            assert_eq!(actual, expected, "Mismatch for selection: {}", selection);
+        }
+    }
+
+    #[test]
+    fn test_routing_choice() {
+        let slice = test_slice(); // [2, 4, 8], strides [32, 8, 1]
+
+        // One host in each zone, chosen by the chooser: always the
+        // last candidate.
+        let selection = all(any(all(true_())));
+        let tree = collect_commactor_routing_tree_with(&selection, &slice, &mut |choice| {
+            assert_eq!(choice.candidates(), &[0, 1, 2, 3]);
+            assert_eq!(choice.frame().dim, 1);
+            *choice.candidates().last().unwrap()
+        });
+        let mut delivered: Vec<_> = tree.delivered.into_keys().collect();
+        delivered.sort();
+        let expected: Vec<_> = all(range(3, all(true_())))
+            .eval(&EvalOpts::strict(), &slice)
+            .unwrap()
+            .collect();
+        assert_eq!(
+            delivered, expected,
+            "chooser should pick host 3 in every zone"
+        );
+
+        // Intersecting with `any` restricts the candidates to the
+        // other branch's indices.
+        let selection = all(intersection(range(1..3, all(true_())), any(all(true_()))));
+        let tree = collect_commactor_routing_tree_with(&selection, &slice, &mut |choice| {
+            assert_eq!(choice.candidates(), &[1, 2]);
+            *choice.candidates().last().unwrap()
+        });
+        let mut delivered: Vec<_> = tree.delivered.into_keys().collect();
+        delivered.sort();
+        let expected: Vec<_> = all(range(2, all(true_())))
+            .eval(&EvalOpts::strict(), &slice)
+            .unwrap()
+            .collect();
+        assert_eq!(delivered, expected);
+
+        // Evaluation agrees: a single host in 1..3 per zone.
+        let selected: Vec<_> = selection
+            .eval(&EvalOpts::strict(), &slice)
+            .unwrap()
+            .collect();
+        assert_eq!(selected.len(), 16);
+        assert!(
+            selected
+                .iter()
+                .all(|&rank| (1..3).contains(&(rank % 32 / 8)))
+        );
+    }
+
+    #[test]
+    fn test_routing_first() {
+        let slice = test_slice(); // [2, 4, 8], strides [32, 8, 1]
+
+        assert_all_routing_strategies_eq!(slice, first(all(all(true_()))));
+        assert_all_routing_strategies_eq!(slice, all(first(range(2..4, true_()))));
+        // Only host 1 survives the intersection, so `first` picks it
+        // in the first zone.
+        assert_all_routing_strategies_eq!(
+            slice,
+            first(intersection(all(all(true_())), range(1, all(true_()))))
+        );
+    }
+
+    #[test]
+    fn test_routing_where() {
+        use crate::selection::CmpOp;
+        use crate::selection::Operand;
+        use crate::selection::Predicate;
+
+        let slice = test_slice(); // [2, 4, 8], strides [32, 8, 1]
+
+        assert_all_routing_strategies_eq!(
+            slice,
+            all(where_(
+                Predicate::new(CmpOp::Lt, 2),
+                where_(Predicate::modulo(2, CmpOp::Eq, 0), true_())
+            ))
+        );
+        assert_all_routing_strategies_eq!(
+            slice,
+            where_(Predicate::modulo(3, CmpOp::Ne, 1), all(all(true_())))
+        );
+        assert_all_routing_strategies_eq!(
+            slice,
+            all(where_(
+                Predicate::new(CmpOp::Lt, Operand::Size(1, 2)),
+                where_(Predicate::new(CmpOp::Ge, Operand::Coord(1)), true_())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_routing_difference() {
+        let slice = test_slice(); // [2, 4, 8], strides [32, 8, 1]
+
+        // All ranks except those on host 3.
+        assert_all_routing_strategies_eq!(
+            slice,
+            difference(all(all(all(true_()))), all(range(3, all(true_()))))
+        );
+        assert_all_routing_strategies_eq!(
+            slice,
+            all(difference(all(all(true_())), range(3, all(true_()))))
+        );
+        // Partial overlap at every level.
+        assert_all_routing_strategies_eq!(
+            slice,
+            difference(
+                all(range(1..4, range(2..6, true_()))),
+                union(range(0, all(range(4..8, true_()))), all(range(2, true_())))
+            )
+        );
+        assert_all_routing_strategies_eq!(slice, difference(all(true_()), all(true_())));
+    }
+
+    #[test]
+    fn test_routing_complement() {
+        let slice = test_slice(); // [2, 4, 8], strides [32, 8, 1]
+
+        assert_all_routing_strategies_eq!(slice, all(complement(range(3, all(true_())))));
+        assert_all_routing_strategies_eq!(slice, complement(range(1, range(0..2, true_()))));
+        assert_all_routing_strategies_eq!(slice, complement(complement(range(1, true_()))));
+        assert_all_routing_strategies_eq!(slice, complement(true_()));
+    }
+
     #[test]
     fn test_routing_04() {
         use crate::selection::dsl::*;
@@ -1513,6 +1869,7 @@ mod tests {
         use crate::selection::dsl::*;
         use crate::selection::routing::RoutingFrame;
         use crate::selection::routing::RoutingStep;
+        use crate::selection::routing::random_choice;
 
         let slice = test_slice(); // shape: [2, 4, 8]
 
@@ -1521,13 +1878,10 @@ mod tests {
         let frame = RoutingFrame::root(selection, slice.clone());
 
         let mut steps = vec![];
-        let _ = frame.next_steps(
-            &mut |_| panic!("Choice encountered in test_routing_07"),
-            &mut |step: RoutingStep| {
-                steps.push(step);
-                ControlFlow::Continue(())
-            },
-        );
+        let _ = frame.next_steps(&mut random_choice, &mut |step: RoutingStep| {
+            steps.push(step);
+            ControlFlow::Continue(())
+        });
 
         // Only one hop should be produced at the `any` dimension.
         assert_eq!(steps.len(), 1);
@@ -14,10 +14,12 @@ use nom::Parser as _;
 
 use crate::Slice;
 use crate::selection::Selection;
+use crate::selection::routing::Choice;
 use crate::selection::routing::RoutingAction;
 use crate::selection::routing::RoutingFrame;
 use crate::selection::routing::RoutingFrameKey;
 use crate::selection::routing::RoutingStep;
+use crate::selection::routing::random_choice;
 use crate::selection::routing::resolve_routing;
 
 /// Parse an input string to a selection.
@@ -195,10 +197,7 @@ pub fn collect_routed_paths(selection: &Selection, slice: &Slice) -> RoutedPathT
             ControlFlow::Continue(())
         };
 
-        let _ = frame.next_steps(
-            &mut |_| panic!("Choice encountered in collect_routed_nodes"),
-            &mut visitor,
-        );
+        let _ = frame.next_steps(&mut random_choice, &mut visitor);
     }
 
     RoutedPathTree {
@@ -300,6 +299,16 @@ pub struct ForwardMessage {
 pub fn collect_commactor_routing_tree(
     selection: &Selection,
     slice: &Slice,
+) -> CommActorRoutingTree {
+    collect_commactor_routing_tree_with(selection, slice, &mut random_choice)
+}
+
+/// Like [`collect_commactor_routing_tree`], but resolves `Any`
+/// choices with the given `chooser` instead of picking at random.
+pub fn collect_commactor_routing_tree_with(
+    selection: &Selection,
+    slice: &Slice,
+    chooser: &mut dyn FnMut(&Choice) -> usize,
 ) -> CommActorRoutingTree {
     use std::collections::VecDeque;
 
@@ -331,8 +340,7 @@ pub fn collect_commactor_routing_tree(
 
         tree.visited.insert(rank);
 
-        let (deliver_here, forwards) =
-            resolve_routing(rank, dests, &mut |_| panic!("choice unexpected")).unwrap();
+        let (deliver_here, forwards) = resolve_routing(rank, dests, chooser).unwrap();
 
         if deliver_here {
             tree.delivered.insert(rank, path.clone());
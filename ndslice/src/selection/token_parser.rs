@@ -22,10 +22,15 @@
 use std::iter::Peekable;
 
 use proc_macro2::Delimiter;
+use proc_macro2::Spacing;
 use proc_macro2::TokenStream;
 use proc_macro2::TokenTree;
 use quote::quote;
 
+use crate::selection::CmpOp;
+use crate::selection::LabelKey;
+use crate::selection::Operand;
+use crate::selection::Predicate;
 use crate::selection::Selection;
 use crate::selection::dsl;
 use crate::shape;
@@ -33,10 +38,14 @@ use crate::shape;
 // Selection expressions grammar:
 // ```text
 // expression ::= union
-// union      ::= intersection ('|' intersection)*
+// union      ::= difference ('|' difference)*
+// difference ::= intersection ('-' intersection)*
 // intersection ::= dimension ('&' dimension)*
 // dimension  ::= group (',' group)*
-// group      ::= range | index | * | ? | (expression)
+// group      ::= range | index | * | ? | predicate | label | complement | (expression)
+// label      ::= '[' string (',' string)* ']' group
+// complement ::= '!' group
+// predicate  ::= ('%' number)? ('==' | '!=' | '<=' | '>=' | '<' | '>') number
 // ```
 
 /// Parses a [`proc_macro2::TokenStream`] representing a selection
@@ -72,6 +81,20 @@ pub fn selection_to_tokens(sel: &Selection) -> proc_macro2::TokenStream {
                 )
             }
         }
+        Selection::Label(labels, inner) => {
+            let labels = labels.iter().map(|label| match label {
+                LabelKey::Value(value) => {
+                    quote!(::ndslice::selection::LabelKey::Value(#value.to_string()))
+                }
+            });
+            let inner = selection_to_tokens(inner);
+            quote! {
+                ::ndslice::selection::Selection::Label(
+                    vec![#(#labels),*],
+                    Box::new(#inner)
+                )
+            }
+        }
         Selection::Any(inner) => {
             let inner = selection_to_tokens(inner);
             quote!(Selection::Any(Box::new(#inner)))
@@ -86,6 +109,47 @@ pub fn selection_to_tokens(sel: &Selection) -> proc_macro2::TokenStream {
             let b = selection_to_tokens(b);
             quote!(Selection::Union(Box::new(#a), Box::new(#b)))
         }
+        Selection::Where(predicate, inner) => {
+            let modulus = match predicate.modulus {
+                Some(m) => quote!(Some(#m)),
+                None => quote!(None),
+            };
+            let op = match predicate.op {
+                CmpOp::Eq => quote!(::ndslice::selection::CmpOp::Eq),
+                CmpOp::Ne => quote!(::ndslice::selection::CmpOp::Ne),
+                CmpOp::Lt => quote!(::ndslice::selection::CmpOp::Lt),
+                CmpOp::Le => quote!(::ndslice::selection::CmpOp::Le),
+                CmpOp::Gt => quote!(::ndslice::selection::CmpOp::Gt),
+                CmpOp::Ge => quote!(::ndslice::selection::CmpOp::Ge),
+            };
+            let value = match predicate.value {
+                Operand::Value(value) => quote!(::ndslice::selection::Operand::Value(#value)),
+                Operand::Size(dim, divisor) => {
+                    quote!(::ndslice::selection::Operand::Size(#dim, #divisor))
+                }
+                Operand::Coord(dim) => quote!(::ndslice::selection::Operand::Coord(#dim)),
+            };
+            let inner = selection_to_tokens(inner);
+            quote! {
+                ::ndslice::selection::Selection::Where(
+                    ::ndslice::selection::Predicate {
+                        modulus: #modulus,
+                        op: #op,
+                        value: #value,
+                    },
+                    Box::new(#inner)
+                )
+            }
+        }
+        Selection::Difference(a, b) => {
+            let a = selection_to_tokens(a);
+            let b = selection_to_tokens(b);
+            quote!(Selection::Difference(Box::new(#a), Box::new(#b)))
+        }
+        Selection::Complement(inner) => {
+            let inner = selection_to_tokens(inner);
+            quote!(Selection::Complement(Box::new(#inner)))
+        }
         _ => unimplemented!(),
     }
 }
@@ -94,11 +158,11 @@ fn parse_expression<I>(tokens: &mut Peekable<I>) -> Result<Selection, String>
 where
     I: Iterator<Item = TokenTree>,
 {
-    let mut lhs = parse_intersection(tokens)?;
+    let mut lhs = parse_difference(tokens)?;
     while let Some(TokenTree::Punct(p)) = tokens.peek() {
         if p.as_char() == '|' {
             tokens.next(); // consume |
-            let rhs = parse_intersection(tokens)?;
+            let rhs = parse_difference(tokens)?;
             lhs = dsl::union(lhs, rhs);
         } else {
             break;
@@ -107,6 +171,23 @@ where
     Ok(lhs)
 }
 
+fn parse_difference<I>(tokens: &mut Peekable<I>) -> Result<Selection, String>
+where
+    I: Iterator<Item = TokenTree>,
+{
+    let mut lhs = parse_intersection(tokens)?;
+    while let Some(TokenTree::Punct(p)) = tokens.peek() {
+        if p.as_char() == '-' {
+            tokens.next(); // consume -
+            let rhs = parse_intersection(tokens)?;
+            lhs = dsl::difference(lhs, rhs);
+        } else {
+            break;
+        }
+    }
+    Ok(lhs)
+}
+
 fn parse_intersection<I>(tokens: &mut Peekable<I>) -> Result<Selection, String>
 where
     I: Iterator<Item = TokenTree>,
@@ -155,6 +236,7 @@ fn apply_dimension_chain(sel: Selection, tail: Selection) -> Result<Selection, S
         Selection::All(inner) => dsl::all(apply_dimension_chain(*inner, tail)?),
         Selection::Any(inner) => dsl::any(apply_dimension_chain(*inner, tail)?),
         Selection::Range(r, inner) => dsl::range(r, apply_dimension_chain(*inner, tail)?),
+        Selection::Label(labels, inner) => dsl::label(labels, apply_dimension_chain(*inner, tail)?),
         Selection::Union(a, b) => dsl::union(
             apply_dimension_chain(*a, tail.clone())?,
             apply_dimension_chain(*b, tail)?,
@@ -163,6 +245,12 @@ fn apply_dimension_chain(sel: Selection, tail: Selection) -> Result<Selection, S
             apply_dimension_chain(*a, tail.clone())?,
             apply_dimension_chain(*b, tail)?,
         ),
+        Selection::Where(p, inner) => dsl::where_(p, apply_dimension_chain(*inner, tail)?),
+        Selection::Difference(a, b) => dsl::difference(
+            apply_dimension_chain(*a, tail.clone())?,
+            apply_dimension_chain(*b, tail)?,
+        ),
+        Selection::Complement(inner) => dsl::complement(apply_dimension_chain(*inner, tail)?),
         Selection::True => tail,
         Selection::False => dsl::false_(),
         other => {
@@ -226,6 +314,58 @@ where
             // literal-prefixed range or index
             parse_range_or_index(tokens)
         }
+        Some(TokenTree::Punct(p)) if p.as_char() == '%' => {
+            tokens.next(); // consume '%'
+            let modulus = parse_number(tokens, "modulus")?;
+            let op = parse_comparison(tokens)?;
+            let value = parse_operand(tokens)?;
+            Ok(dsl::where_(
+                Predicate::modulo(modulus, op, value),
+                dsl::true_(),
+            ))
+        }
+        Some(TokenTree::Punct(p)) if matches!(p.as_char(), '=' | '<' | '>') => {
+            let op = parse_comparison(tokens)?;
+            let value = parse_operand(tokens)?;
+            Ok(dsl::where_(Predicate::new(op, value), dsl::true_()))
+        }
+        // `!=` starts a predicate; a lone `!` a complement.
+        Some(TokenTree::Punct(p)) if p.as_char() == '!' && p.spacing() == Spacing::Joint => {
+            let op = parse_comparison(tokens)?;
+            let value = parse_operand(tokens)?;
+            Ok(dsl::where_(Predicate::new(op, value), dsl::true_()))
+        }
+        Some(TokenTree::Punct(p)) if p.as_char() == '!' => {
+            tokens.next(); // consume '!'
+            Ok(dsl::complement(parse_atom(tokens)?))
+        }
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
+            // label list, e.g. `["A100", "H100"]*`
+            let group = tokens.next().unwrap(); // consume group
+            let stream = match group {
+                TokenTree::Group(g) => g.stream(),
+                _ => unreachable!(),
+            };
+            let mut labels = vec![];
+            for token in stream {
+                match token {
+                    TokenTree::Literal(lit) => {
+                        let lit = lit.to_string();
+                        let value = lit
+                            .strip_prefix('"')
+                            .and_then(|lit| lit.strip_suffix('"'))
+                            .ok_or_else(|| format!("expected string label, got {}", lit))?;
+                        labels.push(value.to_string());
+                    }
+                    TokenTree::Punct(p) if p.as_char() == ',' => {}
+                    other => return Err(format!("unexpected token in labels: {:?}", other)),
+                }
+            }
+            if labels.is_empty() {
+                return Err("expected at least one label".to_string());
+            }
+            Ok(dsl::label(labels, parse_atom(tokens)?))
+        }
         Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => {
             let group = tokens.next().unwrap(); // consume group
             let mut inner = match group {
@@ -239,6 +379,70 @@ where
     }
 }
 
+fn parse_number<I>(tokens: &mut Peekable<I>, what: &str) -> Result<usize, String>
+where
+    I: Iterator<Item = TokenTree>,
+{
+    match tokens.next() {
+        Some(TokenTree::Literal(lit)) => lit
+            .to_string()
+            .parse::<usize>()
+            .map_err(|e| format!("invalid {}: {}", what, e)),
+        other => Err(format!("expected {}, got {:?}", what, other)),
+    }
+}
+
+fn parse_operand<I>(tokens: &mut Peekable<I>) -> Result<Operand, String>
+where
+    I: Iterator<Item = TokenTree>,
+{
+    match tokens.peek() {
+        Some(TokenTree::Punct(p)) if p.as_char() == '#' => {
+            tokens.next(); // consume '#'
+            let dim = parse_number(tokens, "dimension")?;
+            let divisor = match tokens.peek() {
+                Some(TokenTree::Punct(p)) if p.as_char() == '/' => {
+                    tokens.next(); // consume '/'
+                    parse_number(tokens, "divisor")?
+                }
+                _ => 1,
+            };
+            Ok(Operand::Size(dim, divisor))
+        }
+        Some(TokenTree::Punct(p)) if p.as_char() == '@' => {
+            tokens.next(); // consume '@'
+            Ok(Operand::Coord(parse_number(tokens, "dimension")?))
+        }
+        _ => Ok(Operand::Value(parse_number(tokens, "comparison value")?)),
+    }
+}
+
+fn parse_comparison<I>(tokens: &mut Peekable<I>) -> Result<CmpOp, String>
+where
+    I: Iterator<Item = TokenTree>,
+{
+    let first = match tokens.next() {
+        Some(TokenTree::Punct(p)) => p,
+        other => return Err(format!("expected comparison, got {:?}", other)),
+    };
+    // Two-character operators are lexed as a joint punct followed by
+    // `=`.
+    let eq = first.spacing() == Spacing::Joint
+        && matches!(tokens.peek(), Some(TokenTree::Punct(p)) if p.as_char() == '=');
+    if eq {
+        tokens.next(); // consume '='
+    }
+    match (first.as_char(), eq) {
+        ('=', true) => Ok(CmpOp::Eq),
+        ('!', true) => Ok(CmpOp::Ne),
+        ('<', true) => Ok(CmpOp::Le),
+        ('>', true) => Ok(CmpOp::Ge),
+        ('<', false) => Ok(CmpOp::Lt),
+        ('>', false) => Ok(CmpOp::Gt),
+        (c, _) => Err(format!("invalid comparison starting with {:?}", c)),
+    }
+}
+
 fn parse_range_or_index<I>(tokens: &mut Peekable<I>) -> Result<Selection, String>
 where
     I: Iterator<Item = TokenTree>,
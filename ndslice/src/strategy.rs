@@ -14,8 +14,8 @@
 //!
 //! The main entry point is [`gen_selection(depth)`], which generates
 //! a structurally diverse [`Selection`] of bounded depth, supporting
-//! the `True`, `Range`, `All`, `Union`, and `Intersection`
-//! constructors.
+//! the `True`, `Range`, `Where`, `All`, `Union`, `Intersection`,
+//! `Difference` and `Complement` constructors.
 //!
 //! Example usage:
 //!
@@ -34,7 +34,10 @@
 use proptest::prelude::*;
 
 use crate::Slice;
+use crate::selection::CmpOp;
 use crate::selection::EvalOpts;
+use crate::selection::Operand;
+use crate::selection::Predicate;
 use crate::selection::Selection;
 use crate::selection::dsl;
 use crate::shape::Range;
@@ -326,12 +329,32 @@ pub fn gen_selection(depth: u32, shape: Vec<usize>, dim: usize) -> BoxedStrategy
         .prop_map(|(a, b)| dsl::intersection(a, b))
         .boxed();
 
+    let where_ = (gen_predicate(shape[dim]), recur())
+        .prop_map(|(p, s)| dsl::where_(p, s))
+        .boxed();
+
+    // Difference and complement operate at the same dimension as
+    // their operands.
+    let same_dim = {
+        let shape = shape.clone();
+        move || gen_selection(depth - 1, shape.clone(), dim)
+    };
+
+    let diff = (same_dim(), same_dim())
+        .prop_map(|(a, b)| dsl::difference(a, b))
+        .boxed();
+
+    let compl = same_dim().prop_map(dsl::complement).boxed();
+
     prop_oneof![
         2 => leaf,
         3 => range_strategy,
+        2 => where_,
         3 => all,
         2 => union,
         2 => inter,
+        1 => diff,
+        1 => compl,
     ]
     .prop_filter("valid selection", move |s| {
         let slice = Slice::new_row_major(shape.clone());
@@ -344,6 +367,27 @@ pub fn gen_selection(depth: u32, shape: Vec<usize>, dim: usize) -> BoxedStrategy
     .boxed()
 }
 
+/// Generates a random [`Predicate`] over indices of a dimension of
+/// size `dim_size`: a comparison against a value in `0..=dim_size`,
+/// optionally after reducing the index modulo `1..=dim_size`.
+pub fn gen_predicate(dim_size: usize) -> impl Strategy<Value = Predicate> {
+    let op = prop_oneof![
+        Just(CmpOp::Eq),
+        Just(CmpOp::Ne),
+        Just(CmpOp::Lt),
+        Just(CmpOp::Le),
+        Just(CmpOp::Gt),
+        Just(CmpOp::Ge),
+    ];
+    (prop::option::of(1..=dim_size.max(1)), op, 0..=dim_size).prop_map(|(modulus, op, value)| {
+        Predicate {
+            modulus,
+            op,
+            value: Operand::Value(value),
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
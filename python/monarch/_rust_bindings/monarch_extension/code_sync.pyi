@@ -61,6 +61,10 @@ class CodeSyncMethod:
             self, prefix_path_replacements: Dict[str | Path, WorkspaceLocation]
         ) -> None: ...
 
+    @final
+    class NativeSync(CodeSyncMethod):
+        def __init__(self) -> None: ...
+
 @final
 class RemoteWorkspace:
     """
@@ -81,6 +85,39 @@ class WorkspaceConfig:
         method: CodeSyncMethod = ...,
     ) -> None: ...
 
+@final
+class ReloadReport:
+    """
+    The modules reloaded by a single reload.
+    """
+
+    reloaded: list[str]
+    failed: list[tuple[str, str]]
+
+@final
+class RankSyncReport:
+    """
+    The outcome of a code sync on a single rank of the mesh.
+    """
+
+    rank: int
+    @property
+    def ok(self) -> bool:
+        """
+        Whether the rank was synced and all of its changed modules reloaded.
+        """
+        ...
+    @property
+    def sync_error(self) -> str | None: ...
+    @property
+    def reload(self) -> ReloadReport | None: ...
+    @property
+    def reload_error(self) -> str | None: ...
+    @property
+    def rollback(self) -> ReloadReport | None: ...
+    @property
+    def rollback_error(self) -> str | None: ...
+
 @final
 class CodeSyncMeshClient:
     """
@@ -98,11 +135,18 @@ class CodeSyncMeshClient:
         local: str,
         remote: RemoteWorkspace,
         auto_reload: bool = False,
-    ) -> None: ...
+        atomic: bool = False,
+    ) -> list[RankSyncReport]: ...
     async def sync_workspaces(
         self,
         *,
         instance: Instance,
         workspaces: list[WorkspaceConfig],
         auto_reload: bool = False,
-    ) -> None: ...
+        atomic: bool = False,
+    ) -> list[list[RankSyncReport]]:
+        """
+        Sync each workspace in turn, returning the per-rank reports of each,
+        and stopping after the first workspace that fails on any rank.
+        """
+        ...
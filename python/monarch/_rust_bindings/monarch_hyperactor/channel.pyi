@@ -20,6 +20,7 @@ class ChannelTransport(Enum):
     MetaTlsWithIpV6 = "metatls(ipv6)"
     Local = "local"
     Unix = "unix"
+    Shm = "shm"
     # Sim  # TODO add support
 
 class BindSpec:
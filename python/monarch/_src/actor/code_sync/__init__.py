@@ -6,11 +6,30 @@
 
 # pyre-strict
 
+from typing import List
+
 from monarch._rust_bindings.monarch_extension.code_sync import (  # noqa: F401
     CodeSyncMeshClient,
     CodeSyncMethod,
+    RankSyncReport,
+    ReloadReport,
     RemoteWorkspace,
     WorkspaceConfig,
     WorkspaceLocation,
     WorkspaceShape,
 )
+
+
+class CodeSyncError(RuntimeError):
+    """
+    Raised when a code sync fails to sync or reload on any rank.  `reports`
+    holds what happened on every rank, including those that succeeded.
+    """
+
+    def __init__(self, reports: List[RankSyncReport]) -> None:
+        failed = [report for report in reports if not report.ok]
+        super().__init__(
+            f"code sync failed on {len(failed)} of {len(reports)} ranks:\n"
+            + "\n".join(f"  {report}" for report in failed)
+        )
+        self.reports: List[RankSyncReport] = reports
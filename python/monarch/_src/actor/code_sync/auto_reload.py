@@ -208,9 +208,31 @@ class AutoReloader:
     def reload_changes(self) -> List[str]:
         """
         Reload all modules that have changed since they were last imported.
+
+        Raises an `ImportError` naming every module that failed to reload,
+        after reloading the rest.
+        """
+
+        reloaded, failed = self.try_reload_changes()
+        if failed:
+            raise ImportError(
+                "failed to reload modules:\n"
+                + "\n".join(f"  {name}: {error}" for name, error in failed)
+            )
+        return reloaded
+
+    def try_reload_changes(self) -> Tuple[List[str], List[Tuple[str, str]]]:
+        """
+        Reload all modules that have changed since they were last imported,
+        continuing past modules that fail to reload.
+
+        Returns the names of the modules that were reloaded, and the names and
+        errors of the modules that failed to reload.  A module that failed to
+        reload is not retried until its file changes again.
         """
 
         reloaded = []
+        failed = []
 
         for module_name, (filename, stored_fingerprint) in list(
             self._tracked_modules.items()
@@ -218,11 +240,15 @@ class AutoReloader:
             fingerprint = Fingerprint.for_path(filename)
             if fingerprint == stored_fingerprint:
                 continue
-            reloaded.append(module_name)
-            self._reload(sys.modules[module_name])
+            try:
+                self._reload(sys.modules[module_name])
+            except Exception as e:
+                failed.append((module_name, f"{type(e).__name__}: {e}"))
+            else:
+                reloaded.append(module_name)
             self._tracked_modules[module_name] = (filename, fingerprint)
 
-        return reloaded
+        return reloaded, failed
 
 
 class AutoReloadActor(Actor):
@@ -6,7 +6,7 @@
 
 # pyre-strict
 
-from typing import Any, Awaitable, Callable, Dict, Literal, Optional, Tuple
+from typing import Any, Awaitable, Callable, Dict, List, Literal, Optional, Tuple
 
 from monarch._rust_bindings.monarch_hyperactor.alloc import AllocConstraints, AllocSpec
 from monarch._rust_bindings.monarch_hyperactor.pytokio import PythonTask, Shared
@@ -26,6 +26,7 @@ from monarch._src.actor.allocator import (
     LocalAllocator,
     ProcessAllocator,
 )
+from monarch._src.actor.code_sync import RankSyncReport
 from monarch._src.actor.future import Future
 from monarch._src.actor.proc_mesh import _get_bootstrap_args, ProcMesh
 from monarch._src.actor.shape import MeshTrait, NDSlice, Shape
@@ -325,7 +326,8 @@ class HostMesh(MeshTrait):
         workspace: Workspace,
         conda: bool = False,
         auto_reload: bool = False,
-    ) -> None:
+        atomic: bool = False,
+    ) -> List[RankSyncReport]:
         """
         Sync local code changes to the remote hosts.
 
@@ -333,10 +335,20 @@ class HostMesh(MeshTrait):
             workspace: The workspace to sync.
             conda: If True, also sync the currently activated conda env.
             auto_reload: If True, automatically reload the workspace on changes.
+            atomic: If True, snapshot each remote workspace before syncing, and
+                roll back every host if any fails to sync or reload.
+
+        Returns:
+            What happened on each host of each workspace synced, including
+            the modules reloaded.
+
+        Raises:
+            CodeSyncError: If any host failed to sync or reload, with the
+                reports of every host.
         """
         if self._code_sync_proc_mesh:
-            await self._code_sync_proc_mesh.get()._sync_workspace(
-                workspace, conda, auto_reload
+            return await self._code_sync_proc_mesh.get()._sync_workspace(
+                workspace, conda, auto_reload, atomic
             )
         else:
             raise RuntimeError(
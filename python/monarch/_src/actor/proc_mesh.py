@@ -55,8 +55,10 @@ from monarch._src.actor.actor_mesh import (
 )
 from monarch._src.actor.allocator import AllocHandle, SimAllocator
 from monarch._src.actor.code_sync import (
+    CodeSyncError,
     CodeSyncMeshClient,
     CodeSyncMethod,
+    RankSyncReport,
     RemoteWorkspace,
     WorkspaceConfig,
     WorkspaceLocation,
@@ -638,7 +640,8 @@ class ProcMesh(MeshTrait):
         workspace: Workspace,
         conda: bool = False,
         auto_reload: bool = False,
-    ) -> None:
+        atomic: bool = False,
+    ) -> List[RankSyncReport]:
         raise NotImplementedError(
             "sync_workspace is not implemented for v1 ProcMesh. Use HostMesh.sync_workspace instead."
         )
@@ -648,7 +651,8 @@ class ProcMesh(MeshTrait):
         workspace: Workspace,
         conda: bool = False,
         auto_reload: bool = False,
-    ) -> None:
+        atomic: bool = False,
+    ) -> List[RankSyncReport]:
         """
         Sync local code changes to the remote processes.
 
@@ -656,6 +660,16 @@ class ProcMesh(MeshTrait):
             workspace: The workspace to sync.
             conda: If True, also sync the currently activated conda env.
             auto_reload: If True, automatically reload the workspace on changes.
+            atomic: If True, snapshot each remote workspace before syncing, and
+                roll back every process if any fails to sync or reload.
+
+        Returns:
+            What happened on each rank of each workspace synced, including
+            the modules reloaded.
+
+        Raises:
+            CodeSyncError: If any rank failed to sync or reload, with the
+                reports of every rank.
         """
         if self._code_sync_client is None:
             self._code_sync_client = CodeSyncMeshClient.spawn_blocking(
@@ -770,11 +784,16 @@ class ProcMesh(MeshTrait):
             )
 
         assert self._code_sync_client is not None
-        await self._code_sync_client.sync_workspaces(
+        workspace_reports = await self._code_sync_client.sync_workspaces(
             instance=context().actor_instance._as_rust(),
             workspaces=list(workspaces.values()),
             auto_reload=auto_reload,
+            atomic=atomic,
         )
+        reports = [report for reports in workspace_reports for report in reports]
+        if not all(report.ok for report in reports):
+            raise CodeSyncError(reports)
+        return reports
 
     @classmethod
     def from_alloc(
@@ -69,6 +69,57 @@ class TestAutoReloader(unittest.TestCase):
                 )
                 self.assertEqual(test_module.foo, 2)
 
+    def test_try_reload_changes_partial_failure(self):
+        with importable_workspace() as workspace:
+            reloader = AutoReloader()
+            with SysAuditImportHook.install(reloader.import_callback):
+                good = workspace / "good_module.py"
+                bad = workspace / "bad_module.py"
+                write_text(good, "foo = 1\n")
+                write_text(bad, "foo = 1\n")
+
+                import bad_module  # pyre-ignore: Undefined import [21]
+                import good_module  # pyre-ignore: Undefined import [21]
+
+                write_text(good, "foo = 2\n")
+                write_text(bad, "foo = (\n")
+                for filename in (good, bad):
+                    try:
+                        # force recompile
+                        os.remove(importlib.util.cache_from_source(filename))
+                    except FileNotFoundError:
+                        pass  # python may not always implicitly generate bytecode
+
+                reloaded, failed = reloader.try_reload_changes()
+                self.assertEqual(reloaded, ["good_module"])
+                self.assertEqual([name for name, _ in failed], ["bad_module"])
+                self.assertIn("SyntaxError", failed[0][1])
+                self.assertEqual(good_module.foo, 2)
+                self.assertEqual(bad_module.foo, 1)
+
+                # Failed modules are not retried until they change again.
+                self.assertEqual(reloader.try_reload_changes(), ([], []))
+
+    def test_reload_changes_failure(self):
+        with importable_workspace() as workspace:
+            reloader = AutoReloader()
+            with SysAuditImportHook.install(reloader.import_callback):
+                filename = workspace / "broken_module.py"
+                write_text(filename, "foo = 1\n")
+
+                import broken_module  # pyre-ignore: Undefined import [21]
+
+                write_text(filename, "foo = (\n")
+                try:
+                    # force recompile
+                    os.remove(importlib.util.cache_from_source(filename))
+                except FileNotFoundError:
+                    pass  # python may not always implicitly generate bytecode
+
+                with self.assertRaisesRegex(ImportError, "broken_module"):
+                    reloader.reload_changes()
+                self.assertEqual(broken_module.foo, 1)
+
     def test_builtin_module_no_file_attribute(self):
         """Test that modules without __file__ attribute don't cause AttributeError."""
         reloader = AutoReloader()
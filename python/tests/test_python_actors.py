@@ -1163,7 +1163,11 @@ async def test_sync_workspace() -> None:
             f.flush()
 
         # force a sync and it should populate on the dst workspace
-        await code_sync_mesh.sync_workspace(config.workspace, auto_reload=True)
+        reports = await code_sync_mesh.sync_workspace(
+            config.workspace, auto_reload=True
+        )
+        assert [report.ok for report in reports] == [True]
+        assert reports[0].reload is not None
         for item in list(am.ls.call().get()):
             assert len(item[1]) == 1
             assert item[1][0] == "new_file"
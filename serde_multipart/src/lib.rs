@@ -19,6 +19,11 @@
 //! and receives [`Message`]s; the codec reconstructs the value, enabling
 //! efficient network I/O without compacting data into a single buffer.
 //!
+//! Parts need not live in memory: [`Part::from_file_region`] creates a part
+//! backed by a memory-mapped file region, which is paged in only as it is
+//! written out. Received parts can be consumed incrementally through
+//! [`Part::into_stream`], or written out with [`Part::write_to`].
+//!
 //! Implementation note: this crate uses Rust's min_specialization feature to enable
 //! the use of [`Part`]s with any Serde serializer or deserializer. This feature
 //! is fairly restrictive, and thus the API offered by [`serialize`] / [`deserialize`]
@@ -517,6 +522,54 @@ mod tests {
         assert_eq!(Message::from_framed(framed).unwrap(), message);
     }
 
+    #[test]
+    fn test_file_backed_part() {
+        use std::io::Write;
+
+        let contents: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&contents).unwrap();
+
+        // SAFETY: the file is private to this test, and is not modified.
+        let whole = unsafe { Part::from_file(&file) }.unwrap();
+        assert_eq!(&*whole.to_bytes(), &contents[..]);
+        // SAFETY: as above.
+        let region = unsafe { Part::from_file_region(&file, 4097, 1000) }.unwrap();
+        assert_eq!(&*region.to_bytes(), &contents[4097..5097]);
+        // SAFETY: as above.
+        let empty = unsafe { Part::from_file_region(&file, 10, 0) }.unwrap();
+        assert!(empty.is_empty());
+
+        // File-backed parts are framed like any other.
+        let message = serialize_bincode(&(Part::from("hello"), region.clone())).unwrap();
+        let mut framed = message.framed();
+        let framed = framed.copy_to_bytes(framed.remaining());
+        let (hello, received): (Part, Part) =
+            deserialize_bincode(Message::from_framed(framed).unwrap()).unwrap();
+        assert_eq!(hello, Part::from("hello"));
+        assert_eq!(received.to_bytes(), region.to_bytes());
+
+        let mut written = Vec::new();
+        whole.write_to(&mut written).unwrap();
+        assert_eq!(written, contents);
+    }
+
+    #[test]
+    fn test_part_into_stream() {
+        use futures::StreamExt;
+
+        let part = Part::from_fragments(vec![
+            Bytes::from(vec![1u8; 3 * 1024 * 1024 + 1]),
+            Bytes::new(),
+            Bytes::from("tail"),
+        ]);
+        let chunks: Vec<Bytes> = futures::executor::block_on(part.clone().into_stream().collect());
+        assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 1024 * 1024));
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks.concat(), part.to_bytes());
+    }
+
     #[test]
     fn test_socket_addr() {
         let socket_addr_v6: SocketAddrV6 =
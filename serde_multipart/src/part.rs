@@ -6,12 +6,17 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::fs::File;
+use std::io;
+use std::io::Write;
 use std::ops::Deref;
 
 use bytes::Bytes;
 use bytes::BytesMut;
 use bytes::buf::Reader as BufReader;
 use bytes::buf::Writer as BufWriter;
+use futures::Stream;
+use memmap2::MmapOptions;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -19,6 +24,9 @@ use crate::UnsafeBufCellRef;
 use crate::de;
 use crate::ser;
 
+/// The maximum size of the chunks returned by [`Part::into_stream`].
+const STREAM_CHUNK_SIZE: usize = 1024 * 1024;
+
 /// Part represents a single part of a multipart message. Its type is simple:
 /// it is just a newtype of the byte buffer [`Bytes`], which permits zero copy
 /// shared ownership of the underlying buffers. Part itself provides a customized
@@ -83,6 +91,71 @@ impl Part {
     pub fn from_fragments(fragments: Vec<Bytes>) -> Self {
         Self(fragments)
     }
+
+    /// Create a part backed by the region of `file` that starts at `offset`
+    /// and spans `len` bytes. The region is memory-mapped rather than read,
+    /// so its bytes are paged in only as they are used, e.g., as the part is
+    /// written to a connection. Large files can thus be sent without first
+    /// loading them into memory.
+    ///
+    /// # Safety
+    ///
+    /// The region must not be modified or truncated while the part, or any
+    /// buffer derived from it, is alive. Modifications are visible through
+    /// the part, and accessing a truncated region raises `SIGBUS`.
+    pub unsafe fn from_file_region(file: &File, offset: u64, len: usize) -> io::Result<Self> {
+        if len == 0 {
+            return Ok(Self::default());
+        }
+        // SAFETY: guaranteed by the caller.
+        let mmap = unsafe { MmapOptions::new().offset(offset).len(len).map(file)? };
+        Ok(Self::from(Bytes::from_owner(mmap)))
+    }
+
+    /// Create a part backed by the whole of `file`. See [`Part::from_file_region`].
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the part, or any
+    /// buffer derived from it, is alive.
+    pub unsafe fn from_file(file: &File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        // SAFETY: guaranteed by the caller.
+        unsafe { Self::from_file_region(file, 0, len) }
+    }
+
+    /// Write the part to `writer`, one fragment at a time, without
+    /// concatenating its fragments.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for fragment in &self.0 {
+            writer.write_all(fragment)?;
+        }
+        Ok(())
+    }
+
+    /// Consume the part, returning a stream of its bytes, in chunks of at
+    /// most 1 MiB. The chunks share the part's buffers; parts backed by
+    /// files are paged in only as the stream is consumed.
+    pub fn into_stream(self) -> impl Stream<Item = Bytes> + Send + 'static {
+        futures::stream::iter(self.0.into_iter().flat_map(Chunks))
+    }
+}
+
+/// Splits a buffer into chunks of at most [`STREAM_CHUNK_SIZE`] bytes,
+/// without copying.
+struct Chunks(Bytes);
+
+impl Iterator for Chunks {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let len = self.0.len().min(STREAM_CHUNK_SIZE);
+        Some(self.0.split_to(len))
+    }
 }
 
 impl<T: Into<Bytes>> From<T> for Part {